@@ -0,0 +1,87 @@
+// ACPI tables.
+// Find the firmware's description tables by signature.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "acpi.h"
+
+static uchar
+sum(uchar *addr, int len)
+{
+  int i, sum;
+
+  sum = 0;
+  for(i=0; i<len; i++)
+    sum += addr[i];
+  return sum;
+}
+
+// Look for the RSDP in the len bytes at physical address a.
+static struct acpirsdp*
+rsdpsearch1(uint a, int len)
+{
+  uchar *e, *p, *addr;
+
+  addr = P2V(a);
+  e = addr+len;
+  for(p = addr; p < e; p += 16)
+    if(memcmp(p, "RSD PTR ", 8) == 0 && sum(p, 20) == 0)
+      return (struct acpirsdp*)p;
+  return 0;
+}
+
+// The RSDP is in the first KB of the EBDA or in the BIOS
+// ROM between 0xE0000 and 0xFFFFF.
+static struct acpirsdp*
+rsdpsearch(void)
+{
+  uchar *bda;
+  uint p;
+  struct acpirsdp *rsdp;
+
+  bda = (uchar *) P2V(0x400);
+  if((p = ((bda[0x0F]<<8)| bda[0x0E]) << 4))
+    if((rsdp = rsdpsearch1(p, 1024)))
+      return rsdp;
+  return rsdpsearch1(0xE0000, 0x20000);
+}
+
+// Map the table at physical address pa, if the kernel can
+// reach it and it checks out.
+static struct acpihdr*
+table(uint pa)
+{
+  struct acpihdr *h;
+
+  if(pa == 0 || pa >= PHYSTOP || pa + sizeof(*h) > PHYSTOP)
+    return 0;
+  h = (struct acpihdr*)P2V(pa);
+  if(h->length < sizeof(*h) || pa + h->length > PHYSTOP ||
+     sum((uchar*)h, h->length) != 0)
+    return 0;
+  return h;
+}
+
+// Return the table with signature sig, such as "HPET",
+// or 0 if the firmware provides none.
+void*
+acpitable(char *sig)
+{
+  int i, n;
+  uint *entry;
+  struct acpirsdp *rsdp;
+  struct acpihdr *rsdt, *h;
+
+  if((rsdp = rsdpsearch()) == 0 || (rsdt = table(rsdp->rsdt)) == 0)
+    return 0;
+  if(memcmp(rsdt->signature, "RSDT", 4) != 0)
+    return 0;
+  entry = (uint*)(rsdt + 1);
+  n = (rsdt->length - sizeof(*rsdt)) / 4;
+  for(i = 0; i < n; i++)
+    if((h = table(entry[i])) != 0 && memcmp(h->signature, sig, 4) == 0)
+      return h;
+  return 0;
+}
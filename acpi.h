@@ -0,0 +1,61 @@
+// ACPI tables, as the firmware leaves them in memory.
+// See the ACPI specification, chapter 5.
+
+// Root System Description Pointer.
+struct acpirsdp {
+  uchar signature[8];  // "RSD PTR "
+  uchar checksum;      // First 20 bytes sum to 0
+  uchar oemid[6];
+  uchar revision;
+  uint rsdt;           // Physical address of the RSDT
+};
+
+// Header of every System Description Table.
+struct acpihdr {
+  uchar signature[4];
+  uint length;         // Of the whole table, which sums to 0
+  uchar revision;
+  uchar checksum;
+  uchar oemid[6];
+  uchar oemtableid[8];
+  uint oemrevision;
+  uint creatorid;
+  uint creatorrevision;
+};
+
+// Fixed ACPI Description Table, "FACP", as far as the kernel
+// looks.
+struct acpifadt {
+  struct acpihdr hdr;
+  uint firmwarectrl;
+  uint dsdt;
+  uchar reserved;
+  uchar pmprofile;
+  ushort sciint;
+  uint smicmd;
+  uchar acpienable;
+  uchar acpidisable;
+  uchar s4biosreq;
+  uchar pstatecnt;
+  uint pm1aevtblk;
+  uint pm1bevtblk;
+  uint pm1acntblk;
+  uint pm1bcntblk;
+  uint pm2cntblk;
+  uint pmtmrblk;       // I/O port of the PM timer
+};
+
+// High Precision Event Timer table, "HPET".
+struct acpihpet {
+  struct acpihdr hdr;
+  uint blockid;        // Hardware ID of the timer block
+  uchar space;         // Address space: 0 for memory
+  uchar bitwidth;
+  uchar bitoffset;
+  uchar accesssize;
+  uint addrlo;         // Physical address of the registers
+  uint addrhi;
+  uchar number;
+  ushort mintick;
+  uchar attrs;
+} __attribute__((packed));
@@ -0,0 +1,10 @@
+// Colors for kernel messages: ANSI escape sequences, which the
+// CGA console (see cgaescape in console.c) and serial
+// terminals understand.  Put them around a message's prefix:
+//   cprintf(KWARN "cpu%d:" KEND " something odd\n", cpuid());
+// With the nocolor boot argument, cprintf leaves them out.
+
+#define KERR   "\033[1;31m"  // Bold red: panics and errors
+#define KWARN  "\033[1;33m"  // Bold yellow: warnings
+#define KINFO  "\033[36m"    // Cyan: which subsystem is speaking
+#define KEND   "\033[0m"     // Back to normal
@@ -25,6 +25,7 @@
 #include "sleeplock.h"
 #include "fs.h"
 #include "buf.h"
+#include "sysinfo.h"
 
 struct {
   struct spinlock lock;
@@ -142,3 +143,21 @@ brelse(struct buf *b)
 //PAGEBREAK!
 // Blank page.
 
+
+// Fill in the buffer cache usage of si.
+void
+binfo(struct sysinfo *si)
+{
+  struct buf *b;
+
+  si->nbuf = NBUF;
+  si->bufinuse = si->bufvalid = 0;
+  acquire(&bcache.lock);
+  for(b = bcache.buf; b < bcache.buf+NBUF; b++){
+    if(b->refcnt > 0)
+      si->bufinuse++;
+    if(b->flags & B_VALID)
+      si->bufvalid++;
+  }
+  release(&bcache.lock);
+}
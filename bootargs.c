@@ -0,0 +1,131 @@
+// Kernel boot arguments.
+//
+// QEMU hands the kernel a command line through its firmware
+// configuration device, as the file opt/xv6/cmdline: boot with
+//   make qemu BOOTARGS="noaslr"
+// and bootflag("noaslr") is true; arguments of the form
+// name=value are read with bootopt.  Elsewhere the command line
+// is empty.  kinit2 reads the memory map from the same device.
+
+#include "types.h"
+#include "defs.h"
+#include "x86.h"
+#include "ansi.h"
+
+#define FWCFG_SEL   0x510   // Selector register
+#define FWCFG_DATA  0x511   // Data register
+#define FWCFG_SIG   0x0000  // Item holding "QEMU"
+#define FWCFG_DIR   0x0019  // Item listing the files
+
+static char cmdline[128];
+
+// Read n bytes of the selected item into buf.
+static void
+fwcfgread(void *buf, int n)
+{
+  uchar *p;
+
+  for(p = buf; n > 0; n--)
+    *p++ = inb(FWCFG_DATA);
+}
+
+// Read a big-endian int of the selected item.
+static uint
+fwcfgint(int n)
+{
+  uchar b[4];
+  uint x;
+  int i;
+
+  fwcfgread(b, n);
+  x = 0;
+  for(i = 0; i < n; i++)
+    x = (x << 8) | b[i];
+  return x;
+}
+
+// Read up to max bytes of the firmware configuration file
+// called name into buf.  Returns the number of bytes read, or
+// -1 if there is no such file (or no QEMU).
+int
+fwcfgfile(char *name, void *buf, int max)
+{
+  char sig[4], fname[56];
+  uint n, size, sel;
+
+  outw(FWCFG_SEL, FWCFG_SIG);
+  fwcfgread(sig, sizeof(sig));
+  if(strncmp(sig, "QEMU", sizeof(sig)) != 0)
+    return -1;
+
+  outw(FWCFG_SEL, FWCFG_DIR);
+  for(n = fwcfgint(4); n > 0; n--){
+    size = fwcfgint(4);
+    sel = fwcfgint(2);
+    fwcfgint(2);
+    fwcfgread(fname, sizeof(fname));
+    if(strncmp(fname, name, sizeof(fname)) == 0)
+      break;
+  }
+  if(n == 0)
+    return -1;
+  if(size > max)
+    size = max;
+  outw(FWCFG_SEL, sel);
+  fwcfgread(buf, size);
+  return size;
+}
+
+void
+bootargsinit(void)
+{
+  int n;
+
+  if((n = fwcfgfile("opt/xv6/cmdline", cmdline, sizeof(cmdline)-1)) < 0)
+    return;
+  cmdline[n] = 0;
+  cprintf(KINFO "boot args:" KEND " %s\n", cmdline);
+}
+
+// Is word one of the space-separated boot arguments?
+int
+bootflag(char *word)
+{
+  char *p, *q;
+
+  for(p = cmdline; *p; p = q){
+    while(*p == ' ')
+      p++;
+    for(q = p; *q && *q != ' '; q++)
+      ;
+    if(q > p && q - p == strlen(word) && strncmp(p, word, q - p) == 0)
+      return 1;
+  }
+  return 0;
+}
+
+// If name=value is one of the boot arguments, copy value into
+// buf, which has room for max bytes, and return its length.
+// Otherwise return -1.
+int
+bootopt(char *name, char *buf, int max)
+{
+  char *p, *q;
+  int n;
+
+  n = strlen(name);
+  for(p = cmdline; *p; p = q){
+    while(*p == ' ')
+      p++;
+    for(q = p; *q && *q != ' '; q++)
+      ;
+    if(q - p > n && p[n] == '=' && strncmp(p, name, n) == 0){
+      p += n + 1;
+      if(q - p < max)
+        max = q - p + 1;
+      safestrcpy(buf, p, max);
+      return max - 1;
+    }
+  }
+  return -1;
+}
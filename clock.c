@@ -0,0 +1,344 @@
+// Wall-clock and monotonic time.
+//
+// clockinit() reads the RTC once at boot and measures the rate
+// of the processor's time-stamp counter against the HPET, or the
+// ACPI PM timer or the PIT if there is no HPET.  After that both clocks come from the
+// TSC, or from the HPET if the TSC may change speed and the HPET
+// doesn't wrap: CLOCK_MONOTONIC is the time since clockinit() ran,
+// and CLOCK_REALTIME adds the boot time read from the RTC.
+//
+// The clock tick also copies the time into the vDSO page (see
+// vdso.h), with the TSC reading it goes with, so that user
+// programs can work out the time from the TSC themselves.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "date.h"
+#include "time.h"
+#include "mmu.h"
+#include "x86.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "acpi.h"
+#include "timer.h"
+#include "cpuinfo.h"
+#include "ansi.h"
+#include "errno.h"
+#include "mman.h"
+#include "vdso.h"
+
+#define PIT_CH2     0x42       // PIT channel 2 counter
+#define PIT_MODE    0x43       // PIT mode register
+#define PIT_GATE    0x61       // Channel 2 gate and output
+#define PIT_HZ      1193182    // PIT input clock
+#define CALMS       10         // Calibration interval (ms)
+#define PMTMR_HZ    3579545    // ACPI PM timer clock
+#define PMTMR_MASK  0xFFFFFF   // The PM timer has 24 bits
+
+#define CLKSHIFT    VDSOSHIFT  // Fraction bits in clkmult
+
+
+static int usehpet;     // Read time from the HPET, not the TSC?
+static uint hpetfs;     // HPET period (fs), or 0 if none
+static uint pmtmr;      // ACPI PM timer port, or 0 if none
+static uint tsckhz;     // Boot CPU's TSC ticks per millisecond
+static uint64 clk0;     // Counter when clockinit() ran
+static uint clkmult;    // ns = (counter ticks * clkmult) >> CLKSHIFT
+static uint boottime;   // Unix time when clockinit() ran
+static struct spinlock nslock;  // Guards nanosleep's wakeups
+static struct vdso *vdso;       // The vDSO page
+
+// Divide n by d without libgcc's 64-bit division,
+// storing the remainder in *rem.
+uint64
+divmod64(uint64 n, uint d, uint *rem)
+{
+  uint hi, lo, r;
+
+  hi = n >> 32;
+  r = hi % d;
+  hi /= d;
+  asm("divl %4" : "=a" (lo), "=d" (r) : "a" ((uint)n), "d" (r), "rm" (d));
+  *rem = r;
+  return ((uint64)hi << 32) | lo;
+}
+
+// Convert an RTC reading to seconds since the Unix epoch.
+static uint
+unixtime(struct rtcdate *r)
+{
+  uint y, m, days;
+
+  // Count years from March, so that leap days fall at year end.
+  y = r->year;
+  m = r->month;
+  if(m <= 2){
+    y--;
+    m += 12;
+  }
+  days = 365*y + y/4 - y/100 + y/400 + (153*(m-3) + 2)/5 + r->day - 719469;
+  return ((days*24 + r->hour)*60 + r->minute)*60 + r->second;
+}
+
+// Find the ACPI PM timer's port.
+static uint
+pmtmrinit(void)
+{
+  struct acpifadt *f;
+
+  if((f = acpitable("FACP")) == 0 ||
+     f->hdr.length < sizeof(*f) || f->pmtmrblk >= 0x10000)
+    return 0;
+  return f->pmtmrblk;
+}
+
+// Count TSC ticks while the HPET, the PM timer, or the PIT
+// counts off CALMS milliseconds.  Only the PIT can't be used
+// by two CPUs at once.
+static uint
+tscrate(void)
+{
+  uint count, rem, p0;
+  uint64 t0, h0;
+
+  if(hpetfs){
+    count = divmod64((uint64)CALMS * 1000000000000ULL, hpetfs, &rem);
+    h0 = hpetcount();
+    t0 = rdtsc();
+    while((uint)(hpetcount() - h0) < count)
+      ;
+    return rdtsc() - t0;
+  }
+  if(pmtmr){
+    count = PMTMR_HZ / 1000 * CALMS;
+    p0 = inl(pmtmr);
+    t0 = rdtsc();
+    while(((inl(pmtmr) - p0) & PMTMR_MASK) < count)
+      ;
+    return rdtsc() - t0;
+  }
+  count = PIT_HZ * CALMS / 1000;
+  // Enable the channel 2 gate, with the speaker off.
+  outb(PIT_GATE, (inb(PIT_GATE) & ~0x02) | 0x01);
+  // Channel 2, low then high byte, mode 0: output rises at zero.
+  outb(PIT_MODE, 0xB0);
+  outb(PIT_CH2, count & 0xFF);
+  outb(PIT_CH2, count >> 8);
+  t0 = rdtsc();
+  while((inb(PIT_GATE) & 0x20) == 0)
+    ;
+  return rdtsc() - t0;
+}
+
+void
+clockinit(void)
+{
+  uint rem, rate;
+  int is64;
+  struct rtcdate r;
+
+  initlock(&nslock, "nanosleep");
+  if((vdso = (struct vdso*)kalloc()) == 0)
+    panic("clockinit: vdso");
+  memset(vdso, 0, PGSIZE);
+  cmostime(&r);
+  boottime = unixtime(&r);
+  is64 = 0;
+  hpetfs = hpetinit(&is64);
+  if(hpetfs == 0)
+    pmtmr = pmtmrinit();
+  rate = tscrate();
+  tsckhz = rate / CALMS;
+  mycpu()->tsckhz = tsckhz;
+  if(hpetfs && is64 && !cpuhas(CPUF_INVTSC)){
+    usehpet = 1;
+    clkmult = divmod64((uint64)hpetfs << CLKSHIFT, 1000000, &rem);
+    clk0 = hpetcount();
+  } else {
+    clkmult = divmod64((uint64)CALMS*1000000 << CLKSHIFT, rate, &rem);
+    clk0 = rdtsc();
+  }
+  vdso->boottime = boottime;
+  vdso->mult = clkmult;
+  vdsotick();
+  cprintf(KINFO "clock:" KEND " tsc %d MHz by %s, time from %s\n",
+          rate / (CALMS*1000), hpetfs ? "hpet" : pmtmr ? "pm timer" : "pit",
+          usehpet ? "hpet" : "tsc");
+}
+
+// Measure this CPU's TSC rate, if there's a timer it can share
+// with the others; else assume it matches the boot CPU's.
+// Run once on entry on each CPU other than the first.
+void
+clockcpuinit(void)
+{
+  uint khz;
+
+  khz = tsckhz;
+  if(hpetfs || pmtmr)
+    khz = tscrate() / CALMS;
+  mycpu()->tsckhz = khz;
+  // Time comes from the boot CPU's rate; warn if ours is 1% off.
+  if(!usehpet && (khz > tsckhz + tsckhz/100 || khz < tsckhz - tsckhz/100))
+    cprintf(KWARN "cpu%d:" KEND " tsc %d MHz, not %d; clocks may be off\n",
+            cpuid(), khz / 1000, tsckhz / 1000);
+}
+
+// Spin for us microseconds, by the TSC.  Before clockinit has
+// measured its rate, don't wait at all.
+void
+microdelay(int us)
+{
+  uint rem;
+  uint64 end;
+
+  if(tsckhz == 0)
+    return;
+  end = rdtsc() + divmod64((uint64)us * tsckhz, 1000, &rem);
+  while(rdtsc() < end)
+    ;
+}
+
+// Nanoseconds from clk0 to counter reading c.
+static uint64
+clkns(uint64 c)
+{
+  uint64 d;
+
+  d = c - clk0;
+  return (((uint64)(uint)d * clkmult) >> CLKSHIFT) +
+         (((uint64)(uint)(d >> 32) * clkmult) << (32 - CLKSHIFT));
+}
+
+// Nanoseconds since clockinit().
+uint64
+nsuptime(void)
+{
+  return clkns(usehpet ? hpetcount() : rdtsc());
+}
+
+// Copy the time into the vDSO page.  The clock tick calls this
+// with the timers lock held, so only one CPU at a time writes
+// the page.  Readers retry if seq is odd or changes while they
+// read.
+void
+vdsotick(void)
+{
+  uint64 c;
+
+  vdso->seq++;
+  __sync_synchronize();
+  c = usehpet ? hpetcount() : rdtsc();
+  vdso->ns = clkns(c);
+  vdso->tsc = usehpet ? 0 : c;
+  __sync_synchronize();
+  vdso->seq++;
+}
+
+// Map the vDSO page read-only at VDSO in pgdir.
+int
+vdsomap(pde_t *pgdir)
+{
+  if(vmaadd(pgdir, VDSO, VDSO + PGSIZE, PROT_READ, MAP_SHARED, 0, 0, 0) < 0)
+    return -1;
+  kref((char*)vdso);
+  return lazymap(pgdir, VDSO, (char*)vdso, PTE_U);
+}
+
+// Read clock clk into *ts.
+int
+clockgettime(int clk, struct timespec *ts)
+{
+  uint nsec;
+  uint64 sec;
+
+  if(clk != CLOCK_REALTIME && clk != CLOCK_MONOTONIC)
+    return -EINVAL;
+  sec = divmod64(nsuptime(), 1000000000, &nsec);
+  ts->tv_sec = sec;
+  ts->tv_nsec = nsec;
+  if(clk == CLOCK_REALTIME)
+    ts->tv_sec += boottime;
+  return 0;
+}
+
+// Seconds since the Unix epoch, for file times.
+uint
+wallclock(void)
+{
+  uint nsec;
+
+  return boottime + divmod64(nsuptime(), 1000000000, &nsec);
+}
+
+// Has the current process been killed or sent a signal
+// that will interrupt a sleep?
+static int
+interrupted(void)
+{
+  struct proc *p = myproc();
+
+  return p->killed || (p->sigpending & ~p->sigmask);
+}
+
+// Timer function for nanosleep.
+static void
+nswakeup(void *chan)
+{
+  acquire(&nslock);
+  wakeup(chan);
+  release(&nslock);
+}
+
+// Sleep until time end, in ns since boot, when a timer wakes
+// us, or until killed, or signalled if sigs is set.  Returns
+// the time at which the sleep ended.
+static uint64
+sleepuntil(uint64 end, int sigs)
+{
+  struct timer t;
+  uint64 now;
+
+  memset(&t, 0, sizeof(t));
+  acquire(&nslock);
+  addtimer(&t, end, nswakeup, &t);
+  while((now = nsuptime()) < end && !myproc()->killed &&
+        !(sigs && interrupted()))
+    sleep(&t, &nslock);
+  release(&nslock);
+  canceltimer(&t);
+  return now;
+}
+
+// Sleep for n clock ticks.  Returns -EINTR if killed first.
+int
+ticksleep(uint n)
+{
+  sleepuntil(nsuptime() + (uint64)n * TICKNS, 0);
+  return myproc()->killed ? -EINTR : 0;
+}
+
+// Sleep for the time in *req.  If a signal cuts the sleep
+// short, store the time left in *rem (if rem is not 0) and
+// return -EINTR.
+int
+nanosleep(struct timespec *req, struct timespec *rem)
+{
+  uint nsec;
+  uint64 now, end;
+
+  if(req->tv_nsec >= 1000000000)
+    return -EINVAL;
+  end = nsuptime() + (uint64)req->tv_sec*1000000000 + req->tv_nsec;
+  now = sleepuntil(end, 1);
+  if(now < end){
+    if(rem){
+      rem->tv_sec = divmod64(end - now, 1000000000, &nsec);
+      rem->tv_nsec = nsec;
+    }
+    return -EINTR;
+  }
+  return 0;
+}
@@ -0,0 +1,61 @@
+// Condition variables.
+//
+// A process waits in a loop, holding the lock, until what it
+// wants is true:
+//
+//   acquire(&lk);
+//   while(!ready)
+//     cvwait(&cv);
+//
+// and whoever makes it true signals, also holding the lock.
+// Because cvwait releases the lock only once the process is
+// asleep, the signal can't come in between.  A signal also
+// wakes processes in poll, which may be waiting for cv too.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "x86.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "condvar.h"
+
+void
+initcondvar(struct condvar *cv, struct spinlock *lk, char *name)
+{
+  cv->lk = lk;
+  cv->name = name;
+}
+
+// Release cv's lock, sleep until signalled, and take the lock
+// again.  The condition may have changed again by then, or a
+// signal interrupted the sleep, so look at it again.
+void
+cvwait(struct condvar *cv)
+{
+  if(!holding(cv->lk))
+    panic("cvwait");
+  sleep(cv, cv->lk);
+}
+
+// Wake one process waiting on cv.
+void
+cvsignal(struct condvar *cv)
+{
+  if(!holding(cv->lk))
+    panic("cvsignal");
+  wakeone(cv);
+  pollwakeup();
+}
+
+// Wake every process waiting on cv.
+void
+cvbroadcast(struct condvar *cv)
+{
+  if(!holding(cv->lk))
+    panic("cvbroadcast");
+  wakeup(cv);
+  pollwakeup();
+}
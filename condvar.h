@@ -0,0 +1,6 @@
+// Condition variable: something for processes to wait for,
+// holding lk, until another changes what lk protects.
+struct condvar {
+  struct spinlock *lk;  // Lock that waiters and signallers hold
+  char *name;           // For debugging
+};
@@ -8,22 +8,40 @@
 #include "traps.h"
 #include "spinlock.h"
 #include "sleeplock.h"
+#include "condvar.h"
 #include "fs.h"
 #include "file.h"
 #include "memlayout.h"
 #include "mmu.h"
 #include "proc.h"
 #include "x86.h"
+#include "signal.h"
+#include "kbd.h"
+#include "termios.h"
+#include "fcntl.h"
+#include "errno.h"
+#include "poll.h"
+#include "ansi.h"
 
 static void consputc(int);
+static void consshow(void);
 
 static int panicked = 0;
+static int nocolor;  // Leave colors out of kernel messages
 
 static struct {
   struct spinlock lock;
   int locking;
 } cons;
 
+// Print c on the console, and log it.
+static void
+kputc(int c)
+{
+  klogputc(c, cons.locking);
+  consputc(c);
+}
+
 static void
 printint(int xx, int base, int sign)
 {
@@ -46,15 +64,18 @@ printint(int xx, int base, int sign)
     buf[i++] = '-';
 
   while(--i >= 0)
-    consputc(buf[i]);
+    kputc(buf[i]);
 }
 //PAGEBREAK: 50
 
 // Print to the console. only understands %d, %x, %p, %s.
+// What it prints is also logged; see klog.c.  Escape sequences
+// in fmt, such as the colors of ansi.h, are dropped if the
+// nocolor boot argument was given.
 void
 cprintf(char *fmt, ...)
 {
-  int i, c, locking;
+  int i, e, c, locking;
   uint *argp;
   char *s;
 
@@ -67,8 +88,25 @@ cprintf(char *fmt, ...)
 
   argp = (uint*)(void*)(&fmt + 1);
   for(i = 0; (c = fmt[i] & 0xff) != 0; i++){
+    if(c == 0x1b){
+      // An escape sequence, such as a color: it sets the line's
+      // level in the log (see klog.c), and goes only to the
+      // console.
+      kloglevel(fmt + i, locking);
+      e = i;
+      if(fmt[e+1] == '[')
+        for(e++; fmt[e+1] && (fmt[e+1] < 0x40 || fmt[e+1] > 0x7e); e++)
+          ;
+      if(fmt[e+1])
+        e++;
+      for(; i <= e; i++)
+        if(!nocolor)
+          consputc(fmt[i] & 0xff);
+      i = e;
+      continue;
+    }
     if(c != '%'){
-      consputc(c);
+      kputc(c);
       continue;
     }
     c = fmt[++i] & 0xff;
@@ -86,15 +124,15 @@ cprintf(char *fmt, ...)
       if((s = (char*)*argp++) == 0)
         s = "(null)";
       for(; *s; s++)
-        consputc(*s);
+        kputc(*s);
       break;
     case '%':
-      consputc('%');
+      kputc('%');
       break;
     default:
       // Print unknown % sequence to draw attention.
-      consputc('%');
-      consputc(c);
+      kputc('%');
+      kputc(c);
       break;
     }
   }
@@ -103,21 +141,73 @@ cprintf(char *fmt, ...)
     release(&cons.lock);
 }
 
+// Print a call chain, one function per line.
+static void
+backtrace(uint *pcs, int n)
+{
+  int i;
+
+  for(i=0; i<n && pcs[i] != 0; i++){
+    cprintf("  ");
+    ksymprint(pcs[i]);
+    cprintf("\n");
+  }
+}
+
 void
 panic(char *s)
 {
-  int i;
   uint pcs[10];
 
   cli();
+  if(!stopcpus())
+    for(;;)
+      hlt();
   cons.locking = 0;
+  consshow();  // Show what follows.
   // use lapiccpunum so that we can call panic from mycpu()
-  cprintf("lapicid %d: panic: ", lapicid());
+  cprintf("lapicid %d: " KERR "panic:" KEND " ", lapicid());
   cprintf(s);
   cprintf("\n");
   getcallerpcs(&s, pcs);
-  for(i=0; i<10; i++)
-    cprintf(" %p", pcs[i]);
+  backtrace(pcs, 10);
+  panicked = 1; // freeze other CPU
+  for(;;)
+    ;
+}
+
+// Like panic, but for a fault or trap in the kernel that the
+// caller can't return from: show the registers in tf, the
+// stack of the interrupted code at sp, and its call chain.
+void
+panictf(char *s, struct trapframe *tf, uint *sp)
+{
+  int i;
+  uint pcs[11];
+
+  cli();
+  if(!stopcpus())
+    for(;;)
+      hlt();
+  cons.locking = 0;
+  consshow();  // Show what follows.
+  cprintf("lapicid %d: " KERR "panic:" KEND " %s\n", lapicid(), s);
+  cprintf(" eip %p eflags %x cs %x trap %d err %x\n",
+          tf->eip, tf->eflags, tf->cs, tf->trapno, tf->err);
+  cprintf(" eax %x ebx %x ecx %x edx %x\n",
+          tf->eax, tf->ebx, tf->ecx, tf->edx);
+  cprintf(" esi %x edi %x ebp %x esp %p\n",
+          tf->esi, tf->edi, tf->ebp, sp);
+  // The stack may be what went wrong.
+  if((uint)sp >= KERNBASE && (uint)(sp+8) <= (uint)P2V(PHYSTOP)){
+    cprintf(" stack:");
+    for(i=0; i<8; i++)
+      cprintf(" %x", sp[i]);
+    cprintf("\n");
+  }
+  pcs[0] = tf->eip;
+  getcallerpcs((uint*)tf->ebp + 2, pcs+1);
+  backtrace(pcs, 11);
   panicked = 1; // freeze other CPU
   for(;;)
     ;
@@ -126,44 +216,416 @@ panic(char *s)
 //PAGEBREAK: 50
 #define BACKSPACE 0x100
 #define CRTPORT 0x3d4
-static ushort *crt = (ushort*)P2V(0xb8000);  // CGA memory
+#define COLS    80
+#define ROWS    24
+#define NSCROLL 100  // Lines of scrollback
+#define NPAR    4
+#define INPUT_BUF 128
+static ushort *cga = (ushort*)P2V(0xb8000);  // CGA memory, or text
+static ushort text[ROWS*COLS];  // The screen's cells, if fb.c draws it
+static int usefb;
 
-static void
-cgaputc(int c)
+// Virtual terminals.  Each has its own screen and input, and
+// is the console device with minor number one more than its
+// index; the first is the kernel's console, and the serial
+// port's.  Alt+F1 to Alt+F4 choose the one shown on the CGA
+// screen and typed on at the keyboard.
+//
+// The shown terminal writes to CGA memory, and the others to
+// their screen buffers.  Lines that scroll off the top of a
+// terminal are kept, for Shift+PgUp and Shift+PgDn to page
+// back through.  While the view is scrolled back, the live
+// screen is kept in the buffer and output goes there; typing
+// anything else returns to it.
+//
+// The screens understand a few VT100 escape sequences:
+// ESC [ n A/B/C/D move the cursor, ESC [ r;c H puts it at row
+// r column c, ESC [ n J and ESC [ n K erase the screen and the
+// line, and ESC [ ... m sets colors and attributes.
+struct vt {
+  ushort *crt;           // Live screen: cga or screen
+  ushort screen[ROWS*COLS];
+  int cursor;            // Position, or -1 if not yet read
+
+  enum { NORMAL, ESC, CSI } state;
+  int par[NPAR];         // Numeric parameters of a CSI sequence
+  int npar;
+  ushort attr;           // Attribute for new characters, in bits 8-15
+  int bold;
+  int reverse;
+  int fg, bg;            // CGA colors
+
+  ushort line[NSCROLL][COLS];  // Scrollback
+  int nline;             // Lines kept
+  int head;              // Oldest line
+  int back;              // Lines the view is scrolled back, or 0
+
+  char buf[INPUT_BUF];   // Input
+  uint r;                // Read index
+  uint w;                // Write index
+  uint e;                // Edit index
+  struct condvar input;  // A line came, or the foreground changed
+  int pgrp;              // Foreground process group, or 0 for none
+  struct termios t;      // Settings, from ioctl
+};
+
+static struct vt vts[NVT] = {
+  [0] = { .crt = (ushort*)P2V(0xb8000), .cursor = -1,
+          .attr = 0x0700, .fg = 7, .t = { TCOOKED } },
+};
+static struct vt *shown = &vts[0];
+
+// CGA color for each ANSI color number.
+static uchar ansicolor[8] = { 0, 4, 2, 6, 1, 5, 3, 7 };
+
+static int
+cgagetpos(struct vt *v)
 {
-  int pos;
+  if(v->cursor < 0){
+    // Cursor position: col + 80*row, where the BIOS left it.
+    outb(CRTPORT, 14);
+    v->cursor = inb(CRTPORT+1) << 8;
+    outb(CRTPORT, 15);
+    v->cursor |= inb(CRTPORT+1);
+  }
+  return v->cursor;
+}
 
-  // Cursor position: col + 80*row.
+// Move the hardware cursor to pos, or off the screen.  On a
+// framebuffer, draw what changed on the screen as well.
+static void
+cgacursor(int pos)
+{
+  if(usefb){
+    fbdraw(cga, pos);
+    return;
+  }
   outb(CRTPORT, 14);
-  pos = inb(CRTPORT+1) << 8;
+  outb(CRTPORT+1, pos>>8);
   outb(CRTPORT, 15);
-  pos |= inb(CRTPORT+1);
+  outb(CRTPORT+1, pos);
+}
+
+static void
+cgasetpos(struct vt *v, int pos)
+{
+  v->cursor = pos;
+  if(v == shown && v->back == 0)
+    cgacursor(pos);
+}
+
+// Blank v's screen from from to to with the current background.
+static void
+cgaerase(struct vt *v, int from, int to)
+{
+  for(; from < to; from++)
+    v->crt[from] = ' ' | (v->attr & 0x7000) | 0x0700;
+}
+
+// Scroll the view of the shown terminal back n lines, or
+// forward if n is negative, as far as there are lines to show.
+static void
+cgascroll(int n)
+{
+  struct vt *v;
+  int back, r, l;
+  ushort *src;
+
+  v = shown;
+  back = v->back + n;
+  if(back > v->nline)
+    back = v->nline;
+  if(back < 0)
+    back = 0;
+  if(back == v->back)
+    return;
+  if(v->back == 0){
+    memmove(v->screen, cga, sizeof(v->screen));
+    v->crt = v->screen;
+  }
+  v->back = back;
+  if(back == 0){
+    memmove(cga, v->screen, sizeof(v->screen));
+    v->crt = cga;
+    cgacursor(cgagetpos(v));
+    return;
+  }
+  for(r = 0; r < ROWS; r++){
+    l = v->nline - back + r;
+    if(l < v->nline)
+      src = v->line[(v->head + l) % NSCROLL];
+    else
+      src = v->screen + (l - v->nline)*COLS;
+    memmove(cga + r*COLS, src, COLS*sizeof(cga[0]));
+  }
+  cgacursor(ROWS*COLS + COLS);  // Hide it.
+}
+
+// Return the view to the shown terminal's live screen.
+static void
+cgalive(void)
+{
+  cgascroll(-shown->back);
+}
+
+// Show terminal v on the screen.
+static void
+vtshow(struct vt *v)
+{
+  if(v == shown)
+    return;
+  cgalive();
+  memmove(shown->screen, cga, sizeof(shown->screen));
+  shown->crt = shown->screen;
+  memmove(cga, v->screen, sizeof(v->screen));
+  v->crt = cga;
+  shown = v;
+  cgacursor(cgagetpos(v));
+}
+
+static void
+setattr(struct vt *v)
+{
+  int fg, bg;
+
+  fg = v->fg | (v->bold ? 8 : 0);
+  bg = v->bg;
+  if(v->reverse){
+    fg = v->bg;
+    bg = v->fg;
+  }
+  v->attr = (bg << 12) | (fg << 8);
+}
+
+// Set graphic rendition from the parameters of ESC [ ... m.
+static void
+sgr(struct vt *v)
+{
+  int i, p;
+
+  for(i = 0; i < v->npar; i++){
+    p = v->par[i];
+    if(p == 0){
+      v->bold = v->reverse = 0;
+      v->fg = 7;
+      v->bg = 0;
+    } else if(p == 1)
+      v->bold = 1;
+    else if(p == 7)
+      v->reverse = 1;
+    else if(p == 22)
+      v->bold = 0;
+    else if(p == 27)
+      v->reverse = 0;
+    else if(p >= 30 && p <= 37)
+      v->fg = ansicolor[p - 30];
+    else if(p == 39)
+      v->fg = 7;
+    else if(p >= 40 && p <= 47)
+      v->bg = ansicolor[p - 40];
+    else if(p == 49)
+      v->bg = 0;
+    else if(p >= 90 && p <= 97){
+      v->fg = ansicolor[p - 90];
+      v->bold = 1;
+    }
+  }
+  setattr(v);
+}
+
+// Carry out the CSI sequence ending in c, with the cursor at pos.
+// Returns the new cursor position.
+static int
+csi(struct vt *v, int c, int pos)
+{
+  int n, row, col;
+
+  n = v->par[0] ? v->par[0] : 1;
+  row = pos / COLS;
+  col = pos % COLS;
+  switch(c){
+  case 'A':
+    row = row > n ? row - n : 0;
+    break;
+  case 'B':
+    row = row + n < ROWS ? row + n : ROWS - 1;
+    break;
+  case 'C':
+    col = col + n < COLS ? col + n : COLS - 1;
+    break;
+  case 'D':
+    col = col > n ? col - n : 0;
+    break;
+  case 'H':
+  case 'f':
+    row = v->par[0] ? v->par[0] - 1 : 0;
+    col = v->npar > 1 && v->par[1] ? v->par[1] - 1 : 0;
+    if(row >= ROWS)
+      row = ROWS - 1;
+    if(col >= COLS)
+      col = COLS - 1;
+    break;
+  case 'J':
+    if(v->par[0] == 0)
+      cgaerase(v, pos, ROWS*COLS);
+    else if(v->par[0] == 1)
+      cgaerase(v, 0, pos + 1);
+    else if(v->par[0] == 2)
+      cgaerase(v, 0, ROWS*COLS);
+    break;
+  case 'K':
+    if(v->par[0] == 0)
+      cgaerase(v, pos, pos - col + COLS);
+    else if(v->par[0] == 1)
+      cgaerase(v, pos - col, pos + 1);
+    else if(v->par[0] == 2)
+      cgaerase(v, pos - col, pos - col + COLS);
+    break;
+  case 'm':
+    sgr(v);
+    break;
+  }
+  return row*COLS + col;
+}
+
+// Take c as part of an escape sequence, if it is one.
+// Returns 1 if so, 0 if c is an ordinary character.
+static int
+cgaescape(struct vt *v, int c, int *pos)
+{
+  switch(v->state){
+  case NORMAL:
+    if(c != 0x1b)
+      return 0;
+    v->state = ESC;
+    return 1;
+  case ESC:
+    if(c == '['){
+      v->state = CSI;
+      memset(v->par, 0, sizeof(v->par));
+      v->npar = 1;
+    } else
+      v->state = NORMAL;  // Not one we know; drop it.
+    return 1;
+  case CSI:
+    if(c >= '0' && c <= '9'){
+      if(v->npar <= NPAR)
+        v->par[v->npar-1] = v->par[v->npar-1]*10 + c - '0';
+    } else if(c == ';'){
+      if(v->npar < NPAR)
+        v->npar++;
+    } else if(c >= 0x40 && c <= 0x7e){
+      *pos = csi(v, c, *pos);
+      v->state = NORMAL;
+    } else if(c < 0x20 || c > 0x7e)
+      v->state = NORMAL;
+    return 1;
+  }
+  return 0;
+}
+
+static void
+cgaputc(struct vt *v, int c)
+{
+  int pos;
+
+  pos = cgagetpos(v);
+  if(cgaescape(v, c, &pos)){
+    cgasetpos(v, pos);
+    return;
+  }
 
   if(c == '\n')
-    pos += 80 - pos%80;
+    pos += COLS - pos%COLS;
+  else if(c == '\r')
+    pos -= pos%COLS;
+  else if(c == '\t')
+    pos += 8 - pos%8;
   else if(c == BACKSPACE){
     if(pos > 0) --pos;
   } else
-    crt[pos++] = (c&0xff) | 0x0700;  // black on white
+    v->crt[pos++] = (c&0xff) | v->attr;
 
-  if(pos < 0 || pos > 25*80)
+  if(pos < 0 || pos > 25*COLS)
     panic("pos under/overflow");
 
-  if((pos/80) >= 24){  // Scroll up.
-    memmove(crt, crt+80, sizeof(crt[0])*23*80);
-    pos -= 80;
-    memset(crt+pos, 0, sizeof(crt[0])*(24*80 - pos));
+  if((pos/COLS) >= ROWS){  // Scroll up.
+    if(v->nline < NSCROLL)
+      memmove(v->line[(v->head + v->nline++) % NSCROLL], v->crt,
+              sizeof(v->line[0]));
+    else {
+      memmove(v->line[v->head], v->crt, sizeof(v->line[0]));
+      v->head = (v->head + 1) % NSCROLL;
+    }
+    memmove(v->crt, v->crt+COLS, sizeof(v->crt[0])*(ROWS-1)*COLS);
+    pos -= COLS;
+    cgaerase(v, pos - pos%COLS, ROWS*COLS);
   }
 
-  outb(CRTPORT, 14);
-  outb(CRTPORT+1, pos>>8);
-  outb(CRTPORT, 15);
-  outb(CRTPORT+1, pos);
-  crt[pos] = ' ' | 0x0700;
+  cgasetpos(v, pos);
 }
 
-void
-consputc(int c)
+// Where the kernel's console writes: the screen, the serial
+// port, or both, as the console= boot argument says.
+#define CONSVGA     1
+#define CONSSERIAL  2
+static int consout = CONSVGA | CONSSERIAL;
+static int consbaud = 9600;
+static int consfb;  // Set up a framebuffer if there isn't one
+
+// Read the console= boot argument, a comma-separated list of
+// vga (or tty0), fb, ttyS0, and the serial port's baud rate,
+// as in console=ttyS0,115200.  fb is vga on a framebuffer,
+// which is used anyway if the boot loader set one up.  Without
+// console=, the kernel's console writes to both screen and
+// serial port at 9600 baud.
+static void
+consoleargs(void)
+{
+  char buf[32], *p, *q;
+  int out, baud;
+
+  if(bootopt("console", buf, sizeof(buf)) < 0)
+    return;
+  out = 0;
+  for(p = buf; *p; p = q){
+    for(q = p; *q && *q != ','; q++)
+      ;
+    if(*q)
+      *q++ = 0;
+    if(strncmp(p, "vga", 4) == 0 || strncmp(p, "tty0", 5) == 0)
+      out |= CONSVGA;
+    else if(strncmp(p, "fb", 3) == 0){
+      out |= CONSVGA;
+      consfb = 1;
+    }
+    else if(strncmp(p, "ttyS0", 6) == 0)
+      out |= CONSSERIAL;
+    else if(*p >= '1' && *p <= '9'){
+      for(baud = 0; *p >= '0' && *p <= '9'; p++)
+        baud = baud*10 + *p - '0';
+      if(*p == 0 && baud <= 115200 && 115200 % baud == 0)
+        consbaud = baud;
+      else
+        cprintf(KWARN "console:" KEND " bad baud rate\n");
+    } else
+      cprintf(KWARN "console:" KEND " unknown %s\n", p);
+  }
+  if(out)
+    consout = out;
+}
+
+// The serial port's baud rate.
+int
+consolebaud(void)
+{
+  return consbaud;
+}
+
+// Write c to terminal v.  The kernel's console writes to the
+// serial port too, or instead.
+static void
+vtputc(struct vt *v, int c)
 {
   if(panicked){
     cli();
@@ -171,91 +633,293 @@ consputc(int c)
       ;
   }
 
-  if(c == BACKSPACE){
-    uartputc('\b'); uartputc(' '); uartputc('\b');
+  if(v == &vts[0] && (consout & CONSSERIAL)){
+    if(c == BACKSPACE){
+      uartputc('\b'); uartputc(' '); uartputc('\b');
+    } else
+      uartputc(c);
+  }
+  if(v != &vts[0] || (consout & CONSVGA))
+    cgaputc(v, c);
+}
+
+static void
+consputc(int c)
+{
+  vtputc(&vts[0], c);
+}
+
+// Echo c to terminal v, if it echoes.  ESC, which begins the
+// sequences of extended keys, shows as ^[ rather than acting.
+static void
+vtecho(struct vt *v, int c)
+{
+  if(!(v->t.lflag & ECHO))
+    return;
+  if(c == 0x1b){
+    vtputc(v, '^');
+    vtputc(v, '[');
   } else
-    uartputc(c);
-  cgaputc(c);
+    vtputc(v, c);
 }
 
-#define INPUT_BUF 128
-struct {
-  char buf[INPUT_BUF];
+// Put c, typed on terminal v, in its input, and echo it.
+static void
+vtinput(struct vt *v, int c)
+{
+  if(c == 0 || v->e-v->r >= INPUT_BUF)
+    return;
+  if(c == '\r' && (v->t.lflag & ICRNL))
+    c = '\n';
+  v->buf[v->e++ % INPUT_BUF] = c;
+  vtecho(v, c);
+  if(!(v->t.lflag & ICANON) ||
+     c == '\n' || c == C('D') || v->e == v->r+INPUT_BUF){
+    v->w = v->e;
+    cvbroadcast(&v->input);
+  }
+}
+
+// The VT escape sequence that extended key c sends, or 0.
+static char*
+keyseq(int c)
+{
+  static char *seq[] = {
+    [KEY_HOME] "\033[H",   [KEY_END] "\033[F",
+    [KEY_UP] "\033[A",     [KEY_DN] "\033[B",
+    [KEY_RT] "\033[C",     [KEY_LF] "\033[D",
+    [KEY_INS] "\033[2~",   [KEY_DEL] "\033[3~",
+    [KEY_PGUP] "\033[5~",  [KEY_PGDN] "\033[6~",
+    [KEY_F1] "\033OP",     [KEY_F1+1] "\033OQ",
+    [KEY_F1+2] "\033OR",   [KEY_F1+3] "\033OS",
+    [KEY_F1+4] "\033[15~", [KEY_F1+5] "\033[17~",
+    [KEY_F1+6] "\033[18~", [KEY_F1+7] "\033[19~",
+    [KEY_F1+8] "\033[20~", [KEY_F1+9] "\033[21~",
+    [KEY_F1+10] "\033[23~", [KEY_F12] "\033[24~",
+  };
+
+  if(c < 0 || c >= NELEM(seq))
+    return 0;
+  return seq[c];
+}
+
+// Show the kernel's console, live, for a panic.
+static void
+consshow(void)
+{
+  vtshow(&vts[0]);
+  cgalive();
+}
+
+// Characters the keyboard and serial interrupts have taken
+// from the hardware, for consoletype to process, each with
+// RAWKBD if it came from the keyboard.
+#define RAW_BUF 128
+#define RAWKBD  0x100
+static struct {
+  struct spinlock lock;
+  ushort buf[RAW_BUF];
   uint r;  // Read index
   uint w;  // Write index
-  uint e;  // Edit index
-} input;
-
-#define C(x)  ((x)-'@')  // Control-x
+} raw;
 
+// Interrupt handler for console input: take the characters
+// getc returns, and leave the rest to consoletype.
 void
 consoleintr(int (*getc)(void))
 {
-  int c, doprocdump = 0;
+  int c, kbd;
+
+  kbd = getc == kbdgetc ? RAWKBD : 0;
+  acquire(&raw.lock);
+  while((c = getc()) >= 0)
+    if(raw.w - raw.r < RAW_BUF)
+      raw.buf[raw.w++ % RAW_BUF] = (c & 0xFF) | kbd;
+  release(&raw.lock);
+  raisesoftirq(SOFTIRQ_CONS);
+}
+
+// Take the next character from raw, or return -1.
+static int
+rawgetc(void)
+{
+  int c;
+
+  acquire(&raw.lock);
+  c = -1;
+  if(raw.r != raw.w)
+    c = raw.buf[raw.r++ % RAW_BUF];
+  release(&raw.lock);
+  return c;
+}
+
+// Soft interrupt handler for console input: edit the input
+// line, echo, and act on control characters, as far as the
+// terminal's settings say to.  What is typed
+// at the keyboard goes to the shown terminal, and what comes
+// from the serial port to the kernel's console.
+static void
+consoletype(void)
+{
+  int c, kbd, doprocdump = 0, dolockdump = 0, intrpgrp = 0, intrsig = 0;
+  struct vt *v;
+  char *s;
 
   acquire(&cons.lock);
-  while((c = getc()) >= 0){
-    switch(c){
-    case C('P'):  // Process listing.
-      // procdump() locks cons.lock indirectly; invoke later
-      doprocdump = 1;
-      break;
-    case C('U'):  // Kill line.
-      while(input.e != input.w &&
-            input.buf[(input.e-1) % INPUT_BUF] != '\n'){
-        input.e--;
-        consputc(BACKSPACE);
+  while((c = rawgetc()) >= 0){
+    kbd = c & RAWKBD;
+    v = kbd ? shown : &vts[0];
+    c &= 0xFF;
+    if(!kbd || (c != KEY_SPGUP && c != KEY_SPGDN))
+      cgalive();
+    if(kbd){
+      switch(c){
+      case KEY_SPGUP:  // Page back through what scrolled away.
+        cgascroll(ROWS/2);
+        continue;
+      case KEY_SPGDN:
+        cgascroll(-ROWS/2);
+        continue;
+      case KEY_ALTF1: case KEY_ALTF1+1: case KEY_ALTF1+2: case KEY_ALTF1+3:
+        vtshow(&vts[c - KEY_ALTF1]);
+        continue;
       }
-      break;
-    case C('H'): case '\x7f':  // Backspace
-      if(input.e != input.w){
-        input.e--;
-        consputc(BACKSPACE);
+    }
+    if(v->t.lflag & ISIG){
+      switch(c){
+      case C('P'):  // Process listing.
+        // procdump() locks cons.lock indirectly; invoke later
+        doprocdump = 1;
+        continue;
+      case C('L'):  // Lock statistics.
+        dolockdump = 1;
+        continue;
+      case C('C'):  // Interrupt the foreground job.
+      case C('Z'):  // Stop it.
+        // killpg() takes the ptable lock; invoke later.
+        intrpgrp = v->pgrp;
+        intrsig = c == C('C') ? SIGINT : SIGTSTP;
+        v->e = v->w;
+        vtecho(v, '^');
+        vtecho(v, c + '@');
+        vtecho(v, '\n');
+        continue;
       }
-      break;
-    default:
-      if(c != 0 && input.e-input.r < INPUT_BUF){
-        c = (c == '\r') ? '\n' : c;
-        input.buf[input.e++ % INPUT_BUF] = c;
-        consputc(c);
-        if(c == '\n' || c == C('D') || input.e == input.r+INPUT_BUF){
-          input.w = input.e;
-          wakeup(&input.r);
+    }
+    if(v->t.lflag & ICANON){
+      switch(c){
+      case C('U'):  // Kill line.
+        while(v->e != v->w &&
+              v->buf[(v->e-1) % INPUT_BUF] != '\n'){
+          v->e--;
+          vtecho(v, BACKSPACE);
+        }
+        continue;
+      case C('H'): case '\x7f':  // Backspace
+        if(v->e != v->w){
+          v->e--;
+          vtecho(v, BACKSPACE);
         }
+        continue;
       }
-      break;
     }
+    if(kbd && (s = keyseq(c)) != 0){
+      for(; *s; s++)
+        vtinput(v, *s);
+      continue;
+    }
+    vtinput(v, c);
   }
   release(&cons.lock);
   if(doprocdump) {
     procdump();  // now call procdump() wo. cons.lock held
   }
+  if(dolockdump)
+    lockdump();
+  if(intrpgrp)
+    killpg(intrpgrp, intrsig);
+}
+
+// The terminal that console inode ip is.
+static struct vt*
+vtof(struct inode *ip)
+{
+  if(ip->minor < 1 || ip->minor > NVT)
+    return &vts[0];
+  return &vts[ip->minor - 1];
+}
+
+// Make pgid the foreground process group of the terminal with
+// minor number minor, whose processes may read the terminal and
+// receive its interrupts.
+int
+consolesetpgrp(int minor, int pgid)
+{
+  struct vt *v;
+
+  if(!pgrpexists(pgid))
+    return -ESRCH;
+  if(minor > NVT)
+    return -ENODEV;
+  v = &vts[minor > 0 ? minor - 1 : 0];
+  acquire(&cons.lock);
+  v->pgrp = pgid;
+  cvbroadcast(&v->input);
+  release(&cons.lock);
+  return 0;
+}
+
+// May the current process read terminal v?  Any process may
+// if there is no foreground group or it has gone away.
+// Caller holds cons.lock.
+static int
+isforeground(struct vt *v)
+{
+  if(v->pgrp == 0 || myproc()->pgid == v->pgrp)
+    return 1;
+  return !pgrpexists(v->pgrp);
 }
 
+// Read from the terminal, waiting for a line, or without
+// ICANON, for a character; with O_NONBLOCK in flags, fail
+// rather than wait for the first.
 int
-consoleread(struct inode *ip, char *dst, int n)
+consoleread(struct inode *ip, char *dst, int n, int flags)
 {
+  struct vt *v;
   uint target;
   int c;
 
+  v = vtof(ip);
   iunlock(ip);
   target = n;
   acquire(&cons.lock);
   while(n > 0){
-    while(input.r == input.w){
+    // Without ICANON, return what has come so far.
+    if(v->r == v->w && n < target && !(v->t.lflag & ICANON))
+      break;
+    if((v->r == v->w || !isforeground(v)) && (flags & O_NONBLOCK)){
+      if(n < target)
+        break;
+      release(&cons.lock);
+      ilock(ip);
+      return -EAGAIN;
+    }
+    // Background processes wait to be brought to the foreground.
+    while(v->r == v->w || !isforeground(v)){
       if(myproc()->killed){
         release(&cons.lock);
         ilock(ip);
-        return -1;
+        return -EINTR;
       }
-      sleep(&input.r, &cons.lock);
+      cvwait(&v->input);
     }
-    c = input.buf[input.r++ % INPUT_BUF];
-    if(c == C('D')){  // EOF
+    c = v->buf[v->r++ % INPUT_BUF];
+    if(c == C('D') && (v->t.lflag & ICANON)){  // EOF
       if(n < target){
         // Save ^D for next time, to make sure
         // caller gets a 0-byte result.
-        input.r--;
+        v->r--;
       }
       break;
     }
@@ -273,27 +937,105 @@ consoleread(struct inode *ip, char *dst, int n)
 int
 consolewrite(struct inode *ip, char *buf, int n)
 {
+  struct vt *v;
   int i;
 
+  v = vtof(ip);
   iunlock(ip);
   acquire(&cons.lock);
   for(i = 0; i < n; i++)
-    consputc(buf[i] & 0xff);
+    vtputc(v, buf[i] & 0xff);
   release(&cons.lock);
   ilock(ip);
 
   return n;
 }
 
+// Get or set the settings of console inode ip.
+int
+consoleioctl(struct inode *ip, int req, char *arg)
+{
+  struct vt *v;
+
+  v = vtof(ip);
+  acquire(&cons.lock);
+  switch(req){
+  case TCGETS:
+    memmove(arg, &v->t, sizeof(v->t));
+    break;
+  case TCSETS:
+    memmove(&v->t, arg, sizeof(v->t));
+    if(!(v->t.lflag & ICANON)){
+      // Let readers have a line that was being edited.
+      v->w = v->e;
+      cvbroadcast(&v->input);
+    }
+    break;
+  default:
+    release(&cons.lock);
+    return -1;
+  }
+  release(&cons.lock);
+  return 0;
+}
+
+// A read of console inode ip won't wait if there's input
+// and the reader is in the foreground; a write never waits.
+int
+consolepoll(struct inode *ip)
+{
+  struct vt *v;
+  int r;
+
+  v = vtof(ip);
+  acquire(&cons.lock);
+  r = POLLOUT;
+  if(v->r != v->w && isforeground(v))
+    r |= POLLIN;
+  release(&cons.lock);
+  return r;
+}
+
 void
 consoleinit(void)
 {
+  struct vt *v;
+
   initlock(&cons.lock, "console");
+  kloginit();
+  initlock(&raw.lock, "consraw");
+  consoleargs();
+  nocolor = bootflag("nocolor");
+  if(fbinit(ROWS, COLS, consfb) == 0){
+    // Keep what is on the screen, if it makes sense.
+    memmove(text, cga, sizeof(text));
+    cga = vts[0].crt = text;
+    usefb = 1;
+    if(cgagetpos(&vts[0]) >= ROWS*COLS){
+      vts[0].cursor = 0;
+      cgaerase(&vts[0], 0, ROWS*COLS);
+    }
+    cgacursor(cgagetpos(&vts[0]));
+  }
+  for(v = vts; v < &vts[NVT]; v++){
+    initcondvar(&v->input, &cons.lock, "consinput");
+    if(v == &vts[0])
+      continue;
+    v->crt = v->screen;
+    v->attr = 0x0700;
+    v->fg = 7;
+    v->cursor = 0;
+    v->t.lflag = TCOOKED;
+    cgaerase(v, 0, ROWS*COLS);
+  }
+  softirqinit(SOFTIRQ_CONS, consoletype);
 
   devsw[CONSOLE].write = consolewrite;
   devsw[CONSOLE].read = consoleread;
+  devsw[CONSOLE].ioctl = consoleioctl;
+  devsw[CONSOLE].poll = consolepoll;
   cons.locking = 1;
 
-  ioapicenable(IRQ_KBD, 0);
+  kbdinit();
+  irqregister(IRQ_KBD, kbdintr);
 }
-
@@ -0,0 +1,182 @@
+// Processor identification.
+//
+// cpuinfoinit reads each CPU's CPUID leaves once, as it starts,
+// into its struct cpuinfo: vendor and model, where it sits in
+// the package/core/thread topology, and the optional features
+// the kernel cares about.  Code that uses a feature asks cpuhas
+// rather than running CPUID itself, and the cpuinfo system call
+// reports the lot.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "proc.h"
+#include "x86.h"
+#include "cpuinfo.h"
+
+// Leaf 1 %ecx
+#define CPUID1_X2APIC   (1 << 21)
+#define CPUID1_TSCDL    (1 << 24)
+#define CPUID1_XSAVE    (1 << 26)
+#define CPUID1_AVX      (1 << 28)
+// Leaf 1 %edx
+#define CPUID1_PSE      (1 << 3)
+#define CPUID1_FXSR     (1 << 24)
+#define CPUID1_HTT      (1 << 28)
+// Leaf 7 %ebx
+#define CPUID7_FSGSBASE (1 << 0)
+#define CPUID7_SMEP     (1 << 7)
+#define CPUID7_SMAP     (1 << 20)
+// Leaf 0xD subleaf 1 %eax
+#define CPUIDD_XSAVEOPT (1 << 0)
+#define CPUIDD_XSAVEC   (1 << 1)
+#define CPUIDD_XSAVES   (1 << 3)
+// Extended leaves
+#define CPUID_EXT       0x80000000
+#define CPUIDX_PDPE1GB  (1 << 26)   // Leaf 0x80000001 %edx
+#define CPUID_APM       0x80000007
+#define APM_INVTSC      (1 << 8)    // Leaf 0x80000007 %edx
+
+static struct cpuinfo info[NCPU];
+
+// Bits needed to number n things.
+static uint
+bitsfor(uint n)
+{
+  uint b;
+
+  for(b = 0; (1 << b) < n; b++)
+    ;
+  return b;
+}
+
+// Split ci->apicid into package, core and thread numbers.  Use
+// leaf 0xB's field widths if there is one; else work them out
+// from the counts in leaves 1 and 4.
+static void
+topology(struct cpuinfo *ci, uint max, uint ebx1, uint edx1)
+{
+  uint a, b, c, d, smt, core, logical, cores;
+
+  smt = core = 0;
+  b = 0;
+  if(max >= 0xB)
+    cpuidsub(0xB, 0, &a, &b, &c, &d);
+  if(b != 0){
+    smt = a & 0x1F;
+    ci->apicid = d;
+    cpuidsub(0xB, 1, &a, &b, &c, &d);
+    if(b != 0)
+      core = (a & 0x1F) - smt;
+  } else if((edx1 & CPUID1_HTT) && (ebx1 >> 16 & 0xFF) != 0){
+    logical = ebx1 >> 16 & 0xFF;
+    cores = 1;
+    if(max >= 4){
+      cpuidsub(4, 0, &a, &b, &c, &d);
+      cores = (a >> 26) + 1;
+    }
+    core = bitsfor(cores);
+    smt = bitsfor(logical >> core);
+  }
+  ci->thread = ci->apicid & ((1 << smt) - 1);
+  ci->core = ci->apicid >> smt & ((1 << core) - 1);
+  ci->package = ci->apicid >> (smt + core);
+}
+
+// Identify this CPU.  xv6 can't run without 4MB pages, which
+// entry.S has already used.  Run once on entry on each CPU,
+// before the code that asks cpuhas.
+void
+cpuinfoinit(void)
+{
+  struct cpuinfo *ci;
+  uint a, b, c, d, max, xmax, ebx1, edx1, f;
+
+  ci = &info[cpuid()];
+  memset(ci, 0, sizeof(*ci));
+  cpuidregs(0, &max, &b, &c, &d);
+  memmove(ci->vendor, &b, 4);
+  memmove(ci->vendor+4, &d, 4);
+  memmove(ci->vendor+8, &c, 4);
+
+  cpuidregs(1, &a, &ebx1, &c, &edx1);
+  d = edx1;
+  ci->stepping = a & 0xF;
+  ci->model = a >> 4 & 0xF;
+  ci->family = a >> 8 & 0xF;
+  if(ci->family == 0xF)
+    ci->family += a >> 20 & 0xFF;
+  if(ci->family == 0x6 || ci->family >= 0xF)
+    ci->model += (a >> 16 & 0xF) << 4;
+  ci->apicid = ebx1 >> 24;
+  f = 0;
+  if(d & CPUID1_PSE)
+    f |= CPUF_PSE;
+  if(d & CPUID1_FXSR)
+    f |= CPUF_FXSR;
+  if(c & CPUID1_XSAVE)
+    f |= CPUF_XSAVE;
+  if(c & CPUID1_AVX)
+    f |= CPUF_AVX;
+  if(c & CPUID1_X2APIC)
+    f |= CPUF_X2APIC;
+  if(c & CPUID1_TSCDL)
+    f |= CPUF_TSCDL;
+  if(max >= 7){
+    cpuidregs(7, &a, &b, &c, &d);
+    if(b & CPUID7_FSGSBASE)
+      f |= CPUF_FSGSBASE;
+    if(b & CPUID7_SMEP)
+      f |= CPUF_SMEP;
+    if(b & CPUID7_SMAP)
+      f |= CPUF_SMAP;
+  }
+  if(max >= 0xA){
+    // Version 2, at least one counter, and two fixed ones.
+    cpuidregs(0xA, &a, &b, &c, &d);
+    if((a & 0xFF) >= 2 && (a >> 8 & 0xFF) >= 1 && (d & 0x1F) >= 2)
+      f |= CPUF_PMU;
+  }
+  if(max >= 0xD && (f & CPUF_XSAVE)){
+    cpuidsub(0xD, 1, &a, &b, &c, &d);
+    if(a & CPUIDD_XSAVEOPT)
+      f |= CPUF_XSAVEOPT;
+    if(a & CPUIDD_XSAVEC)
+      f |= CPUF_XSAVEC;
+    if(a & CPUIDD_XSAVES)
+      f |= CPUF_XSAVES;
+  }
+  cpuidregs(CPUID_EXT, &xmax, &b, &c, &d);
+  if(xmax >= CPUID_EXT+1){
+    cpuidregs(CPUID_EXT+1, &a, &b, &c, &d);
+    if(d & CPUIDX_PDPE1GB)
+      f |= CPUF_PDPE1GB;
+  }
+  if(xmax >= CPUID_APM){
+    cpuidregs(CPUID_APM, &a, &b, &c, &d);
+    if(d & APM_INVTSC)
+      f |= CPUF_INVTSC;
+  }
+  ci->features = f;
+  if(!(f & CPUF_PSE))
+    panic("cpuinfoinit: no 4MB pages");
+  topology(ci, max, ebx1, edx1);
+}
+
+// Does this CPU have the features in mask?
+int
+cpuhas(uint mask)
+{
+  return (info[cpuid()].features & mask) == mask;
+}
+
+// Copy what cpuinfoinit found about CPU number cpu to *ci.
+int
+cpuinfo(int cpu, struct cpuinfo *ci)
+{
+  if(cpu < 0 || cpu >= ncpu)
+    return -1;
+  *ci = info[cpu];
+  return 0;
+}
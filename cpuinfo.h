@@ -0,0 +1,31 @@
+// Processor identification from CPUID, shared by the kernel
+// and user programs (see cpuinfo.c).
+
+// Features, in cpuinfo.features.
+#define CPUF_PSE       0x00000001  // 4MB pages
+#define CPUF_FXSR      0x00000002  // fxsave and fxrstor
+#define CPUF_XSAVE     0x00000004  // xsave and xrstor
+#define CPUF_XSAVEOPT  0x00000008  // xsaveopt
+#define CPUF_XSAVEC    0x00000010  // Compacted xsave
+#define CPUF_XSAVES    0x00000020  // Supervisor xsave
+#define CPUF_AVX       0x00000040  // AVX registers
+#define CPUF_X2APIC    0x00000080  // x2APIC mode
+#define CPUF_TSCDL     0x00000100  // TSC-deadline LAPIC timer
+#define CPUF_INVTSC    0x00000200  // TSC rate is constant
+#define CPUF_PDPE1GB   0x00000400  // 1GB pages, in long mode
+#define CPUF_FSGSBASE  0x00000800  // rdfsbase &c, in long mode
+#define CPUF_SMEP      0x00001000  // Supervisor execution prevention
+#define CPUF_SMAP      0x00002000  // Supervisor access prevention
+#define CPUF_PMU       0x00004000  // Usable performance counters
+
+struct cpuinfo {
+  char vendor[16];   // As "GenuineIntel", null-terminated
+  uint family;
+  uint model;
+  uint stepping;
+  uint apicid;       // Initial APIC ID
+  uint package;      // Physical package (socket)
+  uint core;         // Core within the package
+  uint thread;       // Hardware thread within the core
+  uint features;     // CPUF_ flags
+};
@@ -0,0 +1,30 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "date.h"
+
+// Print n with at least two digits.
+static void
+put2(int n, char *sep)
+{
+  printf(1, "%s%d%s", n < 10 ? "0" : "", n, sep);
+}
+
+// Print the date and time from the real-time clock.
+int
+main(void)
+{
+  struct rtcdate r;
+
+  if(date(&r) < 0){
+    printf(2, "date: cannot read the clock\n");
+    exit();
+  }
+  printf(1, "%d-", r.year);
+  put2(r.month, "-");
+  put2(r.day, " ");
+  put2(r.hour, ":");
+  put2(r.minute, ":");
+  put2(r.second, " UTC\n");
+  exit();
+}
@@ -0,0 +1,180 @@
+// Directory entry cache.
+//
+// namex looks up each path element here before locking the
+// directory and reading its blocks.  An entry says that name
+// in directory dir on dev is inode inum; names not found
+// aren't cached, nor are "." and "..".
+//
+// Lookups take no lock: entries are read under RCU (see
+// rcu.c), and dcache.lock only serializes changes.  unlink
+// marks an entry dead before it can free the inode, and frees
+// the entry only after a grace period.  A lookup that found an
+// entry takes a reference to the inode and then checks that
+// the entry isn't dead; if it is, the inode may already be
+// something else, so it looks in the directory instead.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "fs.h"
+#include "file.h"
+#include "slab.h"
+#include "rcu.h"
+
+#define NDHASH   64    // Hash buckets
+#define NDENTRY  256   // Entries cached at most
+
+struct dentry {
+  struct rcuhead rcu;    // First, for dentryfree
+  struct dentry *next;   // In hash bucket
+  uint dev;
+  uint dir;              // Inode number of the directory
+  uint inum;
+  char name[DIRSIZ];
+  volatile int dead;     // Removed from its directory
+};
+
+static struct {
+  struct spinlock lock;
+  struct dentry *volatile hash[NDHASH];
+  int n;
+} dcache;
+
+static struct kmcache dentrycache;
+
+void
+dcacheinit(void)
+{
+  initlock(&dcache.lock, "dcache");
+  kmcacheinit(&dentrycache, "dentry", sizeof(struct dentry));
+}
+
+static uint
+hash(uint dev, uint dir, char *name)
+{
+  uint h;
+  int i;
+
+  h = dev * 31 + dir;
+  for(i = 0; i < DIRSIZ && name[i]; i++)
+    h = h * 31 + (uchar)name[i];
+  return h % NDHASH;
+}
+
+// Free a dentry once readers are done with it.
+static void
+dentryfree(struct rcuhead *h)
+{
+  kmfree(&dentrycache, (struct dentry*)h);
+}
+
+static int
+dotname(char *name)
+{
+  return namecmp(name, ".") == 0 || namecmp(name, "..") == 0;
+}
+
+// Return the cached inode for name in dp, referenced but not
+// locked, or 0 if there is none.  dp needn't be locked.
+struct inode*
+dcachelookup(struct inode *dp, char *name)
+{
+  struct dentry *d;
+  struct inode *ip;
+  int dead;
+
+  ip = 0;
+  dead = 0;
+  rcureadlock();
+  for(d = dcache.hash[hash(dp->dev, dp->inum, name)]; d; d = d->next){
+    if(d->dev == dp->dev && d->dir == dp->inum &&
+       namecmp(d->name, name) == 0){
+      ip = iget(d->dev, d->inum);
+      dead = d->dead;
+      break;
+    }
+  }
+  rcureadunlock();
+  if(ip && dead){
+    iput(ip);
+    ip = 0;
+  }
+  return ip;
+}
+
+// Remember that name in dp is inode inum.
+void
+dcacheenter(struct inode *dp, char *name, uint inum)
+{
+  struct dentry *d, *e, *prev;
+  uint h;
+
+  if(dotname(name))
+    return;
+  if((d = kmalloc(&dentrycache)) == 0)
+    return;
+  memset(d, 0, sizeof(*d));
+  d->dev = dp->dev;
+  d->dir = dp->inum;
+  d->inum = inum;
+  strncpy(d->name, name, DIRSIZ);
+  h = hash(d->dev, d->dir, name);
+
+  acquire(&dcache.lock);
+  for(e = dcache.hash[h]; e; e = e->next){
+    if(e->dev == d->dev && e->dir == d->dir &&
+       namecmp(e->name, name) == 0){
+      release(&dcache.lock);
+      kmfree(&dentrycache, d);
+      return;
+    }
+  }
+  if(dcache.n == NDENTRY){
+    // Full: make room by dropping the bucket's last entry.
+    prev = 0;
+    for(e = dcache.hash[h]; e && e->next; e = e->next)
+      prev = e;
+    if(e == 0){
+      release(&dcache.lock);
+      kmfree(&dentrycache, d);
+      return;
+    }
+    if(prev)
+      prev->next = 0;
+    else
+      dcache.hash[h] = 0;
+    dcache.n--;
+    e->dead = 1;
+    callrcu(&e->rcu, dentryfree);
+  }
+  // Readers may see d as soon as it is in the bucket.
+  d->next = dcache.hash[h];
+  __sync_synchronize();
+  dcache.hash[h] = d;
+  dcache.n++;
+  release(&dcache.lock);
+}
+
+// Name in dp is going away.  Call before the inode can be
+// freed, with dp locked.
+void
+dcacheremove(struct inode *dp, char *name)
+{
+  struct dentry *d, **pp;
+
+  acquire(&dcache.lock);
+  pp = (struct dentry**)&dcache.hash[hash(dp->dev, dp->inum, name)];
+  for(; (d = *pp) != 0; pp = &d->next){
+    if(d->dev == dp->dev && d->dir == dp->inum &&
+       namecmp(d->name, name) == 0){
+      d->dead = 1;
+      *pp = d->next;
+      dcache.n--;
+      callrcu(&d->rcu, dentryfree);
+      break;
+    }
+  }
+  release(&dcache.lock);
+}
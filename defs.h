@@ -1,38 +1,115 @@
 struct buf;
+struct condvar;
 struct context;
+struct cpu;
+struct cpuinfo;
 struct file;
 struct inode;
+struct itimerval;
+struct kmcache;
+struct perfinfo;
+struct pinfo;
 struct pipe;
+struct pollfd;
 struct proc;
+struct rlimit;
 struct rtcdate;
+struct rusage;
+struct rcuhead;
+struct rwlock;
+struct scstat;
+struct sigaction;
+struct shmseg;
 struct spinlock;
 struct sleeplock;
 struct stat;
 struct superblock;
+struct sysinfo;
+struct timer;
+struct timespec;
+struct trapframe;
+
+// acpi.c
+void*           acpitable(char*);
 
 // bio.c
 void            binit(void);
 struct buf*     bread(uint, uint);
 void            brelse(struct buf*);
 void            bwrite(struct buf*);
+void            binfo(struct sysinfo*);
+
+// bootargs.c
+void            bootargsinit(void);
+int             bootflag(char*);
+int             bootopt(char*, char*, int);
+int             fwcfgfile(char*, void*, int);
+
+// clock.c
+void            clockinit(void);
+int             clockgettime(int, struct timespec*);
+int             nanosleep(struct timespec*, struct timespec*);
+void            clockcpuinit(void);
+uint64          divmod64(uint64, uint, uint*);
+void            microdelay(int);
+uint64          nsuptime(void);
+int             ticksleep(uint);
+int             vdsomap(pde_t*);
+void            vdsotick(void);
+uint            wallclock(void);
+
+// condvar.c
+void            initcondvar(struct condvar*, struct spinlock*, char*);
+void            cvwait(struct condvar*);
+void            cvsignal(struct condvar*);
+void            cvbroadcast(struct condvar*);
 
 // console.c
 void            consoleinit(void);
 void            cprintf(char*, ...);
 void            consoleintr(int(*)(void));
+int             consolesetpgrp(int, int);
+int             consolebaud(void);
 void            panic(char*) __attribute__((noreturn));
+void            panictf(char*, struct trapframe*, uint*) __attribute__((noreturn));
+
+// cpuinfo.c
+void            cpuinfoinit(void);
+int             cpuhas(uint);
+int             cpuinfo(int, struct cpuinfo*);
+
+// dcache.c
+void            dcacheinit(void);
+struct inode*   dcachelookup(struct inode*, char*);
+void            dcacheenter(struct inode*, char*, uint);
+void            dcacheremove(struct inode*, char*);
 
 // exec.c
-int             exec(char*, char**);
+int             exec(char*, char**, char**);
+
+// fb.c
+int             fbinit(int, int, int);
+void            fbdraw(ushort*, int);
 
 // file.c
 struct file*    filealloc(void);
 void            fileclose(struct file*);
 struct file*    filedup(struct file*);
+int             filegetdents(struct file*, uint, int);
 void            fileinit(void);
-int             fileread(struct file*, char*, int n);
+int             filepread(struct file*, uint, int, uint);
+int             filepwrite(struct file*, uint, int, uint);
+int             fileread(struct file*, uint, int n);
+int             filesend(struct file*, struct file*, uint*, int);
 int             filestat(struct file*, struct stat*);
-int             filewrite(struct file*, char*, int n);
+int             filewrite(struct file*, uint, int n);
+
+// fpu.c
+char*           fpualloc(void);
+void            fpuclear(void);
+void            fpuinit(void);
+void            fpurestore(char*);
+void            fpusave(char*);
 
 // fs.c
 void            readsb(int dev, struct superblock *sb);
@@ -40,46 +117,92 @@ int             dirlink(struct inode*, char*, uint);
 struct inode*   dirlookup(struct inode*, char*, uint*);
 struct inode*   ialloc(uint, short);
 struct inode*   idup(struct inode*);
+struct inode*   iget(uint, uint);
 void            iinit(int dev);
 void            ilock(struct inode*);
 void            iput(struct inode*);
+void            itrunc(struct inode*);
 void            iunlock(struct inode*);
 void            iunlockput(struct inode*);
 void            iupdate(struct inode*);
 int             namecmp(const char*, const char*);
 struct inode*   namei(char*);
 struct inode*   nameiparent(char*, char*);
+int             devread(struct inode*, char*, int, int);
 int             readi(struct inode*, char*, uint, uint);
 void            stati(struct inode*, struct stat*);
 int             writei(struct inode*, char*, uint, uint);
 
+// hpet.c
+uint64          hpetcount(void);
+uint            hpetinit(int*);
+
 // ide.c
 void            ideinit(void);
-void            ideintr(void);
+int             idedisk(int);
 void            iderw(struct buf*);
 
 // ioapic.c
 void            ioapicenable(int irq, int cpu);
 extern uchar    ioapicid;
 void            ioapicinit(void);
+int             irqaffinity(int, uint);
+void            irqenable(int, int);
+void            irqevict(int);
+
+// ipi.c
+void            callall(void(*)(void*), void*);
+void            callcpu(int, void(*)(void*), void*);
+void            callmany(uint, void(*)(void*), void*);
+void            callpoll(void);
+int             cpusstopped(void);
+void            ipiinit(void);
+int             stopcpus(void);
 
 // kalloc.c
 char*           kalloc(void);
+char*           kallocpages(int);
+char*           kallocdma(int);
 void            kfree(char*);
+void            kfreepages(char*, int);
 void            kinit1(void*, void*);
 void            kinit2(void*, void*);
+void            kref(char*);
+int             krefcount(char*);
+void            kallocinfo(struct sysinfo*);
 
 // kbd.c
+int             kbdgetc(void);
+void            kbdinit(void);
 void            kbdintr(void);
 
+// klog.c
+void            kloginit(void);
+void            klogputc(int, int);
+void            kloglevel(char*, int);
+int             klogread(char*, int);
+
+// ksym.c
+char*           ksymlookup(uint, uint*);
+void            ksymprint(uint);
+
 // lapic.c
-void            cmostime(struct rtcdate *r);
 int             lapicid(void);
 extern volatile uint*    lapic;
 void            lapiceoi(void);
 void            lapicinit(void);
+void            lapicipi(int, int);
+void            lapicnmi(int);
+void            lapiconeshot(uint);
+void            lapicpmi(int);
 void            lapicstartap(uchar, uint);
-void            microdelay(int);
+void            lapictimerinit(void);
+void            lapictimerstop(void);
+
+// lockdebug.c
+void            lockacquired(struct spinlock*, int);
+void            lockreleased(struct spinlock*);
+void            lockdump(void);
 
 // log.c
 void            initlog(int dev);
@@ -87,6 +210,12 @@ void            log_write(struct buf*);
 void            begin_op();
 void            end_op();
 
+// main.c
+void            startcpu(struct cpu*);
+
+// mouse.c
+void            mouseinit(void);
+
 // mp.c
 extern int      ismp;
 void            mpinit(void);
@@ -95,50 +224,160 @@ void            mpinit(void);
 void            picenable(int);
 void            picinit(void);
 
+// pagecache.c
+void            pcinit(void);
+char*           pcget(struct inode*, uint);
+void            pcupdate(struct inode*, uint, char*, uint);
+void            pctrunc(struct inode*);
+int             pcreclaim(void);
+void            pcinfo(struct sysinfo*);
+
+// percpu.c
+void            percpuinit(void);
+
 // pipe.c
 int             pipealloc(struct file**, struct file**);
 void            pipeclose(struct pipe*, int);
-int             piperead(struct pipe*, char*, int);
-int             pipewrite(struct pipe*, char*, int);
+void            pipeinit(void);
+int             pipepoll(struct pipe*, int);
+int             piperead(struct pipe*, char*, int, int);
+int             pipesize(struct pipe*, int);
+int             pipewrite(struct pipe*, char*, int, int);
+
+// poll.c
+int             poll(struct pollfd*, int, int);
+void            pollinit(void);
+void            pollwakeup(void);
 
 //PAGEBREAK: 16
 // proc.c
+int             clone(void(*)(void*), void*, void*);
 int             cpuid(void);
+int             cpuoffline(int);
+int             cpuonline(int);
+void            chargetick(struct proc*, int);
+void            chargetime(struct proc*, int);
 void            exit(void);
 int             fork(void);
+int             futexwait(int*, int);
+int             futexwake(int*, int);
+int             getpgid(int);
+int             getitimer(int, struct itimerval*);
+int             getppid(void);
+int             getrlimit(int, struct rlimit*);
+int             getrusage(int, struct rusage*);
 int             growproc(int);
-int             kill(int);
+int             join(void**);
+int             issig(void);
+int             kill(int, int);
+int             killpg(int, int);
+int             kthread(char*, void(*)(void*), void*);
 struct cpu*     mycpu(void);
 struct proc*    myproc();
+int             pgrpexists(int);
+int             pinfo(struct pinfo*, int);
+int             ptrace(int, int, uint, int, void*);
 void            pinit(void);
 void            procdump(void);
+void            realtimers(void);
+void            replaceuvm(pde_t*, uint);
+int             reclaim(void);
 void            scheduler(void) __attribute__((noreturn));
 void            sched(void);
+int             setitimer(int, struct itimerval*, struct itimerval*);
+int             setpgid(int, int);
+int             setrlimit(int, struct rlimit*);
+int             setgid(int);
+int             setuid(int);
+int             sigperm(int);
 void            setproc(struct proc*);
+int             setsid(void);
+int             settrace(int, int);
+void            sysfilter(uint*);
 void            sleep(void*, struct spinlock*);
+void            trapsig(int);
 void            userinit(void);
 int             wait(void);
+int             waitpid(int, int*, int, struct rusage*);
 void            wakeup(void*);
+void            wakeone(void*);
 void            yield(void);
 
+// pmu.c
+int             perfread(struct perfinfo*);
+void            pmuinit(void);
+void            pmuintr(struct trapframe*);
+void            pmustart(void);
+void            pmustop(struct proc*);
+
+// random.c
+void            randinit(void);
+uint            random(void);
+
+// rtc.c
+void            cmostime(struct rtcdate *r);
+int             rtcalarm(int);
+void            rtcinit(void);
+
 // swtch.S
 void            swtch(struct context**, struct context*);
 
+// shm.c
+void            shminit(void);
+int             shmget(int, uint, int);
+int             shmat(int, uint, int);
+int             shmdt(uint);
+int             shmctl(int, int);
+void            shmdup(struct shmseg*);
+void            shmput(struct shmseg*);
+
 // spinlock.c
 void            acquire(struct spinlock*);
 void            getcallerpcs(void*, uint*);
 int             holding(struct spinlock*);
 void            initlock(struct spinlock*, char*);
 void            release(struct spinlock*);
+void            initrwlock(struct rwlock*, char*);
+void            acquireread(struct rwlock*);
+void            releaseread(struct rwlock*);
+void            acquirewrite(struct rwlock*);
+void            releasewrite(struct rwlock*);
 void            pushcli(void);
 void            popcli(void);
 
+// signal.c
+void            psig(struct trapframe*);
+int             sigaction(int, struct sigaction*, struct sigaction*);
+int             sigreturn(void);
+
+// rcu.c
+void            rcuinit(void);
+void            rcuquiescent(void);
+void            rcureadlock(void);
+void            rcureadunlock(void);
+void            callrcu(struct rcuhead*, void(*)(struct rcuhead*));
+void            synchronizercu(void);
+void            rcutick(void);
+
 // sleeplock.c
 void            acquiresleep(struct sleeplock*);
 void            releasesleep(struct sleeplock*);
 int             holdingsleep(struct sleeplock*);
 void            initsleeplock(struct sleeplock*, char*);
 
+// slab.c
+void            kmcacheinit(struct kmcache*, char*, uint);
+void*           kmalloc(struct kmcache*);
+void            kmfree(struct kmcache*, void*);
+void            kminfo(struct sysinfo*);
+void            slabinit(void);
+
+// softirq.c
+void            dosoftirq(void);
+int             insoftirq(void);
+void            raisesoftirq(int);
+void            softirqinit(int, void(*)(void));
+
 // string.c
 int             memcmp(const void*, const void*, uint);
 void*           memmove(void*, const void*, uint);
@@ -148,43 +387,94 @@ int             strlen(const char*);
 int             strncmp(const char*, const char*, uint);
 char*           strncpy(char*, const char*, int);
 
+// swap.c
+void            swapinit(void);
+int             swapalloc(void);
+void            swapdup(int);
+void            swapfree(int);
+void            swapwrite(int, char*);
+void            swapread(int, char*);
+void            swapinfo(struct sysinfo*);
+
 // syscall.c
 int             argint(int, int*);
-int             argptr(int, char**, int);
-int             argstr(int, char**);
+int             argcopyin(int, void*, int);
+int             argcopyout(int, void*, int);
+int             argstr(int, char*, int);
 int             fetchint(uint, int*);
-int             fetchstr(uint, char**);
+int             fetchstr(uint, char*, int);
+extern struct scstat scstat;  // per-CPU (see percpu.h)
 void            syscall(void);
 
 // timer.c
+void            addtimer(struct timer*, uint64, void(*)(void*), void*);
+int             canceltimer(struct timer*);
+void            tickstart(void);
+void            tickstop(void);
+void            timeradopt(int);
+void            timercpuinit(void);
 void            timerinit(void);
+int             timerintr(void);
 
 // trap.c
+void            dfinit(void);
 void            idtinit(void);
-extern uint     ticks;
+int             irqregister(int, void(*)(void));
+extern uint     nintr[256];  // per-CPU (see percpu.h)
 void            tvinit(void);
-extern struct spinlock tickslock;
 
 // uart.c
 void            uartinit(void);
-void            uartintr(void);
 void            uartputc(int);
 
+// vma.c
+void            vmainit(void);
+int             vmaadd(pde_t*, uint, uint, int, int, struct inode*, uint, uint);
+int             vmacopy(pde_t*, pde_t*, uint);
+int             vmafill(pde_t*, uint, char**, int*);
+int             vmaevict(pde_t*, uint);
+void            vmafree(pde_t*);
+int             vmaoverlap(pde_t*, uint, uint);
+uint            vmamap(uint, uint, int, int, struct inode*, uint, struct shmseg*);
+uint            vmashm(pde_t*, uint);
+int             mmap(uint, uint, int, int, struct inode*, uint);
+int             munmap(uint, uint);
+int             msync(uint, uint);
+void            vmasync(pde_t*, uint, uint);
+
 // vm.c
+extern pde_t*   kpgdir;
 void            seginit(void);
+void            smapinit(void);
 void            kvmalloc(void);
 pde_t*          setupkvm(void);
+void*           fbmap(uint, uint);
 char*           uva2ka(pde_t*, char*);
 int             allocuvm(pde_t*, uint, uint);
 int             deallocuvm(pde_t*, uint, uint);
 void            freevm(pde_t*);
+void            freeuvm(pde_t*);
 void            inituvm(pde_t*, char*, uint);
+int             lazyalloc(pde_t*, uint, uint);
+uint            uvmpte(pde_t*, uint);
+char*           lazypage(pde_t*, uint, uint, uint, int*);
+int             lazymap(pde_t*, uint, char*, int);
+void            lazyfree(char*, int);
+int             uvmevict(pde_t*, uint*, char**, int*);
+char*           uvmpage(pde_t*, uint, uint, int);
+int             uvmshare(pde_t*, pde_t*, uint, uint, int);
+int             cowfault(pde_t*, uint);
+int             uvmprivate(pde_t*, uint);
+char*           uvmdirty(pde_t*, uint);
 int             loaduvm(pde_t*, char*, struct inode*, uint, uint);
 pde_t*          copyuvm(pde_t*, uint);
 void            switchuvm(struct proc*);
 void            switchkvm(void);
-int             copyout(pde_t*, uint, void*, uint);
-void            clearpteu(pde_t *pgdir, char *uva);
+void            tlbflush(pde_t*);
+int             uvmfaultin(uint, uint, int);
+int             copyout(pde_t*, uint, uint, void*, uint);
+int             copyin(pde_t*, uint, void*, uint, uint);
+int             copyinstr(pde_t*, uint, char*, uint, uint);
 
 // number of elements in fixed-size array
 #define NELEM(x) (sizeof(x)/sizeof((x)[0]))
@@ -0,0 +1,12 @@
+// Directory entries as getdents returns them, shared by the
+// kernel and user programs.  Records vary in length, so step
+// from one to the next by reclen, or with opendir and readdir.
+
+struct dent {
+  uint ino;        // Inode number
+  ushort reclen;   // Bytes in this record, a multiple of 4
+  char name[];     // Name, nul-terminated
+};
+
+// Length of the record for a name of n bytes.
+#define DENTSIZE(n)  ((sizeof(struct dent) + (n) + 1 + 3) & ~3)
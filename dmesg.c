@@ -0,0 +1,37 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+
+static char buf[16384];
+
+// Print the kernel's messages without their levels, or with
+// -l n only those of level n or below, the more severe.
+int
+main(int argc, char *argv[])
+{
+  int n, level, i, j;
+
+  level = 7;
+  if(argc == 3 && strcmp(argv[1], "-l") == 0)
+    level = atoi(argv[2]);
+  else if(argc != 1){
+    printf(2, "usage: dmesg [-l level]\n");
+    exit();
+  }
+  if((n = dmesg(buf, sizeof(buf))) < 0){
+    printf(2, "dmesg: failed\n");
+    exit();
+  }
+  for(i = 0; i < n; i = j){
+    for(j = i; j < n && buf[j] != '\n'; j++)
+      ;
+    if(j < n)
+      j++;
+    if(j - i >= 3 && buf[i] == '<' && buf[i+2] == '>'){
+      if(buf[i+1] - '0' <= level)
+        write(1, buf + i + 3, j - i - 3);
+    } else
+      write(1, buf + i, j - i);
+  }
+  exit();
+}
@@ -21,6 +21,10 @@ struct elfhdr {
   ushort shstrndx;
 };
 
+// Values for Elfhdr type
+#define ELF_TYPE_EXEC           2
+#define ELF_TYPE_DYN            3
+
 // Program section header
 struct proghdr {
   uint type;
@@ -35,8 +39,47 @@ struct proghdr {
 
 // Values for Proghdr type
 #define ELF_PROG_LOAD           1
+#define ELF_PROG_DYNAMIC        2
+#define ELF_PROG_INTERP         3
+#define ELF_PROG_PHDR           6
+#define ELF_PROG_GNU_STACK      0x6474e551
 
 // Flag bits for Proghdr flags
 #define ELF_PROG_FLAG_EXEC      1
 #define ELF_PROG_FLAG_WRITE     2
 #define ELF_PROG_FLAG_READ      4
+
+// Auxiliary vector entry types.  exec puts pairs of a type
+// and a value after the environment pointers on the stack.
+#define ELF_AUX_NULL            0
+#define ELF_AUX_PHDR            3
+#define ELF_AUX_PHENT           4
+#define ELF_AUX_PHNUM           5
+#define ELF_AUX_PAGESZ          6
+#define ELF_AUX_BASE            7
+#define ELF_AUX_ENTRY           9
+
+// Dynamic section entry
+struct elfdyn {
+  int tag;
+  uint val;
+};
+
+// Values for Elfdyn tag
+#define ELF_DYN_NULL            0
+#define ELF_DYN_RELA            7
+#define ELF_DYN_REL             17
+#define ELF_DYN_RELSZ           18
+#define ELF_DYN_RELENT          19
+
+// Relocation entry, without addend
+struct elfrel {
+  uint off;
+  uint info;
+};
+
+#define ELF_REL_TYPE(info)      ((info) & 0xff)
+
+// Values for the relocation type
+#define ELF_REL_386_NONE        0
+#define ELF_REL_386_RELATIVE    8
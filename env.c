@@ -0,0 +1,29 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+
+// env [NAME=value ...] [command [arg ...]]: set the variables,
+// then run the command, or print the environment if there is
+// none.
+int
+main(int argc, char *argv[])
+{
+  char **e, *eq;
+  int i;
+
+  for(i = 1; i < argc && (eq = strchr(argv[i], '=')) != 0; i++){
+    *eq = 0;
+    if(setenv(argv[i], eq+1, 1) < 0){
+      printf(2, "env: cannot set %s\n", argv[i]);
+      exit();
+    }
+  }
+  if(i < argc){
+    exec(argv[i], argv+i);
+    printf(2, "env: exec %s failed\n", argv[i]);
+    exit();
+  }
+  for(e = environ; e && *e; e++)
+    printf(1, "%s\n", *e);
+  exit();
+}
@@ -0,0 +1,37 @@
+// Error numbers, shared by the kernel and user programs.  A
+// system call that fails returns the negated number; the stubs
+// in usys.S store it in errno and return -1.
+
+#define EPERM     1   // Not permitted
+#define ENOENT    2   // No such file or directory
+#define ESRCH     3   // No such process
+#define EINTR     4   // Interrupted by a signal
+#define EIO       5   // I/O error
+#define E2BIG     7   // Argument list too long
+#define ENOEXEC   8   // Not an executable
+#define EBADF     9   // Bad file descriptor
+#define ECHILD   10   // No child processes
+#define EAGAIN   11   // Would block, or try again
+#define ENOMEM   12   // Out of memory
+#define EACCES   13   // Permission denied
+#define EFAULT   14   // Bad address
+#define EBUSY    16   // Busy
+#define EEXIST   17   // File exists
+#define EXDEV    18   // Link across devices
+#define ENODEV   19   // No such device
+#define ENOTDIR  20   // Not a directory
+#define EISDIR   21   // Is a directory
+#define EINVAL   22   // Invalid argument
+#define ENFILE   23   // File table full
+#define EMFILE   24   // Too many open files
+#define ENOTTY   25   // Not a terminal
+#define EFBIG    27   // File too large
+#define ENOSPC   28   // No space left on device
+#define ESPIPE   29   // Can't seek, or use an offset, on a pipe
+#define EPIPE    32   // Pipe has no reader
+#define ERANGE   34   // Result out of range
+#define ENAMETOOLONG 36  // Path too long
+#define ENOSYS   38   // No such system call
+#define ENOTEMPTY 39  // Directory not empty
+
+#define NERRNO   40   // Error numbers are below this
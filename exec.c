@@ -6,17 +6,273 @@
 #include "defs.h"
 #include "x86.h"
 #include "elf.h"
+#include "signal.h"
+#include "time.h"
+#include "resource.h"
+#include "mman.h"
+#include "fcntl.h"
+#include "errno.h"
 
-int
-exec(char *path, char **argv)
+#define NAUX 7  // auxiliary vector entries, with the terminator
+
+// Return a random multiple of PGSIZE below range, to place
+// parts of a new address space at, or 0 if the kernel was
+// booted with "noaslr".
+static uint
+randpages(uint range)
+{
+  if(bootflag("noaslr"))
+    return 0;
+  return random() % (range / PGSIZE) * PGSIZE;
+}
+
+// Return the protection for a loadable segment with the
+// given flags, or -1 if it is both writable and executable.
+static int
+segprot(uint flags)
+{
+  int prot;
+
+  if((flags & ELF_PROG_FLAG_WRITE) && (flags & ELF_PROG_FLAG_EXEC))
+    return -1;
+  prot = PROT_READ;
+  if(flags & ELF_PROG_FLAG_WRITE)
+    prot |= PROT_WRITE;
+  if(flags & ELF_PROG_FLAG_EXEC)
+    prot |= PROT_EXEC;
+  return prot;
+}
+
+// Apply the relocations of a position-independent program
+// loaded at base in pgdir, whose process has size sz, with its
+// dynamic section at dyn.  Without a dynamic linker there are no
+// symbols to look up, so only R_386_RELATIVE relocations, which
+// add base to a word, are supported.  They may not be in text,
+// which is read-only.
+static int
+relocate(pde_t *pgdir, uint sz, uint base, uint dyn)
+{
+  struct elfdyn d;
+  struct elfrel r;
+  uint rel, relsz, relent, a, w;
+
+  rel = relsz = 0;
+  relent = sizeof(r);
+  for(;; dyn += sizeof(d)){
+    if(copyin(pgdir, sz, &d, dyn, sizeof(d)) < 0)
+      return -1;
+    if(d.tag == ELF_DYN_NULL)
+      break;
+    switch(d.tag){
+    case ELF_DYN_REL:
+      rel = d.val;
+      break;
+    case ELF_DYN_RELSZ:
+      relsz = d.val;
+      break;
+    case ELF_DYN_RELENT:
+      relent = d.val;
+      break;
+    case ELF_DYN_RELA:
+      return -1;
+    }
+  }
+  if(relent != sizeof(r))
+    return -1;
+
+  for(a = 0; a + sizeof(r) <= relsz; a += sizeof(r)){
+    if(copyin(pgdir, sz, &r, base + rel + a, sizeof(r)) < 0)
+      return -1;
+    switch(ELF_REL_TYPE(r.info)){
+    case ELF_REL_386_NONE:
+      break;
+    case ELF_REL_386_RELATIVE:
+      if(copyin(pgdir, sz, &w, base + r.off, sizeof(w)) < 0)
+        return -1;
+      w += base;
+      if(copyout(pgdir, sz, base + r.off, &w, sizeof(w)) < 0)
+        return -1;
+      break;
+    default:
+      return -1;
+    }
+  }
+  return 0;
+}
+
+// What exec learns about a program or interpreter as it maps it.
+struct image {
+  uint base;   // what its addresses are relative to
+  uint entry;
+  uint phdr;   // address of its program headers, if mapped
+  uint phnum;
+  uint dyn;    // address of its dynamic section, or 0
+};
+
+// Map the ELF file ip, locked, into pgdir above sz, which it
+// advances past the program, and describe it in im.  A
+// position-independent program goes at dynbase, with an area
+// that can't be accessed below it, so the fault handler won't
+// take that for heap.  If interp isn't 0, it gets the path of
+// the program's interpreter, or "" if it has none; otherwise
+// the program may not have one.  The pages are read in from the
+// file as the program touches them.
+static int
+loadelf(pde_t *pgdir, struct inode *ip, uint dynbase, uint *sz,
+        struct image *im, char *interp)
 {
-  char *s, *last;
-  int i, off;
-  uint argc, sz, sp, ustack[3+MAXARG+1];
+  int i, off, prot;
+  uint base, start, pad;
   struct elfhdr elf;
-  struct inode *ip;
   struct proghdr ph;
-  pde_t *pgdir, *oldpgdir;
+
+  if(readi(ip, (char*)&elf, 0, sizeof(elf)) != sizeof(elf))
+    return -1;
+  if(elf.magic != ELF_MAGIC)
+    return -1;
+  if(elf.type == ELF_TYPE_EXEC)
+    base = 0;
+  else if(elf.type == ELF_TYPE_DYN)
+    base = dynbase;
+  else
+    return -1;
+  if(base > PGROUNDUP(*sz) &&
+     vmaadd(pgdir, PGROUNDUP(*sz), base, PROT_NONE, MAP_PRIVATE, 0, 0, 0) < 0)
+    return -1;
+  if(base > *sz)
+    *sz = base;
+
+  im->base = base;
+  im->entry = base + elf.entry;
+  im->phdr = 0;
+  im->phnum = elf.phnum;
+  im->dyn = 0;
+  if(interp)
+    interp[0] = 0;
+  for(i=0, off=elf.phoff; i<elf.phnum; i++, off+=sizeof(ph)){
+    if(readi(ip, (char*)&ph, off, sizeof(ph)) != sizeof(ph))
+      return -1;
+    // The stack is writable, so it can't be executable too.
+    if(ph.type == ELF_PROG_GNU_STACK && (ph.flags & ELF_PROG_FLAG_EXEC))
+      return -1;
+    if(ph.type == ELF_PROG_DYNAMIC)
+      im->dyn = base + ph.vaddr;
+    if(ph.type == ELF_PROG_INTERP){
+      if(interp == 0 || ph.filesz < 2 || ph.filesz > MAXPATH)
+        return -1;
+      if(readi(ip, interp, ph.off, ph.filesz) != ph.filesz ||
+         interp[ph.filesz-1] != 0)
+        return -1;
+    }
+    if(ph.type != ELF_PROG_LOAD)
+      continue;
+    if(ph.memsz < ph.filesz)
+      return -1;
+    if(ph.vaddr + ph.memsz < ph.vaddr || ph.vaddr + ph.memsz > MMAPTOP - base)
+      return -1;
+    // A segment may start part way into a page; its area
+    // starts at the page, with the file data before the
+    // segment's.
+    pad = ph.vaddr % PGSIZE;
+    if(ph.off < pad)
+      return -1;
+    start = base + ph.vaddr - pad;
+    if(start < PGROUNDUP(*sz))
+      return -1;
+    if((prot = segprot(ph.flags)) < 0)
+      return -1;
+    // Only the first filesz bytes come from the file; the
+    // fault handler reads them into a zeroed page, so the rest
+    // of the segment (the bss) and of its last page read as 0.
+    if(vmaadd(pgdir, start, base + ph.vaddr + ph.memsz, prot,
+              MAP_PRIVATE, ip, ph.off - pad, ph.filesz + pad) < 0)
+      return -1;
+    if(ph.off <= elf.phoff &&
+       elf.phoff + elf.phnum*sizeof(ph) <= ph.off + ph.filesz)
+      im->phdr = base + ph.vaddr + (elf.phoff - ph.off);
+    *sz = base + ph.vaddr + ph.memsz;
+  }
+  return 0;
+}
+
+static int execat(char*, char**, char**, int);
+
+// Run script ip, which starts "#!interp arg", as interp with
+// argv preceded by the optional arg and the script's path.
+// Unlocks and puts ip, and ends the caller's operation.
+static int
+script(char *path, char **argv, char **envp, struct inode *ip)
+{
+  char line[MAXPATH+32], *interp, *arg, *s, *nargv[MAXARG+1];
+  int n, i;
+
+  n = readi(ip, line, 0, sizeof(line)-1);
+  iunlockput(ip);
+  end_op();
+  if(n < 2)
+    return -ENOEXEC;
+  line[n] = 0;
+  for(s = line; *s != '\n'; s++)
+    if(*s == 0)
+      return -ENOEXEC;
+  // Trim trailing blanks; the argument is the rest of the
+  // line after the interpreter, blanks and all.
+  while(s > line+2 && (s[-1] == ' ' || s[-1] == '\t'))
+    s--;
+  *s = 0;
+  for(interp = line+2; *interp == ' ' || *interp == '\t'; interp++)
+    ;
+  for(arg = interp; *arg && *arg != ' ' && *arg != '\t'; arg++)
+    ;
+  if(*arg){
+    *arg++ = 0;
+    while(*arg == ' ' || *arg == '\t')
+      arg++;
+  }
+  if(*interp == 0)
+    return -ENOEXEC;
+
+  n = 0;
+  nargv[n++] = interp;
+  if(*arg)
+    nargv[n++] = arg;
+  nargv[n++] = path;
+  for(i = 1; argv[0] && argv[i]; i++){
+    if(n >= MAXARG)
+      return -E2BIG;
+    nargv[n++] = argv[i];
+  }
+  nargv[n] = 0;
+  return execat(interp, nargv, envp, 1);
+}
+
+// Run the program at path with arguments argv and environment
+// envp, which may be 0 for none.  Its main gets both:
+//   int main(int argc, char *argv[], char *envp[])
+int
+exec(char *path, char **argv, char **envp)
+{
+  return execat(path, argv, envp, 0);
+}
+
+// Replace the current process's program with the one at path,
+// or if that's a script, with its interpreter, unless this is
+// already running one for a script.  A program that names an
+// interpreter, a dynamic linker, is mapped along with it, and
+// the interpreter runs first, to relocate the program and start
+// it; otherwise exec relocates a position-independent program
+// itself.  The interpreter finds the program from the auxiliary
+// vector, which follows the environment pointers on the stack.
+static int
+execat(char *path, char **argv, char **envp, int inscript)
+{
+  char *s, *last, interp[MAXPATH];
+  int i, err;
+  uint argc, envc, sz, gap, ssz, stop, sp, n;
+  uint ustack[4+MAXARG+1+MAXENV+1+2*NAUX];
+  struct image prog, ld;
+  struct inode *ip;
+  pde_t *pgdir;
   struct proc *curproc = myproc();
 
   begin_op();
@@ -24,67 +280,126 @@ exec(char *path, char **argv)
   if((ip = namei(path)) == 0){
     end_op();
     cprintf("exec: fail\n");
-    return -1;
+    return -ENOENT;
   }
   ilock(ip);
   pgdir = 0;
+  err = -ENOEXEC;
 
-  // Check ELF header
-  if(readi(ip, (char*)&elf, 0, sizeof(elf)) != sizeof(elf))
-    goto bad;
-  if(elf.magic != ELF_MAGIC)
-    goto bad;
+  if(readi(ip, interp, 0, 2) == 2 && memcmp(interp, "#!", 2) == 0 &&
+     !inscript)
+    return script(path, argv, envp, ip);
 
-  if((pgdir = setupkvm()) == 0)
+  if((pgdir = setupkvm()) == 0){
+    err = -ENOMEM;
     goto bad;
-
-  // Load program into memory.
-  sz = 0;
-  for(i=0, off=elf.phoff; i<elf.phnum; i++, off+=sizeof(ph)){
-    if(readi(ip, (char*)&ph, off, sizeof(ph)) != sizeof(ph))
-      goto bad;
-    if(ph.type != ELF_PROG_LOAD)
-      continue;
-    if(ph.memsz < ph.filesz)
-      goto bad;
-    if(ph.vaddr + ph.memsz < ph.vaddr)
-      goto bad;
-    if((sz = allocuvm(pgdir, sz, ph.vaddr + ph.memsz)) == 0)
-      goto bad;
-    if(ph.vaddr % PGSIZE != 0)
-      goto bad;
-    if(loaduvm(pgdir, (char*)ph.vaddr, ip, ph.off, ph.filesz) < 0)
-      goto bad;
   }
+  sz = 0;
+  if(loadelf(pgdir, ip, DYNBASE + randpages(DYNRAND), &sz, &prog, interp) < 0)
+    goto bad;
   iunlockput(ip);
   end_op();
   ip = 0;
 
-  // Allocate two pages at the next page boundary.
-  // Make the first inaccessible.  Use the second as the user stack.
+  ld.base = 0;
+  if(interp[0]){
+    begin_op();
+    if((ip = namei(interp)) == 0){
+      end_op();
+      err = -ENOENT;
+      goto bad;
+    }
+    ilock(ip);
+    if(loadelf(pgdir, ip, PGROUNDUP(sz) + randpages(DYNRAND), &sz, &ld, 0) < 0 ||
+       ld.base == 0)
+      goto bad;
+    iunlockput(ip);
+    end_op();
+    ip = 0;
+  } else if(prog.base > 0 && prog.dyn &&
+            relocate(pgdir, sz, prog.base, prog.dyn) < 0)
+    goto bad;
+
+  // The heap starts at the next page boundary, past a gap of
+  // random size.  The gap is an area that can't be accessed,
+  // so the fault handler won't take it for heap.
   sz = PGROUNDUP(sz);
-  if((sz = allocuvm(pgdir, sz, sz + 2*PGSIZE)) == 0)
+  gap = randpages(HEAPRAND);
+  if(sz + gap > MMAPTOP)
+    goto bad;
+  if(gap > 0 &&
+     vmaadd(pgdir, sz, sz + gap, PROT_NONE, MAP_PRIVATE, 0, 0, 0) < 0)
+    goto bad;
+  sz += gap;
+  if(sz > curproc->rlimcur[RLIMIT_AS])
     goto bad;
-  clearpteu(pgdir, (char*)(sz - 2*PGSIZE));
-  sp = sz;
 
-  // Push argument strings, prepare rest of stack in ustack.
+  // The stack grows down from a random point below USTACKTOP as
+  // the program faults on it, up to RLIMIT_STACK.  Below it is at
+  // least a page that nothing maps, so an overflow faults instead
+  // of running into other memory.  Give it one page now, for the
+  // arguments.
+  stop = USTACKTOP - randpages(STACKRAND);
+  ssz = PGROUNDUP(curproc->rlimcur[RLIMIT_STACK]);
+  if(ssz < PGSIZE)
+    ssz = PGSIZE;
+  if(vmaadd(pgdir, stop - ssz, stop, PROT_READ|PROT_WRITE,
+            MAP_PRIVATE, 0, 0, 0) < 0)
+    goto bad;
+  if(lazyalloc(pgdir, sz, stop - PGSIZE) < 0)
+    goto bad;
+  if(vdsomap(pgdir) < 0)
+    goto bad;
+  sp = stop;
+
+  // Push argument and environment strings, prepare rest of
+  // stack in ustack.
   for(argc = 0; argv[argc]; argc++) {
-    if(argc >= MAXARG)
+    if(argc >= MAXARG){
+      err = -E2BIG;
       goto bad;
+    }
     sp = (sp - (strlen(argv[argc]) + 1)) & ~3;
-    if(copyout(pgdir, sp, argv[argc], strlen(argv[argc]) + 1) < 0)
+    if(copyout(pgdir, sz, sp, argv[argc], strlen(argv[argc]) + 1) < 0)
+      goto bad;
+    ustack[4+argc] = sp;
+  }
+  ustack[4+argc] = 0;
+  for(envc = 0; envp && envp[envc]; envc++) {
+    if(envc >= MAXENV){
+      err = -E2BIG;
+      goto bad;
+    }
+    sp = (sp - (strlen(envp[envc]) + 1)) & ~3;
+    if(copyout(pgdir, sz, sp, envp[envc], strlen(envp[envc]) + 1) < 0)
       goto bad;
-    ustack[3+argc] = sp;
+    ustack[4+argc+1+envc] = sp;
   }
-  ustack[3+argc] = 0;
+  ustack[4+argc+1+envc] = 0;
+
+  n = 4+argc+1+envc+1;
+  ustack[n++] = ELF_AUX_PHDR;
+  ustack[n++] = prog.phdr;
+  ustack[n++] = ELF_AUX_PHENT;
+  ustack[n++] = sizeof(struct proghdr);
+  ustack[n++] = ELF_AUX_PHNUM;
+  ustack[n++] = prog.phnum;
+  ustack[n++] = ELF_AUX_PAGESZ;
+  ustack[n++] = PGSIZE;
+  ustack[n++] = ELF_AUX_BASE;
+  ustack[n++] = ld.base;
+  ustack[n++] = ELF_AUX_ENTRY;
+  ustack[n++] = prog.entry;
+  ustack[n++] = ELF_AUX_NULL;
+  ustack[n++] = 0;
 
   ustack[0] = 0xffffffff;  // fake return PC
   ustack[1] = argc;
-  ustack[2] = sp - (argc+1)*4;  // argv pointer
+  ustack[2] = sp - (n-4)*4;  // argv pointer
+  ustack[3] = ustack[2] + (argc+1)*4;  // envp pointer
 
-  sp -= (3+argc+1) * 4;
-  if(copyout(pgdir, sp, ustack, (3+argc+1)*4) < 0)
+  sp -= n*4;
+  if(copyout(pgdir, sz, sp, ustack, n*4) < 0)
     goto bad;
 
   // Save program name for debugging.
@@ -93,22 +408,38 @@ exec(char *path, char **argv)
       last = s+1;
   safestrcpy(curproc->name, last, sizeof(curproc->name));
 
+  // Caught signals revert to the default action;
+  // ignored signals stay ignored.
+  for(i = 0; i < NSIG; i++)
+    if(curproc->sighandler[i] != SIG_IGN)
+      curproc->sighandler[i] = SIG_DFL;
+
+  // Close descriptors marked close-on-exec.
+  for(i = 0; i < NOFILE; i++)
+    if(curproc->ofile[i] && (curproc->fdflags[i] & FD_CLOEXEC)){
+      fileclose(curproc->ofile[i]);
+      curproc->ofile[i] = 0;
+    }
+
   // Commit to the user image.
-  oldpgdir = curproc->pgdir;
-  curproc->pgdir = pgdir;
-  curproc->sz = sz;
-  curproc->tf->eip = elf.entry;  // main
+  curproc->tf->eip = interp[0] ? ld.entry : prog.entry;  // main
   curproc->tf->esp = sp;
-  switchuvm(curproc);
-  freevm(oldpgdir);
+  curproc->tf->fs = 0;
+  curproc->tf->gs = 0;
+  curproc->tlsbase = 0;
+  curproc->mmapbase = MMAPTOP - randpages(MMAPRAND);
+  replaceuvm(pgdir, sz);
+  fpuclear();
+  if(curproc->tracer)
+    kill(curproc->pid, SIGTRAP);
   return 0;
 
  bad:
-  if(pgdir)
-    freevm(pgdir);
   if(ip){
     iunlockput(ip);
     end_op();
   }
-  return -1;
+  if(pgdir)
+    freeuvm(pgdir);
+  return err;
 }
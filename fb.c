@@ -0,0 +1,288 @@
+// Framebuffer console.
+//
+// On a linear framebuffer the console keeps its screen of CGA
+// character cells in memory, and fbdraw draws the cells that
+// changed as pixels.  The framebuffer is either the one a
+// multiboot loader set up, as the header in entry.S asks, or,
+// with console=fb, one set up on QEMU's standard VGA through
+// its Bochs VBE registers.  Only 32-bit pixels are supported;
+// otherwise the console stays on CGA text.
+//
+// Glyphs come from a 5x7 font, scaled up to fill the screen.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "x86.h"
+#include "ansi.h"
+
+// The part of the multiboot information about the framebuffer.
+struct mbinfo {
+  uint flags;
+  uint unused[21];
+  uint64 fbaddr;
+  uint fbpitch;      // Bytes per line
+  uint fbwidth;
+  uint fbheight;
+  uchar fbbpp;
+  uchar fbtype;
+  uchar rpos, rsize; // Red bits
+  uchar gpos, gsize; // Green bits
+  uchar bpos, bsize; // Blue bits
+} __attribute__((packed));
+
+#define MB_FB      (1<<12)  // Framebuffer fields are valid
+#define MB_RGB     1        // Framebuffer type with direct color
+
+extern uint mbinfo;  // Set by entry.S
+
+#define PCI_ADDR   0xCF8
+#define PCI_DATA   0xCFC
+#define PCI_BOCHS  0x11111234   // Device and vendor of QEMU's VGA
+
+#define VBE_INDEX  0x1CE
+#define VBE_DATA   0x1CF
+#define VBE_ID     0
+#define VBE_XRES   1
+#define VBE_YRES   2
+#define VBE_BPP    3
+#define VBE_ENABLE 4
+#define VBE_ID0    0xB0C0
+#define VBE_ON     0x01
+#define VBE_LFB    0x40         // Linear framebuffer
+
+#define FBWIDTH    1024         // Mode to set with Bochs VBE
+#define FBHEIGHT   768
+
+#define FONTW      5
+#define FONTH      7
+
+// Glyphs for ' ' to '~', a byte per column, top row in bit 0.
+static uchar font[][FONTW] = {
+  {0x00,0x00,0x00,0x00,0x00}, {0x00,0x00,0x5F,0x00,0x00},
+  {0x00,0x07,0x00,0x07,0x00}, {0x14,0x7F,0x14,0x7F,0x14},
+  {0x24,0x2A,0x7F,0x2A,0x12}, {0x23,0x13,0x08,0x64,0x62},
+  {0x36,0x49,0x55,0x22,0x50}, {0x00,0x05,0x03,0x00,0x00},
+  {0x00,0x1C,0x22,0x41,0x00}, {0x00,0x41,0x22,0x1C,0x00},
+  {0x14,0x08,0x3E,0x08,0x14}, {0x08,0x08,0x3E,0x08,0x08},
+  {0x00,0x50,0x30,0x00,0x00}, {0x08,0x08,0x08,0x08,0x08},
+  {0x00,0x60,0x60,0x00,0x00}, {0x20,0x10,0x08,0x04,0x02},
+  {0x3E,0x51,0x49,0x45,0x3E}, {0x00,0x42,0x7F,0x40,0x00},  // 0 1
+  {0x42,0x61,0x51,0x49,0x46}, {0x21,0x41,0x45,0x4B,0x31},
+  {0x18,0x14,0x12,0x7F,0x10}, {0x27,0x45,0x45,0x45,0x39},
+  {0x3C,0x4A,0x49,0x49,0x30}, {0x01,0x71,0x09,0x05,0x03},
+  {0x36,0x49,0x49,0x49,0x36}, {0x06,0x49,0x49,0x29,0x1E},
+  {0x00,0x36,0x36,0x00,0x00}, {0x00,0x56,0x36,0x00,0x00},
+  {0x08,0x14,0x22,0x41,0x00}, {0x14,0x14,0x14,0x14,0x14},
+  {0x00,0x41,0x22,0x14,0x08}, {0x02,0x01,0x51,0x09,0x06},
+  {0x32,0x49,0x79,0x41,0x3E}, {0x7E,0x11,0x11,0x11,0x7E},  // @ A
+  {0x7F,0x49,0x49,0x49,0x36}, {0x3E,0x41,0x41,0x41,0x22},
+  {0x7F,0x41,0x41,0x22,0x1C}, {0x7F,0x49,0x49,0x49,0x41},
+  {0x7F,0x09,0x09,0x09,0x01}, {0x3E,0x41,0x49,0x49,0x7A},
+  {0x7F,0x08,0x08,0x08,0x7F}, {0x00,0x41,0x7F,0x41,0x00},
+  {0x20,0x40,0x41,0x3F,0x01}, {0x7F,0x08,0x14,0x22,0x41},
+  {0x7F,0x40,0x40,0x40,0x40}, {0x7F,0x02,0x0C,0x02,0x7F},
+  {0x7F,0x04,0x08,0x10,0x7F}, {0x3E,0x41,0x41,0x41,0x3E},
+  {0x7F,0x09,0x09,0x09,0x06}, {0x3E,0x41,0x51,0x21,0x5E},
+  {0x7F,0x09,0x19,0x29,0x46}, {0x46,0x49,0x49,0x49,0x31},
+  {0x01,0x01,0x7F,0x01,0x01}, {0x3F,0x40,0x40,0x40,0x3F},
+  {0x1F,0x20,0x40,0x20,0x1F}, {0x3F,0x40,0x38,0x40,0x3F},
+  {0x63,0x14,0x08,0x14,0x63}, {0x07,0x08,0x70,0x08,0x07},
+  {0x61,0x51,0x49,0x45,0x43}, {0x00,0x7F,0x41,0x41,0x00},
+  {0x02,0x04,0x08,0x10,0x20}, {0x00,0x41,0x41,0x7F,0x00},
+  {0x04,0x02,0x01,0x02,0x04}, {0x40,0x40,0x40,0x40,0x40},
+  {0x00,0x01,0x02,0x04,0x00}, {0x20,0x54,0x54,0x54,0x78},  // ` a
+  {0x7F,0x48,0x44,0x44,0x38}, {0x38,0x44,0x44,0x44,0x20},
+  {0x38,0x44,0x44,0x48,0x7F}, {0x38,0x54,0x54,0x54,0x18},
+  {0x08,0x7E,0x09,0x01,0x02}, {0x0C,0x52,0x52,0x52,0x3E},
+  {0x7F,0x08,0x04,0x04,0x78}, {0x00,0x44,0x7D,0x40,0x00},
+  {0x20,0x40,0x44,0x3D,0x00}, {0x7F,0x10,0x28,0x44,0x00},
+  {0x00,0x41,0x7F,0x40,0x00}, {0x7C,0x04,0x18,0x04,0x78},
+  {0x7C,0x08,0x04,0x04,0x78}, {0x38,0x44,0x44,0x44,0x38},
+  {0x7C,0x14,0x14,0x14,0x08}, {0x08,0x14,0x14,0x18,0x7C},
+  {0x7C,0x08,0x04,0x04,0x08}, {0x48,0x54,0x54,0x54,0x20},
+  {0x04,0x3F,0x44,0x40,0x20}, {0x3C,0x40,0x40,0x20,0x7C},
+  {0x1C,0x20,0x40,0x20,0x1C}, {0x3C,0x40,0x30,0x40,0x3C},
+  {0x44,0x28,0x10,0x28,0x44}, {0x0C,0x50,0x50,0x50,0x3C},
+  {0x44,0x64,0x54,0x4C,0x44}, {0x00,0x08,0x36,0x41,0x00},
+  {0x00,0x00,0x7F,0x00,0x00}, {0x00,0x41,0x36,0x08,0x00},
+  {0x08,0x04,0x08,0x10,0x08},
+};
+
+// Red, green and blue of the CGA colors.
+static uchar cgargb[16][3] = {
+  {0x00,0x00,0x00}, {0x00,0x00,0xAA}, {0x00,0xAA,0x00}, {0x00,0xAA,0xAA},
+  {0xAA,0x00,0x00}, {0xAA,0x00,0xAA}, {0xAA,0x55,0x00}, {0xAA,0xAA,0xAA},
+  {0x55,0x55,0x55}, {0x55,0x55,0xFF}, {0x55,0xFF,0x55}, {0x55,0xFF,0xFF},
+  {0xFF,0x55,0x55}, {0xFF,0x55,0xFF}, {0xFF,0xFF,0x55}, {0xFF,0xFF,0xFF},
+};
+
+static struct {
+  uint *base;
+  uint pitch;        // Pixels per line
+  uint width;
+  uint height;
+  int rows, cols;
+  int cw, ch;        // Cell size in pixels
+  int sx, sy;        // Font pixel size in pixels
+  int x0, y0;        // Top left of the text
+  uint color[16];    // Pixel for each CGA color
+  ushort *drawn;     // Cells as drawn
+  int cursor;        // Cursor as drawn, or -1
+} fb;
+
+static uint
+pciread(int dev, int reg)
+{
+  outl(PCI_ADDR, 0x80000000 | dev << 11 | reg);
+  return inl(PCI_DATA);
+}
+
+static void
+vbeset(int index, int val)
+{
+  outw(VBE_INDEX, index);
+  outw(VBE_DATA, val);
+}
+
+// Switch QEMU's standard VGA, if there is one on PCI bus 0,
+// to FBWIDTH by FBHEIGHT with 32-bit pixels.  Returns the
+// framebuffer's physical address, or 0.
+static uint
+bochsfb(void)
+{
+  int dev, id;
+
+  for(dev = 0; dev < 32; dev++)
+    if(pciread(dev, 0) == PCI_BOCHS)
+      break;
+  if(dev == 32)
+    return 0;
+  outw(VBE_INDEX, VBE_ID);
+  id = inw(VBE_DATA);
+  if(id < VBE_ID0 || id > VBE_ID0 + 0xF)
+    return 0;
+
+  vbeset(VBE_ENABLE, 0);
+  vbeset(VBE_XRES, FBWIDTH);
+  vbeset(VBE_YRES, FBHEIGHT);
+  vbeset(VBE_BPP, 32);
+  vbeset(VBE_ENABLE, VBE_ON | VBE_LFB);
+  fb.width = fb.pitch = FBWIDTH;
+  fb.height = FBHEIGHT;
+  return pciread(dev, 0x10) & ~0xF;
+}
+
+// Use a framebuffer for a console of rows by cols cells, if
+// there is one, or if bochs, one that can be set up through
+// Bochs VBE.  Returns 0 if so, -1 if the console is to stay
+// on CGA text.
+int
+fbinit(int rows, int cols, int bochs)
+{
+  struct mbinfo *mb;
+  uint pa, r, g, b;
+  int i, rpos, gpos, bpos;
+
+  if(rows*cols*sizeof(fb.drawn[0]) > PGSIZE)
+    return -1;
+  pa = 0;
+  rpos = 16;
+  gpos = 8;
+  bpos = 0;
+  mb = mbinfo ? P2V(mbinfo) : 0;
+  if(mb && (mb->flags & MB_FB) && mb->fbtype == MB_RGB &&
+     mb->fbbpp == 32 && mb->fbaddr < 0x100000000ULL){
+    pa = mb->fbaddr;
+    fb.pitch = mb->fbpitch / 4;
+    fb.width = mb->fbwidth;
+    fb.height = mb->fbheight;
+    rpos = mb->rpos;
+    gpos = mb->gpos;
+    bpos = mb->bpos;
+  } else if(bochs)
+    pa = bochsfb();
+  if(pa == 0 || fb.pitch*4*fb.height > FBMAX)
+    return -1;
+  if((fb.drawn = (ushort*)kalloc()) == 0)
+    return -1;
+  if((fb.base = fbmap(pa, fb.pitch*4*fb.height)) == 0){
+    kfree((char*)fb.drawn);
+    return -1;
+  }
+
+  fb.rows = rows;
+  fb.cols = cols;
+  fb.sx = fb.width / (cols * (FONTW+1));
+  fb.sy = fb.height / (rows * (FONTH+1));
+  if(fb.sx < 1)
+    fb.sx = 1;
+  if(fb.sy > 2*fb.sx)
+    fb.sy = 2*fb.sx;
+  if(fb.sy < 1)
+    fb.sy = 1;
+  fb.cw = (FONTW+1) * fb.sx;
+  fb.ch = (FONTH+1) * fb.sy;
+  if(fb.cw*cols > fb.width || fb.ch*rows > fb.height)
+    panic("fbinit: screen too small");
+  fb.x0 = (fb.width - fb.cw*cols) / 2;
+  fb.y0 = (fb.height - fb.ch*rows) / 2;
+  for(i = 0; i < 16; i++){
+    r = cgargb[i][0];
+    g = cgargb[i][1];
+    b = cgargb[i][2];
+    fb.color[i] = r << rpos | g << gpos | b << bpos;
+  }
+  memset(fb.base, 0, fb.pitch*4*fb.height);
+  memset(fb.drawn, 0, PGSIZE);
+  fb.cursor = -1;
+  cprintf(KINFO "fb:" KEND " %dx%d at 0x%x\n", fb.width, fb.height, pa);
+  return 0;
+}
+
+// Draw the cell at pos, underlined if the cursor is there.
+static void
+drawcell(int pos, ushort cell, int cursor)
+{
+  uint fg, bg, *p;
+  uchar *g;
+  int c, x, y, gx, gy;
+
+  c = cell & 0xFF;
+  g = (c > ' ' && c <= '~') ? font[c - ' '] : font[0];
+  fg = fb.color[(cell >> 8) & 0xF];
+  bg = fb.color[(cell >> 12) & 0xF];
+  p = fb.base + (fb.y0 + pos/fb.cols*fb.ch)*fb.pitch +
+      fb.x0 + pos%fb.cols*fb.cw;
+  for(y = 0; y < fb.ch; y++, p += fb.pitch){
+    gy = y / fb.sy;
+    for(x = 0; x < fb.cw; x++){
+      gx = x / fb.sx;
+      if(gy == FONTH)
+        p[x] = cursor ? fg : bg;
+      else
+        p[x] = gx < FONTW && (g[gx] >> gy) & 1 ? fg : bg;
+    }
+  }
+}
+
+// Draw the screen cells where they have changed since last
+// time, and the cursor at pos, or none if pos is off the screen.
+void
+fbdraw(ushort *cells, int pos)
+{
+  int i, n;
+
+  n = fb.rows * fb.cols;
+  if(pos < 0 || pos >= n)
+    pos = -1;
+  for(i = 0; i < n; i++){
+    if(cells[i] != fb.drawn[i] || (i == pos) != (i == fb.cursor)){
+      fb.drawn[i] = cells[i];
+      drawcell(i, cells[i], i == pos);
+    }
+  }
+  fb.cursor = pos;
+}
@@ -2,3 +2,29 @@
 #define O_WRONLY  0x001
 #define O_RDWR    0x002
 #define O_CREATE  0x200
+#define O_APPEND  0x400    // Write at the end of the file
+#define O_NONBLOCK 0x800   // Fail reads and writes that would wait
+#define O_TRUNC   0x1000   // Discard a regular file's contents
+#define O_CLOEXEC 0x80000  // Close the new descriptor on exec
+
+#define O_ACCMODE 0x003    // The access mode bits
+#define O_STATUS  (O_APPEND|O_NONBLOCK)  // Kept with the open file
+#define O_VALID   (O_ACCMODE|O_CREATE|O_TRUNC|O_STATUS|O_CLOEXEC)
+
+// Whether flags make sense to open: known bits, and one of the
+// three access modes.
+#define O_OK(flags) \
+  (((flags) & ~O_VALID) == 0 && ((flags) & O_ACCMODE) != O_ACCMODE)
+
+// Descriptor flags.
+#define FD_CLOEXEC    1  // Close on exec
+
+// fcntl commands.
+#define F_DUPFD       0     // Duplicate to the lowest fd >= arg
+#define F_GETFD       1     // Get descriptor flags
+#define F_SETFD       2     // Set them
+#define F_GETFL       3     // Get access mode and status flags
+#define F_SETFL       4     // Set O_APPEND and O_NONBLOCK
+#define F_DUPFD_CLOEXEC 1030  // F_DUPFD, with FD_CLOEXEC
+#define F_SETPIPE_SZ  1031  // Set a pipe's buffer size, in bytes
+#define F_GETPIPE_SZ  1032  // Get it
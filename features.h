@@ -0,0 +1,10 @@
+// What the system call interface offers, for programs to probe
+// before they use calls newer than the kernel might be.
+// features returns the interface's version and a bitmap of the
+// calls the kernel has, bit n of word n/32 for call n.
+
+#define FEATURES_VERSION  1
+
+#define NFEATURES  ((NSYSCALL + 31) / 32)  // Words in the bitmap
+
+#define HASCALL(bits, n)  (((bits)[(n)/32] >> (n)%32) & 1)
@@ -5,21 +5,29 @@
 #include "types.h"
 #include "defs.h"
 #include "param.h"
+#include "stat.h"
+#include "mmu.h"
+#include "proc.h"
 #include "fs.h"
 #include "spinlock.h"
 #include "sleeplock.h"
 #include "file.h"
+#include "fcntl.h"
+#include "errno.h"
+#include "slab.h"
+#include "dirent.h"
 
 struct devsw devsw[NDEV];
 struct {
-  struct spinlock lock;
-  struct file file[NFILE];
+  struct spinlock lock;  // protects ref counts
+  struct kmcache cache;
 } ftable;
 
 void
 fileinit(void)
 {
   initlock(&ftable.lock, "ftable");
+  kmcacheinit(&ftable.cache, "file", sizeof(struct file));
 }
 
 // Allocate a file structure.
@@ -28,16 +36,12 @@ filealloc(void)
 {
   struct file *f;
 
-  acquire(&ftable.lock);
-  for(f = ftable.file; f < ftable.file + NFILE; f++){
-    if(f->ref == 0){
-      f->ref = 1;
-      release(&ftable.lock);
-      return f;
-    }
-  }
-  release(&ftable.lock);
-  return 0;
+  if((f = kmalloc(&ftable.cache)) == 0)
+    return 0;
+  f->type = FD_NONE;
+  f->ref = 1;
+  f->flags = 0;
+  return f;
 }
 
 // Increment ref count for file f.
@@ -66,9 +70,8 @@ fileclose(struct file *f)
     return;
   }
   ff = *f;
-  f->ref = 0;
-  f->type = FD_NONE;
   release(&ftable.lock);
+  kmfree(&ftable.cache, f);
 
   if(ff.type == FD_PIPE)
     pipeclose(ff.pipe, ff.writable);
@@ -79,6 +82,56 @@ fileclose(struct file *f)
   }
 }
 
+// Read the entries of directory f, from its offset on, into
+// user memory at addr as struct dent records, as many as fit
+// in n bytes.  Returns the bytes read, 0 at the end of the
+// directory, or -EINVAL if even the next entry won't fit.
+int
+filegetdents(struct file *f, uint addr, int n)
+{
+  struct proc *curproc = myproc();
+  struct dirent de;
+  struct dent *d;
+  char *buf;
+  int i, len, r;
+
+  if(f->type != FD_INODE || f->readable == 0)
+    return -EBADF;
+  if(n < 0)
+    return -EINVAL;
+  if(n > PGSIZE)
+    n = PGSIZE;
+  if((buf = kalloc()) == 0)
+    return -ENOMEM;
+  i = r = 0;
+  ilock(f->ip);
+  if(f->ip->type != T_DIR)
+    r = -ENOTDIR;
+  while(r == 0 && readi(f->ip, (char*)&de, f->off, sizeof(de)) == sizeof(de)){
+    if(de.inum != 0){
+      for(len = 0; len < DIRSIZ && de.name[len]; len++)
+        ;
+      if(i + DENTSIZE(len) > n){
+        if(i == 0)
+          r = -EINVAL;
+        break;
+      }
+      d = (struct dent*)(buf + i);
+      d->ino = de.inum;
+      d->reclen = DENTSIZE(len);
+      memmove(d->name, de.name, len);
+      memset(d->name + len, 0, d->reclen - sizeof(*d) - len);
+      i += d->reclen;
+    }
+    f->off += sizeof(de);
+  }
+  iunlock(f->ip);
+  if(r == 0 && copyout(curproc->pgdir, curproc->sz, addr, buf, i) < 0)
+    r = -EFAULT;
+  kfree(buf);
+  return r < 0 ? r : i;
+}
+
 // Get metadata about file f.
 int
 filestat(struct file *f, struct stat *st)
@@ -89,69 +142,224 @@ filestat(struct file *f, struct stat *st)
     iunlock(f->ip);
     return 0;
   }
-  return -1;
+  return -EINVAL;
 }
 
-// Read from file f.
-int
-fileread(struct file *f, char *addr, int n)
+// Read from file f, at offset *off, into user address addr
+// of the current process, a page at a time through a kernel
+// buffer, and advance *off.
+// Stops after the first short read, and after the first page
+// from a pipe, which need not have more to give yet.  With
+// O_NONBLOCK, fails rather than wait for a pipe or device.
+static int
+readat(struct file *f, uint addr, int n, uint *off)
 {
-  int r;
+  struct proc *curproc = myproc();
+  char *buf;
+  int i, n1, r;
 
   if(f->readable == 0)
-    return -1;
-  if(f->type == FD_PIPE)
-    return piperead(f->pipe, addr, n);
-  if(f->type == FD_INODE){
-    ilock(f->ip);
-    if((r = readi(f->ip, addr, f->off, n)) > 0)
-      f->off += r;
-    iunlock(f->ip);
-    return r;
+    return -EBADF;
+  if(f->type != FD_PIPE && f->type != FD_INODE)
+    panic("fileread");
+  if(n < 0)
+    return -EINVAL;
+  if((buf = kalloc()) == 0)
+    return -ENOMEM;
+  i = r = 0;
+  while(i < n){
+    n1 = n - i;
+    if(n1 > PGSIZE)
+      n1 = PGSIZE;
+    if(f->type == FD_PIPE)
+      r = piperead(f->pipe, buf, n1, f->flags);
+    else {
+      ilock(f->ip);
+      if(f->ip->type == T_DEV)
+        r = devread(f->ip, buf, n1, f->flags);
+      else if((r = readi(f->ip, buf, *off, n1)) > 0)
+        *off += r;
+      else if(r < 0)
+        r = -EINVAL;
+      iunlock(f->ip);
+    }
+    if(r > 0 && copyout(curproc->pgdir, curproc->sz, addr + i, buf, r) < 0)
+      r = -EFAULT;
+    if(r < 0)
+      break;
+    i += r;
+    if(r < n1 || f->type == FD_PIPE)
+      break;
   }
-  panic("fileread");
+  kfree(buf);
+  return (i == 0 && r < 0) ? r : i;
 }
 
-//PAGEBREAK!
-// Write to file f.
+// Read from file f at its offset.
+int
+fileread(struct file *f, uint addr, int n)
+{
+  return readat(f, addr, n, &f->off);
+}
+
+// Read from file f at offset off, leaving f's offset alone.
+// Pipes have no offsets.
 int
-filewrite(struct file *f, char *addr, int n)
+filepread(struct file *f, uint addr, int n, uint off)
+{
+  if(f->type != FD_INODE)
+    return -ESPIPE;
+  return readat(f, addr, n, &off);
+}
+
+//PAGEBREAK!
+// The most to write to f at once.
+static int
+chunkmax(struct file *f)
+{
+  int max;
+
+  // write a few blocks at a time to avoid exceeding
+  // the maximum log transaction size, including
+  // i-node, doubly-indirect block, two indirect blocks,
+  // allocation blocks, and 2 blocks of slop for
+  // non-aligned writes.
+  // this really belongs lower down, since writei()
+  // might be writing a device like the console.
+  max = ((MAXOPBLOCKS-1-1-2-2) / 2) * 512;
+  if(max > PGSIZE || f->type == FD_PIPE)
+    max = PGSIZE;
+  return max;
+}
+
+// Write n bytes, no more than chunkmax, from kernel buffer
+// buf to file f at offset *off, and advance *off.
+static int
+writechunk(struct file *f, char *buf, int n, uint *off)
 {
   int r;
 
-  if(f->writable == 0)
-    return -1;
   if(f->type == FD_PIPE)
-    return pipewrite(f->pipe, addr, n);
-  if(f->type == FD_INODE){
-    // write a few blocks at a time to avoid exceeding
-    // the maximum log transaction size, including
-    // i-node, indirect block, allocation blocks,
-    // and 2 blocks of slop for non-aligned writes.
-    // this really belongs lower down, since writei()
-    // might be writing a device like the console.
-    int max = ((MAXOPBLOCKS-1-1-2) / 2) * 512;
-    int i = 0;
-    while(i < n){
-      int n1 = n - i;
-      if(n1 > max)
-        n1 = max;
-
-      begin_op();
-      ilock(f->ip);
-      if ((r = writei(f->ip, addr + i, f->off, n1)) > 0)
-        f->off += r;
-      iunlock(f->ip);
-      end_op();
+    return pipewrite(f->pipe, buf, n, f->flags);
+  begin_op();
+  ilock(f->ip);
+  if((f->flags & O_APPEND) && f->ip->type != T_DEV)
+    *off = f->ip->size;
+  if((r = writei(f->ip, buf, *off, n)) > 0)
+    *off += r;
+  else if(r < 0)
+    r = -EFBIG;
+  iunlock(f->ip);
+  end_op();
+  return r;
+}
 
-      if(r < 0)
-        break;
-      if(r != n1)
+// Write to file f, at offset *off, from user address addr of
+// the current process, a chunk at a time through a kernel
+// buffer, and advance *off.
+// With O_NONBLOCK, a full pipe takes what fits, and the
+// write fails only if that is nothing.  With O_APPEND, each
+// chunk goes at the end of the file.
+static int
+writeat(struct file *f, uint addr, int n, uint *off)
+{
+  struct proc *curproc = myproc();
+  char *buf;
+  int i, n1, r, max;
+
+  if(f->writable == 0)
+    return -EBADF;
+  if(f->type != FD_PIPE && f->type != FD_INODE)
+    panic("filewrite");
+  if(n < 0)
+    return -EINVAL;
+  if((buf = kalloc()) == 0)
+    return -ENOMEM;
+  max = chunkmax(f);
+  i = r = 0;
+  while(i < n){
+    n1 = n - i;
+    if(n1 > max)
+      n1 = max;
+    if(copyin(curproc->pgdir, curproc->sz, buf, addr + i, n1) < 0){
+      r = -EFAULT;
+      break;
+    }
+    r = writechunk(f, buf, n1, off);
+    if(r < 0)
+      break;
+    i += r;
+    if(r != n1){
+      if(!(f->flags & O_NONBLOCK))
         panic("short filewrite");
-      i += r;
+      break;
     }
-    return i == n ? n : -1;
   }
-  panic("filewrite");
+  kfree(buf);
+  if(i == n || (i > 0 && (f->flags & O_NONBLOCK)))
+    return i;
+  return r < 0 ? r : -EIO;
+}
+
+// Write to file f at its offset.
+int
+filewrite(struct file *f, uint addr, int n)
+{
+  return writeat(f, addr, n, &f->off);
+}
+
+// Write to file f at offset off, leaving f's offset alone;
+// but with O_APPEND, still at the end.
+int
+filepwrite(struct file *f, uint addr, int n, uint off)
+{
+  if(f->type != FD_INODE)
+    return -ESPIPE;
+  return writeat(f, addr, n, &off);
+}
+
+// Copy up to n bytes from file in, at offset *off, to file
+// out, a chunk at a time through a kernel buffer rather than
+// user space, and advance *off.  Stops at the end of in, or
+// when a non-blocking out takes less than it was given.
+// Returns the number of bytes copied.
+int
+filesend(struct file *out, struct file *in, uint *off, int n)
+{
+  char *buf;
+  int i, n1, r, w, max;
+
+  if(!out->writable || !in->readable)
+    return -EBADF;
+  if(in->type != FD_INODE || n < 0)
+    return -EINVAL;
+  if((buf = kalloc()) == 0)
+    return -ENOMEM;
+  max = chunkmax(out);
+  i = r = w = 0;
+  while(i < n){
+    n1 = n - i;
+    if(n1 > max)
+      n1 = max;
+    ilock(in->ip);
+    r = in->ip->type == T_DEV ? -1 : readi(in->ip, buf, *off, n1);
+    iunlock(in->ip);
+    if(r < 0)
+      r = -EINVAL;
+    if(r <= 0)
+      break;
+    if((w = writechunk(out, buf, r, &out->off)) < 0)
+      break;
+    *off += w;
+    i += w;
+    if(w != r)
+      break;
+  }
+  kfree(buf);
+  if(i == 0 && r < 0)
+    return r;
+  if(i == 0 && w < 0)
+    return w;
+  return i;
 }
 
@@ -6,6 +6,7 @@ struct file {
   struct pipe *pipe;
   struct inode *ip;
   uint off;
+  int flags; // O_APPEND and O_NONBLOCK, if set
 };
 
 
@@ -22,16 +23,20 @@ struct inode {
   short minor;
   short nlink;
   uint size;
-  uint addrs[NDIRECT+1];
+  uint mtime;
+  uint addrs[NDIRECT+2];
 };
 
 // table mapping major device number to
 // device functions
 struct devsw {
-  int (*read)(struct inode*, char*, int);
+  int (*read)(struct inode*, char*, int, int);  // last is file flags
   int (*write)(struct inode*, char*, int);
+  int (*ioctl)(struct inode*, int, char*);
+  int (*poll)(struct inode*);  // POLLIN and POLLOUT, if ready
 };
 
 extern struct devsw devsw[];
 
 #define CONSOLE 1
+#define MOUSE   2
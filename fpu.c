@@ -0,0 +1,128 @@
+// Floating point and SIMD state.
+//
+// The kernel never uses the x87, SSE, or AVX registers itself,
+// so while a process runs, in user space or in the kernel, they
+// hold its state.  The scheduler saves them in the process's
+// p->fpu area when it stops running on a CPU and loads them back
+// before it runs again.  A new process starts with the state
+// fninit leaves, fork and clone copy the parent's state, and
+// exec starts afresh.
+//
+// The state is saved with xsave, covering x87, SSE, and AVX, if
+// the CPU has it; else with fxsave, covering x87 and SSE; else
+// with fnsave, covering the x87 only.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "x86.h"
+#include "cpuinfo.h"
+
+#define FPU_FNSAVE  0
+#define FPU_FXSAVE  1
+#define FPU_XSAVE   2
+
+#define XCR0_X87    0x1
+#define XCR0_SSE    0x2
+#define XCR0_AVX    0x4
+
+struct {
+  int how;            // FPU_FNSAVE, FPU_FXSAVE, or FPU_XSAVE
+  uint size;          // Bytes of saved state
+  char clean[PGSIZE] __attribute__((aligned(64)));  // After fninit
+} fpu;
+
+// Turn on the FPU and whatever SIMD state this CPU can save.
+// The first CPU also records the clean state for new processes.
+// Run once on entry on each CPU.
+void
+fpuinit(void)
+{
+  uint a, b, c, d, cr4;
+  int how, size;
+
+  lcr0((rcr0() & ~(CR0_EM|CR0_TS)) | CR0_MP | CR0_NE);
+  cr4 = rcr4();
+  how = FPU_FNSAVE;
+  size = 108;
+  if(cpuhas(CPUF_FXSR)){
+    cr4 |= CR4_OSFXSR | CR4_OSXMMEXCPT;
+    how = FPU_FXSAVE;
+    size = 512;
+  }
+  if(cpuhas(CPUF_XSAVE))
+    cr4 |= CR4_OSXSAVE;
+  lcr4(cr4);
+  if(cpuhas(CPUF_XSAVE)){
+    cpuidregs(0xD, &a, &b, &c, &d);
+    lxcr0(a & (XCR0_X87|XCR0_SSE|XCR0_AVX));
+    // Now %ebx is the size for the components just enabled.
+    cpuidregs(0xD, &a, &b, &c, &d);
+    how = FPU_XSAVE;
+    size = b;
+  }
+  fninit();
+
+  if(fpu.size == 0){
+    if(size > sizeof(fpu.clean))
+      panic("fpuinit");
+    fpu.how = how;
+    fpu.size = size;
+    fpusave(fpu.clean);
+  }
+}
+
+// Save this CPU's floating point state in area,
+// leaving the registers as they are.
+void
+fpusave(char *area)
+{
+  switch(fpu.how){
+  case FPU_XSAVE:
+    xsave(area);
+    break;
+  case FPU_FXSAVE:
+    fxsave(area);
+    break;
+  default:
+    fnsave(area);  // which also resets the FPU
+    frstor(area);
+  }
+}
+
+// Load this CPU's floating point state from area.
+void
+fpurestore(char *area)
+{
+  switch(fpu.how){
+  case FPU_XSAVE:
+    xrstor(area);
+    break;
+  case FPU_FXSAVE:
+    fxrstor(area);
+    break;
+  default:
+    frstor(area);
+  }
+}
+
+// Return a new save area holding the clean state,
+// or 0 if memory has run out.  Free it with kfree.
+char*
+fpualloc(void)
+{
+  char *area;
+
+  if((area = kalloc()) == 0)
+    return 0;
+  memmove(area, fpu.clean, fpu.size);
+  return area;
+}
+
+// Reset this CPU's floating point state to the clean state.
+void
+fpuclear(void)
+{
+  fpurestore(fpu.clean);
+}
@@ -0,0 +1,202 @@
+// Tests of the file system's system call interface.
+
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "fcntl.h"
+#include "fs.h"
+#include "dirent.h"
+#include "errno.h"
+
+char buf[(NDIRECT+1)*BSIZE];
+
+// stat reports the current layout, with the type in the mode
+// bits and the blocks a file holds.
+void
+stattest(void)
+{
+  struct stat st;
+  int fd;
+
+  printf(1, "stat test\n");
+
+  fd = open("statfile", O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, buf, BSIZE+1) != BSIZE+1 || fstat(fd, &st) < 0){
+    printf(1, "statfile failed\n");
+    exit();
+  }
+  if(st.version != STAT_VERSION || !S_ISREG(st.mode) || S_ISDIR(st.mode) ||
+     st.type != T_FILE || st.nlink != 1 || st.size != BSIZE+1 ||
+     st.blocks != 2 || st.atime != st.mtime || st.ctime != st.mtime){
+    printf(1, "bad stat of statfile: mode %x blocks %d\n", st.mode, st.blocks);
+    exit();
+  }
+  // Past the direct blocks, the indirect block counts too.
+  if(write(fd, buf, sizeof(buf)) != sizeof(buf) || fstat(fd, &st) < 0 ||
+     st.blocks != NDIRECT+4){
+    printf(1, "statfile holds %d blocks\n", st.blocks);
+    exit();
+  }
+  // Past the indirect blocks, so do the doubly-indirect block
+  // and the first indirect block it lists.
+  while(st.size <= (NDIRECT+NINDIRECT)*BSIZE){
+    if(write(fd, buf, sizeof(buf)) != sizeof(buf) || fstat(fd, &st) < 0){
+      printf(1, "statfile write failed\n");
+      exit();
+    }
+  }
+  if(st.blocks != (st.size+BSIZE-1)/BSIZE + 3){
+    printf(1, "statfile holds %d blocks\n", st.blocks);
+    exit();
+  }
+  close(fd);
+  unlink("statfile");
+
+  if(stat(".", &st) < 0 || !S_ISDIR(st.mode) || S_ISREG(st.mode)){
+    printf(1, "bad stat of .\n");
+    exit();
+  }
+  if(stat("console", &st) < 0 || !S_ISCHR(st.mode) || st.major == 0 ||
+     st.size != 0 || st.blocks != 0){
+    printf(1, "bad stat of console\n");
+    exit();
+  }
+  printf(1, "stat test OK\n");
+}
+
+#define NDENT 40
+
+// readdir lists each entry once, with the inode stat reports,
+// and getdents refuses buffers too small and non-directories.
+void
+direnttest(void)
+{
+  struct stat st;
+  struct dent *de;
+  struct dir *d;
+  char name[8];
+  char seen[NDENT];
+  int fd, i;
+
+  printf(1, "dirent test\n");
+
+  if(mkdir("dd") < 0){
+    printf(1, "mkdir dd failed\n");
+    exit();
+  }
+  strcpy(name, "dd/f0");
+  for(i = 0; i < NDENT; i++){
+    name[3] = 'a' + i/10;
+    name[4] = '0' + i%10;
+    if((fd = open(name, O_CREATE|O_RDWR)) < 0){
+      printf(1, "create %s failed\n", name);
+      exit();
+    }
+    close(fd);
+  }
+
+  if((d = opendir("dd")) == 0){
+    printf(1, "opendir dd failed\n");
+    exit();
+  }
+  memset(seen, 0, sizeof(seen));
+  while((de = readdir(d)) != 0){
+    if(strcmp(de->name, ".") == 0 || strcmp(de->name, "..") == 0)
+      continue;
+    i = (de->name[0] - 'a')*10 + de->name[1] - '0';
+    strcpy(name + 3, de->name);
+    if(strlen(de->name) != 2 || i < 0 || i >= NDENT || seen[i]++ ||
+       stat(name, &st) < 0 || st.ino != de->ino){
+      printf(1, "bad entry %s\n", de->name);
+      exit();
+    }
+  }
+  closedir(d);
+  for(i = 0; i < NDENT; i++){
+    if(!seen[i]){
+      printf(1, "readdir missed entry %d\n", i);
+      exit();
+    }
+  }
+
+  fd = open("dd", O_RDONLY);
+  if(getdents(fd, (struct dent*)name, 4) != -1 || errno != EINVAL){
+    printf(1, "getdents into 4 bytes: errno %d\n", errno);
+    exit();
+  }
+  close(fd);
+  fd = open("dd/a0", O_RDONLY);
+  if(getdents(fd, (struct dent*)buf, sizeof(buf)) != -1 || errno != ENOTDIR){
+    printf(1, "getdents of a file: errno %d\n", errno);
+    exit();
+  }
+  close(fd);
+
+  for(i = 0; i < NDENT; i++){
+    name[3] = 'a' + i/10;
+    name[4] = '0' + i%10;
+    name[5] = 0;
+    unlink(name);
+  }
+  if(unlink("dd") < 0){
+    printf(1, "unlink dd failed\n");
+    exit();
+  }
+  printf(1, "dirent test OK\n");
+}
+
+// O_TRUNC empties a file opened for writing, and open refuses
+// unknown flags and a mode both read-only and write-only.
+void
+opentest(void)
+{
+  struct stat st;
+  int fd;
+
+  printf(1, "open test\n");
+
+  fd = open("truncfile", O_CREATE|O_WRONLY);
+  if(fd < 0 || write(fd, buf, 2*BSIZE) != 2*BSIZE){
+    printf(1, "truncfile failed\n");
+    exit();
+  }
+  close(fd);
+  fd = open("truncfile", O_RDONLY|O_TRUNC);
+  if(fd < 0 || fstat(fd, &st) < 0 || st.size != 2*BSIZE){
+    printf(1, "O_RDONLY|O_TRUNC truncated\n");
+    exit();
+  }
+  close(fd);
+  fd = open("truncfile", O_RDWR|O_TRUNC);
+  if(fd < 0 || fstat(fd, &st) < 0 || st.size != 0 || st.blocks != 0 ||
+     read(fd, buf, 1) != 0 || (fcntl(fd, F_GETFL, 0) & O_ACCMODE) != O_RDWR){
+    printf(1, "O_TRUNC left %d bytes\n", st.size);
+    exit();
+  }
+  close(fd);
+  unlink("truncfile");
+
+  if(open(".", O_RDONLY|0x100) != -1 || errno != EINVAL ||
+     open(".", O_WRONLY|O_RDWR) != -1 || errno != EINVAL){
+    printf(1, "open took bad flags\n");
+    exit();
+  }
+  if(open(".", O_WRONLY) != -1 || errno != EISDIR){
+    printf(1, "opened . for writing\n");
+    exit();
+  }
+  printf(1, "open test OK\n");
+}
+
+int
+main(int argc, char *argv[])
+{
+  printf(1, "fstests starting\n");
+
+  stattest();
+  direnttest();
+  opentest();
+
+  printf(1, "ALL TESTS PASSED\n");
+  exit();
+}
@@ -0,0 +1,66 @@
+// High Precision Event Timer.
+// Only the main counter is used, as a time base (see clock.c);
+// the comparators stay off.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "acpi.h"
+
+// Register offsets, divided by 4 for use as uint[] indices.
+#define GCAP     (0x000/4)  // Capabilities; period (fs) in the next word
+  #define COUNT64  0x00002000  // Main counter is 64 bits
+#define GPERIOD  (0x004/4)
+#define GCONF    (0x010/4)  // Configuration
+  #define ENABLE   0x00000001  // Run the main counter
+#define COUNTLO  (0x0F0/4)  // Main counter
+#define COUNTHI  (0x0F4/4)
+
+#define MAXPERIOD  100000000  // Longest period the spec allows (fs)
+
+static volatile uint *hpet;
+static int wide;
+
+// Find the HPET and start its main counter.  Returns the
+// counter's period in femtoseconds, or 0 if there is no HPET
+// the kernel can use.  Sets *is64 if the counter has 64 bits,
+// and so never wraps.
+uint
+hpetinit(int *is64)
+{
+  struct acpihpet *t;
+  uint period;
+
+  if((t = acpitable("HPET")) == 0)
+    return 0;
+  // The kernel maps device memory only at DEVSPACE and up.
+  if(t->space != 0 || t->addrhi != 0 || t->addrlo < DEVSPACE)
+    return 0;
+  hpet = (volatile uint*)t->addrlo;
+  period = hpet[GPERIOD];
+  if(period == 0 || period > MAXPERIOD){
+    hpet = 0;
+    return 0;
+  }
+  wide = (hpet[GCAP] & COUNT64) != 0;
+  hpet[GCONF] |= ENABLE;
+  *is64 = wide;
+  return period;
+}
+
+// Read the main counter.
+uint64
+hpetcount(void)
+{
+  uint hi, lo;
+
+  if(!wide)
+    return hpet[COUNTLO];
+  // The low half may carry into the high half between reads.
+  do {
+    hi = hpet[COUNTHI];
+    lo = hpet[COUNTLO];
+  } while(hi != hpet[COUNTHI]);
+  return ((uint64)hi << 32) | lo;
+}
@@ -33,6 +33,8 @@ static struct buf *idequeue;
 
 static int havedisk1;
 static void idestart(struct buf*);
+static void idedone(void);
+static void ideintr(void);
 
 // Wait for IDE disk to become ready.
 static int
@@ -53,7 +55,8 @@ ideinit(void)
   int i;
 
   initlock(&idelock, "ide");
-  ioapicenable(IRQ_IDE, ncpu - 1);
+  softirqinit(SOFTIRQ_DISK, idedone);
+  irqregister(IRQ_IDE, ideintr);
   idewait(0);
 
   // Check if disk 1 is present
@@ -69,6 +72,14 @@ ideinit(void)
   outb(0x1f6, 0xe0 | (0<<4));
 }
 
+// Whether there is a disk dev.  Disk 0, the boot disk, is
+// always there.
+int
+idedisk(int dev)
+{
+  return dev == 0 || (dev == 1 && havedisk1);
+}
+
 // Start the request for b.  Caller must hold idelock.
 static void
 idestart(struct buf *b)
@@ -99,9 +110,10 @@ idestart(struct buf *b)
   }
 }
 
-// Interrupt handler.
-void
-ideintr(void)
+// Finish the active request and start the next, deferred by
+// ideintr to soft interrupt time.
+static void
+idedone(void)
 {
   struct buf *b;
 
@@ -130,6 +142,14 @@ ideintr(void)
   release(&idelock);
 }
 
+// Interrupt handler.  The disk holds the result until
+// idedone takes it.
+static void
+ideintr(void)
+{
+  raisesoftirq(SOFTIRQ_DISK);
+}
+
 //PAGEBREAK!
 // Sync buf with disk.
 // If B_DIRTY is set, write buf to disk, clear B_DIRTY, set B_VALID.
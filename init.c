@@ -7,17 +7,39 @@
 
 char *argv[] = { "sh", 0 };
 
-int
-main(void)
-{
-  int pid, wpid;
+// The virtual terminals after the console, minors 2 and up.
+char *ttys[] = { "tty2", "tty3", "tty4" };
 
-  if(open("console", O_RDWR) < 0){
-    mknod("console", 1, 1);
-    open("console", O_RDWR);
+// Open tty as stdin, stdout and stderr, making it if need be.
+void
+opentty(char *tty, int minor)
+{
+  if(open(tty, O_RDWR) < 0){
+    mknod(tty, 1, minor);
+    open(tty, O_RDWR);
   }
   dup(0);  // stdout
   dup(0);  // stderr
+}
+
+// Make device file path, unless it's there.
+void
+mkdev(char *path, int major, int minor)
+{
+  int fd;
+
+  if((fd = open(path, O_RDONLY)) >= 0){
+    close(fd);
+    return;
+  }
+  mknod(path, major, minor);
+}
+
+// Keep a shell running on the terminal open as stdin.
+void
+runsh(void)
+{
+  int pid, wpid;
 
   for(;;){
     printf(1, "init: starting sh\n");
@@ -27,6 +49,7 @@ main(void)
       exit();
     }
     if(pid == 0){
+      setsid();
       exec("sh", argv);
       printf(1, "init: exec sh failed\n");
       exit();
@@ -35,3 +58,24 @@ main(void)
       printf(1, "zombie!\n");
   }
 }
+
+int
+main(void)
+{
+  int i;
+
+  opentty("console", 1);
+  mkdir("/dev");
+  mkdev("/dev/mouse", 2, 0);
+  for(i = 0; i < sizeof(ttys)/sizeof(ttys[0]); i++){
+    if(fork() == 0){
+      close(0);
+      close(1);
+      close(2);
+      opentty(ttys[i], i + 2);
+      runsh();
+    }
+  }
+  runsh();
+  exit();
+}
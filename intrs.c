@@ -0,0 +1,57 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "param.h"
+#include "traps.h"
+
+uint counts[NCPU][256];
+
+// A name for vector v, for the vectors that have one.
+static char*
+vecname(int v)
+{
+  switch(v){
+  case T_PGFLT:                return "page fault";
+  case T_CALL:                 return "cross-cpu call";
+  case T_IRQ0 + IRQ_TIMER:     return "timer";
+  case T_IRQ0 + IRQ_KBD:       return "keyboard";
+  case T_IRQ0 + IRQ_COM1:      return "uart";
+  case T_IRQ0 + IRQ_IDE:       return "ide";
+  case T_IRQ0 + IRQ_ERROR:     return "apic error";
+  case T_IRQ0 + IRQ_SPURIOUS:  return "spurious";
+  }
+  return "";
+}
+
+// Print the number of interrupts and exceptions each CPU has
+// taken on each vector, leaving out vectors no CPU has used.
+int
+main(void)
+{
+  int c, v, n, any;
+
+  for(n = 0; n < NCPU; n++)
+    if(intrinfo(n, counts[n]) < 0)
+      break;
+  if(n == 0){
+    printf(2, "intrs: intrinfo failed\n");
+    exit();
+  }
+
+  printf(1, "VEC");
+  for(c = 0; c < n; c++)
+    printf(1, "\tCPU%d", c);
+  printf(1, "\n");
+  for(v = 0; v < 256; v++){
+    any = 0;
+    for(c = 0; c < n; c++)
+      any |= counts[c][v] != 0;
+    if(!any)
+      continue;
+    printf(1, "%d", v);
+    for(c = 0; c < n; c++)
+      printf(1, "\t%d", counts[c][v]);
+    printf(1, "\t%s\n", vecname(v));
+  }
+  exit();
+}
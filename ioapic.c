@@ -4,7 +4,12 @@
 
 #include "types.h"
 #include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
 #include "traps.h"
+#include "ansi.h"
 
 #define IOAPIC  0xFEC00000   // Default physical address of IO APIC
 
@@ -22,8 +27,19 @@
 #define INT_ACTIVELOW  0x00002000  // Active low (vs high)
 #define INT_LOGICAL    0x00000800  // Destination is CPU id (vs APIC ID)
 
+#define NIRQ  24  // Interrupts a PC's I/O APIC has
+
 volatile struct ioapic *ioapic;
 
+// Which CPU and vector each enabled interrupt is routed to;
+// cpu is -1 for one that isn't enabled.
+static struct {
+  struct spinlock lock;
+  int maxintr;
+  int cpu[NIRQ];
+  int vec[NIRQ];
+} irqs;
+
 // IO APIC MMIO structure: write reg, then read or write data.
 struct ioapic {
   uint reg;
@@ -54,7 +70,7 @@ ioapicinit(void)
   maxintr = (ioapicread(REG_VER) >> 16) & 0xFF;
   id = ioapicread(REG_ID) >> 24;
   if(id != ioapicid)
-    cprintf("ioapicinit: id isn't equal to ioapicid; not a MP\n");
+    cprintf(KWARN "ioapicinit:" KEND " id isn't equal to ioapicid; not a MP\n");
 
   // Mark all interrupts edge-triggered, active high, disabled,
   // and not routed to any CPUs.
@@ -62,14 +78,104 @@ ioapicinit(void)
     ioapicwrite(REG_TABLE+2*i, INT_DISABLED | (T_IRQ0 + i));
     ioapicwrite(REG_TABLE+2*i+1, 0);
   }
+
+  initlock(&irqs.lock, "irqs");
+  if(maxintr >= NIRQ)
+    maxintr = NIRQ - 1;
+  irqs.maxintr = maxintr;
+  for(i = 0; i < NIRQ; i++){
+    irqs.cpu[i] = -1;
+    irqs.vec[i] = T_IRQ0 + i;
+  }
+}
+
+// Route interrupt irq to CPU number cpunum and enable it.
+// The irqs lock must be held.
+static void
+route(int irq, int cpunum)
+{
+  if(irq < 0 || irq > irqs.maxintr)
+    panic("ioapic: bad irq");
+  // Mark interrupt edge-triggered, active high,
+  // enabled, and routed to the given cpunum.
+  ioapicwrite(REG_TABLE+2*irq, irqs.vec[irq]);
+  ioapicwrite(REG_TABLE+2*irq+1, cpus[cpunum].apicid << 24);
+  irqs.cpu[irq] = cpunum;
 }
 
 void
 ioapicenable(int irq, int cpunum)
 {
-  // Mark interrupt edge-triggered, active high,
-  // enabled, and routed to the given cpunum,
-  // which happens to be that cpu's APIC ID.
-  ioapicwrite(REG_TABLE+2*irq, T_IRQ0 + irq);
-  ioapicwrite(REG_TABLE+2*irq+1, cpunum << 24);
+  acquire(&irqs.lock);
+  route(irq, cpunum);
+  release(&irqs.lock);
+}
+
+// Return the online CPU in mask with the fewest interrupts
+// routed to it other than irq, or -1 if mask names none.  Ties go
+// to the higher CPU number, so that CPU 0, which also keeps
+// time, gets devices last.  The irqs lock must be held.
+static int
+leastbusy(int irq, uint mask)
+{
+  int c, i, n, best, bestn;
+
+  best = -1;
+  bestn = 0;
+  for(c = 0; c < ncpu && c < 32; c++){
+    if((mask & (1 << c)) == 0 || cpus[c].offline)
+      continue;
+    n = 0;
+    for(i = 0; i < NIRQ; i++)
+      if(i != irq && irqs.cpu[i] == c)
+        n++;
+    if(best < 0 || n <= bestn){
+      best = c;
+      bestn = n;
+    }
+  }
+  return best;
+}
+
+// Enable interrupt irq as vector, routed to the least busy
+// CPU, so that devices spread out across the CPUs.
+void
+irqenable(int irq, int vector)
+{
+  acquire(&irqs.lock);
+  irqs.vec[irq] = vector;
+  route(irq, leastbusy(irq, ~0));
+  release(&irqs.lock);
+}
+
+// Route the interrupts on CPU number cpu, which is going
+// offline, to the others.
+void
+irqevict(int cpu)
+{
+  int i;
+
+  acquire(&irqs.lock);
+  for(i = 0; i <= irqs.maxintr; i++)
+    if(irqs.cpu[i] == cpu)
+      route(i, leastbusy(i, ~0));
+  release(&irqs.lock);
+}
+
+// Route enabled interrupt irq to the least busy of the CPUs
+// whose bits are set in mask.  Returns the CPU chosen.
+int
+irqaffinity(int irq, uint mask)
+{
+  int c;
+
+  acquire(&irqs.lock);
+  if(irq < 0 || irq > irqs.maxintr || irqs.cpu[irq] < 0 ||
+     (c = leastbusy(irq, mask)) < 0){
+    release(&irqs.lock);
+    return -1;
+  }
+  route(irq, c);
+  release(&irqs.lock);
+  return c;
 }
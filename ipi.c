@@ -0,0 +1,112 @@
+// Cross-CPU function calls.
+//
+// callmany runs a function on other CPUs, from an inter-processor
+// interrupt, and waits until each has returned.  One call is in
+// flight at a time.  A CPU spinning with interrupts off for a
+// lock runs calls made to it meanwhile (see callpoll), since the
+// caller may hold that lock.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "traps.h"
+#include "spinlock.h"
+#include "x86.h"
+
+static struct {
+  struct spinlock lock;
+} ipi;
+
+static volatile uint stopping;  // Set once panic stops the CPUs
+
+void
+ipiinit(void)
+{
+  initlock(&ipi.lock, "ipi");
+}
+
+// Run fn(arg) on each started CPU in mask, with bit i for CPU i,
+// including this one if it is in mask, and wait until all have.
+// fn runs with interrupts off, and must not sleep or call
+// callmany.
+void
+callmany(uint mask, void (*fn)(void*), void *arg)
+{
+  struct cpu *c, *me;
+
+  acquire(&ipi.lock);
+  me = mycpu();
+  for(c = cpus; c < cpus+ncpu; c++){
+    if(c == me || !c->started || !(mask & (1 << (c - cpus))))
+      continue;
+    c->callarg = arg;
+    __sync_synchronize();
+    c->callfn = fn;
+    lapicipi(c->apicid, T_CALL);
+  }
+  if(mask & (1 << (me - cpus)))
+    fn(arg);
+  for(c = cpus; c < cpus+ncpu; c++)
+    while(c->callfn)
+      ;
+  release(&ipi.lock);
+}
+
+// Run fn(arg) on CPU number cpu and wait until it has.
+void
+callcpu(int cpu, void (*fn)(void*), void *arg)
+{
+  callmany(1 << cpu, fn, arg);
+}
+
+// Run fn(arg) on every started CPU and wait until all have.
+void
+callall(void (*fn)(void*), void *arg)
+{
+  callmany(~0, fn, arg);
+}
+
+// Run a call made to this CPU, if there is one.  Called from
+// the IPI, and by CPUs spinning with interrupts off.
+void
+callpoll(void)
+{
+  struct cpu *c;
+  void (*fn)(void*);
+
+  c = mycpu();
+  if((fn = c->callfn) == 0)
+    return;
+  fn(c->callarg);
+  __sync_synchronize();
+  c->callfn = 0;
+}
+
+// Stop every other CPU, for panic.  They may be spinning with
+// interrupts off, so use an NMI, which they can't mask, rather
+// than a call; trap halts a CPU that takes one while stopping
+// is set.  Returns 0 if another CPU has already done this.
+int
+stopcpus(void)
+{
+  struct cpu *c;
+  int me;
+
+  if(xchg(&stopping, 1) != 0)
+    return 0;
+  me = lapicid();
+  for(c = cpus; c < cpus+ncpu; c++)
+    if(c->started && c->apicid != me)
+      lapicnmi(c->apicid);
+  return 1;
+}
+
+// Has panic stopped the CPUs?
+int
+cpusstopped(void)
+{
+  return stopping;
+}
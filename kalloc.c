@@ -1,28 +1,81 @@
 // Physical memory allocator, intended to allocate
 // memory for user processes, kernel stacks, page table pages,
-// and pipe buffers. Allocates 4096-byte pages.
+// and pipe buffers. Allocates 4096-byte pages, or physically
+// contiguous blocks of 2^order pages for callers that need them.
+//
+// Free memory is kept by a buddy allocator: a free block of
+// order k is aligned to 2^k pages, and when it and its buddy,
+// the block it would merge with, are both free, they are joined
+// into one free block of order k+1.
+//
+// Memory below DMATOP, which old ISA devices can reach, is a
+// zone of its own.  kallocdma allocates only from it, and other
+// allocations use it only once the rest of memory runs out.
+//
+// Each page has a reference count, so that a page can be
+// mapped in more than one place (by copy-on-write or shared
+// mappings).  kalloc returns a page with one reference, kref
+// adds one, and kfree drops one, freeing the page at zero.
+//
+// When the free list runs dry, kalloc has reclaim (in proc.c)
+// evict a user page, if its caller can wait for that.
+//
+// Freed pages are filled with junk.  Built with KALLOCDEBUG,
+// kalloc also checks that nothing wrote to a free page or its
+// free list entry, and that no page is freed twice.
 
 #include "types.h"
 #include "defs.h"
 #include "param.h"
 #include "memlayout.h"
 #include "mmu.h"
+#include "proc.h"
 #include "spinlock.h"
+#include "sysinfo.h"
+#include "ansi.h"
 
 void freerange(void *vstart, void *vend);
 extern char end[]; // first address after kernel loaded from ELF file
                    // defined by the kernel linker script in kernel.ld
 
+#define NPAGES  (PHYSTOP/PGSIZE)
+#define FREEBLK 0x80           // In head[]: first page of a free block
+#define JUNK    1              // Fills free pages
+
 struct run {
   struct run *next;
+  struct run *prev;
+#ifdef KALLOCDEBUG
+  uint sum;                    // Checksum of the above, see runsum
+#endif
+};
+
+#define ZONE_DMA     0         // Below DMATOP
+#define ZONE_NORMAL  1         // The rest
+#define NZONE        2
+
+struct zone {
+  struct run *free[MAXORDER+1];  // Free blocks of each order
+  uint npage;                    // Pages handed to the allocator
+  uint nfree;                    // Pages free
 };
 
 struct {
   struct spinlock lock;
   int use_lock;
-  struct run *freelist;
+  struct zone zone[NZONE];
+  uchar head[NPAGES];            // FREEBLK|order, or 0
+  ushort ref[NPAGES];            // References to each physical page
 } kmem;
 
+// The zone holding physical address pa.  DMATOP is a multiple
+// of the largest block size, so buddies are in the same zone.
+static struct zone*
+zoneof(uint pa)
+{
+  return &kmem.zone[pa < DMATOP ? ZONE_DMA : ZONE_NORMAL];
+}
+
 // Initialization happens in two phases.
 // 1. main() calls kinit1() while still using entrypgdir to place just
 // the pages mapped by entrypgdir on free list.
@@ -36,10 +89,45 @@ kinit1(void *vstart, void *vend)
   freerange(vstart, vend);
 }
 
+// A region of the firmware's memory map (QEMU's etc/e820).
+struct e820 {
+  uint64 addr;
+  uint64 len;
+  uint type;
+} __attribute__((packed));
+
+#define E820_RAM  1
+
 void
 kinit2(void *vstart, void *vend)
 {
-  freerange(vstart, vend);
+  struct e820 map[32];
+  uint64 lo, hi;
+  int i, n;
+
+  // Free the RAM the memory map lists; without one, assume
+  // everything up to PHYSDEFAULT.  RAM past vend, including
+  // any above 4GB, is out of reach.
+  n = fwcfgfile("etc/e820", map, sizeof(map)) / (int)sizeof(map[0]);
+  if(n <= 0){
+    if(vend > P2V(PHYSDEFAULT))
+      vend = P2V(PHYSDEFAULT);
+    freerange(vstart, vend);
+  }
+  for(i = 0; i < n; i++){
+    if(map[i].type != E820_RAM)
+      continue;
+    lo = map[i].addr;
+    hi = map[i].addr + map[i].len;
+    if(lo < V2P(vstart))
+      lo = V2P(vstart);
+    if(hi > V2P(vend))
+      hi = V2P(vend);
+    if(lo < hi)
+      freerange(P2V((uint)lo), P2V((uint)hi));
+  }
+  cprintf(KINFO "mem:" KEND " %d MB\n", (kmem.zone[ZONE_DMA].npage +
+    kmem.zone[ZONE_NORMAL].npage) / (1024*1024/PGSIZE));
   kmem.use_lock = 1;
 }
 
@@ -48,32 +136,251 @@ freerange(void *vstart, void *vend)
 {
   char *p;
   p = (char*)PGROUNDUP((uint)vstart);
-  for(; p + PGSIZE <= (char*)vend; p += PGSIZE)
+  for(; p + PGSIZE <= (char*)vend; p += PGSIZE){
+    zoneof(V2P(p))->npage++;
     kfree(p);
+  }
 }
 //PAGEBREAK: 21
-// Free the page of physical memory pointed at by v,
-// which normally should have been returned by a
-// call to kalloc().  (The exception is when
-// initializing the allocator; see kinit above.)
+// Drop a reference to the page of physical memory pointed
+// at by v, and free it if that was the last one.  v
+// normally should have been returned by a call to kalloc().
+// (The exception is when initializing the allocator; see
+// kinit above.)
 void
 kfree(char *v)
 {
-  struct run *r;
+  ushort *ref;
 
   if((uint)v % PGSIZE || v < end || V2P(v) >= PHYSTOP)
     panic("kfree");
 
+  ref = &kmem.ref[V2P(v) / PGSIZE];
+  if(kmem.use_lock)
+    acquire(&kmem.lock);
+  if(*ref > 1){
+    // Still in use elsewhere.
+    (*ref)--;
+    if(kmem.use_lock)
+      release(&kmem.lock);
+    return;
+  }
+  if(kmem.use_lock)
+    release(&kmem.lock);
+
+  kfreepages(v, 0);
+}
+
+#ifdef KALLOCDEBUG
+// Report a corrupted free page at physical address pa.
+static void
+badfree(char *what, uint pa)
+{
+  cprintf("kalloc: %s at 0x%x\n", what, pa);
+  panic("kalloc");
+}
+
+static uint
+runsum(struct run *r)
+{
+  return (uint)r->next ^ (uint)r->prev ^ (uint)r ^ 0x5A5A5A5A;
+}
+#endif
+
+// Record, or check, that free list entry r is as kalloc left it.
+static void
+sealrun(struct run *r)
+{
+#ifdef KALLOCDEBUG
+  r->sum = runsum(r);
+#endif
+}
+
+static void
+checkrun(struct run *r)
+{
+#ifdef KALLOCDEBUG
+  if(r->sum != runsum(r))
+    badfree("free list entry overwritten", V2P(r));
+#endif
+}
+
+// Check that the n bytes of the free block at v still hold junk.
+static void
+checkjunk(char *v, uint n)
+{
+#ifdef KALLOCDEBUG
+  uint i;
+
+  for(i = 0; i < n; i++)
+    if(v[i] != JUNK)
+      badfree("free page written", V2P(v + i));
+#endif
+}
+
+static void
+pushfree(struct run *r, int order)
+{
+  struct zone *z = zoneof(V2P(r));
+
+  r->prev = 0;
+  r->next = z->free[order];
+  if(r->next){
+    checkrun(r->next);
+    r->next->prev = r;
+    sealrun(r->next);
+  }
+  sealrun(r);
+  z->free[order] = r;
+  kmem.head[V2P(r) / PGSIZE] = FREEBLK | order;
+}
+
+// Take r off its free list, and put junk back where it was,
+// since r is no longer the start of a free block.
+static void
+unlinkfree(struct run *r, int order)
+{
+  checkrun(r);
+  if(r->prev){
+    checkrun(r->prev);
+    r->prev->next = r->next;
+    sealrun(r->prev);
+  } else
+    zoneof(V2P(r))->free[order] = r->next;
+  if(r->next){
+    checkrun(r->next);
+    r->next->prev = r->prev;
+    sealrun(r->next);
+  }
+  kmem.head[V2P(r) / PGSIZE] = 0;
+  memset(r, JUNK, sizeof(*r));
+}
+
+// Free the block of 2^order pages at v, which kallocpages
+// returned, merging it with its buddies as far as possible.
+void
+kfreepages(char *v, int order)
+{
+  uint pn, bn, i;
+
+  if(order < 0 || order > MAXORDER)
+    panic("kfreepages");
+  pn = V2P(v) / PGSIZE;
+  if(pn % (1 << order) || v < end || pn + (1 << order) > NPAGES)
+    panic("kfreepages");
+
+  if(kmem.use_lock)
+    acquire(&kmem.lock);
+  for(i = 0; i < (1 << order); i++){
+#ifdef KALLOCDEBUG
+    // While booting, pages are freed without being allocated.
+    if(kmem.ref[pn + i] == 0 && kmem.use_lock)
+      badfree("double free", (pn + i) * PGSIZE);
+#endif
+    kmem.ref[pn + i] = 0;
+  }
+  if(kmem.use_lock)
+    release(&kmem.lock);
+
   // Fill with junk to catch dangling refs.
-  memset(v, 1, PGSIZE);
+  memset(v, JUNK, PGSIZE << order);
+
+  if(kmem.use_lock)
+    acquire(&kmem.lock);
+  zoneof(V2P(v))->nfree += 1 << order;
+  for(; order < MAXORDER; order++){
+    bn = pn ^ (1 << order);
+    if(bn >= NPAGES || kmem.head[bn] != (FREEBLK | order))
+      break;
+    unlinkfree((struct run*)P2V(bn * PGSIZE), order);
+    pn &= ~(1 << order);
+  }
+  pushfree((struct run*)P2V(pn * PGSIZE), order);
+  if(kmem.use_lock)
+    release(&kmem.lock);
+}
+
+// Allocate a block of 2^order pages from zone z.
+// Returns 0 if it has no free block big enough.
+// The caller must hold kmem.lock.
+static char*
+zalloc(struct zone *z, int order)
+{
+  struct run *r;
+  int k;
+  uint pn, i;
+
+  for(k = order; k <= MAXORDER && z->free[k] == 0; k++)
+    ;
+  if(k > MAXORDER)
+    return 0;
+  r = z->free[k];
+  unlinkfree(r, k);
+  // Split it, freeing the upper halves.
+  while(k > order){
+    k--;
+    pushfree((struct run*)((char*)r + (PGSIZE << k)), k);
+  }
+  checkjunk((char*)r, PGSIZE << order);
+  pn = V2P(r) / PGSIZE;
+  for(i = 0; i < (1 << order); i++)
+    kmem.ref[pn + i] = 1;
+  z->nfree -= 1 << order;
+  return (char*)r;
+}
+
+// Allocate a physically contiguous block of 2^order pages,
+// aligned to its size.  Each page starts with one reference.
+// Returns 0 if there is no free block big enough; unlike
+// kalloc, it does not wait for reclaim.
+char*
+kallocpages(int order)
+{
+  char *r;
+
+  if(order < 0 || order > MAXORDER)
+    return 0;
+  if(kmem.use_lock)
+    acquire(&kmem.lock);
+  if((r = zalloc(&kmem.zone[ZONE_NORMAL], order)) == 0)
+    r = zalloc(&kmem.zone[ZONE_DMA], order);
+  if(kmem.use_lock)
+    release(&kmem.lock);
+  return r;
+}
+
+// Like kallocpages, but from memory below DMATOP, for devices
+// that cannot reach higher.
+char*
+kallocdma(int order)
+{
+  char *r;
 
+  if(order < 0 || order > MAXORDER)
+    return 0;
   if(kmem.use_lock)
     acquire(&kmem.lock);
-  r = (struct run*)v;
-  r->next = kmem.freelist;
-  kmem.freelist = r;
+  r = zalloc(&kmem.zone[ZONE_DMA], order);
   if(kmem.use_lock)
     release(&kmem.lock);
+  return r;
+}
+
+// Can kalloc's caller sleep while reclaim frees a page?
+// Only a process holding no spinlocks can.
+static int
+canreclaim(void)
+{
+  struct cpu *c;
+  int r;
+
+  if(!kmem.use_lock)
+    return 0;  // still booting
+  pushcli();
+  c = mycpu();
+  r = c->proc != 0 && c->ncli == 1;
+  popcli();
+  return r;
 }
 
 // Allocate one 4096-byte page of physical memory.
@@ -82,15 +389,55 @@ kfree(char *v)
 char*
 kalloc(void)
 {
-  struct run *r;
+  char *r;
+
+  for(;;){
+    r = kallocpages(0);
+    if(r || !canreclaim() || !reclaim())
+      return r;
+  }
+}
+
+// Add a reference to the allocated page pointed at by v,
+// for a second mapping of it.
+void
+kref(char *v)
+{
+  if((uint)v % PGSIZE || v < end || V2P(v) >= PHYSTOP)
+    panic("kref");
 
   if(kmem.use_lock)
     acquire(&kmem.lock);
-  r = kmem.freelist;
-  if(r)
-    kmem.freelist = r->next;
+  if(kmem.ref[V2P(v) / PGSIZE] == 0)
+    panic("kref: free page");
+  kmem.ref[V2P(v) / PGSIZE]++;
   if(kmem.use_lock)
     release(&kmem.lock);
-  return (char*)r;
 }
 
+// Return the number of references to the page pointed at by v.
+int
+krefcount(char *v)
+{
+  int n;
+
+  if(kmem.use_lock)
+    acquire(&kmem.lock);
+  n = kmem.ref[V2P(v) / PGSIZE];
+  if(kmem.use_lock)
+    release(&kmem.lock);
+  return n;
+}
+
+
+// Fill in the page counts of si.
+void
+kallocinfo(struct sysinfo *si)
+{
+  acquire(&kmem.lock);
+  si->dmapages = kmem.zone[ZONE_DMA].npage;
+  si->dmafree = kmem.zone[ZONE_DMA].nfree;
+  si->totalpages = si->dmapages + kmem.zone[ZONE_NORMAL].npage;
+  si->freepages = si->dmafree + kmem.zone[ZONE_NORMAL].nfree;
+  release(&kmem.lock);
+}
@@ -3,6 +3,69 @@
 #include "defs.h"
 #include "kbd.h"
 
+// Set 1 code for each set 2 code below 0x84, as the keyboard
+// controller translates them.  0 if there is none.
+static uchar set2to1[0x84] = {
+  0x00, 0x43, 0x41, 0x3f, 0x3d, 0x3b, 0x3c, 0x58,  // 0x00
+  0x64, 0x44, 0x42, 0x40, 0x3e, 0x0f, 0x29, 0x59,
+  0x65, 0x38, 0x2a, 0x70, 0x1d, 0x10, 0x02, 0x5a,  // 0x10
+  0x66, 0x71, 0x2c, 0x1f, 0x1e, 0x11, 0x03, 0x5b,
+  0x67, 0x2e, 0x2d, 0x20, 0x12, 0x05, 0x04, 0x5c,  // 0x20
+  0x68, 0x39, 0x2f, 0x21, 0x14, 0x13, 0x06, 0x5d,
+  0x69, 0x31, 0x30, 0x23, 0x22, 0x15, 0x07, 0x5e,  // 0x30
+  0x6a, 0x72, 0x32, 0x24, 0x16, 0x08, 0x09, 0x5f,
+  0x6b, 0x33, 0x25, 0x17, 0x18, 0x0b, 0x0a, 0x60,  // 0x40
+  0x6c, 0x34, 0x35, 0x26, 0x27, 0x19, 0x0c, 0x61,
+  0x6d, 0x73, 0x28, 0x74, 0x1a, 0x0d, 0x62, 0x6e,  // 0x50
+  0x3a, 0x36, 0x1c, 0x1b, 0x75, 0x2b, 0x63, 0x76,
+  0x55, 0x56, 0x77, 0x78, 0x79, 0x7a, 0x0e, 0x7b,  // 0x60
+  0x7c, 0x4f, 0x7d, 0x4b, 0x47, 0x7e, 0x7f, 0x6f,
+  0x52, 0x53, 0x50, 0x4c, 0x4d, 0x48, 0x01, 0x45,  // 0x70
+  0x57, 0x4e, 0x51, 0x4a, 0x37, 0x49, 0x46, 0x54,
+  0x00, 0x00, 0x00, 0x41,                          // 0x80
+};
+
+static int set2;  // Scan codes arrive untranslated, in set 2
+
+// Find out which scan code set the keyboard sends.  Most
+// controllers translate set 2 to set 1, but not all do.
+void
+kbdinit(void)
+{
+  int i;
+
+  for(i = 0; i < 10000 && (inb(KBSTATP) & KBS_IBF); i++)
+    microdelay(10);
+  outb(KBCMDP, KBC_RDCFG);
+  for(i = 0; i < 10000 && !(inb(KBSTATP) & KBS_DIB); i++)
+    microdelay(10);
+  if(i < 10000 && !(inb(KBDATAP) & KBC_XLATE))
+    set2 = 1;
+}
+
+// Translate data, a byte of set 2, to set 1.  Returns -1 for
+// a byte that has no translation of its own, such as the
+// prefix of a key release, which sets the top bit of the next.
+static int
+fromset2(uint data)
+{
+  static uint release;
+
+  if(data == 0xF0){
+    release = 0x80;
+    return -1;
+  }
+  if(data == 0xE0 || data == 0xE1)
+    return data;
+  if(data >= NELEM(set2to1) || set2to1[data] == 0){
+    release = 0;
+    return -1;
+  }
+  data = set2to1[data] | release;
+  release = 0;
+  return data;
+}
+
 int
 kbdgetc(void)
 {
@@ -13,9 +76,11 @@ kbdgetc(void)
   uint st, data, c;
 
   st = inb(KBSTATP);
-  if((st & KBS_DIB) == 0)
+  if((st & KBS_DIB) == 0 || (st & KBS_AUXB))  // the mouse's
     return -1;
   data = inb(KBDATAP);
+  if(set2 && (int)(data = fromset2(data)) < 0)
+    return 0;
 
   if(data == 0xE0){
     shift |= E0ESC;
@@ -33,6 +98,8 @@ kbdgetc(void)
 
   shift |= shiftcode[data];
   shift ^= togglecode[data];
+  if((shift & ALT) && data >= 0x3B && data <= 0x3E)
+    return KEY_ALTF1 + data - 0x3B;
   c = charcode[shift & (CTL | SHIFT)][data];
   if(shift & CAPSLOCK){
     if('a' <= c && c <= 'z')
@@ -2,6 +2,11 @@
 
 #define KBSTATP         0x64    // kbd controller status port(I)
 #define KBS_DIB         0x01    // kbd data in buffer
+#define KBS_IBF         0x02    // controller input buffer full
+#define KBS_AUXB        0x20    // data in buffer is from the mouse
+#define KBCMDP          0x64    // kbd controller command port(O)
+#define KBC_RDCFG       0x20    // read the configuration byte
+#define KBC_XLATE       0x40    // config: translate set 2 to set 1
 #define KBDATAP         0x60    // kbd data port(I)
 
 #define NO              0
@@ -16,7 +21,9 @@
 
 #define E0ESC           (1<<6)
 
-// Special keycodes
+// Special keycodes.  The console sends the keys from KEY_HOME
+// to KEY_DEL and KEY_F1 to KEY_F12 to programs as VT escape
+// sequences, and acts on the others itself.
 #define KEY_HOME        0xE0
 #define KEY_END         0xE1
 #define KEY_UP          0xE2
@@ -27,6 +34,11 @@
 #define KEY_PGDN        0xE7
 #define KEY_INS         0xE8
 #define KEY_DEL         0xE9
+#define KEY_SPGUP       0xEA    // Shift+PgUp: scroll the console back
+#define KEY_SPGDN       0xEB    // Shift+PgDn
+#define KEY_ALTF1       0xEC    // Alt+F1 to Alt+F4: switch terminals
+#define KEY_F1          0xF0    // F1 to F12 follow
+#define KEY_F12         (KEY_F1+11)
 
 // C('A') == Control-A
 #define C(x) (x - '@')
@@ -57,16 +69,17 @@ static uchar normalmap[256] =
   'd',  'f',  'g',  'h',  'j',  'k',  'l',  ';',  // 0x20
   '\'', '`',  NO,   '\\', 'z',  'x',  'c',  'v',
   'b',  'n',  'm',  ',',  '.',  '/',  NO,   '*',  // 0x30
-  NO,   ' ',  NO,   NO,   NO,   NO,   NO,   NO,
-  NO,   NO,   NO,   NO,   NO,   NO,   NO,   '7',  // 0x40
+  NO,   ' ',  NO,   KEY_F1, KEY_F1+1, KEY_F1+2, KEY_F1+3, KEY_F1+4,
+  KEY_F1+5, KEY_F1+6, KEY_F1+7, KEY_F1+8, KEY_F1+9, NO, NO, '7',  // 0x40
   '8',  '9',  '-',  '4',  '5',  '6',  '+',  '1',
-  '2',  '3',  '0',  '.',  NO,   NO,   NO,   NO,   // 0x50
+  '2',  '3',  '0',  '.',  NO,   NO,   NO,   KEY_F1+10,  // 0x50
+  [0x58] KEY_F12,
   [0x9C] '\n',      // KP_Enter
   [0xB5] '/',       // KP_Div
   [0xC8] KEY_UP,    [0xD0] KEY_DN,
   [0xC9] KEY_PGUP,  [0xD1] KEY_PGDN,
   [0xCB] KEY_LF,    [0xCD] KEY_RT,
-  [0x97] KEY_HOME,  [0xCF] KEY_END,
+  [0xC7] KEY_HOME,  [0xCF] KEY_END,
   [0xD2] KEY_INS,   [0xD3] KEY_DEL
 };
 
@@ -79,16 +92,17 @@ static uchar shiftmap[256] =
   'D',  'F',  'G',  'H',  'J',  'K',  'L',  ':',  // 0x20
   '"',  '~',  NO,   '|',  'Z',  'X',  'C',  'V',
   'B',  'N',  'M',  '<',  '>',  '?',  NO,   '*',  // 0x30
-  NO,   ' ',  NO,   NO,   NO,   NO,   NO,   NO,
-  NO,   NO,   NO,   NO,   NO,   NO,   NO,   '7',  // 0x40
+  NO,   ' ',  NO,   KEY_F1, KEY_F1+1, KEY_F1+2, KEY_F1+3, KEY_F1+4,
+  KEY_F1+5, KEY_F1+6, KEY_F1+7, KEY_F1+8, KEY_F1+9, NO, NO, '7',  // 0x40
   '8',  '9',  '-',  '4',  '5',  '6',  '+',  '1',
-  '2',  '3',  '0',  '.',  NO,   NO,   NO,   NO,   // 0x50
+  '2',  '3',  '0',  '.',  NO,   NO,   NO,   KEY_F1+10,  // 0x50
+  [0x58] KEY_F12,
   [0x9C] '\n',      // KP_Enter
   [0xB5] '/',       // KP_Div
   [0xC8] KEY_UP,    [0xD0] KEY_DN,
-  [0xC9] KEY_PGUP,  [0xD1] KEY_PGDN,
+  [0xC9] KEY_SPGUP, [0xD1] KEY_SPGDN,
   [0xCB] KEY_LF,    [0xCD] KEY_RT,
-  [0x97] KEY_HOME,  [0xCF] KEY_END,
+  [0xC7] KEY_HOME,  [0xCF] KEY_END,
   [0xD2] KEY_INS,   [0xD3] KEY_DEL
 };
 
@@ -106,7 +120,7 @@ static uchar ctlmap[256] =
   [0xC8] KEY_UP,    [0xD0] KEY_DN,
   [0xC9] KEY_PGUP,  [0xD1] KEY_PGDN,
   [0xCB] KEY_LF,    [0xCD] KEY_RT,
-  [0x97] KEY_HOME,  [0xCF] KEY_END,
+  [0xC7] KEY_HOME,  [0xCF] KEY_END,
   [0xD2] KEY_INS,   [0xD3] KEY_DEL
 };
 
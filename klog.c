@@ -0,0 +1,140 @@
+// Kernel message log.
+//
+// cprintf also writes what it prints here, into a ring of NLOG
+// bytes that the dmesg system call reads.  Each line starts
+// with its level and the seconds since boot, as in
+//   <4>[   12.000345] cpu1: NMI at 8:80104d2c
+// The level is 3 for a line colored as an error with KERR (see
+// ansi.h), 4 for one colored as a warning with KWARN, and 6
+// otherwise.  The colors themselves are left out.
+//
+// cprintf calls in with cons.lock held, or with locking 0 when
+// it takes no locks, as in a panic.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "spinlock.h"
+#include "ansi.h"
+
+#define NLOG  (PGSIZE << KLOGORDER)
+
+static struct {
+  struct spinlock lock;
+  char buf[NLOG];
+  uint w;        // Bytes written, ever
+  uint level;    // Index of the current line's level digit
+  char next;     // Level of the next line, if not 6
+  int bol;       // At the beginning of a line
+} klog = { .bol = 1 };
+
+void
+kloginit(void)
+{
+  initlock(&klog.lock, "klog");
+}
+
+static void
+put(char c)
+{
+  klog.buf[klog.w++ % NLOG] = c;
+}
+
+// Log x in decimal, padded with pad to width.
+static void
+putnum(uint x, int width, char pad)
+{
+  char d[10];
+  int i;
+
+  i = 0;
+  do{
+    d[i++] = '0' + x % 10;
+  }while((x /= 10) != 0);
+  for(; width > i; width--)
+    put(pad);
+  while(--i >= 0)
+    put(d[i]);
+}
+
+// Log c, which cprintf is printing.
+void
+klogputc(int c, int locking)
+{
+  uint ns;
+  uint64 s;
+
+  if(locking)
+    acquire(&klog.lock);
+  if(klog.bol){
+    s = divmod64(nsuptime(), 1000000000, &ns);
+    put('<');
+    klog.level = klog.w;
+    put(klog.next ? klog.next : '6');
+    put('>');
+    put('[');
+    putnum(s, 5, ' ');
+    put('.');
+    putnum(ns / 1000, 6, '0');
+    put(']');
+    put(' ');
+    klog.next = 0;
+    klog.bol = 0;
+  }
+  put(c);
+  if(c == '\n')
+    klog.bol = 1;
+  if(locking)
+    release(&klog.lock);
+}
+
+// cprintf is printing escape sequence esc: if it is KERR or
+// KWARN, raise the level of the line to match.
+void
+kloglevel(char *esc, int locking)
+{
+  char level;
+
+  if(strncmp(esc, KERR, sizeof(KERR)-1) == 0)
+    level = '3';
+  else if(strncmp(esc, KWARN, sizeof(KWARN)-1) == 0)
+    level = '4';
+  else
+    return;
+  if(locking)
+    acquire(&klog.lock);
+  if(klog.bol){
+    if(klog.next == 0 || level < klog.next)
+      klog.next = level;
+  } else if(klog.w - klog.level < NLOG && level < klog.buf[klog.level % NLOG])
+    klog.buf[klog.level % NLOG] = level;
+  if(locking)
+    release(&klog.lock);
+}
+
+// Copy the most recent messages, up to n bytes of whole lines,
+// into dst.  Returns the number of bytes copied.
+int
+klogread(char *dst, int n)
+{
+  uint r, w;
+  int i;
+
+  acquire(&klog.lock);
+  w = klog.w;
+  // Keep the byte before the first, to look for a newline.
+  if(n > NLOG - 1)
+    n = NLOG - 1;
+  if(n > w)
+    n = w;
+  // Start after a newline, unless that's the start of the log.
+  r = w - n;
+  if(r > 0)
+    while(r < w && klog.buf[(r-1) % NLOG] != '\n')
+      r++;
+  for(i = 0; r < w; i++, r++)
+    dst[i] = klog.buf[r % NLOG];
+  release(&klog.lock);
+  return i;
+}
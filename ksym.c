@@ -0,0 +1,45 @@
+// Kernel function names, for backtraces.
+//
+// The build links the kernel twice, the second time with a
+// table of its functions sorted by address (see ksyms.pl).
+
+#include "types.h"
+#include "defs.h"
+
+struct ksym {
+  uint addr;
+  char *name;
+};
+
+extern struct ksym ksyms[];  // Ends with a zero entry
+extern char etext[];
+
+// Return the name of the function containing pc, and set *off
+// to pc's offset in it.  Returns 0 if pc isn't kernel text.
+char*
+ksymlookup(uint pc, uint *off)
+{
+  struct ksym *s, *best;
+
+  if(pc >= (uint)etext)
+    return 0;
+  best = 0;
+  for(s = ksyms; s->addr != 0 && s->addr <= pc; s++)
+    best = s;
+  if(best == 0)
+    return 0;
+  *off = pc - best->addr;
+  return best->name;
+}
+
+// Print pc, and the function it's in if there is one.
+void
+ksymprint(uint pc)
+{
+  char *name;
+  uint off;
+
+  cprintf("%p", pc);
+  if((name = ksymlookup(pc, &off)) != 0)
+    cprintf(" %s+0x%x", name, off);
+}
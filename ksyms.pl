@@ -0,0 +1,28 @@
+#!/usr/bin/perl -w
+
+# Generate symtab.S, the kernel's table of function names for
+# symbolized backtraces (see ksym.c), from "nm -n kernel" on
+# standard input.  With no input, generate an empty table, for
+# the first link of the kernel, which the names come from.
+# The table goes in .rodata, after the text, so adding it
+# doesn't move any function.
+
+my @syms;
+while(<STDIN>){
+    my ($addr, $type, $name) = split;
+    next unless defined $name && $type =~ /^[tT]$/;
+    push @syms, [$addr, $name];
+}
+
+print "# generated by ksyms.pl - do not edit\n";
+print ".section .rodata\n";
+print ".globl ksyms\n";
+print ".p2align 2\n";
+print "ksyms:\n";
+for(my $i = 0; $i <= $#syms; $i++){
+    print "  .long 0x$syms[$i][0], ksymname$i\n";
+}
+print "  .long 0, 0\n";
+for(my $i = 0; $i <= $#syms; $i++){
+    print "ksymname$i: .string \"$syms[$i][1]\"\n";
+}
@@ -4,11 +4,12 @@
 #include "param.h"
 #include "types.h"
 #include "defs.h"
-#include "date.h"
 #include "memlayout.h"
 #include "traps.h"
 #include "mmu.h"
+#include "proc.h"
 #include "x86.h"
+#include "cpuinfo.h"
 
 // Local APIC registers, divided by 4 for use as uint[] indices.
 #define ID      (0x0020/4)   // ID
@@ -21,6 +22,7 @@
 #define ICRLO   (0x0300/4)   // Interrupt Command
   #define INIT       0x00000500   // INIT/RESET
   #define STARTUP    0x00000600   // Startup IPI
+  #define NMI        0x00000400   // Non-maskable interrupt
   #define DELIVS     0x00001000   // Delivery status
   #define ASSERT     0x00004000   // Assert interrupt (vs deassert)
   #define DEASSERT   0x00000000
@@ -32,6 +34,7 @@
 #define TIMER   (0x0320/4)   // Local Vector Table 0 (TIMER)
   #define X1         0x0000000B   // divide counts by 1
   #define PERIODIC   0x00020000   // Periodic
+  #define TSCDEADLINE 0x00040000  // Interrupt when TSC reaches MSR
 #define PCINT   (0x0340/4)   // Performance Counter LVT
 #define LINT0   (0x0350/4)   // Local Vector Table 1 (LINT0)
 #define LINT1   (0x0360/4)   // Local Vector Table 2 (LINT1)
@@ -41,6 +44,9 @@
 #define TCCR    (0x0390/4)   // Timer Current Count
 #define TDCR    (0x03E0/4)   // Timer Divide Configuration
 
+#define MSR_TSC_DEADLINE  0x6E0  // TSC value of the next timer interrupt
+#define CALMS             10     // Timer calibration interval (ms)
+
 volatile uint *lapic;  // Initialized in mp.c
 
 //PAGEBREAK!
@@ -60,13 +66,12 @@ lapicinit(void)
   // Enable local APIC; set spurious interrupt vector.
   lapicw(SVR, ENABLE | (T_IRQ0 + IRQ_SPURIOUS));
 
-  // The timer repeatedly counts down at bus frequency
-  // from lapic[TICR] and then issues an interrupt.
-  // If xv6 cared more about precise timekeeping,
-  // TICR would be calibrated using an external time source.
+  // The timer counts down once at bus frequency from
+  // lapic[TICR] and then issues an interrupt.  Leave it
+  // stopped until lapictimerinit measures its rate.
   lapicw(TDCR, X1);
-  lapicw(TIMER, PERIODIC | (T_IRQ0 + IRQ_TIMER));
-  lapicw(TICR, 10000000);
+  lapicw(TIMER, T_IRQ0 + IRQ_TIMER);
+  lapicw(TICR, 0);
 
   // Disable logical interrupt lines.
   lapicw(LINT0, MASKED);
@@ -113,11 +118,89 @@ lapiceoi(void)
     lapicw(EOI, 0);
 }
 
-// Spin for a given number of microseconds.
-// On real hardware would want to tune this dynamically.
+// Send interrupt vector to the processor with the given APIC ID.
+void
+lapicipi(int apicid, int vector)
+{
+  lapicw(ICRHI, apicid<<24);
+  lapicw(ICRLO, FIXED | ASSERT | vector);
+  while(lapic[ICRLO] & DELIVS)
+    ;
+}
+
+// Send a non-maskable interrupt to the processor with the
+// given APIC ID.  It arrives even if interrupts are off.
+void
+lapicnmi(int apicid)
+{
+  lapicw(ICRHI, apicid<<24);
+  lapicw(ICRLO, NMI | ASSERT);
+  while(lapic[ICRLO] & DELIVS)
+    ;
+}
+
+// Deliver performance counter overflow interrupts as vector.
+// Delivering one masks the next, so call again after each.
+void
+lapicpmi(int vector)
+{
+  lapicw(PCINT, vector);
+}
+
+// Measure this CPU's timer rate against the TSC, or use the
+// TSC-deadline mode instead if the CPU has it.
+// Run once on each CPU, after clockinit or clockcpuinit.
+void
+lapictimerinit(void)
+{
+  if(!lapic)
+    return;
+  if(cpuhas(CPUF_TSCDL)){
+    mycpu()->lapickhz = 0;
+    lapicw(TIMER, TSCDEADLINE | (T_IRQ0 + IRQ_TIMER));
+    return;
+  }
+  lapicw(TICR, 0xFFFFFFFF);
+  microdelay(CALMS*1000);
+  mycpu()->lapickhz = (0xFFFFFFFF - lapic[TCCR]) / CALMS;
+  lapicw(TICR, 0);
+}
+
+// Interrupt this CPU with the timer in ns nanoseconds,
+// or at once if ns is 0, replacing any earlier setting.
+void
+lapiconeshot(uint ns)
+{
+  struct cpu *c;
+  uint rem;
+  uint64 n;
+
+  if(!lapic)
+    return;
+  c = mycpu();
+  if(c->lapickhz == 0){
+    n = divmod64((uint64)ns * c->tsckhz, 1000000, &rem);
+    wrmsr(MSR_TSC_DEADLINE, rdtsc() + n);
+    return;
+  }
+  n = divmod64((uint64)ns * c->lapickhz, 1000000, &rem);
+  if(n == 0)
+    n = 1;
+  else if(n > 0xFFFFFFFF)
+    n = 0xFFFFFFFF;
+  lapicw(TICR, n);
+}
+
+// Cancel this CPU's timer interrupt, if one is set.
 void
-microdelay(int us)
+lapictimerstop(void)
 {
+  if(!lapic)
+    return;
+  if(mycpu()->lapickhz == 0)
+    wrmsr(MSR_TSC_DEADLINE, 0);
+  else
+    lapicw(TICR, 0);
 }
 
 #define CMOS_PORT    0x70
@@ -159,71 +242,3 @@ lapicstartap(uchar apicid, uint addr)
     microdelay(200);
   }
 }
-
-#define CMOS_STATA   0x0a
-#define CMOS_STATB   0x0b
-#define CMOS_UIP    (1 << 7)        // RTC update in progress
-
-#define SECS    0x00
-#define MINS    0x02
-#define HOURS   0x04
-#define DAY     0x07
-#define MONTH   0x08
-#define YEAR    0x09
-
-static uint
-cmos_read(uint reg)
-{
-  outb(CMOS_PORT,  reg);
-  microdelay(200);
-
-  return inb(CMOS_RETURN);
-}
-
-static void
-fill_rtcdate(struct rtcdate *r)
-{
-  r->second = cmos_read(SECS);
-  r->minute = cmos_read(MINS);
-  r->hour   = cmos_read(HOURS);
-  r->day    = cmos_read(DAY);
-  r->month  = cmos_read(MONTH);
-  r->year   = cmos_read(YEAR);
-}
-
-// qemu seems to use 24-hour GWT and the values are BCD encoded
-void
-cmostime(struct rtcdate *r)
-{
-  struct rtcdate t1, t2;
-  int sb, bcd;
-
-  sb = cmos_read(CMOS_STATB);
-
-  bcd = (sb & (1 << 2)) == 0;
-
-  // make sure CMOS doesn't modify time while we read it
-  for(;;) {
-    fill_rtcdate(&t1);
-    if(cmos_read(CMOS_STATA) & CMOS_UIP)
-        continue;
-    fill_rtcdate(&t2);
-    if(memcmp(&t1, &t2, sizeof(t1)) == 0)
-      break;
-  }
-
-  // convert
-  if(bcd) {
-#define    CONV(x)     (t1.x = ((t1.x >> 4) * 10) + (t1.x & 0xf))
-    CONV(second);
-    CONV(minute);
-    CONV(hour  );
-    CONV(day   );
-    CONV(month );
-    CONV(year  );
-#undef     CONV
-  }
-
-  *r = t1;
-  r->year += 2000;
-}
@@ -0,0 +1,90 @@
+// A dynamic linker, which exec runs first for a program that
+// names it as its interpreter.  It finds the program from the
+// auxiliary vector that exec puts after the environment
+// pointers, and relocates it.  There are no shared libraries
+// yet, so it only handles relative relocations.  Nothing
+// relocates ldso itself, so it must not need relocation: no
+// pointers in its data, and no ulib, which isn't built
+// position-independent.
+
+#include "types.h"
+#include "user.h"
+#include "elf.h"
+
+static void
+fail(char *msg)
+{
+  int n;
+
+  for(n = 0; msg[n]; n++)
+    ;
+  write(2, "ldso: ", 6);
+  write(2, msg, n);
+  write(2, "\n", 1);
+  _exit();
+}
+
+static uint
+auxval(uint *aux, uint type)
+{
+  for(; aux[0] != ELF_AUX_NULL; aux += 2)
+    if(aux[0] == type)
+      return aux[1];
+  return 0;
+}
+
+// Relocate the program whose stack starts at sp, and return
+// its entry, for ldstart.S.
+uint
+ldso(uint *sp)
+{
+  char **envp;
+  uint *aux, base, dyn, rel, relsz, i, *w;
+  struct proghdr *ph;
+  struct elfdyn *d;
+  struct elfrel *r;
+
+  for(envp = (char**)sp[3]; *envp; envp++)
+    ;
+  aux = (uint*)(envp + 1);
+  ph = (struct proghdr*)auxval(aux, ELF_AUX_PHDR);
+  if(ph == 0 || auxval(aux, ELF_AUX_PHENT) != sizeof(*ph))
+    fail("no program headers");
+
+  // The program is at base plus its link addresses; the
+  // headers say where they are linked.
+  base = 0;
+  dyn = 0;
+  for(i = 0; i < auxval(aux, ELF_AUX_PHNUM); i++){
+    if(ph[i].type == ELF_PROG_PHDR)
+      base = (uint)ph - ph[i].vaddr;
+    if(ph[i].type == ELF_PROG_DYNAMIC)
+      dyn = ph[i].vaddr;
+  }
+  if(dyn == 0)
+    fail("no dynamic section");
+
+  rel = relsz = 0;
+  for(d = (struct elfdyn*)(base + dyn); d->tag != ELF_DYN_NULL; d++){
+    if(d->tag == ELF_DYN_REL)
+      rel = d->val;
+    else if(d->tag == ELF_DYN_RELSZ)
+      relsz = d->val;
+    else if(d->tag == ELF_DYN_RELA ||
+            (d->tag == ELF_DYN_RELENT && d->val != sizeof(*r)))
+      fail("bad relocations");
+  }
+  for(r = (struct elfrel*)(base + rel); relsz >= sizeof(*r); r++, relsz -= sizeof(*r)){
+    switch(ELF_REL_TYPE(r->info)){
+    case ELF_REL_386_NONE:
+      break;
+    case ELF_REL_386_RELATIVE:
+      w = (uint*)(base + r->off);
+      *w += base;
+      break;
+    default:
+      fail("unsupported relocation");
+    }
+  }
+  return auxval(aux, ELF_AUX_ENTRY);
+}
@@ -0,0 +1,356 @@
+// Tests of the user library.
+
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "mmu.h"
+#include "memlayout.h"
+#include "signal.h"
+#include "time.h"
+#include "vdso.h"
+#include "param.h"
+#include "syscall.h"
+#include "features.h"
+#include "errno.h"
+#include "wait.h"
+#include "fcntl.h"
+
+// Every process can read the vDSO page, but not write it, and
+// gettimeofday's time from it agrees with the kernel's.
+void
+vdsotest(void)
+{
+  struct vdso *v = (struct vdso*)VDSO;
+  struct timespec t0, t1;
+  struct timeval tv;
+  int pid, ppid;
+
+  printf(1, "vdso test\n");
+
+  clock_gettime(CLOCK_REALTIME, &t0);
+  gettimeofday(&tv);
+  clock_gettime(CLOCK_REALTIME, &t1);
+  if(tv.tv_sec < t0.tv_sec || tv.tv_sec > t1.tv_sec ||
+     (tv.tv_sec == t0.tv_sec && tv.tv_usec < t0.tv_nsec / 1000) ||
+     (tv.tv_sec == t1.tv_sec && tv.tv_usec > t1.tv_nsec / 1000)){
+    printf(1, "gettimeofday %d.%d not between %d.%d and %d.%d\n",
+           tv.tv_sec, tv.tv_usec, t0.tv_sec, t0.tv_nsec, t1.tv_sec, t1.tv_nsec);
+    exit();
+  }
+  if(v->boottime == 0 || v->boottime > t1.tv_sec){
+    printf(1, "vdso boot time %d\n", v->boottime);
+    exit();
+  }
+
+  ppid = getpid();
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    v->seq = 1;
+    printf(1, "oops could write the vdso page\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  wait();
+  printf(1, "vdso test OK\n");
+}
+
+// features reports the calls this kernel has, and hascall and
+// waitpid go by it.
+void
+featurestest(void)
+{
+  uint bits[NFEATURES];
+  int pid, status;
+
+  printf(1, "features test\n");
+
+  memset(bits, 0, sizeof(bits));
+  if(features(bits, NFEATURES) != FEATURES_VERSION ||
+     !HASCALL(bits, SYS_fork) || !HASCALL(bits, SYS_features) ||
+     HASCALL(bits, 0)){
+    printf(1, "bad features\n");
+    exit();
+  }
+  if(features(bits, 0) != FEATURES_VERSION ||
+     features(bits, -1) != -1 || errno != EINVAL){
+    printf(1, "features took a bad count\n");
+    exit();
+  }
+  if(!hascall(SYS_wait4) || !hascall(SYS_getdents) ||
+     hascall(0) || hascall(NSYSCALL) || hascall(-1)){
+    printf(1, "bad hascall\n");
+    exit();
+  }
+
+  if((pid = fork()) == 0)
+    exit();
+  if(waitpid(pid, &status, 0) != pid || !WIFEXITED(status)){
+    printf(1, "waitpid failed\n");
+    exit();
+  }
+  printf(1, "features test OK\n");
+}
+
+// malloc hands out aligned blocks that don't overlap, reuses
+// freed ones, merged, before growing the heap, and gives a big
+// free run at the end of the heap back.
+void
+malloctest(void)
+{
+  enum { N = 200 };
+  char *p[N], *brk, *q;
+  int i, j, n;
+
+  printf(1, "malloc test\n");
+
+  mallopt(M_TRIM_THRESHOLD, 0);
+  for(i = 0; i < N; i++){
+    n = (i % 7) * 40 + (i % 3) * 1000;
+    if((p[i] = malloc(n)) == 0 || ((uint)p[i] & 7)){
+      printf(1, "malloc %d failed\n", n);
+      exit();
+    }
+    memset(p[i], i, n);
+  }
+  for(i = 0; i < N; i += 2)
+    free(p[i]);
+  for(i = 1; i < N; i += 2){
+    n = (i % 7) * 40 + (i % 3) * 1000;
+    for(j = 0; j < n; j++){
+      if(p[i][j] != (char)i){
+        printf(1, "block %d overwritten\n", i);
+        exit();
+      }
+    }
+    free(p[i]);
+  }
+
+  // All of it is free again, and in one piece.
+  brk = sbrk(0);
+  if((q = malloc(100*1000)) == 0 || sbrk(0) != brk){
+    printf(1, "malloc grew the heap for freed memory\n");
+    exit();
+  }
+  free(q);
+
+  mallopt(M_TRIM_THRESHOLD, 512*1024);
+  if((q = malloc(1024*1024)) == 0 || sbrk(0) < brk + 1024*1024){
+    printf(1, "malloc of 1MB failed\n");
+    exit();
+  }
+  q[1024*1024-1] = 1;
+  free(q);
+  if(sbrk(0) >= brk + 1024*1024){
+    printf(1, "free kept 1MB\n");
+    exit();
+  }
+  mallopt(M_TRIM_THRESHOLD, 0);
+  brk = sbrk(0);
+  q = malloc(1024*1024);
+  free(q);
+  if(sbrk(0) < brk + 512*1024){
+    printf(1, "free trimmed with trimming off\n");
+    exit();
+  }
+  printf(1, "malloc test OK\n");
+}
+
+// What printf wrote into pipe p.
+char*
+printed(int p[2])
+{
+  static char out[200];
+  int n;
+
+  n = read(p[0], out, sizeof(out)-1);
+  out[n < 0 ? 0 : n] = 0;
+  return out;
+}
+
+#define CHECK(want, ...) do { \
+    char *got; \
+    printf(p[1], __VA_ARGS__); \
+    if(strcmp(got = printed(p), want) != 0){ \
+      printf(1, "printf gave \"%s\", not \"%s\"\n", got, want); \
+      exit(); \
+    } \
+  } while(0)
+
+// printf pads, truncates and formats floats and 64-bit ints.
+void
+printftest(void)
+{
+  int p[2];
+
+  printf(1, "printf test\n");
+
+  if(pipe(p) < 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  CHECK("[   42]", "[%5d]", 42);
+  CHECK("[42   ]", "[%-5d]", 42);
+  CHECK("[-0042]", "[%05d]", -42);
+  CHECK("[  007]", "[%5.3d]", 7);
+  CHECK("[000000000000000000000000000042]", "[%.30d]", 42);
+  CHECK("[+7 ff FF 17]", "[%+d %x %X %o]", 7, 255, 255, 15);
+  CHECK("[   ab]", "[%*.*s]", 5, 2, "abc");
+  CHECK("[4294967295 -2147483648]", "[%u %ld]", -1, 0x80000000);
+  CHECK("[18446744073709551615 -1]", "[%llu %lld]", -1ULL, -1LL);
+  CHECK("[123456789012 1cbe991a14]", "[%llu %llx]",
+        123456789012ULL, 123456789012ULL);
+  CHECK("[3.141593 3.14 3]", "[%f %.2f %.0f]", 3.14159265, 3.14159265, 3.0);
+  CHECK("[-0.001 1000000]", "[%.3f %.0f]", -0.00149, 999999.5);
+  CHECK("[1.500000e+10 2.5E-07]", "[%e %.1E]", 1.5e10, 2.5e-7);
+  CHECK("[0.0001 1e-05 123457 1.23457e+06]", "[%g %g %g %g]",
+        0.0001, 0.00001, 123456.7, 1234567.0);
+  CHECK("[0.5 100 0]", "[%g %g %g]", 0.5, 100.0, 0.0);
+  CHECK("[  1.50|inf|nan]", "[%6.2f|%f|%f]", 1.5, 1.0/0.0, 0.0/0.0);
+  CHECK("[%q 100%]", "[%q %d%%]", 100);
+  close(p[0]);
+  close(p[1]);
+  printf(1, "printf test OK\n");
+}
+
+// snprintf cuts its output to fit, always nul-terminated, and
+// returns the length it would have had.
+void
+snprintftest(void)
+{
+  char buf[16];
+
+  printf(1, "snprintf test\n");
+
+  memset(buf, 'x', sizeof(buf));
+  if(snprintf(buf, sizeof(buf), "%d-%s", 12, "ab") != 5 ||
+     strcmp(buf, "12-ab") != 0){
+    printf(1, "snprintf gave %s\n", buf);
+    exit();
+  }
+  if(snprintf(buf, 6, "%s %5.1f", "pi is", 3.14159) != 11 ||
+     strcmp(buf, "pi is") != 0 || buf[6] != 'x'){
+    printf(1, "snprintf didn't stop at the end: %s\n", buf);
+    exit();
+  }
+  if(snprintf(0, 0, "%0200d", 1) != 200 || snprintf(buf, 1, "abc") != 3 ||
+     buf[0] != 0){
+    printf(1, "snprintf into nothing failed\n");
+    exit();
+  }
+  // More zeros than any buffer printf keeps on its stack.
+  if(snprintf(buf, sizeof(buf), "%.400x", 0xab) != 400 ||
+     strcmp(buf, "000000000000000") != 0){
+    printf(1, "snprintf of a long precision gave %s\n", buf);
+    exit();
+  }
+  if(sprintf(buf, "%x%c", 0xbeef, '!') != 5 || strcmp(buf, "beef!") != 0){
+    printf(1, "sprintf gave %s\n", buf);
+    exit();
+  }
+  printf(1, "snprintf test OK\n");
+}
+
+// In a child with its stdin and stdout on pipes: stdin reads
+// lines and bytes, streams refuse use the wrong way, stdout to
+// a pipe holds output until it is flushed, or a line at a time
+// when asked, and exit flushes it.
+void
+stdiochild(int in[2], int out[2])
+{
+  char line[16];
+  int ppid;
+
+  ppid = getppid();
+  dup2(in[0], 0);
+  dup2(out[1], 1);
+  close(in[0]);
+  close(out[1]);
+  fcntl(out[0], F_SETFL, O_NONBLOCK);
+
+  if(fgets(line, sizeof(line), stdin) == 0 || strcmp(line, "one\n") != 0 ||
+     fgetc(stdin) != 't' || fread(line, 1, sizeof(line), stdin) != 2 ||
+     line[0] != 'w' || line[1] != 'o' || getchar() != EOF || !feof(stdin)){
+    fprintf(stderr, "stdin read wrong\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  if(fputc('x', stdin) != EOF || !ferror(stdin) ||
+     fgetc(stderr) != EOF || !ferror(stderr)){
+    fprintf(stderr, "stream used the wrong way\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+
+  fputs("ab", stdout);
+  putchar('c');
+  if(read(out[0], line, 1) != -1){
+    fprintf(stderr, "stdout wasn't buffered\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  fflush(stdout);
+  if(read(out[0], line, 3) != 3 || line[0] != 'a' || line[2] != 'c'){
+    fprintf(stderr, "fflush didn't write\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  setvbuf(stdout, 0, _IOLBF, 0);
+  puts("d");
+  if(read(out[0], line, 2) != 2 || line[0] != 'd' || line[1] != '\n' ||
+     setvbuf(stderr, 0, _IOFBF, 0) != -1){
+    fprintf(stderr, "line buffering didn't write\n");
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  fprintf(stdout, "e%d", 42);
+  exit();
+}
+
+void
+stdiotest(void)
+{
+  int in[2], out[2], pid, n;
+  char buf[16];
+
+  printf(1, "stdio test\n");
+
+  if(pipe(in) < 0 || pipe(out) < 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  write(in[1], "one\ntwo", 7);
+  close(in[1]);
+  if((pid = fork()) == 0)
+    stdiochild(in, out);
+  close(in[0]);
+  close(out[1]);
+  wait();
+  n = read(out[0], buf, sizeof(buf) - 1);
+  buf[n < 0 ? 0 : n] = 0;
+  close(out[0]);
+  if(strcmp(buf, "e42") != 0){
+    printf(1, "exit flushed \"%s\"\n", buf);
+    exit();
+  }
+  printf(1, "stdio test OK\n");
+}
+
+int
+main(int argc, char *argv[])
+{
+  printf(1, "libtests starting\n");
+
+  vdsotest();
+  featurestest();
+  malloctest();
+  printftest();
+  snprintftest();
+  stdiotest();
+
+  printf(1, "ALL TESTS PASSED\n");
+  exit();
+}
@@ -0,0 +1,196 @@
+// Lock debugging, in kernels built with LOCKDEBUG.
+//
+// Spin locks are grouped into classes by name, so that all the
+// pipes' locks are one class, say.  Each CPU keeps a list of
+// the locks it holds.  Taking a lock of class B while holding
+// one of class A records that A comes before B; if B has ever
+// come before A, two CPUs could each hold one and wait for the
+// other, and the next release that leaves the CPU holding no
+// locks prints a warning.  Each CPU also counts, for each
+// class, how often its locks were taken and found held, and for
+// how long they were held.  ^L on the console prints it all.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "proc.h"
+#include "x86.h"
+#include "spinlock.h"
+#include "percpu.h"
+#include "ansi.h"
+
+#ifdef LOCKDEBUG
+
+#define NCLASS  64   // Lock classes tracked
+#define NHELD   16   // Locks one CPU can hold that are tracked
+
+#define BEFORE    1  // In order[a][b]: a has been held while taking b
+#define REPORTED  2  // ... and b before a, and that was reported
+
+// Classes, by lock name.  A slot's name is set once, by
+// cmpxchg, and never cleared.
+static char *volatile classes[NCLASS];
+static uchar order[NCLASS][NCLASS];
+
+struct lockstat {
+  uint nacquire;     // Times taken
+  uint ncontend;     // Times found held by another CPU
+  uint64 hold;       // Total TSC cycles held
+  uint64 maxhold;    // Longest hold
+};
+
+struct lockcpu {
+  struct {
+    struct spinlock *lk;
+    int class;
+    uint64 since;    // TSC when taken
+  } held[NHELD];
+  int nheld;
+  int busy;          // Printing, so don't track cons.lock
+  int a, b;          // Inversion to report, a+1 and b+1
+  struct lockstat stat[NCLASS];
+};
+
+static PERCPU(struct lockcpu lockcpu);
+
+// Find or make lk's class.  Returns -1 if the table is full.
+static int
+classof(struct spinlock *lk)
+{
+  int i;
+
+  if(lk->class)
+    return lk->class - 1;
+  for(i = 0; i < NCLASS; i++){
+    if(classes[i] == 0)
+      cmpxchg((uint*)&classes[i], 0, (uint)lk->name);
+    if(strncmp(classes[i], lk->name, 32) == 0){
+      lk->class = i + 1;
+      return i;
+    }
+  }
+  return -1;
+}
+
+// This CPU has just taken lk, and had to wait for it if
+// contended.  Called with interrupts off.
+void
+lockacquired(struct spinlock *lk, int contended)
+{
+  struct lockcpu *c;
+  int i, k, h;
+
+  c = thiscpu(lockcpu);
+  if(c->busy || (k = classof(lk)) < 0)
+    return;
+  c->stat[k].nacquire++;
+  if(contended)
+    c->stat[k].ncontend++;
+  for(i = 0; i < c->nheld; i++){
+    h = c->held[i].class;
+    if(h == k)
+      continue;
+    if(!order[h][k])
+      order[h][k] = BEFORE;
+    if(order[k][h] == BEFORE && c->a == 0){
+      order[k][h] = REPORTED;
+      c->a = h + 1;
+      c->b = k + 1;
+    }
+  }
+  if(c->nheld < NHELD){
+    c->held[c->nheld].lk = lk;
+    c->held[c->nheld].class = k;
+    c->held[c->nheld].since = rdtsc();
+    c->nheld++;
+  }
+}
+
+// This CPU has just released lk.  Called with interrupts off.
+void
+lockreleased(struct spinlock *lk)
+{
+  struct lockcpu *c;
+  struct lockstat *s;
+  uint64 t;
+  int i, a, b;
+
+  c = thiscpu(lockcpu);
+  if(c->busy)
+    return;
+  for(i = c->nheld - 1; i >= 0; i--)
+    if(c->held[i].lk == lk)
+      break;
+  if(i < 0)
+    return;
+  s = &c->stat[c->held[i].class];
+  t = rdtsc() - c->held[i].since;
+  s->hold += t;
+  if(t > s->maxhold)
+    s->maxhold = t;
+  c->nheld--;
+  for(; i < c->nheld; i++)
+    c->held[i] = c->held[i+1];
+
+  if(c->nheld == 0 && c->a){
+    a = c->a - 1;
+    b = c->b - 1;
+    c->a = c->b = 0;
+    c->busy = 1;
+    cprintf(KWARN "cpu%d:" KEND " lock order: %s then %s, but also %s then %s\n",
+            cpuid(), classes[a], classes[b], classes[b], classes[a]);
+    c->busy = 0;
+  }
+}
+
+// Print each class's counts, summed over the CPUs, and the
+// order inversions found.  Runs when user types ^L on console.
+// No lock, as for procdump.
+void
+lockdump(void)
+{
+  struct lockstat *s;
+  uint n, nc, khz, r;
+  uint64 hold, max;
+  int i, k, a, b;
+
+  cprintf("class\t\ttaken\tcontended\tavg us\tmax us\n");
+  for(k = 0; k < NCLASS && classes[k]; k++){
+    n = nc = 0;
+    hold = max = 0;
+    khz = 0;
+    for(i = 0; i < ncpu; i++){
+      s = &percpuon(i, lockcpu)->stat[k];
+      n += s->nacquire;
+      nc += s->ncontend;
+      hold += s->hold;
+      if(s->maxhold > max)
+        max = s->maxhold;
+      if(cpus[i].tsckhz)
+        khz = cpus[i].tsckhz;
+    }
+    if(n == 0)
+      continue;
+    if(khz < 1000)
+      khz = 1000;
+    cprintf("%s\t%s%d\t%d\t\t%d\t%d\n", classes[k],
+            strlen(classes[k]) < 8 ? "\t" : "", n, nc,
+            (uint)divmod64(divmod64(hold, n, &r), khz / 1000, &r),
+            (uint)divmod64(max, khz / 1000, &r));
+  }
+  for(a = 0; a < NCLASS; a++)
+    for(b = 0; b < NCLASS; b++)
+      if(order[a][b] == REPORTED)
+        cprintf("inversion: %s and %s\n", classes[a], classes[b]);
+}
+
+#else
+
+void
+lockdump(void)
+{
+  cprintf("no lock statistics; build with LOCKDEBUG=1\n");
+}
+
+#endif
@@ -3,6 +3,7 @@
 #include "param.h"
 #include "spinlock.h"
 #include "sleeplock.h"
+#include "condvar.h"
 #include "fs.h"
 #include "buf.h"
 
@@ -38,6 +39,7 @@ struct logheader {
 
 struct log {
   struct spinlock lock;
+  struct condvar room; // A commit finished, or an op ended
   int start;
   int size;
   int outstanding; // how many FS sys calls are executing.
@@ -58,6 +60,7 @@ initlog(int dev)
 
   struct superblock sb;
   initlock(&log.lock, "log");
+  initcondvar(&log.room, &log.lock, "log");
   readsb(dev, &sb);
   log.start = sb.logstart;
   log.size = sb.nlog;
@@ -128,10 +131,10 @@ begin_op(void)
   acquire(&log.lock);
   while(1){
     if(log.committing){
-      sleep(&log, &log.lock);
+      cvwait(&log.room);
     } else if(log.lh.n + (log.outstanding+1)*MAXOPBLOCKS > LOGSIZE){
       // this op might exhaust log space; wait for commit.
-      sleep(&log, &log.lock);
+      cvwait(&log.room);
     } else {
       log.outstanding += 1;
       release(&log.lock);
@@ -158,7 +161,7 @@ end_op(void)
     // begin_op() may be waiting for log space,
     // and decrementing log.outstanding has decreased
     // the amount of reserved space.
-    wakeup(&log);
+    cvbroadcast(&log.room);
   }
   release(&log.lock);
 
@@ -168,7 +171,7 @@ end_op(void)
     commit();
     acquire(&log.lock);
     log.committing = 0;
-    wakeup(&log);
+    cvbroadcast(&log.room);
     release(&log.lock);
   }
 }
@@ -2,6 +2,7 @@
 #include "stat.h"
 #include "user.h"
 #include "fs.h"
+#include "dirent.h"
 
 char*
 fmtname(char *path)
@@ -27,7 +28,8 @@ ls(char *path)
 {
   char buf[512], *p;
   int fd;
-  struct dirent de;
+  struct dir *dir;
+  struct dent *de;
   struct stat st;
 
   if((fd = open(path, 0)) < 0){
@@ -41,31 +43,26 @@ ls(char *path)
     return;
   }
 
-  switch(st.type){
-  case T_FILE:
+  if(!S_ISDIR(st.mode))
     printf(1, "%s %d %d %d\n", fmtname(path), st.type, st.ino, st.size);
-    break;
-
-  case T_DIR:
-    if(strlen(path) + 1 + DIRSIZ + 1 > sizeof buf){
-      printf(1, "ls: path too long\n");
-      break;
-    }
+  else if(strlen(path) + 1 + DIRSIZ + 1 > sizeof buf)
+    printf(1, "ls: path too long\n");
+  else if((dir = fdopendir(fd)) == 0)
+    printf(2, "ls: cannot read %s\n", path);
+  else {
     strcpy(buf, path);
     p = buf+strlen(buf);
     *p++ = '/';
-    while(read(fd, &de, sizeof(de)) == sizeof(de)){
-      if(de.inum == 0)
-        continue;
-      memmove(p, de.name, DIRSIZ);
-      p[DIRSIZ] = 0;
+    while((de = readdir(dir)) != 0){
+      strcpy(p, de->name);
       if(stat(buf, &st) < 0){
         printf(1, "ls: cannot stat %s\n", buf);
         continue;
       }
       printf(1, "%s %d %d %d\n", fmtname(buf), st.type, st.ino, st.size);
     }
-    break;
+    closedir(dir);
+    return;
   }
   close(fd);
 }
@@ -0,0 +1,51 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "cpuinfo.h"
+
+static struct {
+  uint flag;
+  char *name;
+} flags[] = {
+  { CPUF_PSE,      "pse" },
+  { CPUF_FXSR,     "fxsr" },
+  { CPUF_XSAVE,    "xsave" },
+  { CPUF_XSAVEOPT, "xsaveopt" },
+  { CPUF_XSAVEC,   "xsavec" },
+  { CPUF_XSAVES,   "xsaves" },
+  { CPUF_AVX,      "avx" },
+  { CPUF_X2APIC,   "x2apic" },
+  { CPUF_TSCDL,    "tsc_deadline" },
+  { CPUF_INVTSC,   "invtsc" },
+  { CPUF_PDPE1GB,  "pdpe1gb" },
+  { CPUF_FSGSBASE, "fsgsbase" },
+  { CPUF_SMEP,     "smep" },
+  { CPUF_SMAP,     "smap" },
+  { CPUF_PMU,      "pmu" },
+};
+
+// Print each CPU's model, where it sits in the topology,
+// and the features the kernel found.
+int
+main(void)
+{
+  struct cpuinfo ci;
+  int c, i;
+
+  if(cpuinfo(0, &ci) < 0){
+    printf(2, "lscpu: cpuinfo failed\n");
+    exit();
+  }
+  for(c = 0; cpuinfo(c, &ci) == 0; c++){
+    printf(1, "cpu%d: %s family %d model %d stepping %d\n",
+           c, ci.vendor, ci.family, ci.model, ci.stepping);
+    printf(1, "\tapic %d package %d core %d thread %d\n",
+           ci.apicid, ci.package, ci.core, ci.thread);
+    printf(1, "\tflags");
+    for(i = 0; i < sizeof(flags)/sizeof(flags[0]); i++)
+      if(ci.features & flags[i].flag)
+        printf(1, " %s", flags[i].name);
+    printf(1, "\n");
+  }
+  exit();
+}
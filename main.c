@@ -5,6 +5,7 @@
 #include "mmu.h"
 #include "proc.h"
 #include "x86.h"
+#include "ansi.h"
 
 static void startothers(void);
 static void mpmain(void)  __attribute__((noreturn));
@@ -17,22 +18,48 @@ extern char end[]; // first address after kernel loaded from ELF file
 int
 main(void)
 {
-  kinit1(end, P2V(4*1024*1024)); // phys page allocator
+  char *entryend;
+
+  // The memory entry.S mapped, in 4MB pages.
+  entryend = P2V((V2P(end) + ENTRYFREE + (4<<20) - 1) & ~((4<<20) - 1));
+  kinit1(end, entryend); // phys page allocator
   kvmalloc();      // kernel page table
   mpinit();        // detect other processors
+  cpuinfoinit();   // identify processor
+  percpuinit();    // per-CPU variables
   lapicinit();     // interrupt controller
   seginit();       // segment descriptors
+  dfinit();        // double fault task
+  smapinit();      // user memory protection
+  fpuinit();       // floating point
+  pmuinit();       // performance counters
   picinit();       // disable pic
   ioapicinit();    // another interrupt controller
+  tvinit();        // trap vectors
+  ipiinit();       // cross-CPU calls
+  rtcinit();       // real-time clock
+  clockinit();     // wall clock
+  timerinit();     // high-resolution timers
+  randinit();      // random numbers
+  bootargsinit();  // boot arguments
   consoleinit();   // console hardware
   uartinit();      // serial port
+  mouseinit();     // PS/2 mouse
   pinit();         // process table
-  tvinit();        // trap vectors
   binit();         // buffer cache
+  slabinit();      // kernel object caches
+  rcuinit();       // read-copy-update
+  dcacheinit();    // directory entry cache
   fileinit();      // file table
+  pipeinit();      // pipes
+  pollinit();      // poll
+  vmainit();       // virtual memory areas
+  shminit();       // shared memory segments
+  pcinit();        // page cache
+  swapinit();      // swap space
   ideinit();       // disk 
   startothers();   // start other processors
-  kinit2(P2V(4*1024*1024), P2V(PHYSTOP)); // must come after startothers()
+  kinit2(entryend, P2V(PHYSTOP)); // must come after startothers()
   userinit();      // first user process
   mpmain();        // finish this processor's setup
 }
@@ -42,8 +69,15 @@ static void
 mpenter(void)
 {
   switchkvm();
+  cpuinfoinit();
   seginit();
+  dfinit();
+  smapinit();
+  fpuinit();
   lapicinit();
+  pmuinit();
+  clockcpuinit();
+  timercpuinit();
   mpmain();
 }
 
@@ -51,7 +85,7 @@ mpenter(void)
 static void
 mpmain(void)
 {
-  cprintf("cpu%d: starting %d\n", cpuid(), cpuid());
+  cprintf(KINFO "cpu%d:" KEND " starting %d\n", cpuid(), cpuid());
   idtinit();       // load idt register
   xchg(&(mycpu()->started), 1); // tell startothers() we're up
   scheduler();     // start running processes
@@ -64,34 +98,44 @@ static void
 startothers(void)
 {
   extern uchar _binary_entryother_start[], _binary_entryother_size[];
-  uchar *code;
   struct cpu *c;
-  char *stack;
 
   // Write entry code to unused memory at 0x7000.
   // The linker has placed the image of entryother.S in
-  // _binary_entryother_start.
-  code = P2V(0x7000);
-  memmove(code, _binary_entryother_start, (uint)_binary_entryother_size);
+  // _binary_entryother_start.  It stays there, for startcpu
+  // to restart CPUs that have gone offline.
+  memmove(P2V(0x7000), _binary_entryother_start,
+          (uint)_binary_entryother_size);
 
   for(c = cpus; c < cpus+ncpu; c++){
     if(c == mycpu())  // We've started already.
       continue;
+    startcpu(c);
+  }
+}
+
+// Start AP processor c, not yet started or halted offline,
+// running the entry code, and wait until it is up.
+void
+startcpu(struct cpu *c)
+{
+  uchar *code;
 
-    // Tell entryother.S what stack to use, where to enter, and what
-    // pgdir to use. We cannot use kpgdir yet, because the AP processor
-    // is running in low  memory, so we use entrypgdir for the APs too.
-    stack = kalloc();
-    *(void**)(code-4) = stack + KSTACKSIZE;
-    *(void(**)(void))(code-8) = mpenter;
-    *(int**)(code-12) = (void *) V2P(entrypgdir);
+  // Tell entryother.S what stack to use, where to enter, and what
+  // pgdir to use. We cannot use kpgdir yet, because the AP processor
+  // is running in low  memory, so we use entrypgdir for the APs too.
+  code = P2V(0x7000);
+  if(c->stack == 0)
+    c->stack = kalloc();
+  *(void**)(code-4) = c->stack + KSTACKSIZE;
+  *(void(**)(void))(code-8) = mpenter;
+  *(int**)(code-12) = (void *) V2P(entrypgdir);
 
-    lapicstartap(c->apicid, V2P(code));
+  lapicstartap(c->apicid, V2P(code));
 
-    // wait for cpu to finish mpmain()
-    while(c->started == 0)
-      ;
-  }
+  // wait for cpu to finish mpmain()
+  while(c->started == 0)
+    ;
 }
 
 // The boot page table used in entry.S and entryother.S.
@@ -103,7 +147,8 @@ __attribute__((__aligned__(PGSIZE)))
 pde_t entrypgdir[NPDENTRIES] = {
   // Map VA's [0, 4MB) to PA's [0, 4MB)
   [0] = (0) | PTE_P | PTE_W | PTE_PS,
-  // Map VA's [KERNBASE, KERNBASE+4MB) to PA's [0, 4MB)
+  // Map VA's [KERNBASE, KERNBASE+4MB) to PA's [0, 4MB), and
+  // entry.S maps more if the kernel is bigger.
   [KERNBASE>>PDXSHIFT] = (0) | PTE_P | PTE_W | PTE_PS,
 };
 
@@ -25,11 +25,12 @@ ideinit(void)
   disksize = (uint)_binary_fs_img_size/BSIZE;
 }
 
-// Interrupt handler.
-void
-ideintr(void)
+// Whether there is a disk dev.  There is only disk 1, in
+// memory, and no boot disk.
+int
+idedisk(int dev)
 {
-  // no-op
+  return dev == 1;
 }
 
 // Sync buf with disk.
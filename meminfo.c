@@ -0,0 +1,34 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "sysinfo.h"
+
+// Print a page count in kilobytes.
+static void
+pr(char *name, uint pages)
+{
+  printf(1, "%s\t%d kB\n", name, pages * 4);
+}
+
+int
+main(void)
+{
+  struct sysinfo si;
+
+  if(sysinfo(&si) < 0){
+    printf(2, "meminfo: sysinfo failed\n");
+    exit();
+  }
+  pr("MemTotal:", si.totalpages);
+  pr("MemFree:", si.freepages);
+  pr("DmaTotal:", si.dmapages);
+  pr("DmaFree:", si.dmafree);
+  pr("Slab:", si.slabpages);
+  printf(1, "SlabObjs:\t%d\n", si.slabobjs);
+  pr("Mapped:", si.pcpages);
+  printf(1, "Buffers:\t%d of %d in use, %d valid\n",
+         si.bufinuse, si.nbuf, si.bufvalid);
+  pr("SwapTotal:", si.swappages);
+  pr("SwapFree:", si.swapfree);
+  exit();
+}
@@ -1,12 +1,26 @@
 // Memory layout
 
 #define EXTMEM  0x100000            // Start of extended memory
-#define PHYSTOP 0xE000000           // Top physical memory
+#define PHYSTOP 0x7D000000          // Top physical memory the kernel can use
+#define PHYSDEFAULT 0xE000000       // Top physical memory, if there's no memory map
+#define DMATOP  0x1000000           // Top physical memory ISA DMA can reach
 #define DEVSPACE 0xFE000000         // Other devices are at high addresses
+#define FBBASE  (KERNBASE+PHYSTOP)  // Framebuffer, if any, is mapped here
+#define FBMAX   (DEVSPACE-FBBASE)   // Largest framebuffer
 
 // Key addresses for address space layout (see kmap in vm.c for layout)
 #define KERNBASE 0x80000000         // First kernel virtual address
 #define KERNLINK (KERNBASE+EXTMEM)  // Address where kernel is linked
+#define ENTRYFREE 0x100000          // Least memory entry.S maps past the kernel
+#define USTACKTOP KERNBASE          // Top of the user stack
+#define MAXUSTACK 0x800000          // Largest user stack (RLIMIT_STACK)
+#define STACKRAND 0x1000000         // exec puts the stack top up to this far below USTACKTOP
+#define VDSO (USTACKTOP-STACKRAND-MAXUSTACK-2*PGSIZE)  // The vDSO page (see vdso.h)
+#define MMAPTOP VDSO                // mmap places mappings below here
+#define MMAPRAND 0x10000000         // and starts up to this far below MMAPTOP
+#define HEAPRAND 0x2000000          // The heap starts up to this far above the program
+#define DYNBASE 0x100000            // exec loads position-independent programs here
+#define DYNRAND 0x1000000           // or up to this far above
 
 #define V2P(a) (((uint) (a)) - KERNBASE)
 #define P2V(a) ((void *)(((char *) (a)) + KERNBASE))
@@ -0,0 +1,16 @@
+// Memory mappings, shared by the kernel and user programs.
+
+#define PROT_NONE      0x0  // Pages may not be accessed
+#define PROT_READ      0x1  // Pages may be read
+#define PROT_WRITE     0x2  // Pages may be written
+#define PROT_EXEC      0x4  // Pages may be executed
+
+#define MAP_SHARED     0x01  // Share changes with other mappers
+#define MAP_PRIVATE    0x02  // Changes are private to the process
+#define MAP_ANONYMOUS  0x20  // Zeroed memory, not from a file
+
+#define MAP_FAILED     ((void*)-1)
+
+#define MS_ASYNC       0x1  // msync: schedule the writes (done at once)
+#define MS_INVALIDATE  0x2  // msync: no effect; mappings are coherent
+#define MS_SYNC        0x4  // msync: write before returning
@@ -2,14 +2,30 @@
 // x86 memory management unit (MMU).
 
 // Eflags register
+#define FL_TF           0x00000100      // Trap Flag
 #define FL_IF           0x00000200      // Interrupt Enable
+#define FL_AC           0x00040000      // Alignment Check (SMAP override)
+
+// Eflags bits that user code may change: the arithmetic
+// flags, TF, and DF.
+#define FL_USER         0x00000DD5
 
 // Control Register flags
 #define CR0_PE          0x00000001      // Protection Enable
+#define CR0_MP          0x00000002      // Monitor coProcessor
+#define CR0_EM          0x00000004      // Emulation
+#define CR0_TS          0x00000008      // Task Switched
+#define CR0_NE          0x00000020      // Numeric Error
 #define CR0_WP          0x00010000      // Write Protect
 #define CR0_PG          0x80000000      // Paging
 
 #define CR4_PSE         0x00000010      // Page size extension
+#define CR4_OSFXSR      0x00000200      // fxsave/fxrstor and SSE
+#define CR4_OSXMMEXCPT  0x00000400      // SIMD floating point exceptions
+#define CR4_OSXSAVE     0x00040000      // xsave/xrstor and XCR0
+#define CR4_SMEP        0x00100000      // Supervisor mode execution prevention
+#define CR4_SMAP        0x00200000      // Supervisor mode access prevention
+
 
 // various segment selectors.
 #define SEG_KCODE 1  // kernel code
@@ -17,9 +33,12 @@
 #define SEG_UCODE 3  // user code
 #define SEG_UDATA 4  // user data+stack
 #define SEG_TSS   5  // this process's task state
+#define SEG_UTLS  6  // user thread-local storage, at p->tlsbase
+#define SEG_DFTSS 7  // task state for double faults
+#define SEG_KCPU  8  // this CPU's per-CPU variables, in %gs
 
 // cpu->gdt[NSEGS] holds the above segments.
-#define NSEGS     6
+#define NSEGS     9
 
 #ifndef __ASSEMBLER__
 // Segment Descriptor
@@ -58,6 +77,7 @@ struct segdesc {
 #define STA_R       0x2     // Readable (executable segments)
 
 // System segment type bits
+#define STS_TG      0x5     // Task Gate
 #define STS_T32A    0x9     // Available 32-bit TSS
 #define STS_IG32    0xE     // 32-bit Interrupt Gate
 #define STS_TG32    0xF     // 32-bit Trap Gate
@@ -83,6 +103,7 @@ struct segdesc {
 #define NPDENTRIES      1024    // # directory entries per page directory
 #define NPTENTRIES      1024    // # PTEs per page table
 #define PGSIZE          4096    // bytes mapped by a page
+#define PDSIZE          (PGSIZE*NPTENTRIES)  // bytes mapped by a page directory entry
 
 #define PTXSHIFT        12      // offset of PTX in a linear address
 #define PDXSHIFT        22      // offset of PDX in a linear address
@@ -94,12 +115,24 @@ struct segdesc {
 #define PTE_P           0x001   // Present
 #define PTE_W           0x002   // Writeable
 #define PTE_U           0x004   // User
+#define PTE_A           0x020   // Accessed
+#define PTE_D           0x040   // Dirty
 #define PTE_PS          0x080   // Page Size
+#define PTE_COW         0x200   // Copy-on-write (a bit left for software)
+#define PTE_SWAP        0x400   // Not present, but out on swap (see swap.c)
+
+// Page fault error code bits, in the trap frame's err.
+#define FEC_P           0x001   // Fault on a present page
+#define FEC_W           0x002   // Fault was a write
+#define FEC_U           0x004   // Fault was in user mode
 
 // Address in page table or page directory entry
 #define PTE_ADDR(pte)   ((uint)(pte) & ~0xFFF)
 #define PTE_FLAGS(pte)  ((uint)(pte) &  0xFFF)
 
+// Swap slot in a PTE_SWAP entry, which holds it in place of the address
+#define SWAPSLOT(pte)   ((uint)(pte) >> PTXSHIFT)
+
 #ifndef __ASSEMBLER__
 typedef uint pte_t;
 
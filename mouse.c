@@ -0,0 +1,201 @@
+// PS/2 mouse, on the keyboard controller's auxiliary port.
+//
+// The mouse sends a 3-byte packet on IRQ 12 each time it moves
+// or a button changes; mouseintr turns each into a struct
+// mouseevent (see mouse.h) in a ring that reads of the mouse
+// device take from.  The keyboard leaves bytes from the
+// auxiliary port in the controller for us.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "traps.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "condvar.h"
+#include "fs.h"
+#include "file.h"
+#include "mmu.h"
+#include "proc.h"
+#include "x86.h"
+#include "kbd.h"
+#include "mouse.h"
+#include "fcntl.h"
+#include "errno.h"
+#include "poll.h"
+#include "ansi.h"
+
+#define KBC_WRCFG     0x60    // write the configuration byte
+#define KBC_AUXON     0xA8    // enable the auxiliary port
+#define KBC_TOAUX     0xD4    // send the next data byte to the mouse
+#define KBC_AUXINT    0x02    // config: interrupt on auxiliary data
+#define KBC_AUXOFF    0x20    // config: auxiliary clock disabled
+
+#define AUX_DEFAULTS  0xF6    // mouse: default settings
+#define AUX_ENABLE    0xF4    // mouse: start sending packets
+#define AUX_ACK       0xFA
+
+// Packet byte 0.
+#define PKT_BUTTONS   0x07
+#define PKT_SYNC      0x08    // always set
+#define PKT_XSIGN     0x10
+#define PKT_YSIGN     0x20
+#define PKT_OVERFLOW  0xC0
+
+#define NEVENT  64
+
+static struct {
+  struct spinlock lock;
+  struct condvar ready;
+  struct mouseevent ev[NEVENT];
+  uint r;          // Events read
+  uint w;          // Events written
+  uchar pkt[3];
+  int npkt;        // Bytes of pkt received
+} mouse;
+
+// Wait until the controller can take a byte.
+static int
+waitin(void)
+{
+  int i;
+
+  for(i = 0; i < 10000 && (inb(KBSTATP) & KBS_IBF); i++)
+    microdelay(10);
+  return i < 10000 ? 0 : -1;
+}
+
+// Wait for a byte from the controller and return it.
+static int
+waitout(void)
+{
+  int i;
+
+  for(i = 0; i < 10000 && !(inb(KBSTATP) & KBS_DIB); i++)
+    microdelay(10);
+  return i < 10000 ? inb(KBDATAP) : -1;
+}
+
+// Send command c to the mouse and wait for its ack.
+static int
+auxcmd(int c)
+{
+  if(waitin() < 0)
+    return -1;
+  outb(KBCMDP, KBC_TOAUX);
+  if(waitin() < 0)
+    return -1;
+  outb(KBDATAP, c);
+  return waitout() == AUX_ACK ? 0 : -1;
+}
+
+// Add the event in mouse.pkt to the ring, dropping it if the
+// ring is full.
+static void
+addevent(void)
+{
+  struct mouseevent *e;
+  uchar b;
+
+  b = mouse.pkt[0];
+  if(mouse.w - mouse.r == NEVENT)
+    return;
+  e = &mouse.ev[mouse.w++ % NEVENT];
+  e->buttons = b & PKT_BUTTONS;
+  e->dx = e->dy = 0;
+  if(!(b & PKT_OVERFLOW)){
+    e->dx = mouse.pkt[1] - ((b & PKT_XSIGN) ? 0x100 : 0);
+    e->dy = mouse.pkt[2] - ((b & PKT_YSIGN) ? 0x100 : 0);
+  }
+  cvbroadcast(&mouse.ready);
+}
+
+static void
+mouseintr(void)
+{
+  uint st, data;
+
+  acquire(&mouse.lock);
+  while(((st = inb(KBSTATP)) & KBS_DIB) && (st & KBS_AUXB)){
+    data = inb(KBDATAP);
+    // Find the start of a packet again after losing a byte.
+    if(mouse.npkt == 0 && !(data & PKT_SYNC))
+      continue;
+    mouse.pkt[mouse.npkt++] = data;
+    if(mouse.npkt == sizeof(mouse.pkt)){
+      addevent();
+      mouse.npkt = 0;
+    }
+  }
+  release(&mouse.lock);
+}
+
+// Read as many whole events as fit in n bytes, waiting until
+// there is one, unless flags has O_NONBLOCK.
+static int
+mouseread(struct inode *ip, char *dst, int n, int flags)
+{
+  int m;
+
+  if(n < sizeof(struct mouseevent))
+    return -EINVAL;
+  iunlock(ip);
+  acquire(&mouse.lock);
+  while(mouse.r == mouse.w){
+    if(myproc()->killed || (flags & O_NONBLOCK)){
+      release(&mouse.lock);
+      ilock(ip);
+      return myproc()->killed ? -EINTR : -EAGAIN;
+    }
+    cvwait(&mouse.ready);
+  }
+  for(m = 0; n - m >= sizeof(struct mouseevent) && mouse.r != mouse.w;
+      m += sizeof(struct mouseevent))
+    memmove(dst + m, &mouse.ev[mouse.r++ % NEVENT], sizeof(struct mouseevent));
+  release(&mouse.lock);
+  ilock(ip);
+  return m;
+}
+
+static int
+mousepoll(struct inode *ip)
+{
+  int r;
+
+  acquire(&mouse.lock);
+  r = mouse.r != mouse.w ? POLLIN : 0;
+  release(&mouse.lock);
+  return r;
+}
+
+void
+mouseinit(void)
+{
+  int cfg;
+
+  initlock(&mouse.lock, "mouse");
+  initcondvar(&mouse.ready, &mouse.lock, "mouse");
+  devsw[MOUSE].read = mouseread;
+  devsw[MOUSE].poll = mousepoll;
+
+  // Turn on the auxiliary port and its interrupt.
+  if(waitin() < 0)
+    goto bad;
+  outb(KBCMDP, KBC_AUXON);
+  if(waitin() < 0)
+    goto bad;
+  outb(KBCMDP, KBC_RDCFG);
+  if((cfg = waitout()) < 0 || waitin() < 0)
+    goto bad;
+  outb(KBCMDP, KBC_WRCFG);
+  if(waitin() < 0)
+    goto bad;
+  outb(KBDATAP, (cfg | KBC_AUXINT) & ~KBC_AUXOFF);
+  if(auxcmd(AUX_DEFAULTS) < 0 || auxcmd(AUX_ENABLE) < 0)
+    goto bad;
+  irqregister(IRQ_MOUSE, mouseintr);
+  return;
+
+bad:
+  cprintf(KWARN "mouse:" KEND " no PS/2 mouse\n");
+}
@@ -0,0 +1,14 @@
+// PS/2 mouse events, shared by the kernel and user programs.
+// Reading the mouse device returns whole events, as many as
+// fit, waiting for the first.
+
+struct mouseevent {
+  int dx;          // Movement right since the last event
+  int dy;          // Movement up since the last event
+  uint buttons;    // Buttons now down
+};
+
+// buttons bits.
+#define MOUSE_LEFT    0x1
+#define MOUSE_RIGHT   0x2
+#define MOUSE_MIDDLE  0x4
@@ -0,0 +1,189 @@
+// Page cache for shared file mappings.
+//
+// Every MAP_SHARED mapping of a file page maps the same
+// physical page, so that processes see each other's stores.
+// The cache holds one reference to each page and mappings
+// hold the rest; a page no one maps can be reused once its
+// changes are on disk, which msync and munmap see to (see
+// vmasync in vma.c).  writei copies what it writes into any
+// cached pages, so mappings see write system calls too.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "fs.h"
+#include "file.h"
+#include "mmu.h"
+#include "sysinfo.h"
+
+struct pcentry {
+  struct inode *ip;   // file, or 0 if the entry is free
+  uint off;           // page-aligned offset in the file
+  char *page;         // or 0 if reclaimed (see pcreclaim)
+  int busy;           // being read in from the file
+};
+
+struct {
+  struct spinlock lock;
+  struct pcentry e[NPCACHE];
+} pcache;
+
+void
+pcinit(void)
+{
+  initlock(&pcache.lock, "pcache");
+}
+
+// Return the cached page holding offset off of ip, reading it
+// in if need be, with a reference for the caller.  Returns 0
+// if the cache is full of mapped pages, memory ran out, or the
+// file can't be read.  Sleeps; the caller must not hold ip's
+// lock.
+char*
+pcget(struct inode *ip, uint off)
+{
+  struct pcentry *e, *free;
+  struct inode *oldip;
+  char *page;
+  int n;
+
+  off = PGROUNDDOWN(off);
+  acquire(&pcache.lock);
+again:
+  free = 0;
+  for(e = pcache.e; e < &pcache.e[NPCACHE]; e++){
+    if(e->ip == ip && e->off == off){
+      if(e->busy){
+        sleep(e, &pcache.lock);
+        goto again;
+      }
+      if(e->page){
+        kref(e->page);
+        release(&pcache.lock);
+        return e->page;
+      }
+      // Reclaimed; read it in again.
+      free = e;
+      break;
+    }
+    if(free == 0 && (e->ip == 0 ||
+       (!e->busy && (e->page == 0 || krefcount(e->page) == 1))))
+      free = e;
+  }
+  if(free == 0 || (page = kalloc()) == 0){
+    release(&pcache.lock);
+    return 0;
+  }
+
+  // Reuse the entry; its old page is no longer mapped.
+  e = free;
+  oldip = e->ip;
+  if(e->ip && e->page)
+    kfree(e->page);
+  e->ip = idup(ip);
+  e->off = off;
+  e->page = page;
+  e->busy = 1;
+  release(&pcache.lock);
+
+  memset(page, 0, PGSIZE);
+  ilock(ip);
+  n = readi(ip, page, off, PGSIZE);
+  iunlock(ip);
+
+  acquire(&pcache.lock);
+  e->busy = 0;
+  wakeup(e);
+  if(n < 0){
+    kfree(e->page);
+    e->ip = 0;
+    page = 0;
+  } else
+    kref(page);
+  release(&pcache.lock);
+
+  if(oldip || n < 0){
+    begin_op();
+    if(oldip)
+      iput(oldip);
+    if(n < 0)
+      iput(ip);
+    end_op();
+  }
+  return page;
+}
+
+// writei wrote n bytes from src at offset off of ip: copy
+// them into any cached pages.  Caller holds ip's lock.
+void
+pcupdate(struct inode *ip, uint off, char *src, uint n)
+{
+  struct pcentry *e;
+  uint lo, hi;
+
+  acquire(&pcache.lock);
+  for(e = pcache.e; e < &pcache.e[NPCACHE]; e++){
+    if(e->ip != ip || e->busy || e->page == 0 ||
+       off >= e->off + PGSIZE || off + n <= e->off)
+      continue;
+    lo = off > e->off ? off : e->off;
+    hi = off + n < e->off + PGSIZE ? off + n : e->off + PGSIZE;
+    memmove(e->page + (lo - e->off), src + (lo - off), hi - lo);
+  }
+  release(&pcache.lock);
+}
+
+// itrunc discarded the contents of ip: zero its cached pages,
+// so mappings see it empty too.  Caller holds ip's lock.
+void
+pctrunc(struct inode *ip)
+{
+  struct pcentry *e;
+
+  acquire(&pcache.lock);
+  for(e = pcache.e; e < &pcache.e[NPCACHE]; e++)
+    if(e->ip == ip && !e->busy && e->page)
+      memset(e->page, 0, PGSIZE);
+  release(&pcache.lock);
+}
+
+// Free a cached page that no one maps, for reclaim, keeping
+// its entry so that pcget reads it in again.  Returns 0 if
+// there is none.
+int
+pcreclaim(void)
+{
+  struct pcentry *e;
+  char *page;
+
+  page = 0;
+  acquire(&pcache.lock);
+  for(e = pcache.e; e < &pcache.e[NPCACHE]; e++){
+    if(e->ip && e->page && !e->busy && krefcount(e->page) == 1){
+      page = e->page;
+      e->page = 0;
+      break;
+    }
+  }
+  release(&pcache.lock);
+  if(page == 0)
+    return 0;
+  kfree(page);
+  return 1;
+}
+
+// Fill in the page cache usage of si.
+void
+pcinfo(struct sysinfo *si)
+{
+  struct pcentry *e;
+
+  si->pcpages = 0;
+  acquire(&pcache.lock);
+  for(e = pcache.e; e < &pcache.e[NPCACHE]; e++)
+    if(e->ip && e->page)
+      si->pcpages++;
+  release(&pcache.lock);
+}
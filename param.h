@@ -1,14 +1,36 @@
 #define NPROC        64  // maximum number of processes
 #define KSTACKSIZE 4096  // size of per-process kernel stack
+#define MAXORDER     10  // largest kallocpages block is 2^MAXORDER pages
 #define NCPU          8  // maximum number of CPUs
 #define NOFILE       16  // open files per process
-#define NFILE       100  // open files per system
+#define NSIG         32  // signal numbers (bits in a signal mask)
+#define NRLIMIT       4  // resource limits per process
+#define NITIMER       3  // interval timers per process
+#define NPERF         3  // performance counts per process (see perf.h)
+#define NPERFPC      64  // sampled user PCs kept per process
+#define NSYSCALL     80  // system call numbers are below this (see syscall.h)
+#define HZ          100  // nominal clock ticks per second
+#define TICKNS  (1000000000/HZ)  // clock tick length (ns)
+#define WATCHDOG  (10*HZ)  // ticks a CPU may go without a timer interrupt
+#define USTACKSIZE (1024*1024)  // default user stack limit (bytes)
 #define NINODE       50  // maximum number of active i-nodes
+#define NVMA        256  // maximum number of virtual memory areas
+#define NPCACHE     128  // pages in the page cache for shared file mappings
+#define NSHM         16  // maximum number of shared memory segments
+#define NSHMPG       64  // maximum pages in a shared memory segment
 #define NDEV         10  // maximum major device number
+#define NVT           4  // virtual terminals on the console
+#define PIPEPAGES    16  // most pages in a pipe's buffer
+#define KLOGORDER     2  // kernel message log is 2^KLOGORDER pages
 #define ROOTDEV       1  // device number of file system root disk
+#define SWAPDEV       0  // device number of swap space (the boot disk)
+#define SWAPSTART  2048  // first block of swap space, past the kernel
+#define SWAPPGS    2048  // pages of swap space
 #define MAXARG       32  // max exec arguments
-#define MAXOPBLOCKS  10  // max # of blocks any FS op writes
+#define MAXENV       32  // max exec environment strings
+#define MAXPATH     128  // maximum path name, including nul
+#define MAXOPBLOCKS  12  // max # of blocks any FS op writes
 #define LOGSIZE      (MAXOPBLOCKS*3)  // max data blocks in on-disk log
 #define NBUF         (MAXOPBLOCKS*3)  // size of disk block cache
-#define FSSIZE       1000  // size of file system in blocks
+#define FSSIZE      20000  // size of file system in blocks
 
@@ -0,0 +1,35 @@
+// Per-CPU variables (see percpu.h).
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "percpu.h"
+
+extern char percpu[], epercpu[];  // kernel.ld
+
+uint percpuoff[NCPU];
+
+// Give each CPU its own copy of the .percpu section, starting
+// as the initial values there.  Run once, on the first CPU,
+// after mpinit has counted the CPUs and before seginit points
+// their %gs at the copies.
+void
+percpuinit(void)
+{
+  uint n;
+  int i, order;
+  char *p;
+
+  n = epercpu - percpu;
+  for(order = 0; (PGSIZE << order) < n; order++)
+    ;
+  for(i = 0; i < ncpu; i++){
+    if((p = kallocpages(order)) == 0)
+      panic("percpuinit");
+    memmove(p, percpu, n);
+    percpuoff[i] = p - percpu;
+  }
+}
@@ -0,0 +1,35 @@
+// Per-CPU variables.
+//
+// A variable defined with PERCPU lives in the kernel's .percpu
+// section, which percpuinit copies once for each CPU.  Each
+// CPU's %gs segment is based so that %gs:&v is that CPU's copy
+// of v; the copy in the section itself is only the initial
+// value and must not be used directly.
+//
+//   static PERCPU(uint pending);
+//   percpuadd(pending, 1);
+//
+// percpuread, percpuadd and percpuor are single instructions,
+// so an interrupt can't come between the read and the write,
+// and they need no pushcli; they work on 4-byte variables.
+// A pointer from thiscpu is only good while interrupts are
+// off, as for mycpu.  percpuon reaches another CPU's copy.
+
+#define PERCPU(decl) __attribute__((section(".percpu"))) decl
+
+extern uint percpuoff[NCPU];  // Add to &v to find CPU n's copy
+
+#define percpuread(v) ({                                  \
+  __typeof__(v) _v;                                       \
+  asm volatile("movl %%gs:%1, %0" : "=r" (_v) : "m" (v)); \
+  _v; })
+#define percpuwrite(v, x) \
+  asm volatile("movl %1, %%gs:%0" : "=m" (v) : "ri" (x))
+#define percpuadd(v, n) \
+  asm volatile("addl %1, %%gs:%0" : "+m" (v) : "ri" (n))
+#define percpuor(v, n) \
+  asm volatile("orl %1, %%gs:%0" : "+m" (v) : "ri" (n))
+
+#define percpuon(c, v) \
+  ((__typeof__(&(v)))((char*)&(v) + percpuoff[c]))
+#define thiscpu(v)  percpuon(cpuid(), v)
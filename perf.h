@@ -0,0 +1,12 @@
+// Hardware performance counts, shared by the kernel and user
+// programs (see perfread).
+
+#define PERF_CYCLES   0  // Core cycles in user mode
+#define PERF_INSTRS   1  // Instructions retired in user mode
+#define PERF_LLCMISS  2  // Last-level cache misses in user mode
+
+struct perfinfo {
+  uint64 count[NPERF];  // Indexed by PERF_CYCLES, etc.
+  uint npc;             // User PCs sampled so far
+  uint pc[NPERFPC];     // The latest, at pc[(npc-1) % NPERFPC]
+};
@@ -0,0 +1,31 @@
+// echo, linked as a position-independent executable to test
+// exec's relocation.  ulib isn't built position-independent, so
+// it has only the system calls, and its separators come from a
+// table of pointers that exec must relocate.
+
+#include "types.h"
+#include "user.h"
+
+char *sep[] = { " ", "\n" };
+
+static void
+putstr(char *s)
+{
+  int n;
+
+  for(n = 0; s[n]; n++)
+    ;
+  write(1, s, n);
+}
+
+int
+main(int argc, char *argv[])
+{
+  int i;
+
+  for(i = 1; i < argc; i++){
+    putstr(argv[i]);
+    putstr(sep[i+1 < argc ? 0 : 1]);
+  }
+  _exit();
+}
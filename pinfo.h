@@ -0,0 +1,11 @@
+// Process information returned by the pinfo system call.
+
+struct pinfo {
+  int pid;            // Process ID
+  int ppid;           // Parent's pid, or 0 for init
+  char state[8];      // "sleep", "run", etc.
+  uint sz;            // Size of process memory (bytes)
+  int nfd;            // Number of open files
+  uint cputicks;      // Clock ticks spent running
+  char name[16];      // Process name
+};
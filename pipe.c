@@ -6,19 +6,39 @@
 #include "fs.h"
 #include "spinlock.h"
 #include "sleeplock.h"
+#include "condvar.h"
 #include "file.h"
+#include "fcntl.h"
+#include "errno.h"
+#include "poll.h"
+#include "slab.h"
 
-#define PIPESIZE 512
+// A pipe's buffer is size bytes, a page to begin with, which
+// F_SETPIPE_SZ can change to as many as PIPEPAGES pages.  The
+// size is a power of 2, so that byte counts wrap around it.  Its
+// pages are allocated as writes first reach them, and freed
+// when the pipe is.
 
 struct pipe {
   struct spinlock lock;
-  char data[PIPESIZE];
+  struct condvar canread;   // Data or EOF has come
+  struct condvar canwrite;  // Room has come, or the reader left
+  char *page[PIPEPAGES];    // The buffer, or 0 for pages not yet used
+  uint size;      // bytes in the buffer
   uint nread;     // number of bytes read
   uint nwrite;    // number of bytes written
   int readopen;   // read fd is still open
   int writeopen;  // write fd is still open
 };
 
+static struct kmcache pipecache;
+
+void
+pipeinit(void)
+{
+  kmcacheinit(&pipecache, "pipe", sizeof(struct pipe));
+}
+
 int
 pipealloc(struct file **f0, struct file **f1)
 {
@@ -28,13 +48,17 @@ pipealloc(struct file **f0, struct file **f1)
   *f0 = *f1 = 0;
   if((*f0 = filealloc()) == 0 || (*f1 = filealloc()) == 0)
     goto bad;
-  if((p = (struct pipe*)kalloc()) == 0)
+  if((p = kmalloc(&pipecache)) == 0)
     goto bad;
   p->readopen = 1;
   p->writeopen = 1;
   p->nwrite = 0;
   p->nread = 0;
+  p->size = PGSIZE;
+  memset(p->page, 0, sizeof(p->page));
   initlock(&p->lock, "pipe");
+  initcondvar(&p->canread, &p->lock, "piperead");
+  initcondvar(&p->canwrite, &p->lock, "pipewrite");
   (*f0)->type = FD_PIPE;
   (*f0)->readable = 1;
   (*f0)->writable = 0;
@@ -48,7 +72,7 @@ pipealloc(struct file **f0, struct file **f1)
 //PAGEBREAK: 20
  bad:
   if(p)
-    kfree((char*)p);
+    kmfree(&pipecache, p);
   if(*f0)
     fileclose(*f0);
   if(*f1)
@@ -56,66 +80,180 @@ pipealloc(struct file **f0, struct file **f1)
   return -1;
 }
 
+// Free the pages of a buffer.
+static void
+freepages(char **page)
+{
+  int i;
+
+  for(i = 0; i < PIPEPAGES; i++)
+    if(page[i])
+      kfree(page[i]);
+}
+
+// The place for byte n of a pipe's data, in the buffer of size
+// bytes in page, allocating its page if need be.  Returns 0 if that
+// fails.
+static char*
+bufbyte(char **page, uint size, uint n)
+{
+  n %= size;
+  if(page[n / PGSIZE] == 0 && (page[n / PGSIZE] = kalloc()) == 0)
+    return 0;
+  return page[n / PGSIZE] + n % PGSIZE;
+}
+
+// Get p's buffer size, and if n is not 0, first change it to n
+// bytes rounded up to a power of 2 pages.  Fails if n is too
+// large, or the data in the pipe won't fit.
+int
+pipesize(struct pipe *p, int n)
+{
+  char *page[PIPEPAGES], *b;
+  uint i, size;
+
+  if(n < 0 || n > PIPEPAGES*PGSIZE)
+    return -EINVAL;
+  acquire(&p->lock);
+  if(n > 0){
+    for(size = PGSIZE; size < n; size *= 2)
+      ;
+    n = size;
+    if(p->nwrite - p->nread > n){
+      release(&p->lock);
+      return -EBUSY;
+    }
+    // Copy the data to the start of a new buffer.
+    memset(page, 0, sizeof(page));
+    for(i = 0; p->nread + i != p->nwrite; i++){
+      if((b = bufbyte(page, n, i)) == 0){
+        release(&p->lock);
+        freepages(page);
+        return -ENOMEM;
+      }
+      *b = *bufbyte(p->page, p->size, p->nread + i);
+    }
+    freepages(p->page);
+    memmove(p->page, page, sizeof(page));
+    p->nwrite -= p->nread;
+    p->nread = 0;
+    p->size = n;
+    cvbroadcast(&p->canwrite);
+  }
+  n = p->size;
+  release(&p->lock);
+  return n;
+}
+
 void
 pipeclose(struct pipe *p, int writable)
 {
   acquire(&p->lock);
   if(writable){
     p->writeopen = 0;
-    wakeup(&p->nread);
+    cvbroadcast(&p->canread);
   } else {
     p->readopen = 0;
-    wakeup(&p->nwrite);
+    cvbroadcast(&p->canwrite);
   }
   if(p->readopen == 0 && p->writeopen == 0){
     release(&p->lock);
-    kfree((char*)p);
+    freepages(p->page);
+    kmfree(&pipecache, p);
   } else
     release(&p->lock);
 }
 
 //PAGEBREAK: 40
+// Write n bytes to p, waiting for room, or with O_NONBLOCK in
+// flags, as many as there is room for, failing if none.
 int
-pipewrite(struct pipe *p, char *addr, int n)
+pipewrite(struct pipe *p, char *addr, int n, int flags)
 {
   int i;
+  char *b;
 
   acquire(&p->lock);
   for(i = 0; i < n; i++){
-    while(p->nwrite == p->nread + PIPESIZE){  //DOC: pipewrite-full
-      if(p->readopen == 0 || myproc()->killed){
+    while(p->nwrite == p->nread + p->size){  //DOC: pipewrite-full
+      if(p->readopen == 0){
+        release(&p->lock);
+        return -EPIPE;
+      }
+      if(myproc()->killed){
+        release(&p->lock);
+        return -EINTR;
+      }
+      if(i == 0 && (flags & O_NONBLOCK)){
+        release(&p->lock);
+        return -EAGAIN;
+      }
+      if(flags & O_NONBLOCK)
+        goto out;
+      cvbroadcast(&p->canread);
+      cvwait(&p->canwrite);  //DOC: pipewrite-sleep
+    }
+    if((b = bufbyte(p->page, p->size, p->nwrite)) == 0){
+      if(i == 0){
         release(&p->lock);
-        return -1;
+        return -ENOMEM;
       }
-      wakeup(&p->nread);
-      sleep(&p->nwrite, &p->lock);  //DOC: pipewrite-sleep
+      break;
     }
-    p->data[p->nwrite++ % PIPESIZE] = addr[i];
+    *b = addr[i];
+    p->nwrite++;
   }
-  wakeup(&p->nread);  //DOC: pipewrite-wakeup1
+out:
+  cvbroadcast(&p->canread);  //DOC: pipewrite-wakeup1
   release(&p->lock);
-  return n;
+  return i;
+}
+
+// Whether reading or, if writable, writing p would wait, as a
+// set of poll bits.
+int
+pipepoll(struct pipe *p, int writable)
+{
+  int r;
+
+  r = 0;
+  acquire(&p->lock);
+  if(writable){
+    if(!p->readopen)
+      r = POLLERR;
+    else if(p->nwrite < p->nread + p->size)
+      r = POLLOUT;
+  } else {
+    if(p->nread != p->nwrite)
+      r = POLLIN;
+    if(!p->writeopen)
+      r |= POLLHUP;
+  }
+  release(&p->lock);
+  return r;
 }
 
+// Read up to n bytes from p, waiting for some, or with
+// O_NONBLOCK in flags, failing if there are none yet.
 int
-piperead(struct pipe *p, char *addr, int n)
+piperead(struct pipe *p, char *addr, int n, int flags)
 {
   int i;
 
   acquire(&p->lock);
   while(p->nread == p->nwrite && p->writeopen){  //DOC: pipe-empty
-    if(myproc()->killed){
+    if(myproc()->killed || (flags & O_NONBLOCK)){
       release(&p->lock);
-      return -1;
+      return myproc()->killed ? -EINTR : -EAGAIN;
     }
-    sleep(&p->nread, &p->lock); //DOC: piperead-sleep
+    cvwait(&p->canread); //DOC: piperead-sleep
   }
   for(i = 0; i < n; i++){  //DOC: piperead-copy
     if(p->nread == p->nwrite)
       break;
-    addr[i] = p->data[p->nread++ % PIPESIZE];
+    addr[i] = *bufbyte(p->page, p->size, p->nread++);
   }
-  wakeup(&p->nwrite);  //DOC: piperead-wakeup
+  cvbroadcast(&p->canwrite);  //DOC: piperead-wakeup
   release(&p->lock);
   return i;
 }
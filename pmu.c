@@ -0,0 +1,149 @@
+// Hardware performance counters.
+//
+// On CPUs with architectural performance monitoring (version 2
+// or later), each CPU counts user-mode cycles and instructions
+// in two fixed counters and last-level cache misses in the
+// first programmable one.  The counts belong to the process
+// running: the scheduler zeroes the counters before it runs a
+// process and adds them to p->perf when it stops, and since
+// they count only in user mode, nothing is counted meanwhile.
+//
+// The cycle counter also samples: it starts PMUPERIOD cycles
+// short of overflowing, and the overflow interrupt records the
+// user PC in p->perfpc and starts it again.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "x86.h"
+#include "traps.h"
+#include "perf.h"
+#include "cpuinfo.h"
+
+#define PMUPERIOD  1000000  // Cycles between samples
+
+#define MSR_PMC0             0x0C1
+#define MSR_PERFEVTSEL0      0x186
+#define MSR_FIXED_CTR0       0x309  // Instructions retired
+#define MSR_FIXED_CTR1       0x30A  // Core cycles
+#define MSR_FIXED_CTR_CTRL   0x38D
+#define MSR_PERF_GLOBAL_STATUS    0x38E
+#define MSR_PERF_GLOBAL_CTRL      0x38F
+#define MSR_PERF_GLOBAL_OVF_CTRL  0x390
+
+// MSR_PERFEVTSEL0 bits
+#define EVT_LLCMISS  0x412E      // Event 0x2E, umask 0x41
+#define EVT_USR      0x00010000  // Count in user mode
+#define EVT_EN       0x00400000  // Enable
+
+// MSR_FIXED_CTR_CTRL fields, 4 bits per counter
+#define FIXED_USR    0x2         // Count in user mode
+#define FIXED_PMI    0x8         // Interrupt on overflow
+
+// MSR_PERF_GLOBAL_* bits
+#define GLOBAL_PMC0    (1ULL << 0)
+#define GLOBAL_FIXED0  (1ULL << 32)
+#define GLOBAL_FIXED1  (1ULL << 33)
+
+static struct {
+  int ok;        // Does the CPU have what we need?
+  uint64 mask;   // Width of the fixed counters
+  uint64 start;  // Cycle counter value PMUPERIOD before overflow
+} pmu;
+
+// Set up this CPU's counters, if it has them.
+// Run once on entry on each CPU.
+void
+pmuinit(void)
+{
+  uint a, b, c, d;
+
+  if(!cpuhas(CPUF_PMU))
+    return;
+  cpuidregs(0xA, &a, &b, &c, &d);
+  pmu.mask = (1ULL << ((d >> 5) & 0xFF)) - 1;
+  pmu.start = (pmu.mask + 1) - PMUPERIOD;
+  pmu.ok = 1;
+
+  wrmsr(MSR_PERFEVTSEL0, EVT_LLCMISS | EVT_USR | EVT_EN);
+  wrmsr(MSR_FIXED_CTR_CTRL, FIXED_USR | (FIXED_USR|FIXED_PMI) << 4);
+  wrmsr(MSR_PERF_GLOBAL_CTRL, GLOBAL_PMC0|GLOBAL_FIXED0|GLOBAL_FIXED1);
+  lapicpmi(T_PMI);
+}
+
+// Start counting from zero for the process about to run.
+void
+pmustart(void)
+{
+  if(!pmu.ok)
+    return;
+  wrmsr(MSR_FIXED_CTR0, 0);
+  wrmsr(MSR_FIXED_CTR1, pmu.start);
+  wrmsr(MSR_PMC0, 0);
+}
+
+// Add the counts since pmustart to p's.
+void
+pmustop(struct proc *p)
+{
+  uint64 cyc;
+
+  if(!pmu.ok)
+    return;
+  p->perf[PERF_INSTRS] += rdmsr(MSR_FIXED_CTR0);
+  p->perf[PERF_LLCMISS] += rdmsr(MSR_PMC0);
+  cyc = rdmsr(MSR_FIXED_CTR1) & pmu.mask;
+  if(cyc >= pmu.start)
+    p->perf[PERF_CYCLES] += cyc - pmu.start;
+  else {
+    // Overflowed, and the interrupt is still to come;
+    // it will find nothing to do.
+    p->perf[PERF_CYCLES] += PMUPERIOD + cyc;
+    wrmsr(MSR_PERF_GLOBAL_OVF_CTRL, GLOBAL_FIXED1);
+  }
+}
+
+// Counter overflow interrupt: sample the user PC.
+void
+pmuintr(struct trapframe *tf)
+{
+  uint64 status;
+  struct proc *p = myproc();
+
+  if(!pmu.ok)
+    return;
+  status = rdmsr(MSR_PERF_GLOBAL_STATUS);
+  if((status & GLOBAL_FIXED1) && p){
+    pmustop(p);
+    // The interrupt can arrive after a trap into the kernel.
+    if((tf->cs&3) != DPL_USER)
+      tf = p->tf;
+    p->perfpc[p->nperfpc++ % NPERFPC] = tf->eip;
+    pmustart();
+  }
+  wrmsr(MSR_PERF_GLOBAL_OVF_CTRL, status);
+  // Delivering the interrupt masked it.
+  lapicpmi(T_PMI);
+}
+
+// Copy the current process's counts and samples to pi.
+// Fails if there are no counters.
+int
+perfread(struct perfinfo *pi)
+{
+  struct proc *p = myproc();
+
+  if(!pmu.ok)
+    return -1;
+  pushcli();
+  pmustop(p);
+  pmustart();
+  popcli();
+  memmove(pi->count, p->perf, sizeof(pi->count));
+  pi->npc = p->nperfpc;
+  memmove(pi->pc, p->perfpc, sizeof(pi->pc));
+  return 0;
+}
@@ -0,0 +1,151 @@
+// Waiting on many files at once.
+//
+// Each kind of file says whether it is ready without waiting:
+// pipepoll for pipes, and devsw poll for devices; anything else
+// always is.  A process polling looks at each file in turn and,
+// if none is ready, sleeps until a condition variable that
+// files wait on is signalled (see pollwakeup, which condvar.c
+// calls), and looks again.  Pollers don't know which variable
+// they wait for, so every signal wakes them all.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "stat.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "fs.h"
+#include "file.h"
+#include "timer.h"
+#include "poll.h"
+#include "errno.h"
+
+static struct {
+  struct spinlock lock;
+  uint seq;        // Signals so far
+  int npoll;       // Processes asleep in poll
+} pollw;
+
+void
+pollinit(void)
+{
+  initlock(&pollw.lock, "poll");
+}
+
+// Something files wait for may have changed: wake pollers.
+void
+pollwakeup(void)
+{
+  acquire(&pollw.lock);
+  pollw.seq++;
+  if(pollw.npoll > 0)
+    wakeup(&pollw.seq);
+  release(&pollw.lock);
+}
+
+// Timer function for poll's timeout.
+static void
+polltimeout(void *arg)
+{
+  pollwakeup();
+}
+
+// Has the current process been killed or sent a signal that
+// will interrupt a sleep?  (As in nanosleep.)
+static int
+interrupted(void)
+{
+  struct proc *p = myproc();
+
+  return p->killed || (p->sigpending & ~p->sigmask);
+}
+
+// Which of events are ready on f, plus any errors.
+static int
+filepoll(struct file *f, int events)
+{
+  int r, major;
+
+  r = POLLIN | POLLOUT;
+  if(f->type == FD_PIPE)
+    r = pipepoll(f->pipe, f->writable);
+  else if(f->type == FD_INODE){
+    ilock(f->ip);
+    major = f->ip->major;
+    if(f->ip->type == T_DEV && major >= 0 && major < NDEV &&
+       devsw[major].poll)
+      r = devsw[major].poll(f->ip);
+    iunlock(f->ip);
+  }
+  if(!f->readable)
+    r &= ~POLLIN;
+  if(!f->writable)
+    r &= ~POLLOUT;
+  return r & (events | POLLERR | POLLHUP);
+}
+
+// Set revents in each of the n fds, and return how many are
+// not 0.
+static int
+pollscan(struct pollfd *fds, int n)
+{
+  struct proc *curproc = myproc();
+  struct pollfd *pf;
+  struct file *f;
+  int nready;
+
+  nready = 0;
+  for(pf = fds; pf < fds + n; pf++){
+    pf->revents = 0;
+    if(pf->fd < 0)
+      continue;
+    if(pf->fd >= NOFILE || (f = curproc->ofile[pf->fd]) == 0)
+      pf->revents = POLLNVAL;
+    else
+      pf->revents = filepoll(f, pf->events);
+    if(pf->revents)
+      nready++;
+  }
+  return nready;
+}
+
+// Wait until one of the n fds is ready for what its events
+// ask, or for timeout ms if timeout is not negative.  Returns
+// the number ready, 0 if the time ran out, or -EINTR if a
+// signal came first.
+int
+poll(struct pollfd *fds, int n, int timeout)
+{
+  struct timer t;
+  uint64 end;
+  uint seq;
+  int nready;
+
+  end = nsuptime() + (uint64)timeout * 1000000;
+  memset(&t, 0, sizeof(t));
+  if(timeout > 0)
+    addtimer(&t, end, polltimeout, 0);
+  for(;;){
+    acquire(&pollw.lock);
+    seq = pollw.seq;
+    release(&pollw.lock);
+    if((nready = pollscan(fds, n)) > 0 ||
+       (timeout >= 0 && nsuptime() >= end))
+      break;
+    if(interrupted()){
+      nready = -EINTR;
+      break;
+    }
+    acquire(&pollw.lock);
+    pollw.npoll++;
+    while(pollw.seq == seq && !interrupted())
+      sleep(&pollw.seq, &pollw.lock);
+    pollw.npoll--;
+    release(&pollw.lock);
+  }
+  if(timeout > 0)
+    canceltimer(&t);
+  return nready;
+}
@@ -0,0 +1,13 @@
+// poll, shared by the kernel and user programs.
+
+struct pollfd {
+  int fd;          // Descriptor to watch, or negative to skip
+  short events;    // What to watch for
+  short revents;   // What happened: events, or the last three
+};
+
+#define POLLIN    0x01  // A read won't wait
+#define POLLOUT   0x04  // A write won't wait
+#define POLLERR   0x08  // Writing to a pipe with no reader
+#define POLLHUP   0x10  // Reading from a pipe with no writer
+#define POLLNVAL  0x20  // fd isn't open
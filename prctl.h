@@ -0,0 +1,13 @@
+// Codes for prctl and arch_prctl, shared by the kernel and user
+// programs.
+
+#define PR_SET_SYSFILTER  1  // Allow only the calls in the bitmap at arg
+#define PR_GET_SYSFILTER  2  // Copy the bitmap of allowed calls to arg
+
+#define ARCH_SET_FS  0x1002  // Set the base of the %fs segment
+#define ARCH_GET_FS  0x1003  // Get the base of the %fs segment
+
+// A system call filter is a bitmap of system call numbers, bit
+// n of word n/32 for call n.  exit and sigreturn are always
+// allowed.
+#define NSYSFILTER  ((NSYSCALL + 31) / 32)  // Words in the bitmap
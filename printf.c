@@ -1,85 +1,460 @@
+#include <stdarg.h>
 #include "types.h"
 #include "stat.h"
 #include "user.h"
 
+// Where formatted output goes: to emit, which writes it to file
+// descriptor fd or stdio stream file, by way of a small buffer
+// so that a printf is usually one write, or, if emit is 0, to a
+// caller's buffer of size bytes.
+struct out {
+  void (*emit)(struct out*, int);
+  int fd;
+  FILE *file;
+  char fdbuf[128];
+  char *buf;
+  int size;
+  int n;         // Bytes output so far
+};
+
+static void
+emitfd(struct out *o, int n)
+{
+  write(o->fd, o->fdbuf, n);
+}
+
 static void
-putc(int fd, char c)
+emitfile(struct out *o, int n)
 {
-  write(fd, &c, 1);
+  fwrite(o->fdbuf, 1, n, o->file);
 }
 
 static void
-printint(int fd, int xx, int base, int sgn)
+flush(struct out *o)
 {
-  static char digits[] = "0123456789ABCDEF";
-  char buf[16];
-  int i, neg;
-  uint x;
+  if(o->emit && o->n % sizeof(o->fdbuf) != 0)
+    o->emit(o, o->n % sizeof(o->fdbuf));
+}
 
-  neg = 0;
-  if(sgn && xx < 0){
-    neg = 1;
-    x = -xx;
+// Output c, dropping what doesn't fit in the caller's buffer,
+// which keeps room for a terminating nul.
+static void
+putc(struct out *o, char c)
+{
+  if(o->emit == 0){
+    if(o->n < o->size - 1)
+      o->buf[o->n] = c;
   } else {
-    x = xx;
+    o->fdbuf[o->n % sizeof(o->fdbuf)] = c;
+    if(o->n % sizeof(o->fdbuf) == sizeof(o->fdbuf) - 1)
+      o->emit(o, sizeof(o->fdbuf));
   }
+  o->n++;
+}
+
+// Divide *x by base, which is at most 0x10000, and return the
+// remainder, in 32-bit steps, since user programs don't link
+// the compiler's 64-bit division.
+static uint
+divmod(uint64 *x, uint base)
+{
+  uint hi, q1, q0, t, r;
+
+  hi = *x >> 32;
+  r = hi % base;
+  hi /= base;
+  t = (r << 16) | ((uint)*x >> 16);
+  q1 = t / base;
+  t = ((t % base) << 16) | ((uint)*x & 0xFFFF);
+  q0 = t / base;
+  *x = ((uint64)hi << 32) | (q1 << 16) | q0;
+  return t % base;
+}
+
+// Write the digits of x in base into buf, at least prec of
+// them, which must be at most 2, and return how many.
+static int
+fmtint(char *buf, uint64 x, int base, int prec, char *digits)
+{
+  char tmp[24];
+  int i, n;
 
   i = 0;
-  do{
-    buf[i++] = digits[x % base];
-  }while((x /= base) != 0);
-  if(neg)
-    buf[i++] = '-';
+  while(x != 0)
+    tmp[i++] = digits[divmod(&x, base)];
+  while(i < prec)
+    tmp[i++] = '0';
+  for(n = 0; i > 0; n++)
+    buf[n] = tmp[--i];
+  return n;
+}
+
+static double
+pow10(int n)
+{
+  double p;
 
-  while(--i >= 0)
-    putc(fd, buf[i]);
+  for(p = 1; n > 0; n--)
+    p *= 10;
+  for(; n < 0; n++)
+    p /= 10;
+  return p;
 }
 
-// Print to the given fd. Only understands %d, %x, %p, %s.
-void
-printf(int fd, const char *fmt, ...)
+// Split x > 0 into m * 10^e with 1 <= m < 10, and return e.
+// The last digits or so are off, not correctly rounded.
+static int
+normalize(double *x)
+{
+  int e;
+
+  e = 0;
+  for(; *x >= 1e10; e += 10)
+    *x /= 1e10;
+  for(; *x >= 10; e++)
+    *x /= 10;
+  for(; *x < 1e-10; e -= 10)
+    *x *= 1e10;
+  for(; *x < 1; e--)
+    *x *= 10;
+  return e;
+}
+
+// Round x >= 0 at the last of prec digits after the point of
+// %e (if conv is 'e') or %f, and split it into m * 10^e.
+// Ties round up, not to even as C's printf does.
+static int
+round(double *x, int prec, int conv)
+{
+  double m;
+  int e;
+
+  if(*x == 0)
+    return 0;
+  m = *x;
+  e = normalize(&m);
+  *x += 0.5 * pow10(conv == 'e' ? e - prec : -prec);
+  return normalize(x);
+}
+
+// Write finite x >= 0 into buf as %e or %f (conv) would with
+// precision prec, and return the length.  Digits past the
+// 17th, which a double doesn't hold, are 0.
+static int
+fmtflt(char *buf, double x, int prec, int conv)
+{
+  int e, n, pos, d, sig;
+
+  e = round(&x, prec, conv);
+  n = sig = 0;
+  pos = conv == 'e' || e < 0 ? 0 : e;
+  for(; pos >= -prec; pos--){
+    if((conv != 'e' && pos > e) || sig >= 17)
+      buf[n++] = '0';
+    else {
+      d = (int)x;
+      buf[n++] = '0' + d;
+      x = (x - d) * 10;
+      sig++;
+    }
+    if(pos == 0 && prec > 0)
+      buf[n++] = '.';
+  }
+  if(conv == 'e'){
+    buf[n++] = 'e';
+    buf[n++] = e < 0 ? '-' : '+';
+    n += fmtint(buf + n, e < 0 ? -e : e, 10, 2, "0123456789");
+  }
+  return n;
+}
+
+// Write finite x >= 0 as %g would, with prec significant
+// digits and no trailing zeros after the point.
+static int
+fmtgen(char *buf, double x, int prec)
+{
+  double d;
+  int e, n, end, i;
+
+  if(prec == 0)
+    prec = 1;
+  // The exponent %e would print decides between %e and %f.
+  d = x;
+  e = round(&d, prec - 1, 'e');
+  if(e < -4 || e >= prec)
+    n = fmtflt(buf, x, prec - 1, 'e');
+  else
+    n = fmtflt(buf, x, prec - 1 - e, 'f');
+  for(end = 0; end < n && buf[end] != 'e'; end++)
+    ;
+  for(i = 0; i < end && buf[i] != '.'; i++)
+    ;
+  if(i == end)
+    return n;
+  for(i = end; buf[i - 1] == '0'; i--)
+    ;
+  if(buf[i - 1] == '.')
+    i--;
+  memmove(buf + i, buf + end, n - end);
+  return i + n - end;
+}
+
+// Format to o.  Understands the flags -, 0, +, space, a width
+// and precision (or * for either), the length l, ll, h or z,
+// and the conversions d, i, u, x, X, o, p, c, s, f, e and g
+// (with F, E, G), and %%.
+static void
+format(struct out *o, const char *fmt, va_list ap)
 {
-  char *s;
-  int c, i, state;
-  uint *ap;
+  char buf[360], *s, *pre;
+  int c, i, n, npre, nzero, left, zero, width, prec, longs, digits;
+  uint64 x;
+  double d;
 
-  state = 0;
-  ap = (uint*)(void*)&fmt + 1;
   for(i = 0; fmt[i]; i++){
     c = fmt[i] & 0xff;
-    if(state == 0){
-      if(c == '%'){
-        state = '%';
+    if(c != '%'){
+      putc(o, c);
+      continue;
+    }
+
+    pre = "";
+    left = zero = 0;
+    for(;;){
+      c = fmt[++i];
+      if(c == '-')
+        left = 1;
+      else if(c == '0')
+        zero = 1;
+      else if(c == '+' || (c == ' ' && *pre != '+'))
+        pre = c == '+' ? "+" : " ";
+      else
+        break;
+    }
+    width = 0;
+    if(c == '*'){
+      width = va_arg(ap, int);
+      if(width < 0){
+        left = 1;
+        width = -width;
+      }
+      c = fmt[++i];
+    } else
+      for(; c >= '0' && c <= '9'; c = fmt[++i])
+        width = width*10 + c - '0';
+    prec = -1;
+    if(c == '.'){
+      c = fmt[++i];
+      prec = 0;
+      if(c == '*'){
+        prec = va_arg(ap, int);
+        c = fmt[++i];
+      } else
+        for(; c >= '0' && c <= '9'; c = fmt[++i])
+          prec = prec*10 + c - '0';
+    }
+    longs = 0;
+    for(; c == 'l' || c == 'h' || c == 'z'; c = fmt[++i])
+      if(c == 'l')
+        longs++;
+    if(c == 0)
+      break;
+
+    // Format into buf, or point s at the text, with n bytes,
+    // which follow nzero zeros.
+    s = buf;
+    n = nzero = 0;
+    if(c == 'd' || c == 'i' || c == 'u' || c == 'x' || c == 'X' ||
+       c == 'o' || c == 'p'){
+      if(longs >= 2){
+        x = va_arg(ap, uint64);
+        if(c == 'd' || c == 'i'){
+          if((long long)x < 0){
+            x = -x;
+            pre = "-";
+          }
+        } else
+          pre = "";
       } else {
-        putc(fd, c);
+        x = va_arg(ap, uint);
+        if(c == 'd' || c == 'i'){
+          if((int)x < 0){
+            x = -(int)x;
+            pre = "-";
+          }
+        } else
+          pre = "";
+      }
+      // The zeros the precision asks for go out as nzero, not
+      // in buf, which they could overrun.
+      digits = prec < 0 ? 1 : prec;
+      if(c == 'x' || c == 'p')
+        n = fmtint(buf, x, 16, digits > 0, "0123456789abcdef");
+      else if(c == 'X')
+        n = fmtint(buf, x, 16, digits > 0, "0123456789ABCDEF");
+      else if(c == 'o')
+        n = fmtint(buf, x, 8, digits > 0, "01234567");
+      else
+        n = fmtint(buf, x, 10, digits > 0, "0123456789");
+      if(digits > n)
+        nzero = digits - n;
+      if(prec >= 0)
+        zero = 0;
+    } else if(c == 'f' || c == 'F' || c == 'e' || c == 'E' ||
+              c == 'g' || c == 'G'){
+      d = va_arg(ap, double);
+      if(d < 0 || (d == 0 && 1/d < 0)){
+        d = -d;
+        pre = "-";
       }
-    } else if(state == '%'){
-      if(c == 'd'){
-        printint(fd, *ap, 10, 1);
-        ap++;
-      } else if(c == 'x' || c == 'p'){
-        printint(fd, *ap, 16, 0);
-        ap++;
-      } else if(c == 's'){
-        s = (char*)*ap;
-        ap++;
+      if(prec < 0)
+        prec = 6;
+      if(prec > 40)
+        prec = 40;
+      if(d != d || d > 1.7976931348623157e308){
+        s = d != d ? "nan" : "inf";
+        n = 3;
+        zero = 0;
+      } else if(c == 'f' || c == 'F')
+        n = fmtflt(buf, d, prec, 'f');
+      else if(c == 'e' || c == 'E')
+        n = fmtflt(buf, d, prec, 'e');
+      else
+        n = fmtgen(buf, d, prec);
+      if(c == 'E' || c == 'G')
+        for(digits = 0; digits < n; digits++)
+          if(buf[digits] == 'e')
+            buf[digits] = 'E';
+    } else {
+      pre = "";
+      zero = 0;
+      if(c == 's'){
+        s = va_arg(ap, char*);
         if(s == 0)
           s = "(null)";
-        while(*s != 0){
-          putc(fd, *s);
-          s++;
-        }
+        for(n = 0; s[n] && (prec < 0 || n < prec); n++)
+          ;
       } else if(c == 'c'){
-        putc(fd, *ap);
-        ap++;
+        buf[n++] = va_arg(ap, int);
       } else if(c == '%'){
-        putc(fd, c);
+        buf[n++] = '%';
       } else {
         // Unknown % sequence.  Print it to draw attention.
-        putc(fd, '%');
-        putc(fd, c);
+        buf[n++] = '%';
+        buf[n++] = c;
       }
-      state = 0;
     }
+
+    // Pad to width: with spaces before the sign, or zeros after
+    // it, or spaces at the end.
+    npre = strlen(pre);
+    width -= npre + nzero + n;
+    if(!left && !zero)
+      for(; width > 0; width--)
+        putc(o, ' ');
+    while(*pre)
+      putc(o, *pre++);
+    if(zero && !left)
+      for(; width > 0; width--)
+        putc(o, '0');
+    while(nzero-- > 0)
+      putc(o, '0');
+    while(n-- > 0)
+      putc(o, *s++);
+    for(; width > 0; width--)
+      putc(o, ' ');
   }
 }
+
+// Print to the given fd.
+void
+printf(int fd, const char *fmt, ...)
+{
+  va_list ap;
+
+  va_start(ap, fmt);
+  vprintf(fd, fmt, ap);
+  va_end(ap);
+}
+
+void
+vprintf(int fd, const char *fmt, va_list ap)
+{
+  struct out o;
+
+  o.emit = emitfd;
+  o.fd = fd;
+  o.n = 0;
+  format(&o, fmt, ap);
+  flush(&o);
+}
+
+// Print to stdio stream f.  Returns the bytes printed, or -1
+// if writing failed.
+int
+fprintf(FILE *f, const char *fmt, ...)
+{
+  va_list ap;
+  int n;
+
+  va_start(ap, fmt);
+  n = vfprintf(f, fmt, ap);
+  va_end(ap);
+  return n;
+}
+
+int
+vfprintf(FILE *f, const char *fmt, va_list ap)
+{
+  struct out o;
+
+  o.emit = emitfile;
+  o.file = f;
+  o.n = 0;
+  format(&o, fmt, ap);
+  flush(&o);
+  return ferror(f) ? -1 : o.n;
+}
+
+// Format into buf, which holds size bytes, and nul-terminate it.
+// Returns the length the whole output would have, which is size
+// or more if it was cut short.
+int
+vsnprintf(char *buf, int size, const char *fmt, va_list ap)
+{
+  struct out o;
+
+  o.emit = 0;
+  o.buf = buf;
+  o.size = size;
+  o.n = 0;
+  format(&o, fmt, ap);
+  if(size > 0)
+    buf[o.n < size ? o.n : size - 1] = 0;
+  return o.n;
+}
+
+int
+snprintf(char *buf, int size, const char *fmt, ...)
+{
+  va_list ap;
+  int n;
+
+  va_start(ap, fmt);
+  n = vsnprintf(buf, size, fmt, ap);
+  va_end(ap);
+  return n;
+}
+
+// Like snprintf, with no limit; buf must be big enough.
+int
+sprintf(char *buf, const char *fmt, ...)
+{
+  va_list ap;
+  int n;
+
+  va_start(ap, fmt);
+  n = vsnprintf(buf, 0x7FFFFFFF, fmt, ap);
+  va_end(ap);
+  return n;
+}
@@ -6,6 +6,14 @@
 #include "x86.h"
 #include "proc.h"
 #include "spinlock.h"
+#include "sleeplock.h"
+#include "signal.h"
+#include "pinfo.h"
+#include "time.h"
+#include "resource.h"
+#include "ptrace.h"
+#include "wait.h"
+#include "errno.h"
 
 struct {
   struct spinlock lock;
@@ -19,11 +27,17 @@ extern void forkret(void);
 extern void trapret(void);
 
 static void wakeup1(void *chan);
+static void psignal1(struct proc *p, int sig);
+static void kthreadexit(void);
+
+// Serializes cpuoffline and cpuonline.
+static struct sleeplock hotplug;
 
 void
 pinit(void)
 {
   initlock(&ptable.lock, "ptable");
+  initsleeplock(&hotplug, "hotplug");
 }
 
 // Must be called with interrupts disabled
@@ -96,6 +110,12 @@ found:
     p->state = UNUSED;
     return 0;
   }
+  if((p->fpu = fpualloc()) == 0){
+    kfree(p->kstack);
+    p->kstack = 0;
+    p->state = UNUSED;
+    return 0;
+  }
   sp = p->kstack + KSTACKSIZE;
 
   // Leave room for trap frame.
@@ -120,6 +140,7 @@ found:
 void
 userinit(void)
 {
+  int i;
   struct proc *p;
   extern char _binary_initcode_start[], _binary_initcode_size[];
 
@@ -130,6 +151,7 @@ userinit(void)
     panic("userinit: out of memory?");
   inituvm(p->pgdir, _binary_initcode_start, (int)_binary_initcode_size);
   p->sz = PGSIZE;
+  p->mmapbase = MMAPTOP;
   memset(p->tf, 0, sizeof(*p->tf));
   p->tf->cs = (SEG_UCODE << 3) | DPL_USER;
   p->tf->ds = (SEG_UDATA << 3) | DPL_USER;
@@ -141,6 +163,13 @@ userinit(void)
 
   safestrcpy(p->name, "initcode", sizeof(p->name));
   p->cwd = namei("/");
+  p->pgid = p->pid;
+  p->sid = p->pid;
+  for(i = 0; i < NRLIMIT; i++)
+    p->rlimcur[i] = p->rlimmax[i] = RLIM_INFINITY;
+  p->rlimcur[RLIMIT_NOFILE] = p->rlimmax[RLIMIT_NOFILE] = NOFILE;
+  p->rlimcur[RLIMIT_STACK] = USTACKSIZE;
+  p->rlimmax[RLIMIT_STACK] = MAXUSTACK;
 
   // this assignment to p->state lets other cores
   // run this process. the acquire forces the above
@@ -159,21 +188,153 @@ int
 growproc(int n)
 {
   uint sz;
+  struct proc *p;
   struct proc *curproc = myproc();
 
   sz = curproc->sz;
   if(n > 0){
-    if((sz = allocuvm(curproc->pgdir, sz, sz + n)) == 0)
-      return -1;
+    // Memory is allocated when first touched; see lazyalloc.
+    // The heap may not grow into mmap'd areas or the stack.
+    if(sz + n < sz || sz + n > MMAPTOP ||
+       sz + n > curproc->rlimcur[RLIMIT_AS] ||
+       vmaoverlap(curproc->pgdir, sz, sz + n))
+      return -ENOMEM;
+    sz += n;
   } else if(n < 0){
     if((sz = deallocuvm(curproc->pgdir, sz, sz + n)) == 0)
-      return -1;
+      return -ENOMEM;
   }
+
+  // Threads sharing the address space must see the new size.
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->state != UNUSED && p->pgdir == curproc->pgdir)
+      p->sz = sz;
+  release(&ptable.lock);
+
+  switchuvm(curproc);
+  return 0;
+}
+
+// Does a process other than p use p's page table?
+// Caller must hold ptable.lock.
+static int
+sharedpgdir(struct proc *p)
+{
+  struct proc *q;
+
+  for(q = ptable.proc; q < &ptable.proc[NPROC]; q++)
+    if(q != p && q->state != UNUSED && q->pgdir == p->pgdir)
+      return 1;
+  return 0;
+}
+
+// Install a new user address space in the current process,
+// as exec does, and free the old one unless another thread
+// is still using it.
+void
+replaceuvm(pde_t *pgdir, uint sz)
+{
+  int shared;
+  pde_t *oldpgdir;
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  oldpgdir = curproc->pgdir;
+  shared = sharedpgdir(curproc);
+  curproc->pgdir = pgdir;
   curproc->sz = sz;
+  release(&ptable.lock);
+
   switchuvm(curproc);
+  if(!shared)
+    freeuvm(oldpgdir);
+}
+
+// Free a page of memory, for kalloc when it runs out: give up
+// a cached file page no one maps, or else evict a page from
+// some process (see uvmevict).  The clock hand goes around the
+// processes and through each address space, at most twice, so
+// that pages touched since the hand last passed are skipped.
+// Returns 0 if there was nothing to free.  Sleeps.
+int
+reclaim(void)
+{
+  static int hand;      // process the clock hand is at
+  static uint va;       // and the address in its memory
+  struct proc *p;
+  pde_t *pgdir;
+  char *page;
+  int n, slot, r;
+
+  if(pcreclaim())
+    return 1;
+  for(n = 0; n <= 2*NPROC; n++){
+    acquire(&ptable.lock);
+    p = &ptable.proc[hand];
+    pgdir = p->pgdir;
+    r = 0;
+    if(pgdir && p->state != UNUSED && p->state != EMBRYO)
+      r = uvmevict(pgdir, &va, &page, &slot);
+    if(r == 0){
+      hand = (hand + 1) % NPROC;
+      va = 0;
+    }
+    release(&ptable.lock);
+    if(r){
+      tlbflush(pgdir);
+      if(slot >= 0)
+        swapwrite(slot, page);
+      kfree(page);
+      return 1;
+    }
+  }
   return 0;
 }
 
+// Free a ZOMBIE process's kernel stack and mark it UNUSED.
+// Returns its address space if no other thread still shares
+// it; freeing that can sleep, so the caller must do it with
+// freeuvm after releasing ptable.lock, which it must hold.
+static pde_t*
+freeproc(struct proc *p)
+{
+  pde_t *pgdir;
+
+  kfree(p->kstack);
+  p->kstack = 0;
+  kfree(p->fpu);
+  p->fpu = 0;
+  pgdir = 0;
+  if(p->pgdir && !sharedpgdir(p))
+    pgdir = p->pgdir;
+  p->pgdir = 0;
+  p->ustack = 0;
+  p->pid = 0;
+  p->pgid = 0;
+  p->sid = 0;
+  p->uid = p->euid = 0;
+  p->gid = p->egid = 0;
+  p->parent = 0;
+  p->name[0] = 0;
+  p->killed = 0;
+  p->xsig = 0;
+  p->tracer = 0;
+  p->stopped = 0;
+  p->jobstop = 0;
+  p->cputicks = 0;
+  p->utime = p->stime = 0;
+  p->cutime = p->cstime = 0;
+  memset(p->itvalue, 0, sizeof(p->itvalue));
+  memset(p->itinterval, 0, sizeof(p->itinterval));
+  p->sigpending = 0;
+  p->sigmask = 0;
+  memset(p->perf, 0, sizeof(p->perf));
+  p->nperfpc = 0;
+  p->state = UNUSED;
+  return pgdir;
+}
+
 // Create a new process copying p as the parent.
 // Sets up stack to return as if from system call.
 // Caller must set state of returned proc to RUNNABLE.
@@ -186,34 +347,139 @@ fork(void)
 
   // Allocate process.
   if((np = allocproc()) == 0){
-    return -1;
+    return -EAGAIN;
   }
 
   // Copy process state from proc.
   if((np->pgdir = copyuvm(curproc->pgdir, curproc->sz)) == 0){
     kfree(np->kstack);
     np->kstack = 0;
+    kfree(np->fpu);
+    np->fpu = 0;
     np->state = UNUSED;
-    return -1;
+    return -EAGAIN;
+  }
+  if(vmacopy(curproc->pgdir, np->pgdir, curproc->sz) < 0){
+    freeuvm(np->pgdir);
+    np->pgdir = 0;
+    kfree(np->kstack);
+    np->kstack = 0;
+    kfree(np->fpu);
+    np->fpu = 0;
+    np->state = UNUSED;
+    return -EAGAIN;
   }
   np->sz = curproc->sz;
+  np->mmapbase = curproc->mmapbase;
   np->parent = curproc;
   *np->tf = *curproc->tf;
+  fpusave(np->fpu);
+  np->tlsbase = curproc->tlsbase;
+  np->trace = curproc->trace;
+  memmove(np->sysdeny, curproc->sysdeny, sizeof(np->sysdeny));
 
   // Clear %eax so that fork returns 0 in the child.
   np->tf->eax = 0;
 
   for(i = 0; i < NOFILE; i++)
-    if(curproc->ofile[i])
+    if(curproc->ofile[i]){
+      np->ofile[i] = filedup(curproc->ofile[i]);
+      np->fdflags[i] = curproc->fdflags[i];
+    }
+  np->cwd = idup(curproc->cwd);
+
+  np->pgid = curproc->pgid;
+  np->sid = curproc->sid;
+  np->uid = curproc->uid;
+  np->euid = curproc->euid;
+  np->gid = curproc->gid;
+  np->egid = curproc->egid;
+  memmove(np->rlimcur, curproc->rlimcur, sizeof(np->rlimcur));
+  memmove(np->rlimmax, curproc->rlimmax, sizeof(np->rlimmax));
+  np->sigmask = curproc->sigmask;
+  memmove(np->sighandler, curproc->sighandler, sizeof(np->sighandler));
+  memmove(np->sighmask, curproc->sighmask, sizeof(np->sighmask));
+
+  safestrcpy(np->name, curproc->name, sizeof(curproc->name));
+
+  pid = np->pid;
+
+  acquire(&ptable.lock);
+
+  np->state = RUNNABLE;
+
+  release(&ptable.lock);
+
+  return pid;
+}
+
+// Create a new thread sharing the current process's address
+// space.  The thread runs fn(arg) on the PGSIZE-byte user stack
+// at stack, which the caller allocates and frees (see join).
+// Like fork, it gets its own references to the open files and
+// current directory.  Returns the new thread's pid.
+int
+clone(void (*fn)(void*), void *arg, void *stack)
+{
+  int i, pid;
+  uint sp, ustack[2];
+  struct proc *np;
+  struct proc *curproc = myproc();
+
+  sp = (uint)stack + PGSIZE;
+  if(sp < (uint)stack || sp > KERNBASE)
+    return -EINVAL;
+
+  // Enter fn with a fake return PC, as exec does for main.
+  ustack[0] = 0xffffffff;
+  ustack[1] = (uint)arg;
+  sp -= sizeof(ustack);
+  if(copyout(curproc->pgdir, curproc->sz, sp, ustack, sizeof(ustack)) < 0)
+    return -EFAULT;
+
+  if((np = allocproc()) == 0)
+    return -EAGAIN;
+
+  np->pgdir = curproc->pgdir;
+  np->sz = curproc->sz;
+  np->mmapbase = curproc->mmapbase;
+  np->parent = curproc;
+  np->ustack = stack;
+  *np->tf = *curproc->tf;
+  fpusave(np->fpu);
+  np->tlsbase = curproc->tlsbase;
+  np->trace = curproc->trace;
+  np->tf->eip = (uint)fn;
+  np->tf->esp = sp;
+
+  for(i = 0; i < NOFILE; i++)
+    if(curproc->ofile[i]){
       np->ofile[i] = filedup(curproc->ofile[i]);
+      np->fdflags[i] = curproc->fdflags[i];
+    }
   np->cwd = idup(curproc->cwd);
 
+  np->pgid = curproc->pgid;
+  np->sid = curproc->sid;
+  np->uid = curproc->uid;
+  np->euid = curproc->euid;
+  np->gid = curproc->gid;
+  np->egid = curproc->egid;
+  memmove(np->rlimcur, curproc->rlimcur, sizeof(np->rlimcur));
+  memmove(np->rlimmax, curproc->rlimmax, sizeof(np->rlimmax));
+  np->sigmask = curproc->sigmask;
+  memmove(np->sighandler, curproc->sighandler, sizeof(np->sighandler));
+  memmove(np->sighmask, curproc->sighmask, sizeof(np->sighmask));
+
   safestrcpy(np->name, curproc->name, sizeof(curproc->name));
 
   pid = np->pid;
 
   acquire(&ptable.lock);
 
+  // Under ptable.lock, so as not to miss a sysfilter by
+  // another thread.
+  memmove(np->sysdeny, curproc->sysdeny, sizeof(np->sysdeny));
   np->state = RUNNABLE;
 
   release(&ptable.lock);
@@ -221,6 +487,47 @@ fork(void)
   return pid;
 }
 
+// Create a kernel thread that runs fn(arg) in kernel mode.
+// The thread has no user page table; the scheduler runs it
+// on the kernel page table, and it sleeps, wakes, and is
+// preempted like any other process.  If fn returns, the
+// thread exits and is reaped by init.
+// Must be called after userinit().  Returns the new pid.
+int
+kthread(char *name, void (*fn)(void*), void *arg)
+{
+  struct proc *p;
+  uint *sp;
+
+  if((p = allocproc()) == 0)
+    return -1;
+
+  // allocproc() arranged for forkret to return to trapret.
+  // Make it return into fn instead, with kthreadexit as
+  // fn's return address and arg as fn's argument.
+  // A kernel thread never uses its trap frame.
+  sp = (uint*)(p->context + 1);
+  sp[0] = (uint)fn;
+  sp[1] = (uint)kthreadexit;
+  sp[2] = (uint)arg;
+
+  p->parent = initproc;
+  safestrcpy(p->name, name, sizeof(p->name));
+
+  acquire(&ptable.lock);
+  p->state = RUNNABLE;
+  release(&ptable.lock);
+
+  return p->pid;
+}
+
+// A kernel thread's function returns here.
+static void
+kthreadexit(void)
+{
+  exit();
+}
+
 // Exit the current process.  Does not return.
 // An exited process remains in the zombie state
 // until its parent calls wait() to find out it exited.
@@ -242,23 +549,39 @@ exit(void)
     }
   }
 
-  begin_op();
-  iput(curproc->cwd);
-  end_op();
-  curproc->cwd = 0;
+  if(curproc->cwd){
+    begin_op();
+    iput(curproc->cwd);
+    end_op();
+    curproc->cwd = 0;
+  }
 
   acquire(&ptable.lock);
 
   // Parent might be sleeping in wait().
   wakeup1(curproc->parent);
+  if(curproc->parent && curproc->parent->pgdir != curproc->pgdir)
+    psignal1(curproc->parent, SIGCHLD);
 
-  // Pass abandoned children to init.
+  // So might a tracer.
+  if(curproc->tracer){
+    wakeup1(curproc->tracer);
+    curproc->tracer = 0;
+  }
+
+  // Pass abandoned children to init, and let go of
+  // processes being traced.
   for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
     if(p->parent == curproc){
       p->parent = initproc;
       if(p->state == ZOMBIE)
         wakeup1(initproc);
     }
+    if(p->tracer == curproc){
+      p->tracer = 0;
+      p->stopped = 0;
+      wakeup1(p);
+    }
   }
 
   // Jump into the scheduler, never to return.
@@ -271,39 +594,100 @@ exit(void)
 // Return -1 if this process has no children.
 int
 wait(void)
+{
+  return waitpid(-1, 0, 0, 0);
+}
+
+// Convert clock ticks to a timeval.
+static void
+tickstotv(uint t, struct timeval *tv)
+{
+  tv->tv_sec = t / HZ;
+  tv->tv_usec = t % HZ * (1000000 / HZ);
+}
+
+// Convert nanoseconds to a timeval.
+static void
+nstotv(uint64 ns, struct timeval *tv)
+{
+  uint rem;
+
+  tv->tv_sec = divmod64(ns, 1000000000, &rem);
+  tv->tv_usec = rem / 1000;
+}
+
+// Wait for child process pid (any child if pid is -1) to
+// exit, or for a process traced by this one to stop, or with
+// WUNTRACED for a child to be stopped by a signal, and
+// return its pid.  Store its status (see wait.h) in *status
+// and, for an exited child, the CPU time it and its reaped
+// children used in *ru, if they are not 0.  With WNOHANG,
+// return 0 rather than wait.  Return -1 if there is no such
+// process.
+int
+waitpid(int pid, int *status, int options, struct rusage *ru)
 {
   struct proc *p;
-  int havekids, pid;
+  int havekids, cpid;
+  pde_t *pgdir;
   struct proc *curproc = myproc();
   
   acquire(&ptable.lock);
   for(;;){
-    // Scan through table looking for exited children.
+    // Scan through table looking for exited children
+    // and stopped tracees.
+    // Threads are collected by join, not wait.
     havekids = 0;
     for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
-      if(p->parent != curproc)
+      if(pid != -1 && p->pid != pid)
+        continue;
+      if(p->tracer == curproc){
+        havekids = 1;
+        if(p->stopped && !p->stopreported){
+          p->stopreported = 1;
+          if(status)
+            *status = p->stopsig << 8 | 0x7F;
+          release(&ptable.lock);
+          return p->pid;
+        }
+      }
+      if(p->parent != curproc || p->pgdir == curproc->pgdir)
         continue;
       havekids = 1;
+      if((options & WUNTRACED) && p->jobstop && !p->jobstopreported){
+        p->jobstopreported = 1;
+        if(status)
+          *status = p->jobstop << 8 | 0x7F;
+        release(&ptable.lock);
+        return p->pid;
+      }
       if(p->state == ZOMBIE){
         // Found one.
-        pid = p->pid;
-        kfree(p->kstack);
-        p->kstack = 0;
-        freevm(p->pgdir);
-        p->pid = 0;
-        p->parent = 0;
-        p->name[0] = 0;
-        p->killed = 0;
-        p->state = UNUSED;
+        cpid = p->pid;
+        if(status)
+          *status = p->xsig;
+        curproc->cutime += p->utime + p->cutime;
+        curproc->cstime += p->stime + p->cstime;
+        if(ru){
+          nstotv(p->utime + p->cutime, &ru->ru_utime);
+          nstotv(p->stime + p->cstime, &ru->ru_stime);
+        }
+        pgdir = freeproc(p);
         release(&ptable.lock);
-        return pid;
+        if(pgdir)
+          freeuvm(pgdir);
+        return cpid;
       }
     }
 
     // No point waiting if we don't have any children.
     if(!havekids || curproc->killed){
       release(&ptable.lock);
-      return -1;
+      return havekids ? -EINTR : -ECHILD;
+    }
+    if(options & WNOHANG){
+      release(&ptable.lock);
+      return 0;
     }
 
     // Wait for children to exit.  (See wakeup1 call in proc_exit.)
@@ -311,6 +695,86 @@ wait(void)
   }
 }
 
+// Wait for a thread created by clone to exit and return its pid.
+// Stores the thread's stack in *stack so the caller can free it.
+// Return -1 if this process has no threads.
+int
+join(void **stack)
+{
+  struct proc *p;
+  int havekids, pid;
+  pde_t *pgdir;
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  for(;;){
+    havekids = 0;
+    for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
+      if(p->parent != curproc || p->pgdir != curproc->pgdir)
+        continue;
+      havekids = 1;
+      if(p->state == ZOMBIE){
+        pid = p->pid;
+        *stack = p->ustack;
+        pgdir = freeproc(p);
+        release(&ptable.lock);
+        if(pgdir)
+          freeuvm(pgdir);
+        return pid;
+      }
+    }
+
+    if(!havekids || curproc->killed){
+      release(&ptable.lock);
+      return havekids ? -EINTR : -ECHILD;
+    }
+
+    sleep(curproc, &ptable.lock);  //DOC: join-sleep
+  }
+}
+
+// Halt CPU c, which found nothing to run, until an interrupt.
+// Stop its clock tick meanwhile, unless ITIMER_REAL timers need
+// counting down (see realtimers), so that it wakes only for
+// devices and pending timers.
+static void
+idle(struct cpu *c, int alarms)
+{
+  struct proc *p;
+
+  // With interrupts off, nothing on this CPU can make a process
+  // runnable before the hlt.  Another CPU can, but it is awake
+  // to do so, and will find the process itself.
+  cli();
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->state == RUNNABLE){
+      sti();
+      return;
+    }
+  if(!alarms)
+    tickstop();
+  c->idle = 1;
+  stihlt();
+  c->idle = 0;
+  tickstart();
+}
+
+// Park CPU c, which cpuoffline has asked to go offline: halt
+// with interrupts off, until cpuonline restarts it with INIT
+// and SIPI, which reset it.  It runs no process and holds no
+// lock here, so nothing is left waiting on it.
+static void
+park(struct cpu *c)
+{
+  cli();
+  tickstop();
+  lapictimerstop();
+  cprintf("cpu%d: offline\n", cpuid());
+  xchg(&c->started, 0);
+  for(;;)
+    hlt();
+}
+
 //PAGEBREAK: 42
 // Per-CPU process scheduler.
 // Each CPU calls scheduler() after setting itself up.
@@ -324,26 +788,44 @@ scheduler(void)
 {
   struct proc *p;
   struct cpu *c = mycpu();
+  int ran, alarms;
   c->proc = 0;
   
   for(;;){
     // Enable interrupts on this processor.
     sti();
+    rcuquiescent();
+
+    if(c->offline)
+      park(c);
 
     // Loop over process table looking for process to run.
+    ran = alarms = 0;
     acquire(&ptable.lock);
     for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
+      if(p->state != ZOMBIE && p->itvalue[ITIMER_REAL])
+        alarms = 1;
       if(p->state != RUNNABLE)
         continue;
+      ran = 1;
 
       // Switch to chosen process.  It is the process's job
       // to release ptable.lock and then reacquire it
       // before jumping back to us.
+      // Kernel threads have no page table of their own and
+      // run on kpgdir, which the scheduler is already using.
       c->proc = p;
-      switchuvm(p);
+      if(p->pgdir)
+        switchuvm(p);
       p->state = RUNNING;
 
+      fpurestore(p->fpu);
+      pmustart();
+      p->tmark = nsuptime();
       swtch(&(c->scheduler), p->context);
+      chargetime(p, 0);
+      pmustop(p);
+      fpusave(p->fpu);
       switchkvm();
 
       // Process is done running for now.
@@ -352,7 +834,61 @@ scheduler(void)
     }
     release(&ptable.lock);
 
+    if(!ran)
+      idle(c, alarms);
+  }
+}
+
+static void
+nothing(void *unused)
+{
+}
+
+// Take CPU number n offline.  Its device interrupts go to other
+// CPUs at once; it parks once it has finished what it is
+// running, and its timers then move to this CPU.  CPU 0, which
+// the others boot from, can't go.
+int
+cpuoffline(int n)
+{
+  struct cpu *c;
+
+  if(n <= 0 || n >= ncpu)
+    return -EINVAL;
+  c = &cpus[n];
+  acquiresleep(&hotplug);
+  if(!c->started || c->offline){
+    releasesleep(&hotplug);
+    return -EBUSY;
   }
+  c->offline = 1;
+  irqevict(n);
+  callcpu(n, nothing, 0);  // wake it if idle
+  while(c->started)
+    yield();
+  timeradopt(n);
+  releasesleep(&hotplug);
+  return 0;
+}
+
+// Bring CPU number n back online, by the sequence that booted it.
+int
+cpuonline(int n)
+{
+  struct cpu *c;
+
+  if(n <= 0 || n >= ncpu)
+    return -EINVAL;
+  c = &cpus[n];
+  acquiresleep(&hotplug);
+  if(!c->offline){
+    releasesleep(&hotplug);
+    return -EBUSY;
+  }
+  c->offline = 0;
+  startcpu(c);
+  releasesleep(&hotplug);
+  return 0;
 }
 
 // Enter scheduler.  Must hold only ptable.lock
@@ -473,62 +1009,937 @@ wakeup(void *chan)
   release(&ptable.lock);
 }
 
-// Kill the process with the given pid.
-// Process won't exit until it returns
-// to user space (see trap in trap.c).
-int
-kill(int pid)
+// Wake up one process sleeping on chan, for when one can
+// take all there is.
+void
+wakeone(void *chan)
 {
   struct proc *p;
 
   acquire(&ptable.lock);
-  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
-    if(p->pid == pid){
-      p->killed = 1;
-      // Wake process from sleep if necessary.
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->state == SLEEPING && p->chan == chan){
+      p->state = RUNNABLE;
+      break;
+    }
+  release(&ptable.lock);
+}
+
+// Post signal sig to process p.  Signals that will kill p
+// take effect at once: p won't exit until it returns to user
+// space (see trap in trap.c), but setting p->killed makes it
+// abandon any sleep.  Others wait in p->sigpending for psig,
+// including those that will stop p.  SIGCONT continues p
+// whatever its action, and cancels pending stops.
+// The ptable lock must be held.
+static void
+psignal1(struct proc *p, int sig)
+{
+  uint bit;
+
+  bit = 1 << sig;
+  if(sig == SIGCONT){
+    p->sigpending &= ~SIGDFL_STOP;
+    if(p->jobstop){
+      p->jobstop = 0;
       if(p->state == SLEEPING)
         p->state = RUNNABLE;
+    }
+  } else if(SIGDFL_STOP & bit)
+    p->sigpending &= ~(1 << SIGCONT);
+
+  if(sig == SIGKILL){
+    p->killed = 1;
+    p->xsig = sig;
+  } else if(p->tracer)
+    p->sigpending |= bit;  // The tracer decides, in issig.
+  else if(p->sighandler[sig] == SIG_IGN)
+    return;
+  else if(p->sighandler[sig] == SIG_DFL){
+    if(SIGDFL_IGNORE & bit)
+      return;
+    if((p->sigmask & bit) || (SIGDFL_STOP & bit))
+      p->sigpending |= bit;
+    else {
+      p->killed = 1;
+      p->xsig = sig;
+    }
+  } else
+    p->sigpending |= bit;
+
+  // Wake process from sleep if necessary.
+  if(p->state == SLEEPING)
+    p->state = RUNNABLE;
+}
+
+// Send signal sig to the process with the given pid, or
+// to process group -pid if pid is negative.
+// Signal 0 only checks that the process exists.
+int
+kill(int pid, int sig)
+{
+  struct proc *p;
+
+  if(pid < 0)
+    return killpg(-pid, sig);
+  if(sig < 0 || sig >= NSIG)
+    return -EINVAL;
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
+    if(p->pid == pid && p->state != UNUSED){
+      if(sig != 0)
+        psignal1(p, sig);
       release(&ptable.lock);
       return 0;
     }
   }
   release(&ptable.lock);
-  return -1;
+  return -ESRCH;
 }
 
-//PAGEBREAK: 36
-// Print a process listing to console.  For debugging.
-// Runs when user types ^P on console.
-// No lock to avoid wedging a stuck machine further.
+// Send the current process signal sig for a fault it took in
+// user space.  Returning to the faulting instruction with the
+// signal ignored or blocked would only fault again, so then the
+// default action is taken instead, as if there were no handler.
 void
-procdump(void)
+trapsig(int sig)
 {
-  static char *states[] = {
-  [UNUSED]    "unused",
-  [EMBRYO]    "embryo",
-  [SLEEPING]  "sleep ",
-  [RUNNABLE]  "runble",
-  [RUNNING]   "run   ",
-  [ZOMBIE]    "zombie"
-  };
-  int i;
+  uint bit;
+  struct proc *p = myproc();
+
+  bit = 1 << sig;
+  acquire(&ptable.lock);
+  if(p->sighandler[sig] == SIG_IGN || (p->sigmask & bit)){
+    p->sighandler[sig] = SIG_DFL;
+    p->sigmask &= ~bit;
+  }
+  psignal1(p, sig);
+  release(&ptable.lock);
+}
+
+// Send signal sig to every process in process group pgid.
+int
+killpg(int pgid, int sig)
+{
+  int found;
   struct proc *p;
-  char *state;
-  uint pc[10];
 
+  if(pgid <= 0 || sig < 0 || sig >= NSIG)
+    return -EINVAL;
+  found = 0;
+  acquire(&ptable.lock);
   for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
-    if(p->state == UNUSED)
-      continue;
-    if(p->state >= 0 && p->state < NELEM(states) && states[p->state])
-      state = states[p->state];
-    else
-      state = "???";
-    cprintf("%d %s %s", p->pid, state, p->name);
-    if(p->state == SLEEPING){
-      getcallerpcs((uint*)p->context->ebp+2, pc);
-      for(i=0; i<10 && pc[i] != 0; i++)
-        cprintf(" %p", pc[i]);
+    if(p->pgid == pgid && p->state != UNUSED){
+      if(sig != 0)
+        psignal1(p, sig);
+      found = 1;
     }
-    cprintf("\n");
   }
+  release(&ptable.lock);
+  return found ? 0 : -ESRCH;
+}
+
+// Stop traced process p on its way to handle signal sig, and
+// wait for the tracer to resume it.  Returns the signal the
+// tracer asked to deliver instead, or 0 for none.
+// The ptable lock must be held.
+static int
+tracestop(struct proc *p, int sig)
+{
+  p->stopsig = sig;
+  p->stopped = 1;
+  p->stopreported = 0;
+  wakeup1(p->tracer);
+  while(p->stopped && !p->killed)
+    sleep(p, &ptable.lock);
+  p->stopped = 0;
+  if(p->killed || p->stopsig <= 0 || p->stopsig >= NSIG)
+    return 0;
+  return p->stopsig;
+}
+
+// Stop p for signal sig until it is sent SIGCONT or killed,
+// and let its parent's waitpid report the stop.
+// The ptable lock must be held.
+static void
+jobstopself(struct proc *p, int sig)
+{
+  p->jobstop = sig;
+  p->jobstopreported = 0;
+  if(p->parent)
+    wakeup1(p->parent);
+  while(p->jobstop && !p->killed)
+    sleep(p, &ptable.lock);
+  p->jobstop = 0;
+}
+
+// Return a pending, unblocked signal that the current process
+// has a handler for, removing it from the pending set, or 0 if
+// there is none.  Pending signals whose action has meanwhile
+// become the default or SIG_IGN are disposed of here, and
+// those whose default is to stop the process stop it here.  A
+// traced process stops first, and its tracer picks the signal.
+int
+issig(void)
+{
+  int i, sig;
+  uint bit;
+  struct proc *p = myproc();
+
+  acquire(&ptable.lock);
+  for(i = 1; i < NSIG; i++){
+    bit = 1 << i;
+    if((p->sigpending & bit) == 0 || (p->sigmask & bit))
+      continue;
+    p->sigpending &= ~bit;
+    sig = i;
+    if(p->tracer && (sig = tracestop(p, sig)) == 0)
+      continue;
+    if(sig == SIGKILL || sig == SIGSTOP || p->sighandler[sig] == SIG_DFL){
+      if((SIGDFL_STOP >> sig) & 1)
+        jobstopself(p, sig);
+      else if(((SIGDFL_IGNORE >> sig) & 1) == 0){
+        p->killed = 1;
+        p->xsig = sig;
+      }
+      continue;
+    }
+    if(p->sighandler[sig] == SIG_IGN)
+      continue;
+    release(&ptable.lock);
+    return sig;
+  }
+  release(&ptable.lock);
+  return 0;
+}
+
+// Credentials.  Processes start out as uid 0, the superuser,
+// and inherit their parent's IDs.
+
+// May the current process signal or trace p?  The superuser
+// may; others only processes with the same real or effective
+// uid.  The ptable lock must be held.
+static int
+credok(struct proc *p)
+{
+  struct proc *curproc = myproc();
+
+  return curproc->euid == 0 || curproc->uid == p->uid ||
+         curproc->euid == p->uid;
+}
+
+// Check that the current process may send a signal to
+// process pid, or to every process in group -pid.
+int
+sigperm(int pid)
+{
+  int ok;
+  struct proc *p;
+
+  ok = 1;
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
+    if(p->state == UNUSED)
+      continue;
+    if((pid > 0 && p->pid == pid) || (pid < 0 && p->pgid == -pid))
+      ok = ok && credok(p);
+  }
+  release(&ptable.lock);
+  return ok;
+}
+
+// Set the real and effective user IDs to uid.  Other than the
+// superuser, a process may only set its effective uid back to
+// its real uid.
+int
+setuid(int uid)
+{
+  struct proc *curproc = myproc();
+
+  if(curproc->euid == 0)
+    curproc->uid = curproc->euid = uid;
+  else if(uid == curproc->uid)
+    curproc->euid = uid;
+  else
+    return -EPERM;
+  return 0;
+}
+
+// Set the real and effective group IDs, as setuid does.
+int
+setgid(int gid)
+{
+  struct proc *curproc = myproc();
+
+  if(curproc->euid == 0)
+    curproc->gid = curproc->egid = gid;
+  else if(gid == curproc->gid)
+    curproc->egid = gid;
+  else
+    return -EPERM;
+  return 0;
+}
+
+// Return the pid of the current process's parent, or 0 for
+// init.  Taking the lock keeps exit() from reparenting the
+// process to init halfway through.
+int
+getppid(void)
+{
+  int ppid;
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  ppid = curproc->parent ? curproc->parent->pid : 0;
+  release(&ptable.lock);
+  return ppid;
+}
+
+// Resource limits.  Children inherit their parent's limits,
+// and they survive exec.  A process may lower its hard limits
+// but never raise them.
+
+int
+getrlimit(int resource, struct rlimit *rl)
+{
+  struct proc *curproc = myproc();
+
+  if(resource < 0 || resource >= NRLIMIT)
+    return -EINVAL;
+  rl->rlim_cur = curproc->rlimcur[resource];
+  rl->rlim_max = curproc->rlimmax[resource];
+  return 0;
+}
+
+int
+setrlimit(int resource, struct rlimit *rl)
+{
+  struct proc *curproc = myproc();
+
+  if(resource < 0 || resource >= NRLIMIT || rl->rlim_cur > rl->rlim_max)
+    return -EINVAL;
+  if(rl->rlim_max > curproc->rlimmax[resource])
+    return -EPERM;
+  curproc->rlimcur[resource] = rl->rlim_cur;
+  curproc->rlimmax[resource] = rl->rlim_max;
+  return 0;
+}
+
+// Called from the timer interrupt after charging a tick to p.
+// Past the soft CPU limit, send SIGXCPU once a second;
+// at the hard limit, SIGKILL.
+static void
+cpulimit(struct proc *p)
+{
+  uint cur, max, t;
+
+  cur = p->rlimcur[RLIMIT_CPU];
+  max = p->rlimmax[RLIMIT_CPU];
+  t = p->cputicks;
+  if(max != RLIM_INFINITY && t >= max*HZ)
+    kill(p->pid, SIGKILL);
+  else if(cur != RLIM_INFINITY && t >= cur*HZ && (t - cur*HZ) % HZ == 0)
+    kill(p->pid, SIGXCPU);
+}
+
+// Report CPU time used by the current process, or by
+// its children that have been waited for.
+int
+getrusage(int who, struct rusage *ru)
+{
+  uint64 ut, st;
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  if(who == RUSAGE_SELF){
+    ut = curproc->utime;
+    st = curproc->stime;
+  } else if(who == RUSAGE_CHILDREN){
+    ut = curproc->cutime;
+    st = curproc->cstime;
+  } else {
+    release(&ptable.lock);
+    return -EINVAL;
+  }
+  release(&ptable.lock);
+  nstotv(ut, &ru->ru_utime);
+  nstotv(st, &ru->ru_stime);
+  return 0;
+}
+
+// Interval timers.  Each counts down in clock ticks and, when
+// it expires, sends its signal and reloads from its interval.
+// The ptable lock protects them from the timer interrupt.
+
+static int itimersig[NITIMER] = {
+[ITIMER_REAL]     SIGALRM,
+[ITIMER_VIRTUAL]  SIGVTALRM,
+[ITIMER_PROF]     SIGPROF,
+};
+
+// Count down interval timer which of p by a tick.
+// The ptable lock must be held.
+static void
+itimertick(struct proc *p, int which)
+{
+  if(p->itvalue[which] == 0 || --p->itvalue[which] > 0)
+    return;
+  p->itvalue[which] = p->itinterval[which];
+  psignal1(p, itimersig[which]);
+}
+
+// Called from the timer interrupt on one CPU, every global
+// tick (see globaltick in trap.c).  Idle CPUs keep their ticks
+// while any of these timers is set (see idle).
+void
+realtimers(void)
+{
+  struct proc *p;
+
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->state != UNUSED && p->state != ZOMBIE)
+      itimertick(p, ITIMER_REAL);
+  release(&ptable.lock);
+}
+
+// Called from the timer interrupt to charge a tick to p,
+// which was running in user mode if user is set.  The tick
+// counts for RLIMIT_CPU and the interval timers; utime and
+// stime are charged by chargetime.
+void
+chargetick(struct proc *p, int user)
+{
+  acquire(&ptable.lock);
+  p->cputicks++;
+  if(user)
+    itimertick(p, ITIMER_VIRTUAL);
+  itimertick(p, ITIMER_PROF);
+  release(&ptable.lock);
+  cpulimit(p);
+}
+
+// Charge the time since p last went between user mode and the
+// kernel, or started running, to utime if user is set, else to
+// stime.  trap calls this on the way in from user mode and out
+// again, and the scheduler when p stops running.  Either way it
+// runs on p's CPU while p can't, so p's times need no lock.
+void
+chargetime(struct proc *p, int user)
+{
+  uint64 now;
+
+  now = nsuptime();
+  if(user)
+    p->utime += now - p->tmark;
+  else
+    p->stime += now - p->tmark;
+  p->tmark = now;
+}
+
+// Round a timeval up to clock ticks.
+static uint
+tvtoticks(struct timeval *tv)
+{
+  return tv->tv_sec*HZ + (tv->tv_usec + 1000000/HZ - 1) / (1000000/HZ);
+}
+
+int
+getitimer(int which, struct itimerval *it)
+{
+  uint value, interval;
+  struct proc *curproc = myproc();
+
+  if(which < 0 || which >= NITIMER)
+    return -EINVAL;
+  acquire(&ptable.lock);
+  value = curproc->itvalue[which];
+  interval = curproc->itinterval[which];
+  release(&ptable.lock);
+  tickstotv(value, &it->it_value);
+  tickstotv(interval, &it->it_interval);
+  return 0;
+}
+
+// Arm interval timer which from *it, or disarm it if
+// it->it_value is zero.  Store the old setting in *old if
+// old is not 0.
+int
+setitimer(int which, struct itimerval *it, struct itimerval *old)
+{
+  uint value, interval;
+  struct proc *curproc = myproc();
+
+  if(which < 0 || which >= NITIMER ||
+     it->it_value.tv_usec >= 1000000 || it->it_interval.tv_usec >= 1000000)
+    return -EINVAL;
+  value = tvtoticks(&it->it_value);
+  interval = tvtoticks(&it->it_interval);
+  if(old && getitimer(which, old) < 0)
+    return -EINVAL;
+  acquire(&ptable.lock);
+  curproc->itvalue[which] = value;
+  curproc->itinterval[which] = interval;
+  release(&ptable.lock);
+  return 0;
+}
+
+// Process groups and sessions.  A process group collects the
+// processes of one job, so that a signal can be sent to all of
+// them at once; its pgid is the pid of the process that created
+// it.  A session collects the jobs started from one login shell.
+// Groups never span sessions.
+
+// Look up a live process by pid.  The ptable lock must be held.
+static struct proc*
+findproc(int pid)
+{
+  struct proc *p;
+
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->pid == pid && p->state != UNUSED && p->state != ZOMBIE)
+      return p;
+  return 0;
+}
+
+// Does process group pgid have a live member in session sid?
+// The ptable lock must be held.
+static int
+pgrpinsession(int pgid, int sid)
+{
+  struct proc *p;
+
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->pgid == pgid && p->sid == sid &&
+       p->state != UNUSED && p->state != ZOMBIE)
+      return 1;
+  return 0;
+}
+
+// Is pgid a live process group in the current process's session?
+int
+pgrpexists(int pgid)
+{
+  int r;
+
+  acquire(&ptable.lock);
+  r = pgrpinsession(pgid, myproc()->sid);
+  release(&ptable.lock);
+  return r;
+}
+
+// Move process pid (0 means the caller) into process group
+// pgid (0 means pid), creating the group if pgid is pid.
+// pid must be the caller or one of its children, and neither
+// may leave its session or be a session leader.
+int
+setpgid(int pid, int pgid)
+{
+  struct proc *p;
+  struct proc *curproc = myproc();
+
+  if(pid < 0 || pgid < 0)
+    return -EINVAL;
+  acquire(&ptable.lock);
+  p = pid == 0 ? curproc : findproc(pid);
+  if(p == 0 || (p != curproc && p->parent != curproc)){
+    release(&ptable.lock);
+    return -ESRCH;
+  }
+  if(p->sid != curproc->sid || p->sid == p->pid)
+    goto bad;
+  if(pgid == 0)
+    pgid = p->pid;
+  if(pgid != p->pid && !pgrpinsession(pgid, p->sid))
+    goto bad;
+  p->pgid = pgid;
+  release(&ptable.lock);
+  return 0;
+
+bad:
+  release(&ptable.lock);
+  return -EPERM;
+}
+
+// Return the process group of process pid (0 means the caller).
+int
+getpgid(int pid)
+{
+  struct proc *p;
+  int pgid;
+
+  if(pid == 0)
+    return myproc()->pgid;
+  acquire(&ptable.lock);
+  p = findproc(pid);
+  pgid = p ? p->pgid : -ESRCH;
+  release(&ptable.lock);
+  return pgid;
+}
+
+// Start a new session, with the caller as the leader of it
+// and of its only process group.  Fails if the caller already
+// leads a process group.  Returns the new session ID.
+int
+setsid(void)
+{
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  if(pgrpinsession(curproc->pid, curproc->sid)){
+    release(&ptable.lock);
+    return -EPERM;
+  }
+  curproc->pgid = curproc->pid;
+  curproc->sid = curproc->pid;
+  release(&ptable.lock);
+  return curproc->sid;
+}
+
+// Process tracing.  A tracer attaches to a process, which then
+// stops whenever it is about to handle a signal.  waitpid in the
+// tracer reports the stop; the tracer can then examine and change
+// the process's memory and registers before resuming it.
+
+// Return the kernel address of the word at user address addr
+// in tracee p, or 0 if there is none.  If write is set, make
+// the word writable, copying a copy-on-write page, or give p a
+// read-only private copy of a page of a private area, such as
+// its text, so that the tracer can plant breakpoints.  Reading
+// the page in from a file sleeps, so that happens with
+// ptable.lock, which the caller holds, released; fail if p has
+// meanwhile exited, exec'd, or been continued.
+static int*
+traceword(struct proc *p, uint addr, int write)
+{
+  char *page, *mem;
+  pde_t *pgdir;
+  int pid, perm;
+  uint pte;
+
+  if(addr % 4)
+    return 0;
+  pgdir = p->pgdir;
+  if(uva2ka(pgdir, (char*)PGROUNDDOWN(addr)) == 0){
+    pte = uvmpte(pgdir, addr);
+    pid = p->pid;
+    release(&ptable.lock);
+    mem = lazypage(pgdir, p->sz, addr, pte, &perm);
+    acquire(&ptable.lock);
+    if(mem == 0)
+      return 0;
+    if(p->pid != pid || p->pgdir != pgdir || !p->stopped){
+      lazyfree(mem, perm);
+      return 0;
+    }
+    if(lazymap(pgdir, addr, mem, perm) < 0)
+      return 0;
+  }
+  if(write && cowfault(pgdir, addr) < 0 &&
+     (vmaevict(pgdir, addr) < 0 || uvmprivate(pgdir, addr) < 0))
+    return 0;
+  if((page = uva2ka(pgdir, (char*)PGROUNDDOWN(addr))) == 0)
+    return 0;
+  return (int*)(page + addr % PGSIZE);
+}
+
+// Carry out tracing request req (see ptrace.h) on process pid.
+// Requests that pass a word or registers in or out of the
+// caller do so through buf, which sys_ptrace copies.
+int
+ptrace(int req, int pid, uint addr, int data, void *buf)
+{
+  int *w, err;
+  struct proc *p;
+  struct regs *r;
+  struct proc *curproc = myproc();
+
+  acquire(&ptable.lock);
+  err = -EPERM;
+  if(req == PT_TRACEME){
+    if(curproc->tracer || curproc->parent == 0)
+      goto bad;
+    curproc->tracer = curproc->parent;
+    release(&ptable.lock);
+    return 0;
+  }
+
+  if((p = findproc(pid)) == 0){
+    err = -ESRCH;
+    goto bad;
+  }
+  if(req == PT_ATTACH){
+    if(p->tracer || p == initproc || p->pgdir == 0 ||
+       p->pgdir == curproc->pgdir || !credok(p))
+      goto bad;
+    p->tracer = curproc;
+    psignal1(p, SIGTRAP);
+    release(&ptable.lock);
+    return 0;
+  }
+  if(p->tracer != curproc || !p->stopped){
+    err = -ESRCH;
+    goto bad;
+  }
+
+  err = -EIO;
+  switch(req){
+  case PT_PEEKDATA:
+    if((w = traceword(p, addr, 0)) == 0)
+      goto bad;
+    *(int*)buf = *w;
+    break;
+  case PT_POKEDATA:
+    if((w = traceword(p, addr, 1)) == 0)
+      goto bad;
+    *w = data;
+    break;
+  case PT_GETREGS:
+    r = (struct regs*)buf;
+    r->edi = p->tf->edi;
+    r->esi = p->tf->esi;
+    r->ebp = p->tf->ebp;
+    r->esp = p->tf->esp;
+    r->ebx = p->tf->ebx;
+    r->edx = p->tf->edx;
+    r->ecx = p->tf->ecx;
+    r->eax = p->tf->eax;
+    r->eip = p->tf->eip;
+    r->eflags = p->tf->eflags;
+    break;
+  case PT_SETREGS:
+    r = (struct regs*)buf;
+    p->tf->edi = r->edi;
+    p->tf->esi = r->esi;
+    p->tf->ebp = r->ebp;
+    p->tf->esp = r->esp;
+    p->tf->ebx = r->ebx;
+    p->tf->edx = r->edx;
+    p->tf->ecx = r->ecx;
+    p->tf->eax = r->eax;
+    p->tf->eip = r->eip;
+    p->tf->eflags = (p->tf->eflags & ~FL_USER) | (r->eflags & FL_USER);
+    break;
+  case PT_CONT:
+  case PT_STEP:
+  case PT_DETACH:
+    if(data < 0 || data >= NSIG){
+      err = -EINVAL;
+      goto bad;
+    }
+    if(req == PT_STEP)
+      p->tf->eflags |= FL_TF;
+    else
+      p->tf->eflags &= ~FL_TF;
+    if(req == PT_DETACH)
+      p->tracer = 0;
+    p->stopsig = data;
+    p->stopped = 0;
+    wakeup1(p);
+    break;
+  default:
+    err = -EINVAL;
+    goto bad;
+  }
+  release(&ptable.lock);
+  return 0;
+
+bad:
+  release(&ptable.lock);
+  return err;
+}
+
+// Turn system call tracing on or off for process pid (0 means
+// the caller).  A traced process logs each call it makes, and
+// what the call returned, to the console; see syscall.  Its
+// children inherit the setting.
+int
+settrace(int pid, int on)
+{
+  struct proc *p;
+  int err;
+
+  acquire(&ptable.lock);
+  err = 0;
+  if((p = pid == 0 ? myproc() : findproc(pid)) == 0)
+    err = -ESRCH;
+  else if(!credok(p))
+    err = -EPERM;
+  else
+    p->trace = on != 0;
+  release(&ptable.lock);
+  return err;
+}
+
+// Narrow the system call filter of the current process, and
+// of every thread sharing its address space, to the calls in
+// allow; see sys_prctl.
+void
+sysfilter(uint *allow)
+{
+  struct proc *p;
+  int i;
+
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++)
+    if(p->state != UNUSED && p->pgdir == myproc()->pgdir)
+      for(i = 0; i < NELEM(p->sysdeny); i++)
+        p->sysdeny[i] |= ~allow[i];
+  release(&ptable.lock);
+}
+
+// Futexes let threads sleep on a word of shared user memory.
+// The sleep channel is the kernel address of the word, so that
+// every thread mapping the page agrees on it whatever its virtual
+// address.  Checking the word and going to sleep both happen under
+// ptable.lock, which futexwake also holds, so no wakeup is lost.
+// A waiter holds a reference to the page, which keeps reclaim
+// from moving the word to another page meanwhile; it makes the
+// page writable first, so a copy-on-write fault doesn't either.
+
+// If the int at user address addr still holds val,
+// sleep until a futexwake on addr.  Returns 0 after sleeping,
+// -1 if the value differs or addr is bad.
+int
+futexwait(int *addr, int val)
+{
+  int *k, r;
+  char *page;
+  struct proc *curproc = myproc();
+
+  if((uint)addr % sizeof(int) != 0)
+    return -EINVAL;
+  page = uvmpage(curproc->pgdir, curproc->sz, PGROUNDDOWN((uint)addr), 1);
+  if(page == 0)
+    return -EFAULT;
+  k = (int*)(page + ((uint)addr & (PGSIZE-1)));
+
+  r = -1;
+  acquire(&ptable.lock);
+  if(*k == val && !curproc->killed){
+    sleep(k, &ptable.lock);
+    r = 0;
+  }
+  release(&ptable.lock);
+  kfree(page);
+  return r;
+}
+
+// Wake up to n processes sleeping in futexwait on addr.
+// Returns the number woken.
+int
+futexwake(int *addr, int n)
+{
+  int *k, woken;
+  char *page;
+  struct proc *p;
+  struct proc *curproc = myproc();
+
+  if((uint)addr % sizeof(int) != 0)
+    return -EINVAL;
+  page = uvmpage(curproc->pgdir, curproc->sz, PGROUNDDOWN((uint)addr), 0);
+  if(page == 0)
+    return -EFAULT;
+  k = (int*)(page + ((uint)addr & (PGSIZE-1)));
+
+  woken = 0;
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC] && woken < n; p++){
+    if(p->state == SLEEPING && p->chan == k){
+      p->state = RUNNABLE;
+      woken++;
+    }
+  }
+  release(&ptable.lock);
+  kfree(page);
+  return woken;
+}
+
+static char *states[] = {
+[UNUSED]    "unused",
+[EMBRYO]    "embryo",
+[SLEEPING]  "sleep ",
+[RUNNABLE]  "runble",
+[RUNNING]   "run   ",
+[ZOMBIE]    "zombie"
+};
+
+static char*
+procstate(struct proc *p)
+{
+  if(p->state >= 0 && p->state < NELEM(states) && states[p->state])
+    return states[p->state];
+  return "???";
+}
+
+static int
+nopenfiles(struct proc *p)
+{
+  int fd, n;
+
+  n = 0;
+  for(fd = 0; fd < NOFILE; fd++)
+    if(p->ofile[fd])
+      n++;
+  return n;
+}
+
+//PAGEBREAK: 36
+// Print a process listing to console.  For debugging.
+// Runs when user types ^P on console.
+// No lock to avoid wedging a stuck machine further.
+void
+procdump(void)
+{
+  int i;
+  struct proc *p;
+  uint pc[10];
+
+  for(p = ptable.proc; p < &ptable.proc[NPROC]; p++){
+    if(p->state == UNUSED)
+      continue;
+    cprintf("%d %d %s %s sz %d fds %d ticks %d", p->pid,
+            p->parent ? p->parent->pid : 0, procstate(p), p->name,
+            p->sz, nopenfiles(p), p->cputicks);
+    if(p->state == SLEEPING){
+      getcallerpcs((uint*)p->context->ebp+2, pc);
+      for(i=0; i<10 && pc[i] != 0; i++){
+        cprintf(" ");
+        ksymprint(pc[i]);
+      }
+    }
+    cprintf("\n");
+  }
+}
+
+// Copy information about up to n processes into pi.
+// Returns the number of entries filled in.
+int
+pinfo(struct pinfo *pi, int n)
+{
+  int i, len;
+  char *state;
+  struct proc *p;
+
+  i = 0;
+  acquire(&ptable.lock);
+  for(p = ptable.proc; p < &ptable.proc[NPROC] && i < n; p++){
+    if(p->state == UNUSED)
+      continue;
+    pi[i].pid = p->pid;
+    pi[i].ppid = p->parent ? p->parent->pid : 0;
+    // Drop the padding procdump uses to line up columns.
+    state = procstate(p);
+    for(len = 0; len < sizeof(pi[i].state)-1 && state[len] &&
+        state[len] != ' '; len++)
+      pi[i].state[len] = state[len];
+    pi[i].state[len] = 0;
+    pi[i].sz = p->sz;
+    pi[i].nfd = nopenfiles(p);
+    pi[i].cputicks = p->cputicks;
+    safestrcpy(pi[i].name, p->name, sizeof(pi[i].name));
+    i++;
+  }
+  release(&ptable.lock);
+  return i;
 }
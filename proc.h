@@ -3,11 +3,22 @@ struct cpu {
   uchar apicid;                // Local APIC ID
   struct context *scheduler;   // swtch() here to enter scheduler
   struct taskstate ts;         // Used by x86 to find stack for interrupt
+  struct taskstate dfts;       // Task for double faults (see dfinit)
   struct segdesc gdt[NSEGS];   // x86 global descriptor table
   volatile uint started;       // Has the CPU started?
   int ncli;                    // Depth of pushcli nesting.
   int intena;                  // Were interrupts enabled before pushcli?
   struct proc *proc;           // The process running on this cpu or null
+  void (*volatile callfn)(void*);  // Cross-CPU call to run (see callmany)
+  void *callarg;               // Its argument
+  uint wdseen;                 // Watched cpu's timer count, as last seen
+  uint wdstale;                // Our ticks since that count last changed
+  volatile int wdnmi;          // Set when the watchdog sends us an NMI
+  uint tsckhz;                 // TSC ticks per millisecond (see clockinit)
+  uint lapickhz;               // LAPIC timer counts per ms, or 0 if TSC-deadline
+  volatile int idle;           // Halted with nothing to run?
+  volatile int offline;        // Asked to park, or parked (see cpuoffline)
+  char *stack;                 // Stack it boots and schedules on
 };
 
 extern struct cpu cpus[NCPU];
@@ -37,22 +48,59 @@ enum procstate { UNUSED, EMBRYO, SLEEPING, RUNNABLE, RUNNING, ZOMBIE };
 // Per-process state
 struct proc {
   uint sz;                     // Size of process memory (bytes)
+  uint mmapbase;               // mmap places areas below here (see exec)
   pde_t* pgdir;                // Page table
   char *kstack;                // Bottom of kernel stack for this process
+  char *fpu;                   // Saved floating point state (see fpu.c)
+  uint tlsbase;                // Base of the SEG_UTLS segment (see arch_prctl)
   enum procstate state;        // Process state
   int pid;                     // Process ID
+  int pgid;                    // Process group ID
+  int sid;                     // Session ID
+  int uid, euid;               // Real and effective user IDs
+  int gid, egid;               // Real and effective group IDs
   struct proc *parent;         // Parent process
   struct trapframe *tf;        // Trap frame for current syscall
   struct context *context;     // swtch() here to run process
   void *chan;                  // If non-zero, sleeping on chan
   int killed;                  // If non-zero, have been killed
   struct file *ofile[NOFILE];  // Open files
+  char fdflags[NOFILE];        // FD_CLOEXEC, for each of ofile
   struct inode *cwd;           // Current directory
   char name[16];               // Process name (debugging)
+  void *ustack;                // User stack of a thread (see clone)
+  uint cputicks;               // Clock ticks spent running
+  uint64 utime;                // Nanoseconds spent in user mode
+  uint64 stime;                // Nanoseconds spent in the kernel
+  uint64 cutime;               // utime of reaped children
+  uint64 cstime;               // stime of reaped children
+  uint64 tmark;                // When utime or stime was last charged
+  uint itvalue[NITIMER];       // Ticks left on interval timers, or 0
+  uint itinterval[NITIMER];    // Ticks to reload interval timers with
+  uint rlimcur[NRLIMIT];       // Soft resource limits (see resource.h)
+  uint rlimmax[NRLIMIT];       // Hard resource limits
+  int xsig;                    // Signal that killed the process
+  int trace;                   // Log system calls (see syscall.c)
+  uint sysdeny[(NSYSCALL+31)/32];  // System calls refused (see prctl)
+  struct proc *tracer;         // Process tracing this one (see ptrace)
+  int stopped;                 // If non-zero, stopped for the tracer
+  int stopsig;                 // Signal that stopped it, or to deliver
+  int stopreported;            // Stop has been reported by waitpid
+  int jobstop;                 // Signal that stopped it until SIGCONT, or 0
+  int jobstopreported;         // jobstop has been reported by waitpid
+  uint sigpending;             // Signals sent but not yet delivered
+  uint sigmask;                // Signals blocked from delivery
+  void (*sighandler[NSIG])(int); // Signal handlers, SIG_DFL, or SIG_IGN
+  uint sighmask[NSIG];         // Signals blocked while each handler runs
+  uint64 perf[NPERF];          // Performance counts (see pmu.c)
+  uint perfpc[NPERFPC];        // Sampled user PCs, a ring
+  uint nperfpc;                // PCs sampled so far
 };
 
-// Process memory is laid out contiguously, low addresses first:
+// Process memory is laid out low addresses first:
 //   text
 //   original data and bss
-//   fixed-size stack
-//   expandable heap
+//   a gap of random size that nothing maps
+//   expandable heap, up to sz
+//   mmap'd areas, top-down from mmapbase
+//   stack, growing down from a random point below USTACKTOP
@@ -0,0 +1,706 @@
+// Tests of process-level system calls.
+
+#include "param.h"
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "fcntl.h"
+#include "signal.h"
+#include "time.h"
+#include "resource.h"
+#include "ptrace.h"
+#include "wait.h"
+#include "prctl.h"
+#include "perf.h"
+#include "errno.h"
+#include "syscall.h"
+#include "scstat.h"
+
+// RLIMIT_NOFILE and RLIMIT_AS are enforced, RLIMIT_CPU kills
+// a spinning child, and hard limits cannot be raised.
+void
+rlimittest(void)
+{
+  struct rlimit rl;
+  int fd, pid;
+
+  printf(1, "rlimit test\n");
+
+  if(getrlimit(RLIMIT_NOFILE, &rl) != 0 ||
+     rl.rlim_cur != NOFILE || rl.rlim_max != NOFILE){
+    printf(1, "bad default RLIMIT_NOFILE\n");
+    exit();
+  }
+  rl.rlim_max++;
+  if(setrlimit(RLIMIT_NOFILE, &rl) != -1){
+    printf(1, "raised a hard limit\n");
+    exit();
+  }
+
+  rl.rlim_cur = 4;
+  rl.rlim_max = NOFILE;
+  setrlimit(RLIMIT_NOFILE, &rl);
+  fd = dup(0);
+  if(fd != 3 || dup(0) != -1){
+    printf(1, "RLIMIT_NOFILE not enforced\n");
+    exit();
+  }
+  close(fd);
+  rl.rlim_cur = NOFILE;
+  setrlimit(RLIMIT_NOFILE, &rl);
+
+  pid = fork();
+  if(pid == 0){
+    rl.rlim_cur = rl.rlim_max = (uint)sbrk(0) + 4096;
+    setrlimit(RLIMIT_AS, &rl);
+    if(sbrk(4096) == (char*)-1 || sbrk(4096) != (char*)-1)
+      printf(1, "RLIMIT_AS not enforced\n");
+    exit();
+  }
+  wait();
+
+  pid = fork();
+  if(pid == 0){
+    rl.rlim_cur = 1;
+    rl.rlim_max = RLIM_INFINITY;
+    setrlimit(RLIMIT_CPU, &rl);
+    for(;;)
+      ;
+  }
+  if(wait() != pid){
+    printf(1, "RLIMIT_CPU not enforced\n");
+    exit();
+  }
+
+  printf(1, "rlimit test OK\n");
+}
+
+int traceval;
+
+// never called; the ptrace test pokes its text.
+void
+tracetext(void)
+{
+}
+
+// a traced child stops on a signal; its parent reads and
+// writes its memory, text included, and registers,
+// single-steps it, and lets it run to exit.
+void
+ptracetest(void)
+{
+  int pid, status, word, text;
+  int *tp;
+  struct regs r;
+
+  printf(1, "ptrace test\n");
+
+  traceval = 1;
+  tp = (int*)tracetext;
+  text = *tp;
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    ptrace(PT_TRACEME, 0, 0, 0);
+    kill(getpid(), SIGTERM);
+    if(traceval != 2 || *tp != ~text)
+      kill(getpid(), SIGKILL);
+    exit();
+  }
+
+  if(waitpid(pid, &status, 0) != pid || !WIFSTOPPED(status) ||
+     WSTOPSIG(status) != SIGTERM){
+    printf(1, "child did not stop\n");
+    exit();
+  }
+  if(ptrace(PT_PEEKDATA, pid, &traceval, (int)&word) != 0 || word != 1 ||
+     ptrace(PT_POKEDATA, pid, &traceval, 2) != 0){
+    printf(1, "peek/poke failed\n");
+    exit();
+  }
+  if(ptrace(PT_POKEDATA, pid, tp, ~text) != 0 ||
+     ptrace(PT_PEEKDATA, pid, tp, (int)&word) != 0 || word != ~text ||
+     *tp != text){
+    printf(1, "text poke failed\n");
+    exit();
+  }
+  if(ptrace(PT_GETREGS, pid, 0, (int)&r) != 0 || r.eip == 0){
+    printf(1, "getregs failed\n");
+    exit();
+  }
+  if(ptrace(PT_STEP, pid, 0, 0) != 0 ||
+     waitpid(pid, &status, 0) != pid || !WIFSTOPPED(status) ||
+     WSTOPSIG(status) != SIGTRAP){
+    printf(1, "single step failed\n");
+    exit();
+  }
+  if(ptrace(PT_CONT, pid, 0, 0) != 0 ||
+     waitpid(pid, &status, 0) != pid || !WIFEXITED(status)){
+    printf(1, "child did not exit cleanly: %x\n", status);
+    exit();
+  }
+
+  printf(1, "ptrace test OK\n");
+}
+
+// SIGTSTP stops a child, which its parent sees with WUNTRACED,
+// until SIGCONT; SIGSTOP cannot be caught.
+void
+jobstoptest(void)
+{
+  struct sigaction sa;
+  int pid, status, fds[2];
+  char c;
+
+  printf(1, "jobstop test\n");
+
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = SIG_IGN;
+  if(sigaction(SIGSTOP, &sa, 0) != -1){
+    printf(1, "SIGSTOP was caught\n");
+    exit();
+  }
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    kill(getpid(), SIGTSTP);
+    write(fds[1], "x", 1);
+    exit();
+  }
+  close(fds[1]);
+
+  if(waitpid(pid, &status, WUNTRACED) != pid || !WIFSTOPPED(status) ||
+     WSTOPSIG(status) != SIGTSTP){
+    printf(1, "child did not stop\n");
+    exit();
+  }
+  if(kill(pid, SIGCONT) != 0 || read(fds[0], &c, 1) != 1 || c != 'x'){
+    printf(1, "child did not continue\n");
+    exit();
+  }
+  if(waitpid(pid, &status, WUNTRACED) != pid || !WIFEXITED(status)){
+    printf(1, "child did not exit cleanly: %x\n", status);
+    exit();
+  }
+  close(fds[0]);
+
+  printf(1, "jobstop test OK\n");
+}
+
+// a child that spins in user mode is charged user time, which
+// its parent sees through wait4 and RUSAGE_CHILDREN, and short
+// system calls are charged kernel time.
+void
+rusagetest(void)
+{
+  int pid, status, i;
+  struct rusage ru, ru0;
+
+  printf(1, "rusage test\n");
+
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    do
+      getrusage(RUSAGE_SELF, &ru);
+    while(ru.ru_utime.tv_sec == 0 && ru.ru_utime.tv_usec < 50000);
+    exit();
+  }
+  if(wait4(pid, &status, 0, &ru) != pid || !WIFEXITED(status) ||
+     (ru.ru_utime.tv_sec == 0 && ru.ru_utime.tv_usec == 0)){
+    printf(1, "wait4 reported no user time\n");
+    exit();
+  }
+  if(getrusage(RUSAGE_CHILDREN, &ru) != 0 ||
+     (ru.ru_utime.tv_sec == 0 && ru.ru_utime.tv_usec == 0)){
+    printf(1, "RUSAGE_CHILDREN reported no user time\n");
+    exit();
+  }
+
+  // System calls much shorter than a tick are charged as
+  // kernel time, not missed by the clock.
+  getrusage(RUSAGE_SELF, &ru0);
+  for(i = 0; i < 10000; i++)
+    getpid();
+  getrusage(RUSAGE_SELF, &ru);
+  if(ru.ru_stime.tv_sec == ru0.ru_stime.tv_sec &&
+     ru.ru_stime.tv_usec == ru0.ru_stime.tv_usec){
+    printf(1, "system calls charged no kernel time\n");
+    exit();
+  }
+
+  printf(1, "rusage test OK\n");
+}
+
+volatile int nalarms;
+
+void
+alarmhandler(int sig)
+{
+  nalarms++;
+}
+
+// alarm delivers one SIGALRM and cuts a long nanosleep short;
+// a periodic ITIMER_VIRTUAL keeps delivering SIGVTALRM while
+// the process runs.
+void
+itimertest(void)
+{
+  struct itimerval it;
+  struct timespec req, rem;
+  int i;
+
+  printf(1, "itimer test\n");
+
+  nalarms = 0;
+  signal(SIGALRM, alarmhandler);
+  alarm(1);
+  for(i = 0; i < 3*HZ && nalarms == 0; i++)
+    sleep(1);
+  if(nalarms != 1 || alarm(0) != 0){
+    printf(1, "alarm did not go off\n");
+    exit();
+  }
+
+  nalarms = 0;
+  alarm(1);
+  req.tv_sec = 10;
+  req.tv_nsec = 0;
+  if(nanosleep(&req, &rem) != -1 || nalarms != 1 ||
+     rem.tv_sec < 5 || rem.tv_sec >= 10){
+    printf(1, "alarm did not interrupt nanosleep\n");
+    exit();
+  }
+
+  nalarms = 0;
+  signal(SIGVTALRM, alarmhandler);
+  memset(&it, 0, sizeof(it));
+  it.it_value.tv_usec = 10000;
+  it.it_interval.tv_usec = 10000;
+  setitimer(ITIMER_VIRTUAL, &it, 0);
+  while(nalarms < 3)
+    ;
+  memset(&it, 0, sizeof(it));
+  setitimer(ITIMER_VIRTUAL, &it, 0);
+  getitimer(ITIMER_VIRTUAL, &it);
+  if(it.it_value.tv_sec != 0 || it.it_value.tv_usec != 0){
+    printf(1, "itimer not disarmed\n");
+    exit();
+  }
+
+  printf(1, "itimer test OK\n");
+}
+
+// a process that gives up uid 0 cannot get it back, and can
+// no longer signal processes of other users.
+void
+credtest(void)
+{
+  int pid, status;
+
+  printf(1, "cred test\n");
+
+  if(getuid() != 0 || geteuid() != 0){
+    printf(1, "not running as uid 0\n");
+    exit();
+  }
+  pid = fork();
+  if(pid == 0){
+    if(setuid(5) != 0 || getuid() != 5 || geteuid() != 5)
+      kill(getpid(), SIGKILL);
+    if(setuid(0) != -1 || kill(getppid(), 0) != -1)
+      kill(getpid(), SIGKILL);
+    exit();
+  }
+  if(waitpid(pid, &status, 0) != pid || status != 0){
+    printf(1, "setuid not enforced\n");
+    exit();
+  }
+
+  printf(1, "cred test OK\n");
+}
+
+static ushort
+getfpcw(void)
+{
+  ushort cw;
+
+  asm volatile("fnstcw %0" : "=m" (cw));
+  return cw;
+}
+
+static void
+setfpcw(ushort cw)
+{
+  asm volatile("fldcw %0" : : "m" (cw));
+}
+
+// Add up n halves, giving the other process time to run.
+static int
+fpuwork(ushort cw)
+{
+  volatile double x;
+  int i, j;
+
+  x = 0;
+  for(i = 0; i < 100; i++){
+    for(j = 0; j < 100000; j++)
+      x += 0.5;
+    if(getfpcw() != cw)
+      return -1;
+  }
+  return x == 100 * 100000 * 0.5 ? 0 : -1;
+}
+
+// Parent and child each keep their own floating point state,
+// here the x87 control word, across context switches.
+void
+fputest(void)
+{
+  int pid, status;
+
+  printf(1, "fpu test\n");
+
+  pid = fork();
+  if(pid == 0){
+    setfpcw(0x0F7F);  // Round toward zero
+    if(fpuwork(0x0F7F) < 0)
+      kill(getpid(), SIGKILL);
+    exit();
+  }
+  setfpcw(0x037F);  // The default: round to nearest
+  if(fpuwork(0x037F) < 0){
+    printf(1, "fpu state lost in parent\n");
+    exit();
+  }
+  if(waitpid(pid, &status, 0) != pid || status != 0){
+    printf(1, "fpu state lost in child\n");
+    exit();
+  }
+
+  printf(1, "fpu test OK\n");
+}
+
+void
+segvhandler(int sig)
+{
+  exit();
+}
+
+// Fork a child that sets the action for SIGSEGV to h and
+// writes to kernel memory; return its wait status.
+int
+segvchild(sighandler_t h)
+{
+  int pid, status;
+
+  pid = fork();
+  if(pid == 0){
+    signal(SIGSEGV, h);
+    *(volatile int*)0xFFFFF000 = 1;
+    exit();
+  }
+  if(waitpid(pid, &status, 0) != pid)
+    return -1;
+  return status;
+}
+
+// A bad pointer in user space sends the process SIGSEGV:
+// it dies of it by default, a handler can catch it, and
+// ignoring it does not leave the process faulting forever.
+void
+segvtest(void)
+{
+  printf(1, "segv test\n");
+
+  if(segvchild(SIG_DFL) != SIGSEGV){
+    printf(1, "fault did not kill with SIGSEGV\n");
+    exit();
+  }
+  if(segvchild(segvhandler) != 0){
+    printf(1, "SIGSEGV handler did not run\n");
+    exit();
+  }
+  if(segvchild(SIG_IGN) != SIGSEGV){
+    printf(1, "ignored SIGSEGV did not kill\n");
+    exit();
+  }
+
+  printf(1, "segv test OK\n");
+}
+
+int tlsblock[2] = { 0x1234, 0x5678 };
+
+// Read the word at offset off in the %fs segment.
+int
+fsword(int off)
+{
+  int v;
+
+  asm volatile("movl %%fs:(%1), %0" : "=r" (v) : "r" (off));
+  return v;
+}
+
+// arch_prctl points %fs at a thread-local block, and a forked
+// child inherits it.
+void
+tlstest(void)
+{
+  int pid, status;
+  uint base;
+
+  printf(1, "tls test\n");
+
+  if(arch_prctl(ARCH_SET_FS, (uint)tlsblock) != 0){
+    printf(1, "ARCH_SET_FS failed\n");
+    exit();
+  }
+  if(arch_prctl(ARCH_GET_FS, (uint)&base) != 0 || base != (uint)tlsblock){
+    printf(1, "ARCH_GET_FS wrong\n");
+    exit();
+  }
+  if(fsword(0) != 0x1234 || fsword(4) != 0x5678){
+    printf(1, "%%fs does not address the block\n");
+    exit();
+  }
+  sleep(1);  // Let the scheduler switch away and back.
+  if(fsword(4) != 0x5678){
+    printf(1, "%%fs lost across a context switch\n");
+    exit();
+  }
+
+  pid = fork();
+  if(pid == 0){
+    tlsblock[0] = 0;  // The child's copy.
+    if(fsword(0) != 0 || fsword(4) != 0x5678)
+      kill(getpid(), SIGKILL);
+    exit();
+  }
+  if(waitpid(pid, &status, 0) != pid || status != 0){
+    printf(1, "%%fs not inherited by fork\n");
+    exit();
+  }
+
+  printf(1, "tls test OK\n");
+}
+
+// User-mode instructions and cycles are counted, and cycles
+// sampled, if the CPU has performance counters.
+void
+perftest(void)
+{
+  struct perfinfo p0, p1;
+  volatile int i, x;
+
+  printf(1, "perf test\n");
+
+  if(perfread(&p0) < 0){
+    printf(1, "perf test: no performance counters\n");
+    return;
+  }
+  x = 0;
+  for(i = 0; i < 10000000; i++)
+    x += i;
+  perfread(&p1);
+  if(p1.count[PERF_INSTRS] < p0.count[PERF_INSTRS] + 10000000 ||
+     p1.count[PERF_CYCLES] <= p0.count[PERF_CYCLES]){
+    printf(1, "instructions or cycles not counted\n");
+    exit();
+  }
+  if(p1.npc == p0.npc){
+    printf(1, "no cycles sampled\n");
+    exit();
+  }
+
+  printf(1, "perf test OK\n");
+}
+
+// A traced process logs its system calls to the kernel log.
+void
+tracetest(void)
+{
+  static char buf[512];
+  char *want = "open(\"tracetest.none\", 0x0) = -2";
+  int i, n, pid;
+
+  printf(1, "trace test\n");
+
+  if((pid = fork()) < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    if(trace(0, 1) < 0){
+      printf(1, "trace failed\n");
+      exit();
+    }
+    open("tracetest.none", 0);
+    exit();
+  }
+  wait();
+  if(trace(1000000, 1) >= 0 || errno != ESRCH){
+    printf(1, "trace of a missing pid: errno %d\n", errno);
+    exit();
+  }
+  n = dmesg(buf, sizeof(buf));
+  for(i = 0; i + strlen(want) <= n; i++)
+    if(strncmp(buf + i, want, strlen(want)) == 0)
+      break;
+  if(i + strlen(want) > n){
+    printf(1, "trace didn't log %s\n", want);
+    exit();
+  }
+  printf(1, "trace test OK\n");
+}
+
+// Each system call is counted, with the cycles it took, and
+// lands in one bucket of its latency histogram.
+void
+scstattest(void)
+{
+  static struct scstat s0, s1;
+  int i, b, n;
+
+  printf(1, "scstat test\n");
+
+  if(scstat(&s0) < 0){
+    printf(1, "scstat failed\n");
+    exit();
+  }
+  for(i = 0; i < 100; i++)
+    getppid();
+  scstat(&s1);
+  if(s1.count[SYS_getppid] - s0.count[SYS_getppid] < 100 ||
+     s1.cycles[SYS_getppid] <= s0.cycles[SYS_getppid]){
+    printf(1, "getppid counted %d times\n",
+           s1.count[SYS_getppid] - s0.count[SYS_getppid]);
+    exit();
+  }
+  n = 0;
+  for(b = 0; b < NSCHIST; b++)
+    n += s1.hist[SYS_getppid][b];
+  if(n != s1.count[SYS_getppid] || s1.count[SYS_scstat] <= s0.count[SYS_scstat]){
+    printf(1, "scstat histogram holds %d of %d calls\n", n, s1.count[SYS_getppid]);
+    exit();
+  }
+  printf(1, "scstat test OK\n");
+}
+
+volatile int filtergo, filterppid;
+
+// a thread started before the filter was set; it must be
+// filtered too.
+void
+filterthread(void *arg)
+{
+  while(filtergo == 0)
+    ;
+  filterppid = getppid();
+  exit();
+}
+
+// A process filtered by prctl is refused the calls it left out,
+// as are its threads, can't allow them again, and passes the
+// filter to its children.
+void
+sysfiltertest(void)
+{
+  uint allow[NSYSFILTER];
+  int i, pid, ppid;
+  void *stack;
+
+  printf(1, "sysfilter test\n");
+
+  ppid = getpid();
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    for(i = 0; i < NSYSFILTER; i++)
+      allow[i] = ~0;
+    allow[SYS_getppid/32] &= ~(1 << SYS_getppid%32);
+    if(clone(filterthread, 0, malloc(4096)) < 0){
+      printf(1, "clone failed\n");
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    if(prctl(PR_SET_SYSFILTER, (uint)allow) < 0){
+      printf(1, "prctl failed\n");
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    filtergo = 1;
+    if(join(&stack) < 0 || filterppid >= 0){
+      printf(1, "thread escaped the filter\n");
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    free(stack);
+    if(getppid() >= 0 || errno != EPERM || getpid() < 0){
+      printf(1, "filter let getppid through\n");
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    allow[SYS_getppid/32] = ~0;
+    prctl(PR_SET_SYSFILTER, (uint)allow);
+    prctl(PR_GET_SYSFILTER, (uint)allow);
+    if(getppid() >= 0 || (allow[SYS_getppid/32] & (1 << SYS_getppid%32))){
+      printf(1, "filter was widened\n");
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    if((pid = fork()) == 0){
+      if(getppid() >= 0){
+        printf(1, "child escaped the filter\n");
+        kill(ppid, SIGKILL);
+      }
+      exit();
+    }
+    wait();
+    exit();
+  }
+  wait();
+  if(getppid() < 0){
+    printf(1, "filter reached the parent\n");
+    exit();
+  }
+  printf(1, "sysfilter test OK\n");
+}
+
+int
+main(int argc, char *argv[])
+{
+  printf(1, "proctests starting\n");
+
+  rlimittest();
+  ptracetest();
+  jobstoptest();
+  rusagetest();
+  itimertest();
+  credtest();
+  fputest();
+  segvtest();
+  tlstest();
+  perftest();
+  tracetest();
+  scstattest();
+  sysfiltertest();
+
+  printf(1, "ALL TESTS PASSED\n");
+  exit();
+}
@@ -0,0 +1,24 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "param.h"
+#include "pinfo.h"
+
+struct pinfo procs[NPROC];
+
+int
+main(void)
+{
+  int i, n;
+
+  if((n = pinfo(procs, NPROC)) < 0){
+    printf(2, "ps: pinfo failed\n");
+    exit();
+  }
+  printf(1, "PID\tPPID\tSTATE\tSIZE\tFDS\tTICKS\tNAME\n");
+  for(i = 0; i < n; i++)
+    printf(1, "%d\t%d\t%s\t%d\t%d\t%d\t%s\n", procs[i].pid,
+           procs[i].ppid, procs[i].state, procs[i].sz, procs[i].nfd,
+           procs[i].cputicks, procs[i].name);
+  exit();
+}
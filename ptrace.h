@@ -0,0 +1,25 @@
+// Process tracing requests, for ptrace.
+
+#define PT_TRACEME   0  // Let the parent trace the caller
+#define PT_PEEKDATA  1  // Read the word at addr into *(int*)data
+#define PT_POKEDATA  2  // Write data to the word at addr
+#define PT_GETREGS   3  // Read registers into *(struct regs*)data
+#define PT_SETREGS   4  // Set registers from *(struct regs*)data
+#define PT_CONT      5  // Resume, delivering signal data (0 for none)
+#define PT_STEP      6  // Like PT_CONT, but stop after one instruction
+#define PT_ATTACH    7  // Start tracing process pid, stopping it
+#define PT_DETACH    8  // Stop tracing, resuming with signal data
+
+// User registers of a stopped process.
+struct regs {
+  uint edi;
+  uint esi;
+  uint ebp;
+  uint esp;
+  uint ebx;
+  uint edx;
+  uint ecx;
+  uint eax;
+  uint eip;
+  uint eflags;
+};
@@ -0,0 +1,53 @@
+// Kernel random numbers.
+//
+// A xorshift generator, seeded at boot from the time-stamp
+// counter and the RTC.  Each call also stirs in the TSC, so the
+// sequence depends on when the calls happen.  Good enough to
+// randomize address space layout, but not for cryptography.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "date.h"
+#include "x86.h"
+#include "spinlock.h"
+
+static struct spinlock randlock;
+static uint state[4];
+
+void
+randinit(void)
+{
+  struct rtcdate r;
+  uint64 tsc;
+  int i;
+
+  initlock(&randlock, "random");
+  tsc = rdtsc();
+  cmostime(&r);
+  state[0] = (uint)tsc;
+  state[1] = (uint)(tsc >> 32);
+  state[2] = (r.year << 16) ^ (r.month << 8) ^ r.day;
+  state[3] = ((r.hour << 16) ^ (r.minute << 8) ^ r.second) | 1;
+  for(i = 0; i < 16; i++)
+    random();
+}
+
+// Return a random 32-bit number.
+uint
+random(void)
+{
+  uint t;
+
+  acquire(&randlock);
+  t = state[3];
+  t ^= t << 11;
+  t ^= t >> 8;
+  state[3] = state[2];
+  state[2] = state[1];
+  state[1] = state[0] + (uint)rdtsc();
+  t ^= state[0] ^ (state[0] >> 19);
+  state[0] = t;
+  release(&randlock);
+  return t;
+}
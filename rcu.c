@@ -0,0 +1,131 @@
+// Read-copy-update, for data that is read far more often than
+// it changes.
+//
+// Readers take no lock: they bracket their reads with
+// rcureadlock and rcureadunlock, which only turn interrupts
+// off, and must not sleep in between.  A writer, holding a
+// lock of its own, unlinks what it is replacing but can't free
+// it until every reader that might have found it is done.  It
+// gives the object to callrcu, or waits in synchronizercu.
+//
+// A CPU can't be reading while it goes around the scheduler
+// loop, idles, or is offline; each such quiescent state
+// counts.  Once every CPU has passed one since an object was
+// unlinked, a grace period has passed and nobody can still be
+// looking at it.  The global clock tick notices.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "percpu.h"
+#include "rcu.h"
+
+static struct {
+  struct spinlock lock;
+  struct rcuhead *next;   // Waiting for the current batch to finish
+  struct rcuhead *batch;  // Waiting for the grace period in snap
+  uint snap[NCPU];        // Quiescent counts when batch started
+} rcu;
+
+static PERCPU(uint nqs);  // Quiescent states this CPU has passed
+
+void
+rcuinit(void)
+{
+  initlock(&rcu.lock, "rcu");
+}
+
+// This CPU isn't in a read section (see scheduler).
+void
+rcuquiescent(void)
+{
+  percpuadd(nqs, 1);
+}
+
+static void
+snapshot(uint *snap)
+{
+  int i;
+
+  for(i = 0; i < ncpu; i++)
+    snap[i] = *percpuon(i, nqs);
+}
+
+// Has every CPU been quiescent since snapshot(snap)?
+static int
+graceover(uint *snap)
+{
+  struct cpu *c;
+  int i;
+
+  for(i = 0; i < ncpu; i++){
+    c = &cpus[i];
+    if(c->started && !c->idle && *percpuon(i, nqs) == snap[i])
+      return 0;
+  }
+  return 1;
+}
+
+void
+rcureadlock(void)
+{
+  pushcli();
+}
+
+void
+rcureadunlock(void)
+{
+  popcli();
+}
+
+// Call h->fn(h) after a grace period, from the clock tick.
+void
+callrcu(struct rcuhead *h, void (*fn)(struct rcuhead*))
+{
+  h->fn = fn;
+  acquire(&rcu.lock);
+  h->next = rcu.next;
+  rcu.next = h;
+  release(&rcu.lock);
+}
+
+// Wait for a grace period.
+void
+synchronizercu(void)
+{
+  uint snap[NCPU];
+
+  snapshot(snap);
+  while(!graceover(snap))
+    ticksleep(1);
+}
+
+// Run the callbacks whose grace period is over, and start the
+// next batch.  Called on the global clock tick.
+void
+rcutick(void)
+{
+  struct rcuhead *h, *done;
+
+  acquire(&rcu.lock);
+  done = 0;
+  if(rcu.batch && graceover(rcu.snap)){
+    done = rcu.batch;
+    rcu.batch = 0;
+  }
+  if(rcu.batch == 0 && rcu.next){
+    rcu.batch = rcu.next;
+    rcu.next = 0;
+    snapshot(rcu.snap);
+  }
+  release(&rcu.lock);
+
+  while((h = done) != 0){
+    done = h->next;
+    h->fn(h);
+  }
+}
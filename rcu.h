@@ -0,0 +1,5 @@
+// Read-copy-update (see rcu.c).
+struct rcuhead {
+  struct rcuhead *next;
+  void (*fn)(struct rcuhead*);  // Called once readers are done
+};
@@ -0,0 +1,22 @@
+// Resource limits, shared by the kernel and user programs.
+
+#define RLIMIT_CPU     0  // CPU time (seconds)
+#define RLIMIT_NOFILE  1  // Open files
+#define RLIMIT_AS      2  // Size of process memory (bytes)
+#define RLIMIT_STACK   3  // Size of the stack, from the next exec (bytes)
+
+#define RLIM_INFINITY  0xFFFFFFFF
+
+struct rlimit {
+  uint rlim_cur;  // Soft limit, enforced
+  uint rlim_max;  // Hard limit, ceiling for rlim_cur
+};
+
+#define RUSAGE_SELF      0  // The calling process
+#define RUSAGE_CHILDREN  1  // Its children that have been waited for
+
+// Resource usage; needs time.h.
+struct rusage {
+  struct timeval ru_utime;  // Time spent in user mode
+  struct timeval ru_stime;  // Time spent in the kernel
+};
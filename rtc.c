@@ -0,0 +1,213 @@
+// CMOS real-time clock.
+//
+// cmostime reads the date and time, which clockinit does once at
+// boot to set CLOCK_REALTIME, and the date system call on demand.
+// rtcalarm sleeps until the RTC's alarm interrupt, which wakes a
+// CPU that is halted with no timers set.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "date.h"
+#include "traps.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "x86.h"
+
+#define CMOS_PORT    0x70
+#define CMOS_RETURN  0x71
+
+#define SECS    0x00
+#define ASECS   0x01    // Alarm seconds
+#define MINS    0x02
+#define AMINS   0x03    // Alarm minutes
+#define HOURS   0x04
+#define AHOURS  0x05    // Alarm hours
+#define DAY     0x07
+#define MONTH   0x08
+#define YEAR    0x09
+
+#define CMOS_STATA   0x0a
+  #define CMOS_UIP     0x80   // RTC update in progress
+#define CMOS_STATB   0x0b
+  #define CMOS_AIE     0x20   // Alarm interrupt enable
+  #define CMOS_PIE     0x40   // Periodic interrupt enable
+  #define CMOS_UIE     0x10   // Update-ended interrupt enable
+  #define CMOS_BINARY  0x04   // Values are binary, not BCD
+  #define CMOS_24H     0x02   // Hours run 0-23, not 1-12
+#define CMOS_STATC   0x0c
+  #define CMOS_AF      0x20   // Alarm went off
+
+#define HOUR_PM      0x80   // In 12-hour mode, hour is p.m.
+
+static struct {
+  struct spinlock lock;
+  uint alarms;          // Alarm interrupts taken
+  int armed;            // Is an rtcalarm waiting?
+} rtc;
+
+static uint
+cmos_read(uint reg)
+{
+  outb(CMOS_PORT,  reg);
+  return inb(CMOS_RETURN);
+}
+
+static void
+cmos_write(uint reg, uint val)
+{
+  outb(CMOS_PORT, reg);
+  outb(CMOS_RETURN, val);
+}
+
+static void
+fill_rtcdate(struct rtcdate *r)
+{
+  r->second = cmos_read(SECS);
+  r->minute = cmos_read(MINS);
+  r->hour   = cmos_read(HOURS);
+  r->day    = cmos_read(DAY);
+  r->month  = cmos_read(MONTH);
+  r->year   = cmos_read(YEAR);
+}
+
+static uint
+frombcd(uint x)
+{
+  return (x >> 4) * 10 + (x & 0xf);
+}
+
+static uint
+tobcd(uint x)
+{
+  return (x / 10) << 4 | x % 10;
+}
+
+// Read the date and time into *r.
+// The rtc lock must be held.
+static void
+rtcread(struct rtcdate *r)
+{
+  struct rtcdate t1, t2;
+  int sb, pm;
+
+  sb = cmos_read(CMOS_STATB);
+
+  // make sure CMOS doesn't modify time while we read it
+  for(;;) {
+    if(cmos_read(CMOS_STATA) & CMOS_UIP)
+      continue;
+    fill_rtcdate(&t1);
+    if(cmos_read(CMOS_STATA) & CMOS_UIP)
+      continue;
+    fill_rtcdate(&t2);
+    if(memcmp(&t1, &t2, sizeof(t1)) == 0)
+      break;
+  }
+
+  // convert
+  pm = 0;
+  if((sb & CMOS_24H) == 0){
+    pm = t1.hour & HOUR_PM;
+    t1.hour &= ~HOUR_PM;
+  }
+  if((sb & CMOS_BINARY) == 0) {
+#define    CONV(x)     (t1.x = frombcd(t1.x))
+    CONV(second);
+    CONV(minute);
+    CONV(hour  );
+    CONV(day   );
+    CONV(month );
+    CONV(year  );
+#undef     CONV
+  }
+  if((sb & CMOS_24H) == 0)
+    t1.hour = t1.hour % 12 + (pm ? 12 : 0);
+
+  *r = t1;
+  r->year += 2000;
+}
+
+// Write v to register reg in the RTC's format: BCD or binary,
+// and for hours 24- or 12-hour.  The rtc lock must be held.
+static void
+rtcwrite(uint reg, uint v)
+{
+  int sb, pm;
+
+  sb = cmos_read(CMOS_STATB);
+  pm = 0;
+  if(reg == AHOURS && (sb & CMOS_24H) == 0){
+    pm = v >= 12 ? HOUR_PM : 0;
+    v = v % 12 ? v % 12 : 12;
+  }
+  if((sb & CMOS_BINARY) == 0)
+    v = tobcd(v);
+  cmos_write(reg, v | pm);
+}
+
+static void
+rtcintr(void)
+{
+  acquire(&rtc.lock);
+  // Reading status C acknowledges the interrupt.
+  if(cmos_read(CMOS_STATC) & CMOS_AF){
+    rtc.alarms++;
+    wakeup(&rtc.alarms);
+  }
+  release(&rtc.lock);
+}
+
+void
+rtcinit(void)
+{
+  initlock(&rtc.lock, "rtc");
+  // Turn off whatever interrupts the BIOS left on.
+  cmos_write(CMOS_STATB,
+             cmos_read(CMOS_STATB) & ~(CMOS_AIE|CMOS_PIE|CMOS_UIE));
+  cmos_read(CMOS_STATC);
+  irqregister(IRQ_RTC, rtcintr);
+}
+
+// Read the date and time, which the RTC keeps in UTC.
+void
+cmostime(struct rtcdate *r)
+{
+  acquire(&rtc.lock);
+  rtcread(r);
+  release(&rtc.lock);
+}
+
+// Sleep until the RTC's clock reaches secs seconds from now,
+// by its alarm interrupt.  Only one alarm can be set at a time.
+// Returns -1 if another is set, or if killed first.
+int
+rtcalarm(int secs)
+{
+  struct rtcdate r;
+  uint t, n;
+
+  if(secs <= 0 || secs >= 24*60*60)
+    return -1;
+  acquire(&rtc.lock);
+  if(rtc.armed){
+    release(&rtc.lock);
+    return -1;
+  }
+  rtc.armed = 1;
+  rtcread(&r);
+  t = ((r.hour*60 + r.minute)*60 + r.second + secs) % (24*60*60);
+  rtcwrite(AHOURS, t / 3600);
+  rtcwrite(AMINS, t / 60 % 60);
+  rtcwrite(ASECS, t % 60);
+  cmos_write(CMOS_STATB, cmos_read(CMOS_STATB) | CMOS_AIE);
+  n = rtc.alarms;
+  while(rtc.alarms == n && !myproc()->killed)
+    sleep(&rtc.alarms, &rtc.lock);
+  cmos_write(CMOS_STATB, cmos_read(CMOS_STATB) & ~CMOS_AIE);
+  rtc.armed = 0;
+  n = rtc.alarms - n;
+  release(&rtc.lock);
+  return n ? 0 : -1;
+}
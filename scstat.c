@@ -0,0 +1,73 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+#include "param.h"
+#include "syscall.h"
+#include "scstat.h"
+
+static char *names[NSYSCALL] = {
+[SYS_fork] "fork", [SYS_exit] "exit", [SYS_wait] "wait",
+[SYS_pipe] "pipe", [SYS_read] "read", [SYS_kill] "kill",
+[SYS_exec] "exec", [SYS_fstat] "fstat", [SYS_chdir] "chdir",
+[SYS_dup] "dup", [SYS_getpid] "getpid", [SYS_sbrk] "sbrk",
+[SYS_sleep] "sleep", [SYS_uptime] "uptime", [SYS_open] "open",
+[SYS_write] "write", [SYS_mknod] "mknod", [SYS_unlink] "unlink",
+[SYS_link] "link", [SYS_mkdir] "mkdir", [SYS_close] "close",
+[SYS_clone] "clone", [SYS_join] "join", [SYS_futexwait] "futexwait",
+[SYS_futexwake] "futexwake", [SYS_sigaction] "sigaction",
+[SYS_sigreturn] "sigreturn", [SYS_setpgid] "setpgid",
+[SYS_getpgid] "getpgid", [SYS_setsid] "setsid",
+[SYS_tcsetpgrp] "tcsetpgrp", [SYS_getppid] "getppid",
+[SYS_clock_gettime] "clock_gettime", [SYS_nanosleep] "nanosleep",
+[SYS_pinfo] "pinfo", [SYS_getrlimit] "getrlimit",
+[SYS_setrlimit] "setrlimit", [SYS_wait4] "wait4",
+[SYS_ptrace] "ptrace", [SYS_getrusage] "getrusage",
+[SYS_getitimer] "getitimer", [SYS_setitimer] "setitimer",
+[SYS_getuid] "getuid", [SYS_geteuid] "geteuid", [SYS_getgid] "getgid",
+[SYS_getegid] "getegid", [SYS_setuid] "setuid", [SYS_setgid] "setgid",
+[SYS_mmap] "mmap", [SYS_munmap] "munmap", [SYS_shmget] "shmget",
+[SYS_shmat] "shmat", [SYS_shmdt] "shmdt", [SYS_shmctl] "shmctl",
+[SYS_msync] "msync", [SYS_sysinfo] "sysinfo",
+[SYS_arch_prctl] "arch_prctl", [SYS_intrinfo] "intrinfo",
+[SYS_irqaffinity] "irqaffinity", [SYS_perfread] "perfread",
+[SYS_date] "date", [SYS_rtcalarm] "rtcalarm",
+[SYS_cpuonline] "cpuonline", [SYS_cpuinfo] "cpuinfo",
+[SYS_ioctl] "ioctl", [SYS_dmesg] "dmesg", [SYS_poll] "poll",
+[SYS_fcntl] "fcntl", [SYS_dup2] "dup2", [SYS_dup3] "dup3",
+[SYS_pread] "pread", [SYS_pwrite] "pwrite", [SYS_sendfile] "sendfile",
+[SYS_execve] "execve", [SYS_trace] "trace", [SYS_scstat] "scstat",
+[SYS_prctl] "prctl", [SYS_getdents] "getdents", [SYS_features] "features",
+};
+
+struct scstat st;
+
+// Print how many times each system call has been made, with
+// the average TSC cycles it took and how many calls fell in
+// each bucket of the latency histogram (see scstat.h).
+int
+main(void)
+{
+  int i, b;
+  uint avg;
+
+  if(scstat(&st) < 0){
+    perror("scstat");
+    exit();
+  }
+  printf(1, "CALL\t\tCOUNT\tAVG\t<1K\t<4K\t<16K\t<64K\t<256K\t<1M\t<4M\tMORE\n");
+  for(i = 0; i < NSYSCALL; i++){
+    if(st.count[i] == 0)
+      continue;
+    // Without libgcc's 64-bit division.
+    if(st.cycles[i] >> 32)
+      avg = ((uint)(st.cycles[i] >> 10) / st.count[i]) << 10;
+    else
+      avg = (uint)st.cycles[i] / st.count[i];
+    printf(1, "%s\t%s%d\t%d", names[i] ? names[i] : "?",
+           names[i] && strlen(names[i]) >= 8 ? "" : "\t", st.count[i], avg);
+    for(b = 0; b < NSCHIST; b++)
+      printf(1, "\t%d", st.hist[i][b]);
+    printf(1, "\n");
+  }
+  exit();
+}
@@ -0,0 +1,12 @@
+// System call statistics, for the scstat system call.
+
+#define NSCHIST   8   // Buckets in each latency histogram
+
+// Counts for each system call number.  Call i of a number
+// lands in bucket b of its histogram if it took fewer than
+// 1 << (10 + 2*b) TSC cycles; the last bucket takes the rest.
+struct scstat {
+  uint count[NSYSCALL];         // Calls made
+  uint64 cycles[NSYSCALL];      // TSC cycles spent in them
+  uint hist[NSYSCALL][NSCHIST]; // Calls by how long they took
+};
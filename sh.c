@@ -3,6 +3,8 @@
 #include "types.h"
 #include "user.h"
 #include "fcntl.h"
+#include "signal.h"
+#include "wait.h"
 
 // Parsed command representation
 #define EXEC  1
@@ -131,9 +133,10 @@ runcmd(struct cmd *cmd)
 }
 
 int
-getcmd(char *buf, int nbuf)
+getcmd(char *buf, int nbuf, int prompt)
 {
-  printf(2, "$ ");
+  if(prompt)
+    printf(2, "$ ");
   memset(buf, 0, nbuf);
   gets(buf, nbuf);
   if(buf[0] == 0) // EOF
@@ -141,11 +144,32 @@ getcmd(char *buf, int nbuf)
   return 0;
 }
 
+// Wait for job pid to exit, or with job control to stop, and
+// then take back the console.  Background jobs that exit
+// meanwhile are reaped too.
+void
+waitjob(int pid, int jobctl)
+{
+  int wpid, status;
+
+  while((wpid = waitpid(-1, &status, jobctl ? WUNTRACED : 0)) >= 0){
+    if(wpid != pid)
+      continue;
+    if(WIFSTOPPED(status))
+      printf(2, "[%d] stopped\n", pid);
+    break;
+  }
+  if(jobctl)
+    tcsetpgrp(0, getpgid(0));
+}
+
 int
-main(void)
+main(int argc, char *argv[])
 {
   static char buf[100];
-  int fd;
+  char *eq;
+  int fd, pid, jobctl;
+  struct cmd *cmd;
 
   // Ensure that three file descriptors are open.
   while((fd = open("console", O_RDWR)) >= 0){
@@ -155,8 +179,31 @@ main(void)
     }
   }
 
+  // Run the script argv[1], as the interpreter of one that
+  // starts #!/sh.
+  if(argc > 1){
+    if((fd = open(argv[1], O_RDONLY)) < 0){
+      printf(2, "sh: cannot open %s\n", argv[1]);
+      exit();
+    }
+    dup2(fd, 0);
+    close(fd);
+  }
+
+  // If reading from the console, run each command as a job in
+  // its own process group, and give it the console unless it
+  // runs in the background.  ^C then interrupts only the job,
+  // and ^Z stops it; fg and bg continue a stopped job.
+  jobctl = tcsetpgrp(0, getpgid(0)) == 0;
+  if(jobctl){
+    signal(SIGINT, SIG_IGN);
+    signal(SIGTSTP, SIG_IGN);
+  }
+
   // Read and run input commands.
-  while(getcmd(buf, sizeof(buf)) >= 0){
+  while(getcmd(buf, sizeof(buf), argc <= 1) >= 0){
+    if(buf[0] == '#')  // comment
+      continue;
     if(buf[0] == 'c' && buf[1] == 'd' && buf[2] == ' '){
       // Chdir must be called by the parent, not the child.
       buf[strlen(buf)-1] = 0;  // chop \n
@@ -164,9 +211,46 @@ main(void)
         printf(2, "cannot cd %s\n", buf+3);
       continue;
     }
-    if(fork1() == 0)
-      runcmd(parsecmd(buf));
-    wait();
+    if(strncmp(buf, "export ", 7) == 0){
+      // So must export, for later commands to see it.
+      buf[strlen(buf)-1] = 0;  // chop \n
+      if((eq = strchr(buf+7, '=')) == 0){
+        printf(2, "usage: export NAME=value\n");
+        continue;
+      }
+      *eq = 0;
+      if(setenv(buf+7, eq+1, 1) < 0)
+        printf(2, "cannot export %s\n", buf+7);
+      continue;
+    }
+    if(jobctl && (buf[0] == 'f' || buf[0] == 'b') && buf[1] == 'g' &&
+       buf[2] == ' '){
+      pid = atoi(buf+3);
+      if(buf[0] == 'f')
+        tcsetpgrp(0, pid);
+      if(kill(-pid, SIGCONT) < 0){
+        printf(2, "no job %d\n", pid);
+        tcsetpgrp(0, getpgid(0));
+        continue;
+      }
+      if(buf[0] == 'f')
+        waitjob(pid, jobctl);
+      continue;
+    }
+    if((pid = fork1()) == 0){
+      cmd = parsecmd(buf);
+      if(jobctl){
+        setpgid(0, 0);
+        if(cmd->type != BACK)
+          tcsetpgrp(0, getpid());
+        signal(SIGINT, SIG_DFL);
+        signal(SIGTSTP, SIG_DFL);
+      }
+      runcmd(cmd);
+    }
+    if(jobctl)
+      setpgid(pid, pid);
+    waitjob(pid, jobctl);
   }
   exit();
 }
@@ -386,10 +470,10 @@ parseredirs(struct cmd *cmd, char **ps, char *es)
       cmd = redircmd(cmd, q, eq, O_RDONLY, 0);
       break;
     case '>':
-      cmd = redircmd(cmd, q, eq, O_WRONLY|O_CREATE, 1);
+      cmd = redircmd(cmd, q, eq, O_WRONLY|O_CREATE|O_TRUNC, 1);
       break;
     case '+':  // >>
-      cmd = redircmd(cmd, q, eq, O_WRONLY|O_CREATE, 1);
+      cmd = redircmd(cmd, q, eq, O_WRONLY|O_CREATE|O_APPEND, 1);
       break;
     }
   }
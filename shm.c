@@ -0,0 +1,217 @@
+// System V style shared memory segments.
+//
+// shmget creates a segment of zeroed pages, found again by
+// key, and shmat maps all of them into the caller's address
+// space as a shared area (see vma.c), so that every process
+// attaching the segment, or forked from one that has, sees
+// the same memory.  Each page carries a reference for the
+// segment and one for each mapping of it.  A segment goes away
+// when its last attachment does, or at IPC_RMID if it was
+// never attached.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "mman.h"
+#include "shm.h"
+#include "errno.h"
+
+struct shmseg {
+  int used;
+  int key;
+  uint size;             // bytes, a multiple of PGSIZE
+  char *pages[NSHMPG];
+  int nattach;           // areas attached, plus shmat's hold
+  int attached;          // ever attached?
+};
+
+struct {
+  struct spinlock lock;
+  struct shmseg seg[NSHM];
+} shmtable;
+
+void
+shminit(void)
+{
+  initlock(&shmtable.lock, "shm");
+}
+
+// Free segment s and its pages; mappings of them keep
+// their own references.  Caller must hold shmtable.lock.
+static void
+shmfree(struct shmseg *s)
+{
+  int i;
+
+  for(i = 0; i < s->size / PGSIZE; i++)
+    kfree(s->pages[i]);
+  s->used = 0;
+}
+
+// Count another attachment of s.
+void
+shmdup(struct shmseg *s)
+{
+  acquire(&shmtable.lock);
+  s->nattach++;
+  release(&shmtable.lock);
+}
+
+// Drop an attachment of s, freeing it if that was the last.
+void
+shmput(struct shmseg *s)
+{
+  acquire(&shmtable.lock);
+  if(--s->nattach == 0 && s->attached)
+    shmfree(s);
+  release(&shmtable.lock);
+}
+
+// Return the id of the segment with key, creating one of size
+// bytes if there is none and flags has IPC_CREAT.  IPC_PRIVATE
+// always creates a segment.  Returns a negated error number
+// on failure.
+int
+shmget(int key, uint size, int flags)
+{
+  struct shmseg *s;
+  int i, err;
+
+  acquire(&shmtable.lock);
+  err = -EINVAL;
+  if(key != IPC_PRIVATE){
+    for(s = shmtable.seg; s < &shmtable.seg[NSHM]; s++){
+      if(s->used && s->key == key){
+        if((flags & IPC_CREAT) && (flags & IPC_EXCL)){
+          err = -EEXIST;
+          goto bad;
+        }
+        if(size > s->size)
+          goto bad;
+        release(&shmtable.lock);
+        return s - shmtable.seg;
+      }
+    }
+    err = -ENOENT;
+    if((flags & IPC_CREAT) == 0)
+      goto bad;
+    err = -EINVAL;
+  }
+  if(size == 0 || size > NSHMPG*PGSIZE)
+    goto bad;
+  for(s = shmtable.seg; s < &shmtable.seg[NSHM]; s++)
+    if(!s->used)
+      break;
+  err = -ENOSPC;
+  if(s == &shmtable.seg[NSHM])
+    goto bad;
+  err = -ENOMEM;
+
+  s->size = PGROUNDUP(size);
+  for(i = 0; i < s->size / PGSIZE; i++){
+    if((s->pages[i] = kalloc()) == 0){
+      s->size = i * PGSIZE;
+      shmfree(s);
+      goto bad;
+    }
+    memset(s->pages[i], 0, PGSIZE);
+  }
+  s->used = 1;
+  s->key = key;
+  s->nattach = 0;
+  s->attached = 0;
+  release(&shmtable.lock);
+  return s - shmtable.seg;
+
+bad:
+  release(&shmtable.lock);
+  return err;
+}
+
+// Map segment id into the current process, at addr if that is
+// free, read-only if flags has SHM_RDONLY.  Returns the address
+// or a negated error number.
+int
+shmat(int id, uint addr, int flags)
+{
+  struct shmseg *s;
+  uint start, i;
+  int prot, perm;
+  struct proc *curproc = myproc();
+
+  if(id < 0 || id >= NSHM)
+    return -EINVAL;
+  s = &shmtable.seg[id];
+  acquire(&shmtable.lock);
+  if(!s->used){
+    release(&shmtable.lock);
+    return -EINVAL;
+  }
+  s->nattach++;  // hold s while mapping it
+  release(&shmtable.lock);
+
+  prot = PROT_READ;
+  perm = PTE_U;
+  if((flags & SHM_RDONLY) == 0){
+    prot |= PROT_WRITE;
+    perm |= PTE_W;
+  }
+  if((start = vmamap(addr, s->size, prot, MAP_SHARED, 0, 0, s)) == 0){
+    shmput(s);
+    return -ENOMEM;
+  }
+  for(i = 0; i < s->size; i += PGSIZE){
+    kref(s->pages[i / PGSIZE]);
+    if(lazymap(curproc->pgdir, start + i, s->pages[i / PGSIZE], perm) < 0){
+      munmap(start, s->size);
+      shmput(s);
+      return -ENOMEM;
+    }
+  }
+  acquire(&shmtable.lock);
+  s->attached = 1;
+  release(&shmtable.lock);
+  shmput(s);
+  return start;
+}
+
+// Detach the segment the current process attached at addr.
+int
+shmdt(uint addr)
+{
+  uint len;
+
+  if((len = vmashm(myproc()->pgdir, addr)) == 0)
+    return -EINVAL;
+  return munmap(addr, len);
+}
+
+// Control segment id.  The only command is IPC_RMID, which
+// removes the segment now if nothing has it attached, and
+// otherwise hides it from shmget until the last detach.
+int
+shmctl(int id, int cmd)
+{
+  struct shmseg *s;
+
+  if(id < 0 || id >= NSHM || cmd != IPC_RMID)
+    return -EINVAL;
+  s = &shmtable.seg[id];
+  acquire(&shmtable.lock);
+  if(!s->used){
+    release(&shmtable.lock);
+    return -EINVAL;
+  }
+  if(s->nattach == 0)
+    shmfree(s);
+  else {
+    s->key = IPC_PRIVATE;
+    s->attached = 1;
+  }
+  release(&shmtable.lock);
+  return 0;
+}
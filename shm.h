@@ -0,0 +1,9 @@
+// System V style shared memory, shared by the kernel and
+// user programs.
+
+#define IPC_PRIVATE  0       // Key for a new segment no one else finds
+#define IPC_CREAT    01000   // shmget: create the segment if need be
+#define IPC_EXCL     02000   // shmget: fail if the segment exists
+#define IPC_RMID     0       // shmctl: remove the segment
+
+#define SHM_RDONLY   010000  // shmat: attach read-only
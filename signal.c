@@ -0,0 +1,117 @@
+// Signal delivery.
+//
+// kill() posts a signal in proc.c.  Before a process returns
+// to user space, trap() calls psig(), which runs the handler for
+// a pending signal by building a struct sigframe on the user
+// stack and pointing the trap frame at the handler.  The handler
+// returns into trampoline code in the frame that calls sigreturn,
+// which restores the interrupted state from the frame.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "x86.h"
+#include "proc.h"
+#include "signal.h"
+#include "syscall.h"
+#include "traps.h"
+#include "errno.h"
+
+struct sigframe {
+  uint ret;               // Handler's return address: code[]
+  int sig;                // Handler's argument
+  uint mask;              // Blocked signals to restore
+  struct trapframe tf;    // Interrupted user state
+  uchar code[8];          // movl $SYS_sigreturn, %eax; int $T_SYSCALL
+};
+
+// If the current process has a signal to handle, arrange for
+// it to enter the handler when it returns to user space with
+// trap frame tf.
+void
+psig(struct trapframe *tf)
+{
+  int sig;
+  uint sp;
+  struct sigframe f;
+  struct proc *p = myproc();
+
+  if(p->killed || (sig = issig()) == 0)
+    return;
+
+  f.sig = sig;
+  f.mask = p->sigmask;
+  f.tf = *tf;
+  f.code[0] = 0xb8;  // movl $SYS_sigreturn, %eax
+  *(uint*)&f.code[1] = SYS_sigreturn;
+  f.code[5] = 0xcd;  // int $T_SYSCALL
+  f.code[6] = T_SYSCALL;
+  f.code[7] = 0;
+
+  sp = (tf->esp - sizeof(f)) & ~3;
+  f.ret = sp + (uint)&((struct sigframe*)0)->code;
+  if(copyout(p->pgdir, p->sz, sp, &f, sizeof(f)) < 0){
+    // No room on the stack; the process cannot continue.
+    p->killed = 1;
+    return;
+  }
+
+  p->sigmask |= (1 << sig) | p->sighmask[sig];
+  tf->esp = sp;
+  tf->eip = (uint)p->sighandler[sig];
+}
+
+// Examine and change the action for signal sig.
+int
+sigaction(int sig, struct sigaction *act, struct sigaction *oldact)
+{
+  struct proc *p = myproc();
+
+  if(sig <= 0 || sig >= NSIG)
+    return -EINVAL;
+  if(act && ((SIGNOCATCH >> sig) & 1))
+    return -EINVAL;
+  if(oldact){
+    oldact->sa_handler = p->sighandler[sig];
+    oldact->sa_mask = p->sighmask[sig];
+  }
+  if(act){
+    p->sighandler[sig] = act->sa_handler;
+    p->sighmask[sig] = act->sa_mask & ~SIGNOCATCH;
+  }
+  return 0;
+}
+
+// Return from a signal handler to the state saved in the
+// sigframe that psig pushed.  The handler has popped the
+// return address, so the frame starts 4 bytes below %esp.
+// Only user-controllable registers are restored.
+int
+sigreturn(void)
+{
+  uint addr;
+  struct sigframe f;
+  struct proc *p = myproc();
+  struct trapframe *tf = p->tf;
+
+  addr = tf->esp - 4;
+  if(copyin(p->pgdir, p->sz, &f, addr, sizeof(f)) < 0){
+    p->killed = 1;
+    return -1;
+  }
+
+  p->sigmask = f.mask & ~SIGNOCATCH;
+  tf->edi = f.tf.edi;
+  tf->esi = f.tf.esi;
+  tf->ebp = f.tf.ebp;
+  tf->ebx = f.tf.ebx;
+  tf->edx = f.tf.edx;
+  tf->ecx = f.tf.ecx;
+  tf->eip = f.tf.eip;
+  tf->esp = f.tf.esp;
+  tf->eflags = (tf->eflags & ~FL_USER) | (f.tf.eflags & FL_USER);
+
+  // syscall() stores our return value in %eax.
+  return f.tf.eax;
+}
@@ -0,0 +1,36 @@
+// Signals, shared by the kernel and user programs.
+
+#define SIGINT   2   // Interrupt from keyboard
+#define SIGILL   4   // Illegal instruction
+#define SIGTRAP  5   // Trace or breakpoint trap
+#define SIGFPE   8   // Arithmetic exception
+#define SIGKILL  9   // Kill; cannot be caught or ignored
+#define SIGSEGV 11   // Invalid memory reference
+#define SIGALRM 14   // ITIMER_REAL expired
+#define SIGTERM 15   // Termination request
+#define SIGCHLD 17   // Child process exited
+#define SIGCONT 18   // Continue if stopped
+#define SIGSTOP 19   // Stop; cannot be caught or ignored
+#define SIGTSTP 20   // Stop from keyboard
+#define SIGXCPU 24   // CPU time limit exceeded
+#define SIGVTALRM 26 // ITIMER_VIRTUAL expired
+#define SIGPROF 27   // ITIMER_PROF expired
+
+typedef void (*sighandler_t)(int);
+
+#define SIG_DFL ((sighandler_t)0)  // Take the default action
+#define SIG_IGN ((sighandler_t)1)  // Ignore the signal
+
+// Signals whose default action is to be ignored, and those
+// whose default action is to stop the process until SIGCONT;
+// the default action of the others is to terminate.
+#define SIGDFL_IGNORE  ((1 << SIGCHLD) | (1 << SIGCONT))
+#define SIGDFL_STOP    ((1 << SIGSTOP) | (1 << SIGTSTP))
+
+// Signals that cannot be caught, ignored or blocked.
+#define SIGNOCATCH     ((1 << SIGKILL) | (1 << SIGSTOP))
+
+struct sigaction {
+  sighandler_t sa_handler;  // SIG_DFL, SIG_IGN, or a function
+  uint sa_mask;             // Signals to block while the handler runs
+};
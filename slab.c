@@ -0,0 +1,183 @@
+// Slab allocator for fixed-size kernel objects.
+//
+// A cache hands out objects of one size.  It gets memory a slab
+// at a time from kallocpages, and a slab goes back to the page
+// allocator as soon as none of its objects are in use, so a pool
+// of objects grows and shrinks with demand instead of being a
+// fixed array.  Each slab starts with a struct slab and holds a
+// list of its free objects, threaded through their first words.
+// Slabs are naturally aligned buddy blocks, so kmfree finds an
+// object's slab by rounding its address down.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "spinlock.h"
+#include "slab.h"
+#include "sysinfo.h"
+
+struct slab {
+  struct slab *next;
+  struct slab *prev;
+  void *free;          // Free objects
+  uint nfree;
+};
+
+#define MINPERSLAB 8  // Use slabs big enough for this many objects
+
+struct {
+  struct spinlock lock;
+  struct kmcache *caches;
+} kmlist;
+
+void
+slabinit(void)
+{
+  initlock(&kmlist.lock, "kmlist");
+}
+
+// Set up cache c for objects of size bytes.
+void
+kmcacheinit(struct kmcache *c, char *name, uint size)
+{
+  if(size < sizeof(void*))
+    size = sizeof(void*);
+  size = (size + sizeof(void*) - 1) & ~(sizeof(void*) - 1);
+  if(size > (PGSIZE << MAXORDER) - sizeof(struct slab))
+    panic("kmcacheinit");
+
+  initlock(&c->lock, name);
+  c->name = name;
+  c->size = size;
+  for(c->order = 0; c->order < MAXORDER; c->order++)
+    if(((PGSIZE << c->order) - sizeof(struct slab)) / size >= MINPERSLAB)
+      break;
+  c->perslab = ((PGSIZE << c->order) - sizeof(struct slab)) / size;
+  c->partial = c->full = 0;
+  c->nslab = 0;
+  c->nobj = 0;
+
+  acquire(&kmlist.lock);
+  c->next = kmlist.caches;
+  kmlist.caches = c;
+  release(&kmlist.lock);
+}
+
+static void
+unlinkslab(struct slab **list, struct slab *s)
+{
+  if(s->prev)
+    s->prev->next = s->next;
+  else
+    *list = s->next;
+  if(s->next)
+    s->next->prev = s->prev;
+}
+
+static void
+pushslab(struct slab **list, struct slab *s)
+{
+  s->prev = 0;
+  s->next = *list;
+  if(s->next)
+    s->next->prev = s;
+  *list = s;
+}
+
+// Allocate a new slab for c, with all its objects free.
+static struct slab*
+newslab(struct kmcache *c)
+{
+  struct slab *s;
+  char *o;
+  uint i;
+
+  if((s = (struct slab*)kallocpages(c->order)) == 0)
+    return 0;
+  s->free = 0;
+  o = (char*)(s + 1) + (c->perslab - 1) * c->size;
+  for(i = 0; i < c->perslab; i++, o -= c->size){
+    *(void**)o = s->free;
+    s->free = o;
+  }
+  s->nfree = c->perslab;
+  return s;
+}
+
+// Allocate a zeroed object from cache c.
+// Returns 0 if memory has run out.
+void*
+kmalloc(struct kmcache *c)
+{
+  struct slab *s;
+  void *o;
+
+  acquire(&c->lock);
+  if((s = c->partial) == 0){
+    if((s = newslab(c)) == 0){
+      release(&c->lock);
+      return 0;
+    }
+    pushslab(&c->partial, s);
+    c->nslab++;
+  }
+  o = s->free;
+  s->free = *(void**)o;
+  if(--s->nfree == 0){
+    unlinkslab(&c->partial, s);
+    pushslab(&c->full, s);
+  }
+  c->nobj++;
+  release(&c->lock);
+  memset(o, 0, c->size);
+  return o;
+}
+
+// Return object o to cache c, freeing its slab if that
+// was the slab's last object in use.
+void
+kmfree(struct kmcache *c, void *o)
+{
+  struct slab *s;
+
+  s = (struct slab*)((uint)o & ~((PGSIZE << c->order) - 1));
+  if((char*)o < (char*)(s + 1) ||
+     ((char*)o - (char*)(s + 1)) % c->size != 0)
+    panic("kmfree");
+
+  acquire(&c->lock);
+  if(s->nfree == 0){
+    unlinkslab(&c->full, s);
+    pushslab(&c->partial, s);
+  }
+  *(void**)o = s->free;
+  s->free = o;
+  s->nfree++;
+  c->nobj--;
+  if(s->nfree == c->perslab){
+    unlinkslab(&c->partial, s);
+    c->nslab--;
+    release(&c->lock);
+    kfreepages((char*)s, c->order);
+    return;
+  }
+  release(&c->lock);
+}
+
+// Fill in the slab usage of si, totalled over all caches.
+void
+kminfo(struct sysinfo *si)
+{
+  struct kmcache *c;
+
+  si->slabpages = si->slabobjs = 0;
+  acquire(&kmlist.lock);
+  for(c = kmlist.caches; c; c = c->next){
+    acquire(&c->lock);
+    si->slabpages += c->nslab << c->order;
+    si->slabobjs += c->nobj;
+    release(&c->lock);
+  }
+  release(&kmlist.lock);
+}
@@ -0,0 +1,14 @@
+// A cache of fixed-size kernel objects, carved out of slabs
+// of contiguous pages from kallocpages.
+struct kmcache {
+  struct spinlock lock;
+  char *name;             // For debugging
+  uint size;              // Object size in bytes
+  int order;              // Each slab is 2^order pages
+  uint perslab;           // Objects per slab
+  struct slab *partial;   // Slabs with some objects free
+  struct slab *full;      // Slabs with none free
+  uint nslab;             // Slabs allocated
+  uint nobj;              // Objects in use
+  struct kmcache *next;   // On the list of all caches
+};
@@ -1,4 +1,7 @@
 // Sleeping locks
+//
+// A process that has waited SLEEPWARN seconds for one says so
+// on the console, naming the holder, as a hint of deadlock.
 
 #include "types.h"
 #include "defs.h"
@@ -9,6 +12,10 @@
 #include "proc.h"
 #include "spinlock.h"
 #include "sleeplock.h"
+#include "timer.h"
+#include "ansi.h"
+
+#define SLEEPWARN 10  // Seconds to wait before warning
 
 void
 initsleeplock(struct sleeplock *lk, char *name)
@@ -19,25 +26,56 @@ initsleeplock(struct sleeplock *lk, char *name)
   lk->pid = 0;
 }
 
+// Timer function for acquiresleep's warning.
+static void
+waitedlong(void *arg)
+{
+  struct sleeplock *lk = arg;
+
+  acquire(&lk->lk);
+  wakeup(lk);
+  release(&lk->lk);
+}
+
 void
 acquiresleep(struct sleeplock *lk)
 {
+  struct timer t;
+  uint64 warn;
+
   acquire(&lk->lk);
-  while (lk->locked) {
-    sleep(lk, &lk->lk);
+  if(lk->locked && lk->pid == myproc()->pid)
+    panic("acquiresleep");
+  if(lk->locked){
+    memset(&t, 0, sizeof(t));
+    warn = nsuptime() + SLEEPWARN*1000000000ULL;
+    addtimer(&t, warn, waitedlong, lk);
+    while(lk->locked){
+      if(warn && nsuptime() >= warn){
+        cprintf(KWARN "pid %d %s:" KEND " waiting for %s held by pid %d\n",
+                myproc()->pid, myproc()->name, lk->name, lk->pid);
+        warn = 0;
+      }
+      sleep(lk, &lk->lk);
+    }
+    canceltimer(&t);
   }
   lk->locked = 1;
   lk->pid = myproc()->pid;
   release(&lk->lk);
 }
 
+// Release the lock, which the caller must hold, and hand it
+// to one of the waiters.
 void
 releasesleep(struct sleeplock *lk)
 {
   acquire(&lk->lk);
+  if(!lk->locked || lk->pid != myproc()->pid)
+    panic("releasesleep");
   lk->locked = 0;
   lk->pid = 0;
-  wakeup(lk);
+  wakeone(lk);
   release(&lk->lk);
 }
 
@@ -51,6 +89,3 @@ holdingsleep(struct sleeplock *lk)
   release(&lk->lk);
   return r;
 }
-
-
-
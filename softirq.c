@@ -0,0 +1,69 @@
+// Soft interrupts: deferred work for interrupt handlers.
+//
+// A device's interrupt handler does only what must be done at
+// once, such as taking bytes from the hardware, and raises a
+// soft interrupt for the rest.  Raised soft interrupts are
+// pending on the CPU that raised them.  On its way out of an
+// interrupt, trap runs their handlers on that CPU with
+// interrupts enabled, so other devices aren't kept waiting.
+// A soft interrupt handler must not sleep.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "x86.h"
+#include "traps.h"
+#include "percpu.h"
+
+static void (*handler[NSOFTIRQ])(void);
+static PERCPU(uint pending);  // Soft interrupts raised on this CPU
+static PERCPU(int running);   // Is dosoftirq running them?
+
+// Set the handler for soft interrupt n.
+void
+softirqinit(int n, void (*fn)(void))
+{
+  handler[n] = fn;
+}
+
+// Mark soft interrupt n pending on this CPU.
+void
+raisesoftirq(int n)
+{
+  percpuor(pending, 1 << n);
+}
+
+// Run the handlers for pending soft interrupts, including any
+// raised meanwhile by interrupts they let in.  Called by trap
+// with interrupts off.  An interrupt that arrives while the
+// handlers run on this CPU leaves its soft interrupts to them,
+// and doesn't yield the CPU (see trap).
+void
+dosoftirq(void)
+{
+  uint p;
+  int i;
+
+  if(percpuread(running))
+    return;
+  percpuwrite(running, 1);
+  while((p = percpuread(pending)) != 0){
+    percpuwrite(pending, 0);
+    sti();
+    for(i = 0; i < NSOFTIRQ; i++)
+      if((p & (1 << i)) && handler[i])
+        handler[i]();
+    cli();
+  }
+  percpuwrite(running, 0);
+}
+
+// Is this CPU running soft interrupt handlers?
+int
+insoftirq(void)
+{
+  return percpuread(running);
+}
@@ -13,8 +13,12 @@ void
 initlock(struct spinlock *lk, char *name)
 {
   lk->name = name;
-  lk->locked = 0;
+  lk->next = 0;
+  lk->owner = 0;
   lk->cpu = 0;
+#ifdef LOCKDEBUG
+  lk->class = 0;
+#endif
 }
 
 // Acquire the lock.
@@ -24,13 +28,24 @@ initlock(struct spinlock *lk, char *name)
 void
 acquire(struct spinlock *lk)
 {
+  uint t;
+  int contended;
+
   pushcli(); // disable interrupts to avoid deadlock.
   if(holding(lk))
     panic("acquire");
 
-  // The xchg is atomic.
-  while(xchg(&lk->locked, 1) != 0)
-    ;
+  // The xadd is atomic, so each CPU gets its own ticket.
+  // Waiting only reads lk->owner, which stays in this CPU's
+  // cache until release changes it.  The holder may be
+  // waiting for this CPU to run a cross-CPU call (see
+  // callmany), so do that meanwhile.
+  t = xadd(&lk->next, 1);
+  contended = lk->owner != t;
+  while(lk->owner != t){
+    pause();
+    callpoll();
+  }
 
   // Tell the C compiler and the processor to not move loads or stores
   // past this point, to ensure that the critical section's memory
@@ -40,6 +55,9 @@ acquire(struct spinlock *lk)
   // Record info about lock acquisition for debugging.
   lk->cpu = mycpu();
   getcallerpcs(&lk, lk->pcs);
+#ifdef LOCKDEBUG
+  lockacquired(lk, contended);
+#endif
 }
 
 // Release the lock.
@@ -59,11 +77,78 @@ release(struct spinlock *lk)
   // stores; __sync_synchronize() tells them both not to.
   __sync_synchronize();
 
-  // Release the lock, equivalent to lk->locked = 0.
-  // This code can't use a C assignment, since it might
-  // not be atomic. A real OS would use C atomics here.
-  asm volatile("movl $0, %0" : "+m" (lk->locked) : );
+  // Serve the next ticket.  Only the holder writes
+  // lk->owner, so this needn't be a locked instruction.
+  asm volatile("incl %0" : "+m" (lk->owner) : );
+#ifdef LOCKDEBUG
+  lockreleased(lk);
+#endif
+
+  popcli();
+}
+
+void
+initrwlock(struct rwlock *rw, char *name)
+{
+  rw->name = name;
+  rw->state = 0;
+  rw->writers = 0;
+  rw->cpu = 0;
+}
 
+// Acquire the lock for reading, once no writer holds it
+// or is waiting for it.
+void
+acquireread(struct rwlock *rw)
+{
+  uint s;
+
+  pushcli();
+  if(rw->cpu == mycpu())
+    panic("acquireread");
+  for(;;){
+    s = rw->state;
+    if(!(s & RW_WRITER) && rw->writers == 0 &&
+       cmpxchg(&rw->state, s, s+1) == s)
+      break;
+    callpoll();
+  }
+  __sync_synchronize();
+}
+
+void
+releaseread(struct rwlock *rw)
+{
+  if((rw->state & RW_WRITER) || rw->state == 0)
+    panic("releaseread");
+  __sync_synchronize();
+  xadd(&rw->state, -1);
+  popcli();
+}
+
+// Acquire the lock for writing, once the readers have left.
+void
+acquirewrite(struct rwlock *rw)
+{
+  pushcli();
+  if(rw->cpu == mycpu())
+    panic("acquirewrite");
+  xadd(&rw->writers, 1);
+  while(cmpxchg(&rw->state, 0, RW_WRITER) != 0)
+    callpoll();
+  xadd(&rw->writers, -1);
+  __sync_synchronize();
+  rw->cpu = mycpu();
+}
+
+void
+releasewrite(struct rwlock *rw)
+{
+  if(rw->state != RW_WRITER || rw->cpu != mycpu())
+    panic("releasewrite");
+  rw->cpu = 0;
+  __sync_synchronize();
+  asm volatile("movl $0, %0" : "+m" (rw->state) : );
   popcli();
 }
 
@@ -91,7 +176,7 @@ holding(struct spinlock *lock)
 {
   int r;
   pushcli();
-  r = lock->locked && lock->cpu == mycpu();
+  r = lock->next != lock->owner && lock->cpu == mycpu();
   popcli();
   return r;
 }
@@ -1,11 +1,32 @@
-// Mutual exclusion lock.
+// Mutual exclusion lock.  CPUs get it in the order they ask
+// for it, by taking a ticket and waiting for it to come up.
 struct spinlock {
-  uint locked;       // Is the lock held?
+  uint next;           // Next ticket to hand out
+  volatile uint owner; // Ticket holding the lock
 
   // For debugging:
   char *name;        // Name of lock.
   struct cpu *cpu;   // The cpu holding the lock.
   uint pcs[10];      // The call stack (an array of program counters)
                      // that locked the lock.
+#ifdef LOCKDEBUG
+  int class;         // Its class, plus one (see lockdebug.c)
+#endif
 };
 
+// Reader-writer spin lock, for data that is read much more
+// often than it is changed.  Any number of readers may hold it
+// at once, or one writer.  A waiting writer keeps new readers
+// out, so readers can't starve it; so a reader must not take
+// the lock again while holding it.
+struct rwlock {
+  uint state;        // Number of readers, or RW_WRITER
+  uint writers;      // Writers waiting
+
+  // For debugging:
+  char *name;        // Name of lock.
+  struct cpu *cpu;   // The cpu holding it for writing.
+};
+
+#define RW_WRITER 0x80000000
+
@@ -2,10 +2,36 @@
 #define T_FILE 2   // File
 #define T_DEV  3   // Device
 
+#define STAT_VERSION 2  // Layout of struct stat; bumped when it changes
+
+// Mode bits: the file's type, and who may use it.  The file
+// system keeps no permissions, so every file has all of them.
+#define S_IFMT   0170000  // Type bits
+#define S_IFDIR  0040000  // Directory
+#define S_IFCHR  0020000  // Character device
+#define S_IFREG  0100000  // Regular file
+
+#define S_ISDIR(m)  (((m) & S_IFMT) == S_IFDIR)
+#define S_ISCHR(m)  (((m) & S_IFMT) == S_IFCHR)
+#define S_ISREG(m)  (((m) & S_IFMT) == S_IFREG)
+
+// What stat and fstat report about a file, as filled in by
+// stati.  The file system keeps one time and no owner, so
+// atime and ctime are mtime, and uid and gid are 0.
 struct stat {
-  short type;  // Type of file
-  int dev;     // File system's disk device
-  uint ino;    // Inode number
-  short nlink; // Number of links to file
-  uint size;   // Size of file in bytes
+  short version; // STAT_VERSION
+  short type;    // Type of file
+  int dev;       // File system's disk device
+  uint ino;      // Inode number
+  short nlink;   // Number of links to file
+  short major;   // Device numbers (T_DEV only)
+  short minor;
+  ushort uid;    // Owner
+  ushort gid;    // Group
+  uint mode;     // Type and permissions (S_ bits)
+  uint size;     // Size of file in bytes
+  uint blocks;   // BSIZE blocks it holds, with any indirect block
+  uint atime;    // Last access time (Unix time)
+  uint mtime;    // Last modification time
+  uint ctime;    // Last status change time
 };
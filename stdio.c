@@ -0,0 +1,251 @@
+// Buffered input and output: stdin, stdout and stderr.
+//
+// Output to a terminal goes out a line at a time, to anything
+// else when the buffer fills, and to stderr at once.  exit
+// flushes what is left, and reading stdin flushes stdout first,
+// so a prompt shows before the program waits for an answer.
+// Streams go one way: writing stdin, or reading stdout or
+// stderr, is an error.
+
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+
+struct iobuf {
+  int fd;
+  int mode;           // _IOFBF, _IOLBF, _IONBF, or -1 until used
+  int input;          // Read from, not written to
+  int n;              // Bytes in buf: input, or output not yet written
+  int pos;            // Next byte of input to return
+  int eof, err;
+  char *buf;          // BUFSIZ bytes
+};
+
+static char bufs[2][BUFSIZ];
+
+static FILE streams[3] = {
+  { 0, -1, 1, 0, 0, 0, 0, bufs[0] },
+  { 1, -1, 0, 0, 0, 0, 0, bufs[1] },
+  { 2, _IONBF, 0 },  // Unbuffered, so no buf
+};
+
+FILE *stdin = &streams[0];
+FILE *stdout = &streams[1];
+FILE *stderr = &streams[2];
+
+extern void (*exitflush)(void);
+
+static void
+flushall(void)
+{
+  fflush(0);
+}
+
+// Pick f's buffering on first use, and have exit flush it.
+static void
+setup(FILE *f)
+{
+  if(f->mode < 0)
+    f->mode = isatty(f->fd) ? _IOLBF : _IOFBF;
+  exitflush = flushall;
+}
+
+// Write out f's buffered output, or with f 0, all streams'.
+int
+fflush(FILE *f)
+{
+  int i, r;
+
+  if(f == 0){
+    r = 0;
+    for(i = 0; i < sizeof(streams)/sizeof(streams[0]); i++)
+      if(fflush(&streams[i]) < 0)
+        r = EOF;
+    return r;
+  }
+  if(f->input || f->n == 0)
+    return 0;
+  for(i = 0; i < f->n; i += r){
+    if((r = write(f->fd, f->buf + i, f->n - i)) <= 0){
+      f->err = 1;
+      f->n = 0;
+      return EOF;
+    }
+  }
+  f->n = 0;
+  return 0;
+}
+
+// Buffer mode for f's output from now on; buf and size, for a
+// buffer of the caller's, aren't supported and must be 0.
+// stderr has no buffer, so it can only be _IONBF.
+int
+setvbuf(FILE *f, char *buf, int mode, uint size)
+{
+  if(buf != 0 || size != 0 || mode < _IOFBF || mode > _IONBF ||
+     (f->buf == 0 && mode != _IONBF))
+    return -1;
+  fflush(f);
+  f->mode = mode;
+  return 0;
+}
+
+int
+fputc(int c, FILE *f)
+{
+  char ch;
+
+  if(f->input){
+    f->err = 1;
+    return EOF;
+  }
+  setup(f);
+  if(f->mode == _IONBF){
+    ch = c;
+    return fwrite(&ch, 1, 1, f) == 1 ? (uchar)c : EOF;
+  }
+  f->buf[f->n++] = c;
+  if(f->n == BUFSIZ || (c == '\n' && f->mode == _IOLBF))
+    if(fflush(f) < 0)
+      return EOF;
+  return (uchar)c;
+}
+
+int
+putchar(int c)
+{
+  return fputc(c, stdout);
+}
+
+// Write n items of size bytes from p to f.  Returns the number
+// written, all of them unless there was an error.
+uint
+fwrite(const void *p, uint size, uint n, FILE *f)
+{
+  const char *s;
+  uint i, len;
+  int r;
+
+  if(size == 0 || n == 0)
+    return 0;
+  if(f->input){
+    f->err = 1;
+    return 0;
+  }
+  s = p;
+  len = size * n;
+  setup(f);
+  if(f->mode == _IONBF){
+    for(i = 0; i < len; i += r){
+      if((r = write(f->fd, s + i, len - i)) <= 0){
+        f->err = 1;
+        return i / size;
+      }
+    }
+    return len / size;
+  }
+  for(i = 0; i < len; i++)
+    if(fputc(s[i], f) == EOF)
+      return i / size;
+  return len / size;
+}
+
+int
+fputs(const char *s, FILE *f)
+{
+  uint n;
+
+  n = strlen(s);
+  return fwrite(s, 1, n, f) == n ? 0 : EOF;
+}
+
+// s and a newline, to stdout.
+int
+puts(const char *s)
+{
+  if(fputs(s, stdout) < 0 || fputc('\n', stdout) < 0)
+    return EOF;
+  return 0;
+}
+
+int
+fgetc(FILE *f)
+{
+  if(!f->input){
+    f->err = 1;
+    return EOF;
+  }
+  setup(f);
+  if(f->pos == f->n){
+    if(f == stdin)
+      fflush(stdout);
+    f->pos = 0;
+    if((f->n = read(f->fd, f->buf, BUFSIZ)) <= 0){
+      if(f->n < 0)
+        f->err = 1;
+      else
+        f->eof = 1;
+      f->n = 0;
+      return EOF;
+    }
+  }
+  return (uchar)f->buf[f->pos++];
+}
+
+int
+getchar(void)
+{
+  return fgetc(stdin);
+}
+
+// Read a line from f into buf, which holds n bytes, keeping the
+// newline.  Returns 0 if there was nothing to read.
+char*
+fgets(char *buf, int n, FILE *f)
+{
+  int i, c;
+
+  for(i = 0; i < n - 1; ){
+    if((c = fgetc(f)) == EOF)
+      break;
+    buf[i++] = c;
+    if(c == '\n')
+      break;
+  }
+  if(i == 0)
+    return 0;
+  buf[i] = 0;
+  return buf;
+}
+
+// Read n items of size bytes from f into p.  Returns the number
+// of whole items read.
+uint
+fread(void *p, uint size, uint n, FILE *f)
+{
+  char *s;
+  uint i;
+  int c;
+
+  if(size == 0 || n == 0)
+    return 0;
+  s = p;
+  for(i = 0; i < size * n; i++){
+    if((c = fgetc(f)) == EOF)
+      break;
+    s[i] = c;
+  }
+  return i / size;
+}
+
+int
+feof(FILE *f)
+{
+  return f->eof;
+}
+
+int
+ferror(FILE *f)
+{
+  return f->err;
+}
@@ -0,0 +1,33 @@
+#include "types.h"
+#include "stat.h"
+#include "user.h"
+
+// strace command [arg ...]: run the command with system call
+// tracing on, so that the kernel logs each call it and its
+// children make, and what the call returned, to the console.
+// dmesg shows the log afterwards.
+int
+main(int argc, char *argv[])
+{
+  int pid;
+
+  if(argc < 2){
+    printf(2, "usage: strace command [arg ...]\n");
+    exit();
+  }
+  if((pid = fork()) < 0){
+    perror("strace: fork");
+    exit();
+  }
+  if(pid == 0){
+    if(trace(0, 1) < 0){
+      perror("strace: trace");
+      exit();
+    }
+    exec(argv[1], argv+1);
+    perror(argv[1]);
+    exit();
+  }
+  wait();
+  exit();
+}
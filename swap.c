@@ -0,0 +1,136 @@
+// Swap space.
+//
+// When memory runs out, kalloc calls reclaim (in proc.c) to
+// evict a user page.  A page that is a clean copy of its file is
+// simply unmapped.  Any other page is written out to a slot of
+// swap space, and its PTE is replaced by a swap PTE: not
+// present, marked PTE_SWAP, with the slot number where the page
+// address was (see mmu.h).  Touching the page faults, and
+// lazyalloc reads it back in.
+//
+// Swap space is SWAPPGS pages on the boot disk, starting at
+// block SWAPSTART, past the kernel.  fork shares swap PTEs
+// between parent and child, so each slot has a reference
+// count.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "fs.h"
+#include "buf.h"
+#include "sysinfo.h"
+
+#define BPP  (PGSIZE/BSIZE)  // disk blocks per page
+
+struct {
+  struct spinlock lock;
+  ushort ref[SWAPPGS];  // swap PTEs and readers using each slot
+  uchar busy[SWAPPGS];  // being written out
+} swap;
+
+void
+swapinit(void)
+{
+  initlock(&swap.lock, "swap");
+}
+
+// Allocate a slot to write a page out to; swapwrite must follow.
+// Returns -1 if swap is full, or if there is no boot disk to
+// hold it, as with memide.
+int
+swapalloc(void)
+{
+  int i;
+
+  if(!idedisk(SWAPDEV))
+    return -1;
+  acquire(&swap.lock);
+  for(i = 0; i < SWAPPGS; i++){
+    if(swap.ref[i] == 0 && !swap.busy[i]){
+      swap.ref[i] = 1;
+      swap.busy[i] = 1;
+      release(&swap.lock);
+      return i;
+    }
+  }
+  release(&swap.lock);
+  return -1;
+}
+
+// Add a reference to slot.
+void
+swapdup(int slot)
+{
+  acquire(&swap.lock);
+  if(swap.ref[slot] == 0)
+    panic("swapdup");
+  swap.ref[slot]++;
+  release(&swap.lock);
+}
+
+// Drop a reference to slot.
+void
+swapfree(int slot)
+{
+  acquire(&swap.lock);
+  if(swap.ref[slot] == 0)
+    panic("swapfree");
+  swap.ref[slot]--;
+  release(&swap.lock);
+}
+
+// Write page out to slot, from swapalloc.
+void
+swapwrite(int slot, char *page)
+{
+  struct buf *b;
+  int i;
+
+  for(i = 0; i < BPP; i++){
+    b = bread(SWAPDEV, SWAPSTART + slot*BPP + i);
+    memmove(b->data, page + i*BSIZE, BSIZE);
+    bwrite(b);
+    brelse(b);
+  }
+  acquire(&swap.lock);
+  swap.busy[slot] = 0;
+  wakeup(&swap.busy[slot]);
+  release(&swap.lock);
+}
+
+// Read slot into page, waiting for it to be written first.
+// The caller must hold a reference to slot.
+void
+swapread(int slot, char *page)
+{
+  struct buf *b;
+  int i;
+
+  acquire(&swap.lock);
+  while(swap.busy[slot])
+    sleep(&swap.busy[slot], &swap.lock);
+  release(&swap.lock);
+  for(i = 0; i < BPP; i++){
+    b = bread(SWAPDEV, SWAPSTART + slot*BPP + i);
+    memmove(page + i*BSIZE, b->data, BSIZE);
+    brelse(b);
+  }
+}
+
+// Fill in the swap space usage of si.
+void
+swapinfo(struct sysinfo *si)
+{
+  int i;
+
+  si->swappages = SWAPPGS;
+  si->swapfree = 0;
+  acquire(&swap.lock);
+  for(i = 0; i < SWAPPGS; i++)
+    if(swap.ref[i] == 0 && !swap.busy[i])
+      si->swapfree++;
+  release(&swap.lock);
+}
@@ -6,6 +6,10 @@
 #include "proc.h"
 #include "x86.h"
 #include "syscall.h"
+#include "errno.h"
+#include "percpu.h"
+#include "scstat.h"
+#include "features.h"
 
 // User code makes a system call with INT T_SYSCALL.
 // System call number in %eax.
@@ -19,30 +23,20 @@ fetchint(uint addr, int *ip)
 {
   struct proc *curproc = myproc();
 
-  if(addr >= curproc->sz || addr+4 > curproc->sz)
-    return -1;
-  *ip = *(int*)(addr);
-  return 0;
+  return copyin(curproc->pgdir, curproc->sz, ip, addr, sizeof(*ip));
 }
 
-// Fetch the nul-terminated string at addr from the current process.
-// Doesn't actually copy the string - just sets *pp to point at it.
+// Copy the nul-terminated string at addr from the current process
+// into buf, which has room for max bytes.
 // Returns length of string, not including nul.
 int
-fetchstr(uint addr, char **pp)
+fetchstr(uint addr, char *buf, int max)
 {
-  char *s, *ep;
   struct proc *curproc = myproc();
 
-  if(addr >= curproc->sz)
+  if(max <= 0)
     return -1;
-  *pp = (char*)addr;
-  ep = (char*)curproc->sz;
-  for(s = *pp; s < ep; s++){
-    if(*s == 0)
-      return s - *pp;
-  }
-  return -1;
+  return copyinstr(curproc->pgdir, curproc->sz, buf, addr, max);
 }
 
 // Fetch the nth 32-bit system call argument.
@@ -53,54 +47,122 @@ argint(int n, int *ip)
 }
 
 // Fetch the nth word-sized system call argument as a pointer
-// to a block of memory of size bytes.  Check that the pointer
-// lies within the process address space.
+// to a block of memory of size bytes, and copy the block into p.
+// The kernel never dereferences a user pointer itself.
 int
-argptr(int n, char **pp, int size)
+argcopyin(int n, void *p, int size)
 {
   int i;
   struct proc *curproc = myproc();
- 
-  if(argint(n, &i) < 0)
+
+  if(argint(n, &i) < 0 || size < 0)
     return -1;
-  if(size < 0 || (uint)i >= curproc->sz || (uint)i+size > curproc->sz)
+  return copyin(curproc->pgdir, curproc->sz, p, i, size);
+}
+
+// Like argcopyin, but copy size bytes from p out to the block.
+int
+argcopyout(int n, void *p, int size)
+{
+  int i;
+  struct proc *curproc = myproc();
+
+  if(argint(n, &i) < 0 || size < 0)
     return -1;
-  *pp = (char*)i;
-  return 0;
+  return copyout(curproc->pgdir, curproc->sz, i, p, size);
 }
 
-// Fetch the nth word-sized system call argument as a string pointer.
-// Check that the pointer is valid and the string is nul-terminated.
-// (There is no shared writable memory, so the string can't change
+// Fetch the nth word-sized system call argument as a string pointer
+// and copy the string into buf, which has room for max bytes.
+// Check that the string is nul-terminated and fits.  (Working on
+// the copy means the string can't change, even in shared memory,
 // between this check and being used by the kernel.)
 int
-argstr(int n, char **pp)
+argstr(int n, char *buf, int max)
 {
   int addr;
   if(argint(n, &addr) < 0)
     return -1;
-  return fetchstr(addr, pp);
+  return fetchstr(addr, buf, max);
 }
 
+extern int sys_arch_prctl(void);
 extern int sys_chdir(void);
+extern int sys_clock_gettime(void);
+extern int sys_clone(void);
 extern int sys_close(void);
+extern int sys_cpuinfo(void);
+extern int sys_cpuonline(void);
+extern int sys_date(void);
+extern int sys_dmesg(void);
 extern int sys_dup(void);
+extern int sys_dup2(void);
+extern int sys_dup3(void);
 extern int sys_exec(void);
+extern int sys_execve(void);
 extern int sys_exit(void);
+extern int sys_fcntl(void);
+extern int sys_features(void);
 extern int sys_fork(void);
 extern int sys_fstat(void);
+extern int sys_futexwait(void);
+extern int sys_futexwake(void);
+extern int sys_getdents(void);
+extern int sys_getegid(void);
+extern int sys_geteuid(void);
+extern int sys_getgid(void);
+extern int sys_getitimer(void);
+extern int sys_getpgid(void);
 extern int sys_getpid(void);
+extern int sys_getppid(void);
+extern int sys_getrlimit(void);
+extern int sys_getrusage(void);
+extern int sys_getuid(void);
+extern int sys_intrinfo(void);
+extern int sys_ioctl(void);
+extern int sys_irqaffinity(void);
+extern int sys_join(void);
 extern int sys_kill(void);
 extern int sys_link(void);
 extern int sys_mkdir(void);
 extern int sys_mknod(void);
+extern int sys_mmap(void);
+extern int sys_msync(void);
+extern int sys_munmap(void);
+extern int sys_nanosleep(void);
 extern int sys_open(void);
+extern int sys_pinfo(void);
+extern int sys_perfread(void);
 extern int sys_pipe(void);
+extern int sys_poll(void);
+extern int sys_prctl(void);
+extern int sys_pread(void);
+extern int sys_ptrace(void);
+extern int sys_pwrite(void);
 extern int sys_read(void);
+extern int sys_rtcalarm(void);
+extern int sys_scstat(void);
 extern int sys_sbrk(void);
+extern int sys_sendfile(void);
+extern int sys_setgid(void);
+extern int sys_setitimer(void);
+extern int sys_setpgid(void);
+extern int sys_setrlimit(void);
+extern int sys_setsid(void);
+extern int sys_setuid(void);
+extern int sys_shmat(void);
+extern int sys_shmctl(void);
+extern int sys_shmdt(void);
+extern int sys_shmget(void);
+extern int sys_sigaction(void);
+extern int sys_sigreturn(void);
 extern int sys_sleep(void);
+extern int sys_sysinfo(void);
+extern int sys_tcsetpgrp(void);
+extern int sys_trace(void);
 extern int sys_unlink(void);
 extern int sys_wait(void);
+extern int sys_wait4(void);
 extern int sys_write(void);
 extern int sys_uptime(void);
 
@@ -126,20 +188,294 @@ static int (*syscalls[])(void) = {
 [SYS_link]    sys_link,
 [SYS_mkdir]   sys_mkdir,
 [SYS_close]   sys_close,
+[SYS_clone]   sys_clone,
+[SYS_join]    sys_join,
+[SYS_futexwait] sys_futexwait,
+[SYS_futexwake] sys_futexwake,
+[SYS_sigaction] sys_sigaction,
+[SYS_sigreturn] sys_sigreturn,
+[SYS_setpgid] sys_setpgid,
+[SYS_getpgid] sys_getpgid,
+[SYS_setsid]  sys_setsid,
+[SYS_tcsetpgrp] sys_tcsetpgrp,
+[SYS_getppid] sys_getppid,
+[SYS_clock_gettime] sys_clock_gettime,
+[SYS_nanosleep] sys_nanosleep,
+[SYS_pinfo]   sys_pinfo,
+[SYS_getrlimit] sys_getrlimit,
+[SYS_setrlimit] sys_setrlimit,
+[SYS_wait4]   sys_wait4,
+[SYS_ptrace]  sys_ptrace,
+[SYS_getrusage] sys_getrusage,
+[SYS_getitimer] sys_getitimer,
+[SYS_setitimer] sys_setitimer,
+[SYS_getuid]  sys_getuid,
+[SYS_geteuid] sys_geteuid,
+[SYS_getgid]  sys_getgid,
+[SYS_getegid] sys_getegid,
+[SYS_setuid]  sys_setuid,
+[SYS_setgid]  sys_setgid,
+[SYS_mmap]    sys_mmap,
+[SYS_munmap]  sys_munmap,
+[SYS_shmget]  sys_shmget,
+[SYS_shmat]   sys_shmat,
+[SYS_shmdt]   sys_shmdt,
+[SYS_shmctl]  sys_shmctl,
+[SYS_msync]   sys_msync,
+[SYS_sysinfo] sys_sysinfo,
+[SYS_arch_prctl] sys_arch_prctl,
+[SYS_intrinfo] sys_intrinfo,
+[SYS_irqaffinity] sys_irqaffinity,
+[SYS_perfread] sys_perfread,
+[SYS_date]    sys_date,
+[SYS_rtcalarm] sys_rtcalarm,
+[SYS_cpuonline] sys_cpuonline,
+[SYS_cpuinfo] sys_cpuinfo,
+[SYS_ioctl]   sys_ioctl,
+[SYS_dmesg]   sys_dmesg,
+[SYS_poll]    sys_poll,
+[SYS_fcntl]   sys_fcntl,
+[SYS_dup2]    sys_dup2,
+[SYS_dup3]    sys_dup3,
+[SYS_pread]   sys_pread,
+[SYS_pwrite]  sys_pwrite,
+[SYS_sendfile] sys_sendfile,
+[SYS_execve]  sys_execve,
+[SYS_trace]   sys_trace,
+[SYS_scstat]  sys_scstat,
+[SYS_prctl]   sys_prctl,
+[SYS_getdents] sys_getdents,
+[SYS_features] sys_features,
+};
+
+// Names of the system calls, and the kinds of their arguments,
+// for tracing: d is an int, x an address or flags, s a string.
+static struct {
+  char *name;
+  char *args;
+} sysdesc[] = {
+[SYS_fork]    { "fork", "" },
+[SYS_exit]    { "exit", "" },
+[SYS_wait]    { "wait", "" },
+[SYS_pipe]    { "pipe", "x" },
+[SYS_read]    { "read", "dxd" },
+[SYS_kill]    { "kill", "dd" },
+[SYS_exec]    { "exec", "sx" },
+[SYS_fstat]   { "fstat", "dx" },
+[SYS_chdir]   { "chdir", "s" },
+[SYS_dup]     { "dup", "d" },
+[SYS_getpid]  { "getpid", "" },
+[SYS_sbrk]    { "sbrk", "d" },
+[SYS_sleep]   { "sleep", "d" },
+[SYS_uptime]  { "uptime", "" },
+[SYS_open]    { "open", "sx" },
+[SYS_write]   { "write", "dxd" },
+[SYS_mknod]   { "mknod", "sdd" },
+[SYS_unlink]  { "unlink", "s" },
+[SYS_link]    { "link", "ss" },
+[SYS_mkdir]   { "mkdir", "s" },
+[SYS_close]   { "close", "d" },
+[SYS_clone]   { "clone", "xxx" },
+[SYS_join]    { "join", "x" },
+[SYS_futexwait] { "futexwait", "xd" },
+[SYS_futexwake] { "futexwake", "xd" },
+[SYS_sigaction] { "sigaction", "dxx" },
+[SYS_sigreturn] { "sigreturn", "" },
+[SYS_setpgid] { "setpgid", "dd" },
+[SYS_getpgid] { "getpgid", "d" },
+[SYS_setsid]  { "setsid", "" },
+[SYS_tcsetpgrp] { "tcsetpgrp", "dd" },
+[SYS_getppid] { "getppid", "" },
+[SYS_clock_gettime] { "clock_gettime", "dx" },
+[SYS_nanosleep] { "nanosleep", "xx" },
+[SYS_pinfo]   { "pinfo", "xd" },
+[SYS_getrlimit] { "getrlimit", "dx" },
+[SYS_setrlimit] { "setrlimit", "dx" },
+[SYS_wait4]   { "wait4", "dxxx" },
+[SYS_ptrace]  { "ptrace", "ddxx" },
+[SYS_getrusage] { "getrusage", "dx" },
+[SYS_getitimer] { "getitimer", "dx" },
+[SYS_setitimer] { "setitimer", "dxx" },
+[SYS_getuid]  { "getuid", "" },
+[SYS_geteuid] { "geteuid", "" },
+[SYS_getgid]  { "getgid", "" },
+[SYS_getegid] { "getegid", "" },
+[SYS_setuid]  { "setuid", "d" },
+[SYS_setgid]  { "setgid", "d" },
+[SYS_mmap]    { "mmap", "xdxxdd" },
+[SYS_munmap]  { "munmap", "xd" },
+[SYS_shmget]  { "shmget", "ddx" },
+[SYS_shmat]   { "shmat", "dxx" },
+[SYS_shmdt]   { "shmdt", "x" },
+[SYS_shmctl]  { "shmctl", "ddx" },
+[SYS_msync]   { "msync", "xdx" },
+[SYS_sysinfo] { "sysinfo", "x" },
+[SYS_arch_prctl] { "arch_prctl", "xx" },
+[SYS_intrinfo] { "intrinfo", "dx" },
+[SYS_irqaffinity] { "irqaffinity", "dx" },
+[SYS_perfread] { "perfread", "x" },
+[SYS_date]    { "date", "x" },
+[SYS_rtcalarm] { "rtcalarm", "d" },
+[SYS_cpuonline] { "cpuonline", "dd" },
+[SYS_cpuinfo] { "cpuinfo", "dx" },
+[SYS_ioctl]   { "ioctl", "dxx" },
+[SYS_dmesg]   { "dmesg", "xd" },
+[SYS_poll]    { "poll", "xdd" },
+[SYS_fcntl]   { "fcntl", "ddx" },
+[SYS_dup2]    { "dup2", "dd" },
+[SYS_dup3]    { "dup3", "ddx" },
+[SYS_pread]   { "pread", "dxdd" },
+[SYS_pwrite]  { "pwrite", "dxdd" },
+[SYS_sendfile] { "sendfile", "ddxd" },
+[SYS_execve]  { "execve", "sxx" },
+[SYS_trace]   { "trace", "dd" },
+[SYS_scstat]  { "scstat", "x" },
+[SYS_prctl]   { "prctl", "dx" },
+[SYS_getdents] { "getdents", "dxd" },
+[SYS_features] { "features", "xd" },
 };
 
+// Append s to the trace line at p, which ends at e.
+static char*
+tputs(char *p, char *e, char *s)
+{
+  while(*s && p < e)
+    *p++ = *s++;
+  return p;
+}
+
+// Append x to the trace line, in decimal or, for base 16, as 0x
+// and hex digits.
+static char*
+tputn(char *p, char *e, int x, int base)
+{
+  static char digits[] = "0123456789abcdef";
+  char buf[16];
+  uint u;
+  int i;
+
+  u = base == 10 && x < 0 ? -x : x;
+  i = 0;
+  do
+    buf[i++] = digits[u % base];
+  while((u /= base) != 0);
+  if(base == 16){
+    buf[i++] = 'x';
+    buf[i++] = '0';
+  } else if(x < 0)
+    buf[i++] = '-';
+  while(--i >= 0 && p < e)
+    *p++ = buf[i];
+  return p;
+}
+
+// Describe the call the current process is making into buf, as
+//   open("README", 0x0)
+// before the call can change its arguments.  A string that
+// can't be fetched, or is too long, shows as its address.
+static void
+tracecall(int num, char *buf, int n)
+{
+  char *p, *e, *a, s[40];
+  int i, x;
+
+  p = buf;
+  e = buf + n - 1;
+  p = tputs(p, e, sysdesc[num].name);
+  p = tputs(p, e, "(");
+  for(i = 0, a = sysdesc[num].args; *a; i++, a++){
+    if(i > 0)
+      p = tputs(p, e, ", ");
+    if(argint(i, &x) < 0)
+      p = tputs(p, e, "?");
+    else if(*a == 's' && fetchstr(x, s, sizeof(s)) >= 0){
+      p = tputs(p, e, "\"");
+      p = tputs(p, e, s);
+      p = tputs(p, e, "\"");
+    } else
+      p = tputn(p, e, x, *a == 'd' ? 10 : 16);
+  }
+  p = tputs(p, e, ")");
+  *p = 0;
+}
+
+// Copy out the first n words of the bitmap of calls this
+// kernel has, and return the interface's version.
+int
+sys_features(void)
+{
+  uint bits[NFEATURES];
+  int n, i;
+
+  if(argint(1, &n) < 0)
+    return -EFAULT;
+  if(n < 0)
+    return -EINVAL;
+  if(n > NFEATURES)
+    n = NFEATURES;
+  memset(bits, 0, sizeof(bits));
+  for(i = 1; i < NELEM(syscalls); i++)
+    if(syscalls[i])
+      bits[i/32] |= 1 << i%32;
+  if(argcopyout(0, bits, n*sizeof(bits[0])) < 0)
+    return -EFAULT;
+  return FEATURES_VERSION;
+}
+
+PERCPU(struct scstat scstat);
+
+// Count a call of number num that took c TSC cycles.
+static void
+account(int num, uint64 c)
+{
+  struct scstat *s;
+  int b;
+
+  for(b = 0; b < NSCHIST-1 && c >= (1ULL << (10 + 2*b)); b++)
+    ;
+  pushcli();
+  s = thiscpu(scstat);
+  s->count[num]++;
+  s->cycles[num] += c;
+  s->hist[num][b]++;
+  popcli();
+}
+
 void
 syscall(void)
 {
-  int num;
+  int num, r;
+  char *call;
+  uint64 t0;
   struct proc *curproc = myproc();
 
   num = curproc->tf->eax;
   if(num > 0 && num < NELEM(syscalls) && syscalls[num]) {
-    curproc->tf->eax = syscalls[num]();
+    // A traced process logs the call with what it returned, or
+    // before it for exit, which doesn't return.
+    call = 0;
+    if(curproc->trace && (call = kalloc()) != 0){
+      tracecall(num, call, PGSIZE);
+      if(num == SYS_exit){
+        cprintf("%d %s: %s\n", curproc->pid, curproc->name, call);
+        kfree(call);
+        call = 0;
+      }
+    }
+    t0 = rdtsc();
+    if(curproc->sysdeny[num/32] & (1 << num%32))
+      r = -EPERM;  // filtered out by prctl
+    else
+      r = syscalls[num]();
+    account(num, rdtsc() - t0);
+    curproc->tf->eax = r;
+    if(call){
+      cprintf("%d %s: %s = %d\n", curproc->pid, curproc->name, call, r);
+      kfree(call);
+    }
   } else {
     cprintf("%d %s: unknown sys call %d\n",
             curproc->pid, curproc->name, num);
-    curproc->tf->eax = -1;
+    curproc->tf->eax = -ENOSYS;
   }
 }
@@ -20,3 +20,61 @@
 #define SYS_link   19
 #define SYS_mkdir  20
 #define SYS_close  21
+#define SYS_clone  22
+#define SYS_join   23
+#define SYS_futexwait 24
+#define SYS_futexwake 25
+#define SYS_sigaction 26
+#define SYS_sigreturn 27
+#define SYS_setpgid 28
+#define SYS_getpgid 29
+#define SYS_setsid  30
+#define SYS_tcsetpgrp 31
+#define SYS_getppid 32
+#define SYS_clock_gettime 33
+#define SYS_nanosleep 34
+#define SYS_pinfo  35
+#define SYS_getrlimit 36
+#define SYS_setrlimit 37
+#define SYS_wait4  38
+#define SYS_ptrace 39
+#define SYS_getrusage 40
+#define SYS_getitimer 41
+#define SYS_setitimer 42
+#define SYS_getuid 43
+#define SYS_geteuid 44
+#define SYS_getgid 45
+#define SYS_getegid 46
+#define SYS_setuid 47
+#define SYS_setgid 48
+#define SYS_mmap 49
+#define SYS_munmap 50
+#define SYS_shmget 51
+#define SYS_shmat 52
+#define SYS_shmdt 53
+#define SYS_shmctl 54
+#define SYS_msync 55
+#define SYS_sysinfo 56
+#define SYS_arch_prctl 57
+#define SYS_intrinfo 58
+#define SYS_irqaffinity 59
+#define SYS_perfread 60
+#define SYS_date   61
+#define SYS_rtcalarm 62
+#define SYS_cpuonline 63
+#define SYS_cpuinfo 64
+#define SYS_ioctl  65
+#define SYS_dmesg  66
+#define SYS_poll   67
+#define SYS_fcntl  68
+#define SYS_dup2   69
+#define SYS_dup3   70
+#define SYS_pread  71
+#define SYS_pwrite 72
+#define SYS_sendfile 73
+#define SYS_execve 74
+#define SYS_trace  75
+#define SYS_scstat 76
+#define SYS_prctl  77
+#define SYS_getdents 78
+#define SYS_features 79
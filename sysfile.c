@@ -2,6 +2,7 @@
 // File-system system calls.
 // Mostly argument checking, since we don't trust
 // user code, and calls into file.c and fs.c.
+// Failures return a negated error number from errno.h.
 //
 
 #include "types.h"
@@ -14,7 +15,13 @@
 #include "spinlock.h"
 #include "sleeplock.h"
 #include "file.h"
+#include "time.h"
+#include "resource.h"
 #include "fcntl.h"
+#include "mman.h"
+#include "termios.h"
+#include "poll.h"
+#include "errno.h"
 
 // Fetch the nth word-sized system call argument as a file descriptor
 // and return both the descriptor and the corresponding struct file.
@@ -35,23 +42,33 @@ argfd(int n, int *pfd, struct file **pf)
   return 0;
 }
 
-// Allocate a file descriptor for the given file.
+// Allocate a file descriptor for the given file, the lowest
+// free one from min up.
 // Takes over file reference from caller on success.
 static int
-fdalloc(struct file *f)
+fdallocfrom(struct file *f, int min)
 {
   int fd;
   struct proc *curproc = myproc();
 
-  for(fd = 0; fd < NOFILE; fd++){
+  if(min < 0)
+    return -1;
+  for(fd = min; fd < NOFILE && fd < curproc->rlimcur[RLIMIT_NOFILE]; fd++){
     if(curproc->ofile[fd] == 0){
       curproc->ofile[fd] = f;
+      curproc->fdflags[fd] = 0;
       return fd;
     }
   }
   return -1;
 }
 
+static int
+fdalloc(struct file *f)
+{
+  return fdallocfrom(f, 0);
+}
+
 int
 sys_dup(void)
 {
@@ -59,22 +76,103 @@ sys_dup(void)
   int fd;
 
   if(argfd(0, 0, &f) < 0)
-    return -1;
+    return -EBADF;
   if((fd=fdalloc(f)) < 0)
-    return -1;
+    return -EMFILE;
   filedup(f);
   return fd;
 }
 
+// Copy count bytes from file infd to outfd in the kernel.  If
+// offset isn't 0, read from *offset and advance it instead of
+// infd's offset.  infd can't be a pipe.
+int
+sys_sendfile(void)
+{
+  struct file *out, *in;
+  int off, n, r;
+  uint offp, inoff;
+
+  if(argfd(0, 0, &out) < 0 || argfd(1, 0, &in) < 0)
+    return -EBADF;
+  if(argint(2, (int*)&offp) < 0 || argint(3, &n) < 0)
+    return -EFAULT;
+  if(offp == 0)
+    return filesend(out, in, &in->off, n);
+  if(argcopyin(2, &off, sizeof(off)) < 0)
+    return -EFAULT;
+  if(off < 0)
+    return -EINVAL;
+  inoff = off;
+  r = filesend(out, in, &inoff, n);
+  off = inoff;
+  if(argcopyout(2, &off, sizeof(off)) < 0)
+    return -EFAULT;
+  return r;
+}
+
+// Make fd a copy of f, with descriptor flags fdflags, closing
+// whatever fd had open.
+static int
+dupto(struct file *f, int fd, int fdflags)
+{
+  struct proc *curproc = myproc();
+  struct file *old;
+
+  if(fd < 0 || fd >= NOFILE || fd >= curproc->rlimcur[RLIMIT_NOFILE])
+    return -EBADF;
+  old = curproc->ofile[fd];
+  curproc->ofile[fd] = filedup(f);
+  curproc->fdflags[fd] = fdflags;
+  if(old)
+    fileclose(old);
+  return fd;
+}
+
+// Duplicate oldfd as newfd, closing newfd first if it's open.
+int
+sys_dup2(void)
+{
+  struct file *f;
+  int oldfd, newfd;
+
+  if(argfd(0, &oldfd, &f) < 0)
+    return -EBADF;
+  if(argint(1, &newfd) < 0)
+    return -EFAULT;
+  if(newfd == oldfd)
+    return newfd;
+  return dupto(f, newfd, 0);
+}
+
+// Like dup2, but oldfd and newfd must differ, and flags may
+// have O_CLOEXEC, to close newfd on exec.
+int
+sys_dup3(void)
+{
+  struct file *f;
+  int oldfd, newfd, flags;
+
+  if(argfd(0, &oldfd, &f) < 0)
+    return -EBADF;
+  if(argint(1, &newfd) < 0 || argint(2, &flags) < 0)
+    return -EFAULT;
+  if(newfd == oldfd || (flags & ~O_CLOEXEC))
+    return -EINVAL;
+  return dupto(f, newfd, (flags & O_CLOEXEC) ? FD_CLOEXEC : 0);
+}
+
 int
 sys_read(void)
 {
   struct file *f;
   int n;
-  char *p;
+  uint p;
 
-  if(argfd(0, 0, &f) < 0 || argint(2, &n) < 0 || argptr(1, &p, n) < 0)
-    return -1;
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(2, &n) < 0 || argint(1, (int*)&p) < 0)
+    return -EFAULT;
   return fileread(f, p, n);
 }
 
@@ -83,13 +181,51 @@ sys_write(void)
 {
   struct file *f;
   int n;
-  char *p;
+  uint p;
 
-  if(argfd(0, 0, &f) < 0 || argint(2, &n) < 0 || argptr(1, &p, n) < 0)
-    return -1;
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(2, &n) < 0 || argint(1, (int*)&p) < 0)
+    return -EFAULT;
   return filewrite(f, p, n);
 }
 
+// Read from fd at an offset, as read, but leaving the file's
+// offset alone.
+int
+sys_pread(void)
+{
+  struct file *f;
+  int n, off;
+  uint p;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(1, (int*)&p) < 0 || argint(2, &n) < 0 || argint(3, &off) < 0)
+    return -EFAULT;
+  if(off < 0)
+    return -EINVAL;
+  return filepread(f, p, n, off);
+}
+
+// Write to fd at an offset, as write, but leaving the file's
+// offset alone.
+int
+sys_pwrite(void)
+{
+  struct file *f;
+  int n, off;
+  uint p;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(1, (int*)&p) < 0 || argint(2, &n) < 0 || argint(3, &off) < 0)
+    return -EFAULT;
+  if(off < 0)
+    return -EINVAL;
+  return filepwrite(f, p, n, off);
+}
+
 int
 sys_close(void)
 {
@@ -97,54 +233,257 @@ sys_close(void)
   struct file *f;
 
   if(argfd(0, &fd, &f) < 0)
-    return -1;
+    return -EBADF;
   myproc()->ofile[fd] = 0;
   fileclose(f);
   return 0;
 }
 
+int
+sys_getdents(void)
+{
+  struct file *f;
+  int n;
+  uint p;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(2, &n) < 0 || argint(1, (int*)&p) < 0)
+    return -EFAULT;
+  return filegetdents(f, p, n);
+}
+
 int
 sys_fstat(void)
 {
   struct file *f;
-  struct stat *st;
+  struct stat st;
+  uint addr;
+  struct proc *curproc = myproc();
 
-  if(argfd(0, 0, &f) < 0 || argptr(1, (void*)&st, sizeof(*st)) < 0)
-    return -1;
-  return filestat(f, st);
+  int r;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(1, (int*)&addr) < 0)
+    return -EFAULT;
+  if((r = filestat(f, &st)) < 0)
+    return r;
+  if(copyout(curproc->pgdir, curproc->sz, addr, &st, sizeof(st)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+// Control the device open on fd.  The requests so far, for
+// terminals, all take a struct termios, which is copied in and
+// back out again.
+int
+sys_ioctl(void)
+{
+  struct file *f;
+  struct inode *ip;
+  struct termios t;
+  int req, major;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(1, &req) < 0 || argcopyin(2, &t, sizeof(t)) < 0)
+    return -EFAULT;
+  if(f->type != FD_INODE)
+    return -ENOTTY;
+  ip = f->ip;
+  ilock(ip);
+  major = ip->type == T_DEV ? ip->major : -1;
+  iunlock(ip);
+  if(major < 0 || major >= NDEV || !devsw[major].ioctl)
+    return -ENOTTY;
+  if(devsw[major].ioctl(ip, req, (char*)&t) < 0)
+    return -EINVAL;
+  if(argcopyout(2, &t, sizeof(t)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+// Control file descriptor fd: duplicate it, get or set its
+// descriptor flags or the status flags of its open file, or
+// get or set the buffer size of a pipe.
+int
+sys_fcntl(void)
+{
+  struct proc *curproc = myproc();
+  struct file *f;
+  int fd, cmd, arg, r;
+
+  if(argfd(0, &fd, &f) < 0)
+    return -EBADF;
+  if(argint(1, &cmd) < 0 || argint(2, &arg) < 0)
+    return -EFAULT;
+  switch(cmd){
+  case F_DUPFD:
+  case F_DUPFD_CLOEXEC:
+    if(arg < 0 || arg >= NOFILE)
+      return -EINVAL;
+    if((r = fdallocfrom(f, arg)) < 0)
+      return -EMFILE;
+    filedup(f);
+    if(cmd == F_DUPFD_CLOEXEC)
+      curproc->fdflags[r] = FD_CLOEXEC;
+    return r;
+  case F_GETFD:
+    return curproc->fdflags[fd];
+  case F_SETFD:
+    curproc->fdflags[fd] = arg & FD_CLOEXEC;
+    return 0;
+  case F_GETFL:
+    r = f->readable ? (f->writable ? O_RDWR : O_RDONLY) : O_WRONLY;
+    return r | f->flags;
+  case F_SETFL:
+    f->flags = arg & O_STATUS;
+    return 0;
+  case F_GETPIPE_SZ:
+    arg = 0;
+    // fall through
+  case F_SETPIPE_SZ:
+    if(f->type != FD_PIPE || (cmd == F_SETPIPE_SZ && arg <= 0))
+      return -EINVAL;
+    return pipesize(f->pipe, arg);
+  }
+  return -EINVAL;
+}
+
+// Wait for any of an array of fds to be ready; see poll in
+// poll.c.  The array is copied in and its revents back out.
+int
+sys_poll(void)
+{
+  struct pollfd *fds;
+  int n, r, timeout;
+
+  if(argint(1, &n) < 0 || argint(2, &timeout) < 0)
+    return -EFAULT;
+  if(n < 0 || n > PGSIZE / sizeof(*fds))
+    return -EINVAL;
+  if((fds = (struct pollfd*)kalloc()) == 0)
+    return -ENOMEM;
+  if(argcopyin(0, fds, n * sizeof(*fds)) < 0)
+    r = -EFAULT;
+  else if((r = poll(fds, n, timeout)) >= 0 &&
+          argcopyout(0, fds, n * sizeof(*fds)) < 0)
+    r = -EFAULT;
+  kfree((char*)fds);
+  return r;
+}
+
+// Make a process group the foreground group of the
+// terminal open on fd, which must be the console.
+int
+sys_tcsetpgrp(void)
+{
+  struct file *f;
+  int pgid, isconsole, minor;
+
+  if(argfd(0, 0, &f) < 0)
+    return -EBADF;
+  if(argint(1, &pgid) < 0)
+    return -EFAULT;
+  if(f->type != FD_INODE)
+    return -ENOTTY;
+  ilock(f->ip);
+  isconsole = f->ip->type == T_DEV && f->ip->major == CONSOLE;
+  minor = f->ip->minor;
+  iunlock(f->ip);
+  if(!isconsole)
+    return -ENOTTY;
+  return consolesetpgrp(minor, pgid);
+}
+
+// Map a file, or zeroed memory with MAP_ANONYMOUS, into the
+// address space; see mmap in vma.c.
+int
+sys_mmap(void)
+{
+  int addr, len, prot, flags, off, isdev;
+  struct file *f;
+  struct inode *ip;
+
+  if(argint(0, &addr) < 0 || argint(1, &len) < 0 || argint(2, &prot) < 0 ||
+     argint(3, &flags) < 0 || argint(5, &off) < 0)
+    return -EFAULT;
+  ip = 0;
+  if((flags & MAP_ANONYMOUS) == 0){
+    if(argfd(4, 0, &f) < 0)
+      return -EBADF;
+    if(f->type != FD_INODE)
+      return -ENODEV;
+    if(!f->readable)
+      return -EACCES;
+    if((flags & MAP_SHARED) && (prot & PROT_WRITE) && !f->writable)
+      return -EACCES;
+    ilock(f->ip);
+    isdev = f->ip->type == T_DEV;
+    iunlock(f->ip);
+    if(isdev)
+      return -ENODEV;
+    ip = f->ip;
+  }
+  return mmap(addr, len, prot, flags, ip, off);
+}
+
+int
+sys_munmap(void)
+{
+  int addr, len;
+
+  if(argint(0, &addr) < 0 || argint(1, &len) < 0)
+    return -EFAULT;
+  return munmap(addr, len);
+}
+
+int
+sys_msync(void)
+{
+  int addr, len, flags;
+
+  if(argint(0, &addr) < 0 || argint(1, &len) < 0 || argint(2, &flags) < 0)
+    return -EFAULT;
+  return msync(addr, len);
 }
 
 // Create the path new as a link to the same inode as old.
 int
 sys_link(void)
 {
-  char name[DIRSIZ], *new, *old;
+  char name[DIRSIZ], new[MAXPATH], old[MAXPATH];
   struct inode *dp, *ip;
 
-  if(argstr(0, &old) < 0 || argstr(1, &new) < 0)
-    return -1;
+  int err;
+
+  if(argstr(0, old, sizeof(old)) < 0 || argstr(1, new, sizeof(new)) < 0)
+    return -EFAULT;
 
   begin_op();
   if((ip = namei(old)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
 
   ilock(ip);
   if(ip->type == T_DIR){
     iunlockput(ip);
     end_op();
-    return -1;
+    return -EPERM;
   }
 
   ip->nlink++;
   iupdate(ip);
   iunlock(ip);
 
+  err = -ENOENT;
   if((dp = nameiparent(new, name)) == 0)
     goto bad;
   ilock(dp);
   if(dp->dev != ip->dev || dirlink(dp, name, ip->inum) < 0){
+    err = dp->dev != ip->dev ? -EXDEV : -EEXIST;
     iunlockput(dp);
     goto bad;
   }
@@ -161,7 +500,7 @@ bad:
   iupdate(ip);
   iunlockput(ip);
   end_op();
-  return -1;
+  return err;
 }
 
 // Is the directory dp empty except for "." and ".." ?
@@ -186,35 +525,40 @@ sys_unlink(void)
 {
   struct inode *ip, *dp;
   struct dirent de;
-  char name[DIRSIZ], *path;
+  char name[DIRSIZ], path[MAXPATH];
   uint off;
+  int err;
 
-  if(argstr(0, &path) < 0)
-    return -1;
+  if(argstr(0, path, sizeof(path)) < 0)
+    return -EFAULT;
 
   begin_op();
   if((dp = nameiparent(path, name)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
 
   ilock(dp);
 
   // Cannot unlink "." or "..".
+  err = -EINVAL;
   if(namecmp(name, ".") == 0 || namecmp(name, "..") == 0)
     goto bad;
 
+  err = -ENOENT;
   if((ip = dirlookup(dp, name, &off)) == 0)
     goto bad;
   ilock(ip);
 
   if(ip->nlink < 1)
     panic("unlink: nlink < 1");
+  err = -ENOTEMPTY;
   if(ip->type == T_DIR && !isdirempty(ip)){
     iunlockput(ip);
     goto bad;
   }
 
+  dcacheremove(dp, name);
   memset(&de, 0, sizeof(de));
   if(writei(dp, (char*)&de, off, sizeof(de)) != sizeof(de))
     panic("unlink: writei");
@@ -235,17 +579,22 @@ sys_unlink(void)
 bad:
   iunlockput(dp);
   end_op();
-  return -1;
+  return err;
 }
 
+// Create path as a new inode of the given type, and return it
+// locked.  An existing file will do for a new file.  On failure,
+// return 0 with an error number in *err.
 static struct inode*
-create(char *path, short type, short major, short minor)
+create(char *path, short type, short major, short minor, int *err)
 {
   struct inode *ip, *dp;
   char name[DIRSIZ];
 
-  if((dp = nameiparent(path, name)) == 0)
+  if((dp = nameiparent(path, name)) == 0){
+    *err = -ENOENT;
     return 0;
+  }
   ilock(dp);
 
   if((ip = dirlookup(dp, name, 0)) != 0){
@@ -253,6 +602,7 @@ create(char *path, short type, short major, short minor)
     ilock(ip);
     if(type == T_FILE && ip->type == T_FILE)
       return ip;
+    *err = type == T_FILE && ip->type == T_DIR ? -EISDIR : -EEXIST;
     iunlockput(ip);
     return 0;
   }
@@ -285,41 +635,45 @@ create(char *path, short type, short major, short minor)
 int
 sys_open(void)
 {
-  char *path;
-  int fd, omode;
+  char path[MAXPATH];
+  int fd, omode, err;
   struct file *f;
   struct inode *ip;
 
-  if(argstr(0, &path) < 0 || argint(1, &omode) < 0)
-    return -1;
+  if(argstr(0, path, sizeof(path)) < 0 || argint(1, &omode) < 0)
+    return -EFAULT;
+  if(!O_OK(omode))
+    return -EINVAL;
 
   begin_op();
 
   if(omode & O_CREATE){
-    ip = create(path, T_FILE, 0, 0);
+    ip = create(path, T_FILE, 0, 0, &err);
     if(ip == 0){
       end_op();
-      return -1;
+      return err;
     }
   } else {
     if((ip = namei(path)) == 0){
       end_op();
-      return -1;
+      return -ENOENT;
     }
     ilock(ip);
-    if(ip->type == T_DIR && omode != O_RDONLY){
+    if(ip->type == T_DIR && (omode & O_ACCMODE) != O_RDONLY){
       iunlockput(ip);
       end_op();
-      return -1;
+      return -EISDIR;
     }
   }
+  if((omode & O_TRUNC) && (omode & O_ACCMODE) != O_RDONLY && ip->type == T_FILE)
+    itrunc(ip);
 
   if((f = filealloc()) == 0 || (fd = fdalloc(f)) < 0){
     if(f)
       fileclose(f);
     iunlockput(ip);
     end_op();
-    return -1;
+    return f ? -EMFILE : -ENFILE;
   }
   iunlock(ip);
   end_op();
@@ -327,21 +681,27 @@ sys_open(void)
   f->type = FD_INODE;
   f->ip = ip;
   f->off = 0;
-  f->readable = !(omode & O_WRONLY);
-  f->writable = (omode & O_WRONLY) || (omode & O_RDWR);
+  f->readable = (omode & O_ACCMODE) != O_WRONLY;
+  f->writable = (omode & O_ACCMODE) != O_RDONLY;
+  f->flags = omode & O_STATUS;
+  if(omode & O_CLOEXEC)
+    myproc()->fdflags[fd] = FD_CLOEXEC;
   return fd;
 }
 
 int
 sys_mkdir(void)
 {
-  char *path;
+  char path[MAXPATH];
   struct inode *ip;
+  int err;
 
+  if(argstr(0, path, sizeof(path)) < 0)
+    return -EFAULT;
   begin_op();
-  if(argstr(0, &path) < 0 || (ip = create(path, T_DIR, 0, 0)) == 0){
+  if((ip = create(path, T_DIR, 0, 0, &err)) == 0){
     end_op();
-    return -1;
+    return err;
   }
   iunlockput(ip);
   end_op();
@@ -352,16 +712,17 @@ int
 sys_mknod(void)
 {
   struct inode *ip;
-  char *path;
-  int major, minor;
+  char path[MAXPATH];
+  int major, minor, err;
 
-  begin_op();
-  if((argstr(0, &path)) < 0 ||
+  if((argstr(0, path, sizeof(path))) < 0 ||
      argint(1, &major) < 0 ||
-     argint(2, &minor) < 0 ||
-     (ip = create(path, T_DEV, major, minor)) == 0){
+     argint(2, &minor) < 0)
+    return -EFAULT;
+  begin_op();
+  if((ip = create(path, T_DEV, major, minor, &err)) == 0){
     end_op();
-    return -1;
+    return err;
   }
   iunlockput(ip);
   end_op();
@@ -371,20 +732,22 @@ sys_mknod(void)
 int
 sys_chdir(void)
 {
-  char *path;
+  char path[MAXPATH];
   struct inode *ip;
   struct proc *curproc = myproc();
   
+  if(argstr(0, path, sizeof(path)) < 0)
+    return -EFAULT;
   begin_op();
-  if(argstr(0, &path) < 0 || (ip = namei(path)) == 0){
+  if((ip = namei(path)) == 0){
     end_op();
-    return -1;
+    return -ENOENT;
   }
   ilock(ip);
   if(ip->type != T_DIR){
     iunlockput(ip);
     end_op();
-    return -1;
+    return -ENOTDIR;
   }
   iunlock(ip);
   iput(curproc->cwd);
@@ -393,52 +756,105 @@ sys_chdir(void)
   return 0;
 }
 
-int
-sys_exec(void)
+// Copy in the strings of the null-terminated array at user
+// address uv, a page each, since exec discards the memory
+// they are in, into v, which has room for n pointers
+// including the null.  Free them with freeargs, even if
+// this fails.
+static int
+fetchargs(uint uv, char **v, int n)
 {
-  char *path, *argv[MAXARG];
   int i;
-  uint uargv, uarg;
+  uint uarg;
 
-  if(argstr(0, &path) < 0 || argint(1, (int*)&uargv) < 0){
-    return -1;
-  }
-  memset(argv, 0, sizeof(argv));
+  memset(v, 0, n * sizeof(v[0]));
   for(i=0;; i++){
-    if(i >= NELEM(argv))
-      return -1;
-    if(fetchint(uargv+4*i, (int*)&uarg) < 0)
-      return -1;
+    if(i >= n)
+      return -E2BIG;
+    if(fetchint(uv+4*i, (int*)&uarg) < 0)
+      return -EFAULT;
     if(uarg == 0){
-      argv[i] = 0;
-      break;
+      v[i] = 0;
+      return 0;
     }
-    if(fetchstr(uarg, &argv[i]) < 0)
-      return -1;
+    if((v[i] = kalloc()) == 0)
+      return -ENOMEM;
+    if(fetchstr(uarg, v[i], PGSIZE) < 0)
+      return -EFAULT;
   }
-  return exec(path, argv);
+}
+
+static void
+freeargs(char **v, int n)
+{
+  int i;
+
+  for(i = 0; i < n && v[i]; i++)
+    kfree(v[i]);
+}
+
+int
+sys_exec(void)
+{
+  char path[MAXPATH], *argv[MAXARG];
+  int r;
+  uint uargv;
+
+  if(argstr(0, path, sizeof(path)) < 0 || argint(1, (int*)&uargv) < 0){
+    return -EFAULT;
+  }
+  if((r = fetchargs(uargv, argv, NELEM(argv))) == 0)
+    r = exec(path, argv, 0);
+  freeargs(argv, NELEM(argv));
+  return r;
+}
+
+// exec with an environment, envp, which may be 0 for none.
+int
+sys_execve(void)
+{
+  char path[MAXPATH], *argv[MAXARG], *envp[MAXENV+1];
+  int r;
+  uint uargv, uenvp;
+
+  if(argstr(0, path, sizeof(path)) < 0 || argint(1, (int*)&uargv) < 0 ||
+     argint(2, (int*)&uenvp) < 0)
+    return -EFAULT;
+  envp[0] = 0;
+  if((r = fetchargs(uargv, argv, NELEM(argv))) == 0 &&
+     (uenvp == 0 || (r = fetchargs(uenvp, envp, NELEM(envp))) == 0))
+    r = exec(path, argv, envp);
+  freeargs(argv, NELEM(argv));
+  freeargs(envp, NELEM(envp));
+  return r;
 }
 
 int
 sys_pipe(void)
 {
-  int *fd;
+  uint addr;
+  int fd[2];
   struct file *rf, *wf;
-  int fd0, fd1;
+  struct proc *curproc = myproc();
 
-  if(argptr(0, (void*)&fd, 2*sizeof(fd[0])) < 0)
-    return -1;
+  if(argint(0, (int*)&addr) < 0)
+    return -EFAULT;
   if(pipealloc(&rf, &wf) < 0)
-    return -1;
-  fd0 = -1;
-  if((fd0 = fdalloc(rf)) < 0 || (fd1 = fdalloc(wf)) < 0){
-    if(fd0 >= 0)
-      myproc()->ofile[fd0] = 0;
+    return -ENFILE;
+  fd[0] = -1;
+  if((fd[0] = fdalloc(rf)) < 0 || (fd[1] = fdalloc(wf)) < 0){
+    if(fd[0] >= 0)
+      curproc->ofile[fd[0]] = 0;
     fileclose(rf);
     fileclose(wf);
-    return -1;
+    return -EMFILE;
+  }
+  if(copyout(curproc->pgdir, curproc->sz, addr, fd, sizeof(fd)) < 0){
+    curproc->ofile[fd[0]] = 0;
+    curproc->ofile[fd[1]] = 0;
+    fileclose(rf);
+    fileclose(wf);
+    return -EFAULT;
   }
-  fd[0] = fd0;
-  fd[1] = fd1;
   return 0;
 }
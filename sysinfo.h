@@ -0,0 +1,17 @@
+// Memory statistics returned by the sysinfo system call.
+// Sizes are in pages unless noted.
+
+struct sysinfo {
+  uint totalpages;    // Physical pages kalloc manages
+  uint freepages;     // Of those, free
+  uint dmapages;      // Of those, below 16MB, for ISA DMA
+  uint dmafree;       // Of those, free
+  uint slabpages;     // In slabs of kernel object caches
+  uint slabobjs;      // Objects allocated from those caches
+  uint pcpages;       // Holding file pages for shared mappings
+  uint nbuf;          // Disk block buffers (BSIZE bytes each)
+  uint bufinuse;      // Of those, held by some caller
+  uint bufvalid;      // Of those, holding a disk block
+  uint swappages;     // Swap space
+  uint swapfree;      // Of that, free
+};
@@ -6,6 +6,19 @@
 #include "memlayout.h"
 #include "mmu.h"
 #include "proc.h"
+#include "signal.h"
+#include "time.h"
+#include "pinfo.h"
+#include "sysinfo.h"
+#include "resource.h"
+#include "ptrace.h"
+#include "prctl.h"
+#include "perf.h"
+#include "cpuinfo.h"
+#include "percpu.h"
+#include "syscall.h"
+#include "scstat.h"
+#include "errno.h"
 
 int
 sys_fork(void)
@@ -26,14 +39,109 @@ sys_wait(void)
   return wait();
 }
 
+int
+sys_clone(void)
+{
+  int fn, arg, stack;
+
+  if(argint(0, &fn) < 0 || argint(1, &arg) < 0 || argint(2, &stack) < 0)
+    return -EFAULT;
+  return clone((void(*)(void*))fn, (void*)arg, (void*)stack);
+}
+
+int
+sys_join(void)
+{
+  int pid;
+  void *stack;
+
+  if((pid = join(&stack)) < 0)
+    return pid;
+  if(argcopyout(0, &stack, sizeof(stack)) < 0)
+    return -EFAULT;
+  return pid;
+}
+
+int
+sys_futexwait(void)
+{
+  int *addr, val;
+
+  if(argint(0, (int*)&addr) < 0 || argint(1, &val) < 0)
+    return -EFAULT;
+  return futexwait(addr, val);
+}
+
+int
+sys_futexwake(void)
+{
+  int *addr, n;
+
+  if(argint(0, (int*)&addr) < 0 || argint(1, &n) < 0)
+    return -EFAULT;
+  return futexwake(addr, n);
+}
+
 int
 sys_kill(void)
+{
+  int pid, sig;
+
+  if(argint(0, &pid) < 0 || argint(1, &sig) < 0)
+    return -EFAULT;
+  if(!sigperm(pid))
+    return -EPERM;
+  return kill(pid, sig);
+}
+
+int
+sys_sigaction(void)
+{
+  int sig, uact, uoldact;
+  struct sigaction act, oldact;
+  int r;
+
+  if(argint(0, &sig) < 0 || argint(1, &uact) < 0 || argint(2, &uoldact) < 0)
+    return -EFAULT;
+  if(uact && argcopyin(1, &act, sizeof(act)) < 0)
+    return -EFAULT;
+  if((r = sigaction(sig, uact ? &act : 0, uoldact ? &oldact : 0)) < 0)
+    return r;
+  if(uoldact && argcopyout(2, &oldact, sizeof(oldact)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_setpgid(void)
+{
+  int pid, pgid;
+
+  if(argint(0, &pid) < 0 || argint(1, &pgid) < 0)
+    return -EFAULT;
+  return setpgid(pid, pgid);
+}
+
+int
+sys_getpgid(void)
 {
   int pid;
 
   if(argint(0, &pid) < 0)
-    return -1;
-  return kill(pid);
+    return -EFAULT;
+  return getpgid(pid);
+}
+
+int
+sys_setsid(void)
+{
+  return setsid();
+}
+
+int
+sys_sigreturn(void)
+{
+  return sigreturn();
 }
 
 int
@@ -42,6 +150,524 @@ sys_getpid(void)
   return myproc()->pid;
 }
 
+int
+sys_clock_gettime(void)
+{
+  int clk;
+  struct timespec ts;
+  int r;
+
+  if(argint(0, &clk) < 0)
+    return -EFAULT;
+  if((r = clockgettime(clk, &ts)) < 0)
+    return r;
+  if(argcopyout(1, &ts, sizeof(ts)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_nanosleep(void)
+{
+  int urem, r;
+  struct timespec req, rem;
+
+  if(argcopyin(0, &req, sizeof(req)) < 0 || argint(1, &urem) < 0)
+    return -EFAULT;
+  rem.tv_sec = rem.tv_nsec = 0;
+  if((r = nanosleep(&req, &rem)) == 0)
+    return 0;
+  if(urem)
+    argcopyout(1, &rem, sizeof(rem));
+  return r;
+}
+
+int
+sys_pinfo(void)
+{
+  int n;
+  struct pinfo *pi;
+
+  // The table is too big for the kernel stack.
+  if(argint(1, &n) < 0)
+    return -EFAULT;
+  if(n < 0)
+    return -EINVAL;
+  if((pi = (struct pinfo*)kalloc()) == 0)
+    return -ENOMEM;
+  if(n > NPROC)
+    n = NPROC;
+  memset(pi, 0, n*sizeof(*pi));
+  n = pinfo(pi, n);
+  if(argcopyout(0, pi, n*sizeof(*pi)) < 0)
+    n = -EFAULT;
+  kfree((char*)pi);
+  return n;
+}
+
+int
+sys_sysinfo(void)
+{
+  struct sysinfo si;
+
+  kallocinfo(&si);
+  kminfo(&si);
+  pcinfo(&si);
+  binfo(&si);
+  swapinfo(&si);
+  if(argcopyout(0, &si, sizeof(si)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+static void
+copyscstat(void *s)
+{
+  memmove(s, thiscpu(scstat), sizeof(scstat));
+}
+
+// Sum every CPU's system call counts (see syscall).
+int
+sys_scstat(void)
+{
+  struct scstat *sum, *s;
+  int c, i, b, r;
+
+  if((sum = (struct scstat*)kalloc()) == 0)
+    return -ENOMEM;
+  if((s = (struct scstat*)kalloc()) == 0){
+    kfree((char*)sum);
+    return -ENOMEM;
+  }
+  memset(sum, 0, sizeof(*sum));
+  for(c = 0; c < ncpu; c++){
+    // Take the counts on that CPU, so that they're consistent,
+    // if it has started.
+    memmove(s, percpuon(c, scstat), sizeof(*s));
+    callcpu(c, copyscstat, s);
+    for(i = 0; i < NSYSCALL; i++){
+      sum->count[i] += s->count[i];
+      sum->cycles[i] += s->cycles[i];
+      for(b = 0; b < NSCHIST; b++)
+        sum->hist[i][b] += s->hist[i][b];
+    }
+  }
+  r = argcopyout(0, sum, sizeof(*sum)) < 0 ? -EFAULT : 0;
+  kfree((char*)s);
+  kfree((char*)sum);
+  return r;
+}
+
+static void
+copynintr(void *n)
+{
+  memmove(n, thiscpu(nintr), sizeof(nintr));
+}
+
+// Copy CPU number cpu's count of interrupts taken on each of
+// the 256 vectors.  Fails for a CPU that doesn't exist, so
+// callers can ask for CPU 0, 1, ... until a call fails.
+int
+sys_intrinfo(void)
+{
+  int cpu, r;
+  uint *n;
+
+  if(argint(0, &cpu) < 0)
+    return -EFAULT;
+  if(cpu < 0 || cpu >= ncpu)
+    return -EINVAL;
+  if((n = (uint*)kalloc()) == 0)
+    return -ENOMEM;
+  // Take the counts on that CPU, so that they're consistent,
+  // if it has started.
+  memmove(n, percpuon(cpu, nintr), sizeof(nintr));
+  callcpu(cpu, copynintr, n);
+  r = argcopyout(1, n, sizeof(nintr)) < 0 ? -EFAULT : 0;
+  kfree((char*)n);
+  return r;
+}
+
+int
+sys_perfread(void)
+{
+  struct perfinfo pi;
+
+  if(perfread(&pi) < 0)
+    return -EINVAL;
+  if(argcopyout(0, &pi, sizeof(pi)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+// Copy the most recent kernel messages, up to n bytes of
+// whole lines, into buf; see klog.c.
+int
+sys_dmesg(void)
+{
+  char *buf;
+  int n;
+
+  if(argint(1, &n) < 0)
+    return -EFAULT;
+  if(n < 0)
+    return -EINVAL;
+  if((buf = kallocpages(KLOGORDER)) == 0)
+    return -ENOMEM;
+  n = klogread(buf, n);
+  if(argcopyout(0, buf, n) < 0)
+    n = -EFAULT;
+  kfreepages(buf, KLOGORDER);
+  return n;
+}
+
+// Read the real-time clock.
+int
+sys_date(void)
+{
+  struct rtcdate r;
+
+  cmostime(&r);
+  if(argcopyout(0, &r, sizeof(r)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+// Sleep until the real-time clock's alarm goes off, n seconds
+// from now.  Only the superuser may set it.
+int
+sys_rtcalarm(void)
+{
+  int n;
+
+  if(argint(0, &n) < 0)
+    return -EFAULT;
+  if(myproc()->euid != 0)
+    return -EPERM;
+  if(rtcalarm(n) < 0)
+    return -EINVAL;
+  return 0;
+}
+
+// Take CPU number cpu offline, or bring it back online if
+// online is set.  Only the superuser may.
+int
+sys_cpuonline(void)
+{
+  int cpu, online;
+
+  if(argint(0, &cpu) < 0 || argint(1, &online) < 0)
+    return -EFAULT;
+  if(myproc()->euid != 0)
+    return -EPERM;
+  return online ? cpuonline(cpu) : cpuoffline(cpu);
+}
+
+// Report what CPUID says about CPU number cpu.
+int
+sys_cpuinfo(void)
+{
+  struct cpuinfo ci;
+  int cpu;
+
+  if(argint(0, &cpu) < 0)
+    return -EFAULT;
+  if(cpuinfo(cpu, &ci) < 0)
+    return -EINVAL;
+  if(argcopyout(1, &ci, sizeof(ci)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+// Route device interrupt irq to one of the CPUs in a mask.
+// Only the superuser may.
+int
+sys_irqaffinity(void)
+{
+  int irq, mask;
+
+  if(argint(0, &irq) < 0 || argint(1, &mask) < 0)
+    return -EFAULT;
+  if(myproc()->euid != 0)
+    return -EPERM;
+  if(irqaffinity(irq, mask) < 0)
+    return -EINVAL;
+  return 0;
+}
+
+// Set or get the calling process's system call filter.  The
+// filter only narrows: a process can't allow itself a call it
+// was refused.  Setting it covers the caller's threads too, and
+// its children and later threads inherit it.
+int
+sys_prctl(void)
+{
+  int option, i;
+  uint allow[NSYSFILTER];
+  struct proc *curproc = myproc();
+
+  if(argint(0, &option) < 0)
+    return -EFAULT;
+  switch(option){
+  case PR_SET_SYSFILTER:
+    if(argcopyin(1, allow, sizeof(allow)) < 0)
+      return -EFAULT;
+    allow[SYS_exit/32] |= 1 << SYS_exit%32;
+    allow[SYS_sigreturn/32] |= 1 << SYS_sigreturn%32;
+    sysfilter(allow);
+    return 0;
+  case PR_GET_SYSFILTER:
+    for(i = 0; i < NSYSFILTER; i++)
+      allow[i] = ~curproc->sysdeny[i];
+    if(argcopyout(1, allow, sizeof(allow)) < 0)
+      return -EFAULT;
+    return 0;
+  }
+  return -EINVAL;
+}
+
+// Set or get the base of the %fs segment, which thread-local
+// storage is addressed through.  Setting it also loads %fs
+// with the segment on the way back to user space.
+int
+sys_arch_prctl(void)
+{
+  int code, addr;
+  struct proc *curproc = myproc();
+
+  if(argint(0, &code) < 0)
+    return -EFAULT;
+  switch(code){
+  case ARCH_SET_FS:
+    if(argint(1, &addr) < 0)
+      return -EFAULT;
+    curproc->tlsbase = addr;
+    curproc->tf->fs = (SEG_UTLS << 3) | DPL_USER;
+    switchuvm(curproc);
+    return 0;
+  case ARCH_GET_FS:
+    if(argcopyout(1, &curproc->tlsbase, sizeof(curproc->tlsbase)) < 0)
+      return -EFAULT;
+    return 0;
+  }
+  return -EINVAL;
+}
+
+int
+sys_getrlimit(void)
+{
+  int resource;
+  struct rlimit rl;
+  int r;
+
+  if(argint(0, &resource) < 0)
+    return -EFAULT;
+  if((r = getrlimit(resource, &rl)) < 0)
+    return r;
+  if(argcopyout(1, &rl, sizeof(rl)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_setrlimit(void)
+{
+  int resource;
+  struct rlimit rl;
+
+  if(argint(0, &resource) < 0 || argcopyin(1, &rl, sizeof(rl)) < 0)
+    return -EFAULT;
+  return setrlimit(resource, &rl);
+}
+
+int
+sys_wait4(void)
+{
+  int pid, ustatus, options, uru;
+  int status;
+  struct rusage ru;
+
+  if(argint(0, &pid) < 0 || argint(1, &ustatus) < 0 ||
+     argint(2, &options) < 0 || argint(3, &uru) < 0)
+    return -EFAULT;
+  memset(&ru, 0, sizeof(ru));
+  if((pid = waitpid(pid, &status, options, &ru)) <= 0)
+    return pid;
+  if(ustatus && argcopyout(1, &status, sizeof(status)) < 0)
+    return -EFAULT;
+  if(uru && argcopyout(3, &ru, sizeof(ru)) < 0)
+    return -EFAULT;
+  return pid;
+}
+
+int
+sys_getrusage(void)
+{
+  int who;
+  struct rusage ru;
+  int r;
+
+  if(argint(0, &who) < 0)
+    return -EFAULT;
+  if((r = getrusage(who, &ru)) < 0)
+    return r;
+  if(argcopyout(1, &ru, sizeof(ru)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_ptrace(void)
+{
+  int req, pid, addr, data, err;
+  struct regs r;
+
+  if(argint(0, &req) < 0 || argint(1, &pid) < 0 ||
+     argint(2, &addr) < 0 || argint(3, &data) < 0)
+    return -EFAULT;
+  if(req == PT_SETREGS && argcopyin(3, &r, sizeof(r)) < 0)
+    return -EFAULT;
+  if((err = ptrace(req, pid, addr, data, &r)) < 0)
+    return err;
+  if(req == PT_PEEKDATA && argcopyout(3, &r, sizeof(int)) < 0)
+    return -EFAULT;
+  if(req == PT_GETREGS && argcopyout(3, &r, sizeof(r)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_trace(void)
+{
+  int pid, on;
+
+  if(argint(0, &pid) < 0 || argint(1, &on) < 0)
+    return -EFAULT;
+  return settrace(pid, on);
+}
+
+int
+sys_getitimer(void)
+{
+  int which;
+  struct itimerval it;
+  int r;
+
+  if(argint(0, &which) < 0)
+    return -EFAULT;
+  if((r = getitimer(which, &it)) < 0)
+    return r;
+  if(argcopyout(1, &it, sizeof(it)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_setitimer(void)
+{
+  int which, uold, r;
+  struct itimerval it, old;
+
+  if(argint(0, &which) < 0 || argcopyin(1, &it, sizeof(it)) < 0 ||
+     argint(2, &uold) < 0)
+    return -EFAULT;
+  if((r = setitimer(which, &it, &old)) < 0)
+    return r;
+  if(uold && argcopyout(2, &old, sizeof(old)) < 0)
+    return -EFAULT;
+  return 0;
+}
+
+int
+sys_shmget(void)
+{
+  int key, size, flags;
+
+  if(argint(0, &key) < 0 || argint(1, &size) < 0 || argint(2, &flags) < 0)
+    return -EFAULT;
+  return shmget(key, size, flags);
+}
+
+int
+sys_shmat(void)
+{
+  int id, addr, flags;
+
+  if(argint(0, &id) < 0 || argint(1, &addr) < 0 || argint(2, &flags) < 0)
+    return -EFAULT;
+  return shmat(id, addr, flags);
+}
+
+int
+sys_shmdt(void)
+{
+  int addr;
+
+  if(argint(0, &addr) < 0)
+    return -EFAULT;
+  return shmdt(addr);
+}
+
+int
+sys_shmctl(void)
+{
+  int id, cmd;
+
+  if(argint(0, &id) < 0 || argint(1, &cmd) < 0)
+    return -EFAULT;
+  return shmctl(id, cmd);
+}
+
+int
+sys_getuid(void)
+{
+  return myproc()->uid;
+}
+
+int
+sys_geteuid(void)
+{
+  return myproc()->euid;
+}
+
+int
+sys_getgid(void)
+{
+  return myproc()->gid;
+}
+
+int
+sys_getegid(void)
+{
+  return myproc()->egid;
+}
+
+int
+sys_setuid(void)
+{
+  int uid;
+
+  if(argint(0, &uid) < 0)
+    return -EFAULT;
+  return setuid(uid);
+}
+
+int
+sys_setgid(void)
+{
+  int gid;
+
+  if(argint(0, &gid) < 0)
+    return -EFAULT;
+  return setgid(gid);
+}
+
+int
+sys_getppid(void)
+{
+  return getppid();
+}
+
 int
 sys_sbrk(void)
 {
@@ -49,10 +675,10 @@ sys_sbrk(void)
   int n;
 
   if(argint(0, &n) < 0)
-    return -1;
+    return -EFAULT;
   addr = myproc()->sz;
   if(growproc(n) < 0)
-    return -1;
+    return -ENOMEM;
   return addr;
 }
 
@@ -60,32 +686,18 @@ int
 sys_sleep(void)
 {
   int n;
-  uint ticks0;
 
   if(argint(0, &n) < 0)
-    return -1;
-  acquire(&tickslock);
-  ticks0 = ticks;
-  while(ticks - ticks0 < n){
-    if(myproc()->killed){
-      release(&tickslock);
-      return -1;
-    }
-    sleep(&ticks, &tickslock);
-  }
-  release(&tickslock);
-  return 0;
+    return -EFAULT;
+  return ticksleep(n);
 }
 
-// return how many clock tick interrupts have occurred
+// return how many clock ticks' time has passed
 // since start.
 int
 sys_uptime(void)
 {
-  uint xticks;
+  uint rem;
 
-  acquire(&tickslock);
-  xticks = ticks;
-  release(&tickslock);
-  return xticks;
+  return divmod64(nsuptime(), TICKNS, &rem);
 }
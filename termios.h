@@ -0,0 +1,22 @@
+// Terminal settings, shared by the kernel and user programs.
+
+struct termios {
+  uint lflag;
+};
+
+// lflag bits.  With ICANON, reads return a line at a time and
+// the line can be edited with backspace and ^U, and ^D is end
+// of file; without it, reads return what has been typed, a
+// character at a time.
+#define ICANON    0x1  // Line at a time, with editing
+#define ECHO      0x2  // Echo what is typed
+#define ISIG      0x4  // ^C, ^Z, ^P and ^L are special
+#define ICRNL     0x8  // Read a typed return as newline
+
+// A terminal in cooked mode, as at boot; cbreak mode clears
+// ICANON, and raw mode clears them all.
+#define TCOOKED   (ICANON | ECHO | ISIG | ICRNL)
+
+// ioctl requests.
+#define TCGETS    1  // Get the terminal's struct termios
+#define TCSETS    2  // Set it
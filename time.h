@@ -0,0 +1,23 @@
+// Clocks, shared by the kernel and user programs.
+
+#define CLOCK_REALTIME   0  // Seconds since 1970-01-01 00:00 UTC
+#define CLOCK_MONOTONIC  1  // Time since boot
+
+struct timespec {
+  uint tv_sec;   // Seconds
+  uint tv_nsec;  // Nanoseconds, less than 1000000000
+};
+
+struct timeval {
+  uint tv_sec;   // Seconds
+  uint tv_usec;  // Microseconds, less than 1000000
+};
+
+#define ITIMER_REAL     0  // Counts real time; sends SIGALRM
+#define ITIMER_VIRTUAL  1  // Counts user time; sends SIGVTALRM
+#define ITIMER_PROF     2  // Counts user and kernel time; sends SIGPROF
+
+struct itimerval {
+  struct timeval it_interval;  // Reload value, or 0 for one shot
+  struct timeval it_value;     // Time left, or 0 if disarmed
+};
@@ -0,0 +1,219 @@
+// High-resolution timers.
+//
+// Each CPU keeps the timers added on it in a list sorted by
+// deadline, and sets its local APIC timer in one-shot or
+// TSC-deadline mode to interrupt at the first one (see
+// lapiconeshot).  The clock tick is one such timer per CPU,
+// re-added every TICKNS, except while the CPU idles (see
+// tickstop).  A CPU that goes offline hands its timers to
+// another (see timeradopt).  A timer's function runs from the timer
+// interrupt on the CPU that added the timer, with interrupts
+// off, so it must not sleep.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "timer.h"
+
+#define MAXNS  1000000000   // Longest the LAPIC timer is set for (ns)
+
+static struct {
+  struct spinlock lock;
+  struct timer *head[NCPU];   // Pending timers, soonest first
+  struct timer tick[NCPU];    // The clock tick
+} timers;
+
+// Put t on CPU id's list.
+// The timers lock must be held.
+static void
+insert(struct timer *t, int id)
+{
+  struct timer **pp;
+
+  for(pp = &timers.head[id]; *pp && (*pp)->when <= t->when; pp = &(*pp)->next)
+    ;
+  t->next = *pp;
+  *pp = t;
+  t->cpu = &cpus[id];
+}
+
+// Take t off its CPU's list, if it is on one, and return
+// whether it was.  The timers lock must be held.
+static int
+unlink(struct timer *t)
+{
+  struct timer **pp;
+
+  if(t->cpu == 0)
+    return 0;
+  for(pp = &timers.head[t->cpu - cpus]; *pp != t; pp = &(*pp)->next)
+    ;
+  *pp = t->next;
+  t->cpu = 0;
+  return 1;
+}
+
+// Set this CPU's LAPIC timer for its first deadline, or stop
+// it if there is none.  Far-off deadlines get an early
+// interrupt that just sets it again.
+// The timers lock must be held.
+static void
+arm(void)
+{
+  struct timer *t;
+  uint64 now;
+
+  if((t = timers.head[cpuid()]) == 0){
+    lapictimerstop();
+    return;
+  }
+  now = nsuptime();
+  if(t->when <= now)
+    lapiconeshot(0);
+  else if(t->when - now > MAXNS)
+    lapiconeshot(MAXNS);
+  else
+    lapiconeshot(t->when - now);
+}
+
+void
+timerinit(void)
+{
+  initlock(&timers.lock, "timers");
+  timercpuinit();
+}
+
+// Start this CPU's LAPIC timer and clock tick.
+// Run once on entry on each CPU, after clockcpuinit.
+void
+timercpuinit(void)
+{
+  lapictimerinit();
+  addtimer(&timers.tick[cpuid()], nsuptime() + TICKNS, 0, 0);
+}
+
+// Stop this CPU's clock tick while it idles, so that only
+// pending timers wake it.
+void
+tickstop(void)
+{
+  acquire(&timers.lock);
+  if(unlink(&timers.tick[cpuid()]))
+    arm();
+  release(&timers.lock);
+}
+
+// Restart this CPU's clock tick after tickstop.
+void
+tickstart(void)
+{
+  struct timer *t;
+  int id;
+
+  acquire(&timers.lock);
+  id = cpuid();
+  t = &timers.tick[id];
+  if(t->cpu == 0){
+    t->when = nsuptime() + TICKNS;
+    insert(t, id);
+    if(timers.head[id] == t)
+      arm();
+  }
+  release(&timers.lock);
+}
+
+// Move the timers of CPU number cpu, which has gone offline,
+// to this one.
+void
+timeradopt(int cpu)
+{
+  struct timer *t;
+  int id;
+
+  acquire(&timers.lock);
+  id = cpuid();
+  while((t = timers.head[cpu]) != 0){
+    timers.head[cpu] = t->next;
+    insert(t, id);
+  }
+  arm();
+  release(&timers.lock);
+}
+
+// Call fn(arg) from the timer interrupt on this CPU at time
+// when, in ns since boot.  If t is pending, move it.
+void
+addtimer(struct timer *t, uint64 when, void (*fn)(void*), void *arg)
+{
+  int id;
+
+  acquire(&timers.lock);
+  unlink(t);
+  t->when = when;
+  t->fn = fn;
+  t->arg = arg;
+  id = cpuid();
+  insert(t, id);
+  if(timers.head[id] == t)
+    arm();
+  release(&timers.lock);
+}
+
+// Cancel t.  Returns 1 if it was pending, or 0 if it has run
+// or is about to on some CPU, or was never added.
+int
+canceltimer(struct timer *t)
+{
+  int r;
+
+  acquire(&timers.lock);
+  r = unlink(t);
+  release(&timers.lock);
+  return r;
+}
+
+// Run this CPU's expired timers, and set the LAPIC timer for
+// the next.  Called from the timer interrupt.  Returns 1 if
+// the clock tick was due.
+int
+timerintr(void)
+{
+  struct timer *t, *tick;
+  void (*fn)(void*);
+  void *arg;
+  uint64 now;
+  int id, ticked;
+
+  ticked = 0;
+  acquire(&timers.lock);
+  id = cpuid();
+  tick = &timers.tick[id];
+  now = nsuptime();
+  while((t = timers.head[id]) != 0 && t->when <= now){
+    timers.head[id] = t->next;
+    t->cpu = 0;
+    if(t == tick){
+      // Keep ticks TICKNS apart, unless far behind.
+      ticked = 1;
+      vdsotick();
+      t->when += TICKNS;
+      if(t->when <= now)
+        t->when = now + TICKNS;
+      insert(t, id);
+      continue;
+    }
+    // t may be freed once the lock is released.
+    fn = t->fn;
+    arg = t->arg;
+    release(&timers.lock);
+    fn(arg);
+    acquire(&timers.lock);
+    now = nsuptime();
+  }
+  arm();
+  release(&timers.lock);
+  return ticked;
+}
@@ -0,0 +1,8 @@
+// High-resolution timer (see timer.c).
+struct timer {
+  uint64 when;          // Deadline, in ns since boot (see nsuptime)
+  void (*fn)(void*);    // Called from the timer interrupt
+  void *arg;
+  struct cpu *cpu;      // CPU whose list holds it, or 0 if not pending
+  struct timer *next;
+};
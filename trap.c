@@ -7,12 +7,30 @@
 #include "x86.h"
 #include "traps.h"
 #include "spinlock.h"
+#include "signal.h"
+#include "percpu.h"
+#include "ansi.h"
 
 // Interrupt descriptor table (shared by all CPUs).
 struct gatedesc idt[256];
 extern uint vectors[];  // in vectors.S: array of 256 entry pointers
-struct spinlock tickslock;
-uint ticks;
+
+// Interrupts and exceptions this CPU has taken, by vector.
+PERCPU(uint nintr[256]);
+
+// The global clock tick, which runs interval timers (see
+// realtimers), falls to whichever CPU's own tick comes first
+// after it is due.
+static struct {
+  struct spinlock lock;
+  uint64 next;          // When the next one is due (ns)
+} gtick;
+
+// Device interrupt handlers, by vector (see irqregister).
+static struct {
+  struct rwlock lock;
+  void (*handler[256])(void);
+} irqvec;
 
 void
 tvinit(void)
@@ -22,8 +40,56 @@ tvinit(void)
   for(i = 0; i < 256; i++)
     SETGATE(idt[i], 0, SEG_KCODE<<3, vectors[i], 0);
   SETGATE(idt[T_SYSCALL], 1, SEG_KCODE<<3, vectors[T_SYSCALL], DPL_USER);
+  SETGATE(idt[T_BRKPT], 1, SEG_KCODE<<3, vectors[T_BRKPT], DPL_USER);
+  // A double fault switches tasks; see dfinit.
+  SETGATE(idt[T_DBLFLT], 0, SEG_DFTSS<<3, 0, 0);
+  idt[T_DBLFLT].type = STS_TG;
+
+  initlock(&gtick.lock, "gtick");
+  initrwlock(&irqvec.lock, "irqvec");
+}
+
+// May a device have vector v?  Not one that trap handles itself.
+// The irqvec lock must be held for writing.
+static int
+vecfree(int v)
+{
+  switch(v){
+  case T_IRQ0 + IRQ_TIMER:
+  case T_IRQ0 + 7:
+  case T_IRQ0 + IRQ_IDE+1:
+  case T_IRQ0 + IRQ_ERROR:
+  case T_IRQ0 + IRQ_SPURIOUS:
+  case T_SYSCALL:
+  case T_CALL:
+  case T_PMI:
+    return 0;
+  }
+  return v >= T_IRQ0 && v < 256 && irqvec.handler[v] == 0;
+}
+
+// Have fn handle device interrupt irq, and enable it.  The
+// interrupt gets vector T_IRQ0+irq if that's free, and else
+// the first free one.  Returns the vector, or -1 if there are
+// none left.
+int
+irqregister(int irq, void (*fn)(void))
+{
+  int v;
 
-  initlock(&tickslock, "time");
+  acquirewrite(&irqvec.lock);
+  v = T_IRQ0 + irq;
+  if(!vecfree(v))
+    for(v = T_IRQ0; v < 256 && !vecfree(v); v++)
+      ;
+  if(v == 256){
+    releasewrite(&irqvec.lock);
+    return -1;
+  }
+  irqvec.handler[v] = fn;
+  releasewrite(&irqvec.lock);
+  irqenable(irq, v);
+  return v;
 }
 
 void
@@ -32,68 +98,252 @@ idtinit(void)
   lidt(idt, sizeof(idt));
 }
 
+// Stacks for double faults.
+static char dfstack[NCPU][KSTACKSIZE];
+
+// A double fault, usually a fault while pushing a trap frame on
+// a bad kernel stack, arrives here by a task switch, which saved
+// the faulting code's registers in the CPU's task state and gave
+// us a fresh stack.  Report them; there's no going back.
+static void
+dblfault(void)
+{
+  struct trapframe tf;
+  struct taskstate *ts;
+
+  ts = &mycpu()->ts;
+  memset(&tf, 0, sizeof(tf));
+  tf.trapno = T_DBLFLT;
+  tf.eip = (uint)ts->eip;
+  tf.eflags = ts->eflags;
+  tf.cs = ts->cs;
+  tf.eax = ts->eax;
+  tf.ebx = ts->ebx;
+  tf.ecx = ts->ecx;
+  tf.edx = ts->edx;
+  tf.esi = ts->esi;
+  tf.edi = ts->edi;
+  tf.ebp = (uint)ts->ebp;
+  panictf("double fault", &tf, ts->esp);
+}
+
+// Set up this CPU's double fault task, which runs dblfault in
+// the kernel's address space on a stack of its own.
+// Run once on entry on each CPU, after seginit.
+void
+dfinit(void)
+{
+  struct cpu *c;
+  struct taskstate *ts;
+
+  c = mycpu();
+  ts = &c->dfts;
+  memset(ts, 0, sizeof(*ts));
+  ts->cr3 = (void*)V2P(kpgdir);
+  ts->eip = (uint*)dblfault;
+  ts->esp = (uint*)(dfstack[c - cpus] + KSTACKSIZE);
+  ts->cs = SEG_KCODE << 3;
+  ts->ds = ts->es = ts->ss = SEG_KDATA << 3;
+  ts->gs = SEG_KCPU << 3;
+  ts->iomb = (ushort) 0xFFFF;
+  c->gdt[SEG_DFTSS] = SEG16(STS_T32A, ts, sizeof(*ts)-1, 0);
+  c->gdt[SEG_DFTSS].s = 0;
+}
+
+//PAGEBREAK: 13
+// Handle a trap that the kernel has no use for.
+static void
+badtrap(struct trapframe *tf)
+{
+  int sig;
+
+  if(myproc() == 0 || (tf->cs&3) == 0){
+    // In kernel, it must be our mistake.
+    cprintf(KERR "unexpected trap" KEND " %d from cpu %d eip %x (cr2=0x%x)\n",
+            tf->trapno, cpuid(), tf->eip, rcr2());
+    panictf("trap", tf, (uint*)&tf->esp);
+  }
+  // In user space, assume process misbehaved.
+  switch(tf->trapno){
+  case T_ILLOP:
+    sig = SIGILL;
+    break;
+  case T_DIVIDE:
+  case T_FPERR:
+  case T_SIMDERR:
+    sig = SIGFPE;
+    break;
+  default:
+    sig = SIGSEGV;
+  }
+  cprintf(KWARN "pid %d %s:" KEND " trap %d err %d on cpu %d "
+          "eip 0x%x addr 0x%x--signal %d\n",
+          myproc()->pid, myproc()->name, tf->trapno,
+          tf->err, cpuid(), tf->eip, rcr2(), sig);
+  trapsig(sig);
+}
+
+// Watch the next CPU for being wedged, for example spinning
+// with interrupts off: if it takes no timer interrupt during
+// WATCHDOG of ours, send it an NMI, which it can't mask, so
+// that it reports where it is stuck.  Called on each clock
+// tick.
+static void
+watchdog(void)
+{
+  struct cpu *c, *w;
+  uint n;
+
+  c = mycpu();
+  if(ncpu < 2)
+    return;
+  w = &cpus[(c - cpus + 1) % ncpu];
+  if(!w->started)
+    return;
+  if(w->idle){
+    // Halted, maybe with no tick; not stuck.
+    c->wdstale = 0;
+    return;
+  }
+  n = *percpuon(w - cpus, nintr[T_IRQ0 + IRQ_TIMER]);
+  if(n != c->wdseen){
+    c->wdseen = n;
+    c->wdstale = 0;
+  } else if(++c->wdstale == WATCHDOG){
+    cprintf(KERR "cpu%d: watchdog:" KEND " cpu%d is stuck\n", cpuid(), w - cpus);
+    w->wdnmi = 1;
+    lapicnmi(w->apicid);
+  }
+}
+
+// Is the global clock tick due?  If it fell far behind while
+// every CPU idled, don't try to catch up.
+static int
+globaltick(void)
+{
+  uint64 now;
+  int due;
+
+  acquire(&gtick.lock);
+  now = nsuptime();
+  due = gtick.next <= now;
+  if(due){
+    gtick.next += TICKNS;
+    if(gtick.next <= now)
+      gtick.next = now + TICKNS;
+  }
+  release(&gtick.lock);
+  return due;
+}
+
 //PAGEBREAK: 41
 void
 trap(struct trapframe *tf)
 {
+  void (*fn)(void);
+  int tick;
+
+  // Coming from user space, the time since the process last
+  // returned there was user time.  The time until it returns
+  // again is kernel time, charged on the way out.
+  if((tf->cs&3) == DPL_USER)
+    chargetime(myproc(), 1);
+
   if(tf->trapno == T_SYSCALL){
     if(myproc()->killed)
       exit();
     myproc()->tf = tf;
     syscall();
+    psig(tf);
     if(myproc()->killed)
       exit();
+    chargetime(myproc(), 0);
     return;
   }
 
+  percpuadd(nintr[tf->trapno], 1);
+
+  tick = 0;
   switch(tf->trapno){
   case T_IRQ0 + IRQ_TIMER:
-    if(cpuid() == 0){
-      acquire(&tickslock);
-      ticks++;
-      wakeup(&ticks);
-      release(&tickslock);
+    if(timerintr()){
+      tick = 1;
+      if(globaltick()){
+        realtimers();
+        rcutick();
+      }
+      if(myproc() && myproc()->state == RUNNING)
+        chargetick(myproc(), (tf->cs&3) == DPL_USER);
+      watchdog();
     }
     lapiceoi();
     break;
-  case T_IRQ0 + IRQ_IDE:
-    ideintr();
-    lapiceoi();
+  case T_NMI:
+    // Another CPU has panicked.
+    if(cpusstopped())
+      for(;;)
+        hlt();
+    if(mycpu()->wdnmi)
+      panictf("watchdog", tf, (uint*)&tf->esp);
+    cprintf(KWARN "cpu%d:" KEND " NMI at %x:%x\n", cpuid(), tf->cs, tf->eip);
     break;
   case T_IRQ0 + IRQ_IDE+1:
     // Bochs generates spurious IDE1 interrupts.
     break;
-  case T_IRQ0 + IRQ_KBD:
-    kbdintr();
+  case T_CALL:
+    callpoll();
     lapiceoi();
     break;
-  case T_IRQ0 + IRQ_COM1:
-    uartintr();
+  case T_PMI:
+    pmuintr(tf);
     lapiceoi();
     break;
   case T_IRQ0 + 7:
   case T_IRQ0 + IRQ_SPURIOUS:
-    cprintf("cpu%d: spurious interrupt at %x:%x\n",
+    cprintf(KWARN "cpu%d:" KEND " spurious interrupt at %x:%x\n",
             cpuid(), tf->cs, tf->eip);
     lapiceoi();
     break;
+  case T_PGFLT:
+    // A first touch of a lazily filled page, or a write to a
+    // copy-on-write one?  The kernel never uses a user pointer
+    // (see copyin), so a fault in the kernel is a bug.
+    if(myproc() && (tf->cs&3) == DPL_USER){
+      if((tf->err & FEC_P) == 0 &&
+         lazyalloc(myproc()->pgdir, myproc()->sz, rcr2()) == 0)
+        break;
+      if((tf->err & (FEC_P|FEC_W)) == (FEC_P|FEC_W) &&
+         cowfault(myproc()->pgdir, rcr2()) == 0)
+        break;
+    }
+    badtrap(tf);
+    break;
+  case T_DEBUG:
+  case T_BRKPT:
+    if(myproc() && (tf->cs&3) == DPL_USER){
+      // Single step or int3: stop for a tracer.
+      tf->eflags &= ~FL_TF;
+      kill(myproc()->pid, SIGTRAP);
+      break;
+    }
+    badtrap(tf);
+    break;
 
-  //PAGEBREAK: 13
   default:
-    if(myproc() == 0 || (tf->cs&3) == 0){
-      // In kernel, it must be our mistake.
-      cprintf("unexpected trap %d from cpu %d eip %x (cr2=0x%x)\n",
-              tf->trapno, cpuid(), tf->eip, rcr2());
-      panic("trap");
+    acquireread(&irqvec.lock);
+    fn = irqvec.handler[tf->trapno];
+    releaseread(&irqvec.lock);
+    if(fn){
+      fn();
+      lapiceoi();
+      break;
     }
-    // In user space, assume process misbehaved.
-    cprintf("pid %d %s: trap %d err %d on cpu %d "
-            "eip 0x%x addr 0x%x--kill proc\n",
-            myproc()->pid, myproc()->name, tf->trapno,
-            tf->err, cpuid(), tf->eip, rcr2());
-    myproc()->killed = 1;
+    badtrap(tf);
   }
 
+  // Finish the work interrupt handlers deferred.
+  dosoftirq();
+
   // Force process exit if it has been killed and is in user space.
   // (If it is still executing in the kernel, let it keep running
   // until it gets to the regular system call return.)
@@ -102,11 +352,20 @@ trap(struct trapframe *tf)
 
   // Force process to give up CPU on clock tick.
   // If interrupts were on while locks held, would need to check nlock.
+  // Not if the timer interrupted soft interrupt handlers,
+  // which must finish on this CPU first.
   if(myproc() && myproc()->state == RUNNING &&
-     tf->trapno == T_IRQ0+IRQ_TIMER)
+     tick && !insoftirq())
     yield();
 
+  // Run a signal handler, if any, on the way back to user space.
+  if(myproc() && (tf->cs&3) == DPL_USER)
+    psig(tf);
+
   // Check if the process has been killed since we yielded
   if(myproc() && myproc()->killed && (tf->cs&3) == DPL_USER)
     exit();
+
+  if((tf->cs&3) == DPL_USER)
+    chargetime(myproc(), 0);
 }
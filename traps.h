@@ -25,6 +25,8 @@
 // These are arbitrarily chosen, but with care not to overlap
 // processor defined exceptions or interrupt vectors.
 #define T_SYSCALL       64      // system call
+#define T_CALL          65      // Cross-CPU call IPI (see callmany)
+#define T_PMI           66      // performance counter overflow (see pmu.c)
 #define T_DEFAULT      500      // catchall
 
 #define T_IRQ0          32      // IRQ 0 corresponds to int T_IRQ
@@ -32,7 +34,14 @@
 #define IRQ_TIMER        0
 #define IRQ_KBD          1
 #define IRQ_COM1         4
+#define IRQ_RTC          8
+#define IRQ_MOUSE       12
 #define IRQ_IDE         14
 #define IRQ_ERROR       19
 #define IRQ_SPURIOUS    31
 
+// Soft interrupts (see softirq.c), run in this order.
+#define SOFTIRQ_DISK     0
+#define SOFTIRQ_CONS     1
+#define NSOFTIRQ         2
+
@@ -16,6 +16,8 @@
 
 static int uart;    // is there a uart?
 
+static void uartintr(void);
+
 void
 uartinit(void)
 {
@@ -24,10 +26,11 @@ uartinit(void)
   // Turn off the FIFO
   outb(COM1+2, 0);
 
-  // 9600 baud, 8 data bits, 1 stop bit, parity off.
+  // 9600 baud, or as console= says; 8 data bits, 1 stop bit,
+  // parity off.
   outb(COM1+3, 0x80);    // Unlock divisor
-  outb(COM1+0, 115200/9600);
-  outb(COM1+1, 0);
+  outb(COM1+0, 115200/consolebaud());
+  outb(COM1+1, (115200/consolebaud()) >> 8);
   outb(COM1+3, 0x03);    // Lock divisor, 8 data bits.
   outb(COM1+4, 0);
   outb(COM1+1, 0x01);    // Enable receive interrupts.
@@ -41,7 +44,7 @@ uartinit(void)
   // enable interrupts.
   inb(COM1+2);
   inb(COM1+0);
-  ioapicenable(IRQ_COM1, 0);
+  irqregister(IRQ_COM1, uartintr);
 
   // Announce that we're here.
   for(p="xv6...\n"; *p; p++)
@@ -70,7 +73,7 @@ uartgetc(void)
   return inb(COM1+0);
 }
 
-void
+static void
 uartintr(void)
 {
   consoleintr(uartgetc);
@@ -3,6 +3,17 @@
 #include "fcntl.h"
 #include "user.h"
 #include "x86.h"
+#include "signal.h"
+#include "time.h"
+#include "errno.h"
+#include "mmu.h"
+#include "memlayout.h"
+#include "vdso.h"
+#include "dirent.h"
+#include "param.h"
+#include "syscall.h"
+#include "features.h"
+#include "termios.h"
 
 char*
 strcpy(char *s, const char *t)
@@ -23,6 +34,16 @@ strcmp(const char *p, const char *q)
   return (uchar)*p - (uchar)*q;
 }
 
+int
+strncmp(const char *p, const char *q, uint n)
+{
+  while(n > 0 && *p && *p == *q)
+    n--, p++, q++;
+  if(n == 0)
+    return 0;
+  return (uchar)*p - (uchar)*q;
+}
+
 uint
 strlen(const char *s)
 {
@@ -104,3 +125,370 @@ memmove(void *vdst, const void *vsrc, int n)
     *dst++ = *src++;
   return vdst;
 }
+
+// Mutexes for threads, built on futexes.  The lock word is
+// 0 when unlocked, 1 when locked, and 2 when locked and
+// other threads may be sleeping in futexwait on it.
+void
+mutex_lock(int *m)
+{
+  int c;
+
+  if((c = __sync_val_compare_and_swap(m, 0, 1)) == 0)
+    return;
+  if(c != 2)
+    c = xchg((uint*)m, 2);
+  while(c != 0){
+    futexwait(m, 2);
+    c = xchg((uint*)m, 2);
+  }
+}
+
+void
+mutex_unlock(int *m)
+{
+  if(__sync_fetch_and_sub(m, 1) != 1){
+    *m = 0;
+    futexwake(m, 1);
+  }
+}
+
+// Set the handler for signal sig, returning the old one,
+// or SIG_ERR (-1) on failure.
+sighandler_t
+signal(int sig, sighandler_t handler)
+{
+  struct sigaction act, oldact;
+
+  act.sa_handler = handler;
+  act.sa_mask = 0;
+  if(sigaction(sig, &act, &oldact) < 0)
+    return (sighandler_t)-1;
+  return oldact.sa_handler;
+}
+
+// Read the time of day from the vDSO page (see vdso.h) into
+// *ts, without a system call.  Returns -1 if the TSC isn't the
+// kernel's clock, or is too far past the page's last update.
+static int
+vdsotime(struct timespec *ts)
+{
+  struct vdso *v = (struct vdso*)VDSO;
+  uint seq, boot, sec, nsec, billion;
+  uint64 d, ns;
+
+  do {
+    seq = v->seq;
+    __sync_synchronize();
+    if(v->tsc == 0)
+      return -1;
+    d = rdtsc() - v->tsc;
+    ns = v->ns + (((uint64)(uint)d * v->mult) >> VDSOSHIFT);
+    boot = v->boottime;
+    __sync_synchronize();
+  } while((seq & 1) || seq != v->seq);
+  if(d >> 32)
+    return -1;
+  // ns / 10^9, without libgcc's 64-bit division.  The quotient
+  // fits in 32 bits for the next 136 years.
+  billion = 1000000000;
+  asm("divl %4" : "=a" (sec), "=d" (nsec) :
+      "a" ((uint)ns), "d" ((uint)(ns >> 32) % billion), "rm" (billion));
+  ts->tv_sec = boot + sec;
+  ts->tv_nsec = nsec;
+  return 0;
+}
+
+int
+gettimeofday(struct timeval *tv)
+{
+  struct timespec ts;
+
+  if(vdsotime(&ts) < 0 && clock_gettime(CLOCK_REALTIME, &ts) < 0)
+    return -1;
+  tv->tv_sec = ts.tv_sec;
+  tv->tv_usec = ts.tv_nsec / 1000;
+  return 0;
+}
+
+int
+usleep(uint usec)
+{
+  struct timespec ts;
+
+  ts.tv_sec = usec / 1000000;
+  ts.tv_nsec = (usec % 1000000) * 1000;
+  return nanosleep(&ts, 0);
+}
+
+void (*exitflush)(void);  // Set by stdio.c once it is used
+
+// Flush stdio's buffered output, if any, and exit.
+int
+exit(void)
+{
+  if(exitflush)
+    exitflush();
+  _exit();
+}
+
+// Whether fd is a terminal.
+int
+isatty(int fd)
+{
+  struct termios t;
+
+  return ioctl(fd, TCGETS, &t) == 0;
+}
+
+// Whether the kernel has system call num.  A kernel too old
+// for features has only the calls numbered below it.
+int
+hascall(int num)
+{
+  static uint bits[NFEATURES];
+  static int version;
+
+  if(num <= 0 || num >= NFEATURES*32)
+    return 0;
+  if(version == 0 && (version = features(bits, NFEATURES)) < 0)
+    return num < SYS_features;
+  return HASCALL(bits, num);
+}
+
+// Without wait4, wait still reaps any child, though with no
+// status to report.
+int
+waitpid(int pid, int *status, int options)
+{
+  if(!hascall(SYS_wait4)){
+    if(pid != -1 || options != 0){
+      errno = ENOSYS;
+      return -1;
+    }
+    if(status)
+      *status = 0;
+    return wait();
+  }
+  return wait4(pid, status, options, 0);
+}
+
+// Send SIGALRM after secs seconds, or cancel the alarm if secs
+// is 0.  Returns the seconds left on any earlier alarm.
+uint
+alarm(uint secs)
+{
+  struct itimerval it, old;
+
+  memset(&it, 0, sizeof(it));
+  it.it_value.tv_sec = secs;
+  if(setitimer(ITIMER_REAL, &it, &old) < 0)
+    return 0;
+  return old.it_value.tv_sec + (old.it_value.tv_usec > 0);
+}
+
+char **environ;  // Set by _start (see crt0.S)
+static char **envmem;  // environ, once setenv has copied it
+
+// Run path with argv, passing on the environment.
+int
+exec(char *path, char **argv)
+{
+  return execve(path, argv, environ);
+}
+
+// The entry for name in environ, or 0.
+static char**
+findenv(const char *name)
+{
+  char **e;
+  int n;
+
+  n = strlen(name);
+  for(e = environ; e && *e; e++)
+    if(strncmp(*e, name, n) == 0 && (*e)[n] == '=')
+      return e;
+  return 0;
+}
+
+// The value of environment variable name, or 0 if it isn't set.
+char*
+getenv(const char *name)
+{
+  char **e;
+
+  if((e = findenv(name)) == 0)
+    return 0;
+  return *e + strlen(name) + 1;
+}
+
+// Set environment variable name to value, unless it's set and
+// overwrite is 0.  To add one, environ moves to a copy with
+// room for it; the strings setenv replaces aren't freed, since
+// they may be on the stack.
+int
+setenv(const char *name, const char *value, int overwrite)
+{
+  char **e, **ne, *s;
+  int n;
+
+  if(*name == 0 || strchr(name, '=') != 0){
+    errno = EINVAL;
+    return -1;
+  }
+  if((e = findenv(name)) != 0 && !overwrite)
+    return 0;
+  if((s = malloc(strlen(name) + strlen(value) + 2)) == 0)
+    return -1;
+  strcpy(s, name);
+  strcpy(s + strlen(name), "=");
+  strcpy(s + strlen(name) + 1, value);
+  if(e){
+    *e = s;
+    return 0;
+  }
+  for(n = 0; environ && environ[n]; n++)
+    ;
+  if((ne = malloc((n + 2) * sizeof(char*))) == 0){
+    free(s);
+    return -1;
+  }
+  if(n > 0)
+    memmove(ne, environ, n * sizeof(char*));
+  ne[n] = s;
+  ne[n+1] = 0;
+  if(environ == envmem)
+    free(envmem);
+  environ = envmem = ne;
+  return 0;
+}
+
+// Remove name from the environment.
+int
+unsetenv(const char *name)
+{
+  char **e;
+
+  if((e = findenv(name)) == 0)
+    return 0;
+  for(; *e; e++)
+    e[0] = e[1];
+  return 0;
+}
+
+// An open directory, read a buffer of entries at a time.
+struct dir {
+  int fd;
+  int n;          // Bytes of entries in buf
+  int pos;        // Offset of the next entry in buf
+  char buf[512];
+};
+
+// Start reading the directory open as fd, which closedir will
+// close.
+struct dir*
+fdopendir(int fd)
+{
+  struct dir *d;
+
+  if((d = malloc(sizeof(*d))) == 0){
+    errno = ENOMEM;
+    return 0;
+  }
+  d->fd = fd;
+  d->n = d->pos = 0;
+  return d;
+}
+
+struct dir*
+opendir(const char *path)
+{
+  struct dir *d;
+  int fd;
+
+  if((fd = open(path, O_RDONLY)) < 0)
+    return 0;
+  if((d = fdopendir(fd)) == 0)
+    close(fd);
+  return d;
+}
+
+// The next entry of d, or 0 at the end or if getdents fails.
+// The entry lasts until the next call.
+struct dent*
+readdir(struct dir *d)
+{
+  struct dent *e;
+
+  if(d->pos >= d->n){
+    if((d->n = getdents(d->fd, (struct dent*)d->buf, sizeof(d->buf))) <= 0)
+      return 0;
+    d->pos = 0;
+  }
+  e = (struct dent*)(d->buf + d->pos);
+  d->pos += e->reclen;
+  return e;
+}
+
+int
+closedir(struct dir *d)
+{
+  int r;
+
+  r = close(d->fd);
+  free(d);
+  return r;
+}
+
+static char *errstr[NERRNO] = {
+[EPERM]     "not permitted",
+[ENOENT]    "no such file or directory",
+[ESRCH]     "no such process",
+[EINTR]     "interrupted",
+[EIO]       "i/o error",
+[E2BIG]     "argument list too long",
+[ENOEXEC]   "not an executable",
+[EBADF]     "bad file descriptor",
+[ECHILD]    "no child processes",
+[EAGAIN]    "try again",
+[ENOMEM]    "out of memory",
+[EACCES]    "permission denied",
+[EFAULT]    "bad address",
+[EBUSY]     "busy",
+[EEXIST]    "file exists",
+[EXDEV]     "cross-device link",
+[ENODEV]    "no such device",
+[ENOTDIR]   "not a directory",
+[EISDIR]    "is a directory",
+[EINVAL]    "invalid argument",
+[ENFILE]    "file table full",
+[EMFILE]    "too many open files",
+[ENOTTY]    "not a terminal",
+[EFBIG]     "file too large",
+[ENOSPC]    "no space left",
+[ESPIPE]    "illegal seek",
+[EPIPE]     "broken pipe",
+[ERANGE]    "out of range",
+[ENAMETOOLONG] "name too long",
+[ENOSYS]    "no such system call",
+[ENOTEMPTY] "directory not empty",
+};
+
+// A message describing error number e.
+char*
+strerror(int e)
+{
+  if(e <= 0 || e >= NERRNO || errstr[e] == 0)
+    return "unknown error";
+  return errstr[e];
+}
+
+// Print s and the message for errno on the standard error.
+void
+perror(const char *s)
+{
+  if(s && *s)
+    printf(2, "%s: %s\n", s, strerror(errno));
+  else
+    printf(2, "%s\n", strerror(errno));
+}
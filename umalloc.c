@@ -2,89 +2,221 @@
 #include "stat.h"
 #include "user.h"
 #include "param.h"
+#include "mmu.h"
+#include "errno.h"
 
-// Memory allocator by Kernighan and Ritchie,
-// The C programming Language, 2nd ed.  Section 8.7.
+// Memory allocator with segregated free lists.
+//
+// The heap is a run of blocks, each with a header giving its
+// size and the size of the block before it, so free can merge
+// a block with free neighbours on either side.  Free blocks are
+// kept on NBIN lists, a power of two apart in size; malloc takes
+// the first block that fits from the smallest list that might
+// have one, and splits off what it doesn't need.  A fence block,
+// always in use, ends each piece of heap sbrk gave.
 
-typedef long Align;
+#define HDRSIZE   8           // header before each block's data
+#define MINBLOCK  16          // room for a free block's links
+#define NBIN      24
+#define CHUNK     (64*1024)   // least to ask sbrk for at a time
+#define INUSE     1           // low bit of size
 
-union header {
-  struct {
-    union header *ptr;
-    uint size;
-  } s;
-  Align x;
+struct blk {
+  uint prevsize;              // size of the block before, 0 if none
+  uint size;                  // including the header, | INUSE
+  struct blk *next, *prev;    // on a free list, if free
 };
 
-typedef union header Header;
+#define SIZE(b)  ((b)->size & ~INUSE)
+#define NEXT(b)  ((struct blk*)((char*)(b) + SIZE(b)))
+#define PREV(b)  ((struct blk*)((char*)(b) - (b)->prevsize))
 
-static Header base;
-static Header *freep;
+static struct blk *bins[NBIN];
+static struct blk *fence;     // end of the last piece of heap
+static uint trimthreshold = 128*1024;
 
-void
-free(void *ap)
+// The list for free blocks of size bytes: list i holds sizes
+// from 16<<i up to twice that.
+static int
+bin(uint size)
+{
+  int i;
+
+  for(i = 0; i < NBIN-1 && size >= (MINBLOCK << (i+1)); i++)
+    ;
+  return i;
+}
+
+static void
+binadd(struct blk *b)
+{
+  int i;
+
+  i = bin(SIZE(b));
+  b->prev = 0;
+  b->next = bins[i];
+  if(bins[i])
+    bins[i]->prev = b;
+  bins[i] = b;
+}
+
+static void
+binremove(struct blk *b)
+{
+  if(b->prev)
+    b->prev->next = b->next;
+  else
+    bins[bin(SIZE(b))] = b->next;
+  if(b->next)
+    b->next->prev = b->prev;
+}
+
+// Merge free block b, on no list, with free neighbours, which
+// come off their lists.  Returns the merged block.
+static struct blk*
+merge(struct blk *b)
+{
+  struct blk *n, *p;
+
+  n = NEXT(b);
+  if(!(n->size & INUSE)){
+    binremove(n);
+    b->size += n->size;
+  }
+  if(b->prevsize && !(PREV(b)->size & INUSE)){
+    p = PREV(b);
+    binremove(p);
+    p->size += b->size;
+    b = p;
+  }
+  NEXT(b)->prevsize = b->size;
+  return b;
+}
+
+// Cut block b, on no list, down to size bytes, freeing the
+// rest if it is big enough to be a block.
+static void
+split(struct blk *b, uint size)
 {
-  Header *bp, *p;
-
-  bp = (Header*)ap - 1;
-  for(p = freep; !(bp > p && bp < p->s.ptr); p = p->s.ptr)
-    if(p >= p->s.ptr && (bp > p || bp < p->s.ptr))
-      break;
-  if(bp + bp->s.size == p->s.ptr){
-    bp->s.size += p->s.ptr->s.size;
-    bp->s.ptr = p->s.ptr->s.ptr;
-  } else
-    bp->s.ptr = p->s.ptr;
-  if(p + p->s.size == bp){
-    p->s.size += bp->s.size;
-    p->s.ptr = bp->s.ptr;
-  } else
-    p->s.ptr = bp;
-  freep = p;
+  struct blk *r;
+
+  if(SIZE(b) - size < MINBLOCK)
+    return;
+  r = (struct blk*)((char*)b + size);
+  r->size = SIZE(b) - size;
+  r->prevsize = size;
+  NEXT(r)->prevsize = r->size;
+  b->size = size | (b->size & INUSE);
+  binadd(r);
 }
 
-static Header*
-morecore(uint nu)
+// Get at least size more bytes of heap from sbrk, as a free
+// block on its list.
+static struct blk*
+morecore(uint size)
 {
+  struct blk *b;
   char *p;
-  Header *hp;
+  uint n, pad;
 
-  if(nu < 4096)
-    nu = 4096;
-  p = sbrk(nu * sizeof(Header));
-  if(p == (char*)-1)
+  // Ask for room for a new fence too, and to align the block,
+  // so the fence ends at the break.
+  n = size < CHUNK ? CHUNK : size;
+  if(n >= 0x7FFFFFF0){
+    errno = ENOMEM;
+    return 0;
+  }
+  pad = -(uint)sbrk(0) & 7;
+  if((p = sbrk(pad + n + HDRSIZE)) == (char*)-1)
     return 0;
-  hp = (Header*)p;
-  hp->s.size = nu;
-  free((void*)(hp + 1));
-  return freep;
+  if(fence && p == (char*)fence + HDRSIZE){
+    // Right after the last piece: the old fence starts the block.
+    b = fence;
+    b->size = n + HDRSIZE;
+  } else {
+    b = (struct blk*)(p + pad);
+    b->prevsize = 0;
+    b->size = n;
+  }
+  fence = NEXT(b);
+  fence->size = INUSE;
+  b = merge(b);
+  binadd(b);
+  return b;
+}
+
+// Give back to the kernel all but the start of free block b,
+// on no list, if it is big and at the end of the heap.
+static void
+trim(struct blk *b)
+{
+  uint n;
+
+  if(trimthreshold == 0 || SIZE(b) < trimthreshold || NEXT(b) != fence ||
+     sbrk(0) != (char*)fence + HDRSIZE)
+    return;
+  n = (SIZE(b) - MINBLOCK) & ~(PGSIZE-1);
+  if(sbrk(-n) == (char*)-1)
+    return;
+  b->size -= n;
+  fence = NEXT(b);
+  fence->prevsize = b->size;
+  fence->size = INUSE;
+}
+
+void
+free(void *ap)
+{
+  struct blk *b;
+
+  if(ap == 0)
+    return;
+  b = (struct blk*)((char*)ap - HDRSIZE);
+  if(!(b->size & INUSE))
+    return;
+  b->size &= ~INUSE;
+  b = merge(b);
+  trim(b);
+  binadd(b);
 }
 
 void*
 malloc(uint nbytes)
 {
-  Header *p, *prevp;
-  uint nunits;
+  struct blk *b;
+  uint size;
+  int i;
 
-  nunits = (nbytes + sizeof(Header) - 1)/sizeof(Header) + 1;
-  if((prevp = freep) == 0){
-    base.s.ptr = freep = prevp = &base;
-    base.s.size = 0;
+  size = (nbytes + HDRSIZE + 7) & ~7;
+  if(size < nbytes){
+    errno = ENOMEM;
+    return 0;
   }
-  for(p = prevp->s.ptr; ; prevp = p, p = p->s.ptr){
-    if(p->s.size >= nunits){
-      if(p->s.size == nunits)
-        prevp->s.ptr = p->s.ptr;
-      else {
-        p->s.size -= nunits;
-        p += p->s.size;
-        p->s.size = nunits;
-      }
-      freep = prevp;
-      return (void*)(p + 1);
-    }
-    if(p == freep)
-      if((p = morecore(nunits)) == 0)
-        return 0;
+  if(size < MINBLOCK)
+    size = MINBLOCK;
+  for(i = bin(size); i < NBIN; i++)
+    for(b = bins[i]; b; b = b->next)
+      if(SIZE(b) >= size)
+        goto found;
+  if((b = morecore(size)) == 0)
+    return 0;
+found:
+  binremove(b);
+  split(b, size);
+  b->size |= INUSE;
+  return (char*)b + HDRSIZE;
+}
+
+// Set how malloc behaves.  Returns 1, or 0 for an unknown param.
+int
+mallopt(int param, int value)
+{
+  switch(param){
+  case M_TRIM_THRESHOLD:
+    // Give a free run of at least value bytes at the end of the
+    // heap back to the kernel; 0 never does.
+    trimthreshold = value;
+    return 1;
   }
+  return 0;
 }
@@ -1,16 +1,32 @@
 struct stat;
+struct rlimit;
 struct rtcdate;
+struct rusage;
+struct itimerval;
+struct pinfo;
+struct sysinfo;
+struct perfinfo;
+struct sigaction;
+struct timespec;
+struct timeval;
+struct cpuinfo;
+struct pollfd;
+struct dent;
+struct dir;
+struct scstat;
+typedef struct iobuf FILE;
 
-// system calls
+// system calls; a failing call returns -1 and sets errno
+extern int errno;
 int fork(void);
-int exit(void) __attribute__((noreturn));
+int _exit(void) __attribute__((noreturn));
 int wait(void);
 int pipe(int*);
 int write(int, const void*, int);
 int read(int, void*, int);
 int close(int);
-int kill(int);
-int exec(char*, char**);
+int kill(int, int);
+int execve(char*, char**, char**);
 int open(const char*, int);
 int mknod(const char*, short, short);
 int unlink(const char*);
@@ -20,20 +36,127 @@ int mkdir(const char*);
 int chdir(const char*);
 int dup(int);
 int getpid(void);
+int getppid(void);
+int clock_gettime(int, struct timespec*);
+int nanosleep(struct timespec*, struct timespec*);
+int pinfo(struct pinfo*, int);
+int getrlimit(int, struct rlimit*);
+int setrlimit(int, struct rlimit*);
+int wait4(int, int*, int, struct rusage*);
+int ptrace(int, int, void*, int);
+int getrusage(int, struct rusage*);
+int getitimer(int, struct itimerval*);
+int setitimer(int, struct itimerval*, struct itimerval*);
+int getuid(void);
+int geteuid(void);
+int getgid(void);
+int getegid(void);
+int setuid(int);
+int setgid(int);
+void* mmap(void*, uint, int, int, int, int);
+int munmap(void*, uint);
+int msync(void*, uint, int);
+int sysinfo(struct sysinfo*);
+int arch_prctl(int, uint);
+int intrinfo(int, uint*);
+int irqaffinity(int, uint);
+int perfread(struct perfinfo*);
+int date(struct rtcdate*);
+int rtcalarm(int);
+int cpuonline(int, int);
+int cpuinfo(int, struct cpuinfo*);
+int dmesg(char*, int);
+int shmget(int, uint, int);
+void* shmat(int, void*, int);
+int shmdt(void*);
+int shmctl(int, int, void*);
 char* sbrk(int);
 int sleep(int);
 int uptime(void);
+int clone(void(*)(void*), void*, void*);
+int join(void**);
+int futexwait(int*, int);
+int futexwake(int*, int);
+int sigaction(int, struct sigaction*, struct sigaction*);
+int setpgid(int, int);
+int getpgid(int);
+int setsid(void);
+int tcsetpgrp(int, int);
+int ioctl(int, int, void*);
+int poll(struct pollfd*, int, int);
+int fcntl(int, int, int);
+int dup2(int, int);
+int dup3(int, int, int);
+int pread(int, void*, int, int);
+int pwrite(int, const void*, int, int);
+int sendfile(int, int, int*, int);
+int trace(int, int);
+int scstat(struct scstat*);
+int prctl(int, uint);
+int getdents(int, struct dent*, int);
+int features(uint*, int);
 
 // ulib.c
+extern char **environ;  // "NAME=value" strings, 0-terminated
+int exit(void) __attribute__((noreturn));
+int isatty(int);
+int exec(char*, char**);
+char* getenv(const char*);
+int setenv(const char*, const char*, int);
+int unsetenv(const char*);
 int stat(const char*, struct stat*);
 char* strcpy(char*, const char*);
 void *memmove(void*, const void*, int);
 char* strchr(const char*, char c);
 int strcmp(const char*, const char*);
+int strncmp(const char*, const char*, uint);
 void printf(int, const char*, ...);
+void vprintf(int, const char*, __builtin_va_list);
+int snprintf(char*, int, const char*, ...);
+int vsnprintf(char*, int, const char*, __builtin_va_list);
+int sprintf(char*, const char*, ...);
+int fprintf(FILE*, const char*, ...);
+int vfprintf(FILE*, const char*, __builtin_va_list);
 char* gets(char*, int max);
 uint strlen(const char*);
 void* memset(void*, int, uint);
 void* malloc(uint);
 void free(void*);
+int mallopt(int, int);
+#define M_TRIM_THRESHOLD  1  // mallopt: least free run to give back
 int atoi(const char*);
+void mutex_lock(int*);
+void mutex_unlock(int*);
+void (*signal(int, void (*)(int)))(int);
+int gettimeofday(struct timeval*);
+int usleep(uint);
+int waitpid(int, int*, int);
+uint alarm(uint);
+char* strerror(int);
+int hascall(int);
+struct dir* opendir(const char*);
+struct dir* fdopendir(int);
+struct dent* readdir(struct dir*);
+int closedir(struct dir*);
+void perror(const char*);
+
+// stdio.c
+extern FILE *stdin, *stdout, *stderr;
+#define EOF     (-1)
+#define BUFSIZ  512
+#define _IOFBF  0  // Write when the buffer fills
+#define _IOLBF  1  // Write at each newline too
+#define _IONBF  2  // Write at once
+int fflush(FILE*);
+int setvbuf(FILE*, char*, int, uint);
+int fputc(int, FILE*);
+int putchar(int);
+uint fwrite(const void*, uint, uint, FILE*);
+int fputs(const char*, FILE*);
+int puts(const char*);
+int fgetc(FILE*);
+int getchar(void);
+char* fgets(char*, int, FILE*);
+uint fread(void*, uint, uint, FILE*);
+int feof(FILE*);
+int ferror(FILE*);
@@ -7,10 +7,24 @@
 #include "syscall.h"
 #include "traps.h"
 #include "memlayout.h"
+#include "signal.h"
+#include "time.h"
+#include "date.h"
+#include "pinfo.h"
+#include "sysinfo.h"
+#include "mman.h"
+#include "termios.h"
+#include "mouse.h"
+#include "poll.h"
+#include "shm.h"
+#include "cpuinfo.h"
+#include "errno.h"
 
 char buf[8192];
 char name[3];
 char *echoargv[] = { "echo", "ALL", "TESTS", "PASSED", 0 };
+// Its own stdout, a file descriptor, not stdio's stream.
+#define stdout fdout
 int stdout = 1;
 
 // does chdir() call iput(p->cwd) in a transaction?
@@ -390,9 +404,9 @@ preempt(void)
   }
   close(pfds[0]);
   printf(1, "kill... ");
-  kill(pid1);
-  kill(pid2);
-  kill(pid3);
+  kill(pid1, SIGKILL);
+  kill(pid2, SIGKILL);
+  kill(pid3, SIGKILL);
   printf(1, "wait... ");
   wait();
   wait();
@@ -446,7 +460,7 @@ mem(void)
     m1 = malloc(1024*20);
     if(m1 == 0){
       printf(1, "couldn't allocate mem?!!\n");
-      kill(ppid);
+      kill(ppid, SIGKILL);
       exit();
     }
     free(m1);
@@ -698,6 +712,50 @@ unlinkread(void)
   printf(1, "unlinkread ok\n");
 }
 
+// names looked up through the directory entry cache follow
+// unlink, re-creation, and a directory replaced by another.
+void
+dcachetest(void)
+{
+  int fd;
+
+  printf(1, "dcache test\n");
+  if(mkdir("dcd") != 0 || (fd = open("dcd/f", O_CREATE|O_RDWR)) < 0){
+    printf(1, "dcache create failed\n");
+    exit();
+  }
+  write(fd, "a", 1);
+  close(fd);
+  if((fd = open("dcd/f", O_RDONLY)) < 0){
+    printf(1, "dcache open failed\n");
+    exit();
+  }
+  close(fd);
+  if(unlink("dcd/f") != 0 || open("dcd/f", O_RDONLY) >= 0){
+    printf(1, "dcache unlinked name still opens\n");
+    exit();
+  }
+  fd = open("dcd/f", O_CREATE|O_RDWR);
+  write(fd, "b", 1);
+  close(fd);
+  fd = open("dcd/f", O_RDONLY);
+  if(fd < 0 || read(fd, buf, 2) != 1 || buf[0] != 'b'){
+    printf(1, "dcache found the old file\n");
+    exit();
+  }
+  close(fd);
+  if(unlink("dcd/f") != 0 || unlink("dcd") != 0 || mkdir("dcd") != 0){
+    printf(1, "dcache unlink failed\n");
+    exit();
+  }
+  if(open("dcd/f", O_RDONLY) >= 0){
+    printf(1, "dcache found a name in the old directory\n");
+    exit();
+  }
+  unlink("dcd");
+  printf(1, "dcache test OK\n");
+}
+
 void
 linktest(void)
 {
@@ -1411,199 +1469,1878 @@ forktest(void)
   printf(1, "fork test OK\n");
 }
 
+int cloneval;
+int clonelock;
+
 void
-sbrktest(void)
+cloneworker(void *arg)
 {
-  int fds[2], pid, pids[10], ppid;
-  char *a, *b, *c, *lastaddr, *oldbrk, *p, scratch;
-  uint amt;
+  int i;
 
-  printf(stdout, "sbrk test\n");
-  oldbrk = sbrk(0);
+  for(i = 0; i < 1000; i++){
+    mutex_lock(&clonelock);
+    cloneval += (int)arg;
+    mutex_unlock(&clonelock);
+  }
+  exit();
+}
 
-  // can one sbrk() less than a page?
-  a = sbrk(0);
+// threads created by clone share memory with their creator,
+// futex-based mutexes keep them from racing, and join hands
+// back their stacks.
+void
+clonetest(void)
+{
   int i;
-  for(i = 0; i < 5000; i++){
-    b = sbrk(1);
-    if(b != a){
-      printf(stdout, "sbrk test failed %d %x %x\n", i, a, b);
+  void *stack;
+
+  printf(1, "clone test\n");
+
+  cloneval = 0;
+  for(i = 1; i <= 4; i++){
+    if(clone(cloneworker, (void*)i, malloc(4096)) < 0){
+      printf(1, "clone failed\n");
       exit();
     }
-    *b = 1;
-    a = b + 1;
   }
-  pid = fork();
-  if(pid < 0){
-    printf(stdout, "sbrk test fork failed\n");
-    exit();
+  for(i = 1; i <= 4; i++){
+    if(join(&stack) < 0){
+      printf(1, "join stopped early\n");
+      exit();
+    }
+    free(stack);
   }
-  c = sbrk(1);
-  c = sbrk(1);
-  if(c != a + 1){
-    printf(stdout, "sbrk test failed post-fork\n");
+  if(join(&stack) != -1 || wait() != -1){
+    printf(1, "join got too many\n");
     exit();
   }
-  if(pid == 0)
+  if(cloneval != 1000*(1+2+3+4)){
+    printf(1, "threads did not share memory: %d\n", cloneval);
     exit();
-  wait();
+  }
 
-  // can one grow address space to something big?
-#define BIG (100*1024*1024)
-  a = sbrk(0);
-  amt = (BIG) - (uint)a;
-  p = sbrk(amt);
-  if (p != a) {
-    printf(stdout, "sbrk test failed to grow big address space; enough phys mem?\n");
+  printf(1, "clone test OK\n");
+}
+
+int sigcaught;
+
+void
+sighandler(int sig)
+{
+  sigcaught = sig;
+}
+
+// a caught signal runs its handler and then resumes the
+// interrupted code; an uncaught one terminates the process.
+void
+signaltest(void)
+{
+  int pid;
+
+  printf(1, "signal test\n");
+
+  sigcaught = 0;
+  signal(SIGTERM, sighandler);
+  if(kill(getpid(), SIGTERM) != 0){
+    printf(1, "kill self failed\n");
     exit();
   }
-  lastaddr = (char*) (BIG-1);
-  *lastaddr = 99;
+  if(sigcaught != SIGTERM){
+    printf(1, "handler did not run\n");
+    exit();
+  }
+  signal(SIGTERM, SIG_DFL);
 
-  // can one de-allocate?
-  a = sbrk(0);
-  c = sbrk(-4096);
-  if(c == (char*)0xffffffff){
-    printf(stdout, "sbrk could not deallocate\n");
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
     exit();
   }
-  c = sbrk(0);
-  if(c != a - 4096){
-    printf(stdout, "sbrk deallocation produced wrong address, a %x c %x\n", a, c);
+  if(pid == 0){
+    for(;;)
+      sleep(1);
+  }
+  kill(pid, SIGTERM);
+  if(wait() != pid){
+    printf(1, "signal did not kill child\n");
     exit();
   }
 
-  // can one re-allocate that page?
-  a = sbrk(0);
-  c = sbrk(4096);
-  if(c != a || sbrk(0) != a + 4096){
-    printf(stdout, "sbrk re-allocation failed, a %x c %x\n", a, c);
+  printf(1, "signal test OK\n");
+}
+
+// a child moved into its own process group can be
+// signalled through the group.
+void
+pgrptest(void)
+{
+  int pid;
+
+  printf(1, "pgrp test\n");
+
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
     exit();
   }
-  if(*lastaddr == 99){
-    // should be zero
-    printf(stdout, "sbrk de-allocation didn't really deallocate\n");
+  if(pid == 0){
+    for(;;)
+      sleep(1);
+  }
+  if(getpgid(pid) != getpgid(0)){
+    printf(1, "child did not inherit pgrp\n");
     exit();
   }
-
-  a = sbrk(0);
-  c = sbrk(-(sbrk(0) - oldbrk));
-  if(c != a){
-    printf(stdout, "sbrk downsize failed, a %x c %x\n", a, c);
+  if(setpgid(pid, pid) != 0 || getpgid(pid) != pid){
+    printf(1, "setpgid failed\n");
     exit();
   }
-
-  // can we read the kernel's memory?
-  for(a = (char*)(KERNBASE); a < (char*) (KERNBASE+2000000); a += 50000){
-    ppid = getpid();
-    pid = fork();
-    if(pid < 0){
-      printf(stdout, "fork failed\n");
-      exit();
-    }
-    if(pid == 0){
-      printf(stdout, "oops could read %x = %x\n", a, *a);
-      kill(ppid);
-      exit();
-    }
-    wait();
+  if(kill(-pid, SIGKILL) != 0 || wait() != pid){
+    printf(1, "kill pgrp failed\n");
+    exit();
   }
 
-  // if we run the system out of memory, does it clean up the last
-  // failed allocation?
+  printf(1, "pgrp test OK\n");
+}
+
+// getppid names the parent, and then init once the
+// parent exits.
+void
+getppidtest(void)
+{
+  int pid, fds[2];
+  char c;
+
+  printf(1, "getppid test\n");
+
   if(pipe(fds) != 0){
-    printf(1, "pipe() failed\n");
+    printf(1, "pipe failed\n");
     exit();
   }
-  for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
-    if((pids[i] = fork()) == 0){
-      // allocate a lot of memory
-      sbrk(BIG - (uint)sbrk(0));
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    pid = fork();
+    if(pid < 0)
+      exit();
+    if(pid == 0){
+      // wait for the parent to exit
+      while(getppid() != 1)
+        sleep(1);
       write(fds[1], "x", 1);
-      // sit around until killed
-      for(;;) sleep(1000);
     }
-    if(pids[i] != -1)
-      read(fds[0], &scratch, 1);
-  }
-  // if those failed allocations freed up the pages they did allocate,
-  // we'll be able to allocate here
-  c = sbrk(4096);
-  for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
-    if(pids[i] == -1)
-      continue;
-    kill(pids[i]);
-    wait();
+    exit();
   }
-  if(c == (char*)0xffffffff){
-    printf(stdout, "failed sbrk leaked memory\n");
+  close(fds[1]);
+  wait();
+  if(read(fds[0], &c, 1) != 1){
+    printf(1, "orphan was not reparented to init\n");
     exit();
   }
+  close(fds[0]);
 
-  if(sbrk(0) > oldbrk)
-    sbrk(-(sbrk(0) - oldbrk));
+  printf(1, "getppid test OK\n");
+}
 
-  printf(stdout, "sbrk test OK\n");
+// pinfo reports the calling process.
+void
+pinfotest(void)
+{
+  static struct pinfo pi[NPROC];
+  int i, n;
+
+  printf(1, "pinfo test\n");
+
+  n = pinfo(pi, NPROC);
+  for(i = 0; i < n; i++)
+    if(pi[i].pid == getpid())
+      break;
+  if(i == n || strcmp(pi[i].name, "usertests") != 0 ||
+     pi[i].ppid != getppid() || pi[i].nfd < 3){
+    printf(1, "pinfo did not report usertests\n");
+    exit();
+  }
+
+  printf(1, "pinfo test OK\n");
 }
 
+// sysinfo sees pages and slab objects come and go.
 void
-validateint(int *p)
+sysinfotest(void)
 {
-  int res;
-  asm("mov %%esp, %%ebx\n\t"
-      "mov %3, %%esp\n\t"
-      "int %2\n\t"
-      "mov %%ebx, %%esp" :
-      "=a" (res) :
-      "a" (SYS_sleep), "n" (T_SYSCALL), "c" (p) :
-      "ebx");
+  struct sysinfo s0, s1, s2;
+  char *p;
+  int i, fds[2];
+
+  printf(1, "sysinfo test\n");
+
+  if(sysinfo(&s0) < 0 || s0.freepages > s0.totalpages ||
+     s0.dmapages > s0.totalpages || s0.dmafree > s0.dmapages ||
+     s0.swapfree > s0.swappages || s0.bufinuse > s0.nbuf){
+    printf(1, "sysinfo failed\n");
+    exit();
+  }
+  p = sbrk(64*4096);
+  for(i = 0; i < 64; i++)
+    p[i*4096] = 1;
+  sysinfo(&s1);
+  sbrk(-64*4096);
+  sysinfo(&s2);
+  if(s1.freepages > s0.freepages - 64 || s2.freepages < s1.freepages + 64){
+    printf(1, "sysinfo free pages wrong: %d %d %d\n",
+           s0.freepages, s1.freepages, s2.freepages);
+    exit();
+  }
+
+  // Two files and a pipe.
+  sysinfo(&s0);
+  if(pipe(fds) < 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  sysinfo(&s1);
+  close(fds[0]);
+  close(fds[1]);
+  sysinfo(&s2);
+  if(s1.slabobjs != s0.slabobjs + 3 || s2.slabobjs != s0.slabobjs){
+    printf(1, "sysinfo slab objects wrong: %d %d %d\n",
+           s0.slabobjs, s1.slabobjs, s2.slabobjs);
+    exit();
+  }
+
+  printf(1, "sysinfo test OK\n");
 }
 
+// timer interrupts show up in the per-CPU counts.
 void
-validatetest(void)
+intrinfotest(void)
 {
-  int hi, pid;
-  uint p;
+  static uint c0[256], c1[256];
+  uint t0, t1;
+  int i, n, up;
 
-  printf(stdout, "validate test\n");
-  hi = 1100*1024;
+  printf(1, "intrinfo test\n");
 
-  for(p = 0; p <= (uint)hi; p += 4096){
-    if((pid = fork()) == 0){
-      // try to crash the kernel by passing in a badly placed integer
-      validateint((int*)p);
-      exit();
-    }
-    sleep(0);
-    sleep(0);
-    kill(pid);
-    wait();
+  if(intrinfo(-1, c0) != -1 || intrinfo(NCPU, c0) != -1){
+    printf(1, "intrinfo accepted a bad cpu\n");
+    exit();
+  }
+  t0 = 0;
+  for(n = 0; intrinfo(n, c0) == 0; n++)
+    t0 += c0[T_IRQ0 + IRQ_TIMER];
+  if(n == 0){
+    printf(1, "intrinfo failed\n");
+    exit();
+  }
+  // Spin, not sleep: idle CPUs take no clock ticks.
+  up = uptime();
+  while(uptime() < up + 5)
+    ;
+  t1 = 0;
+  for(i = 0; i < n; i++){
+    intrinfo(i, c1);
+    t1 += c1[T_IRQ0 + IRQ_TIMER];
+  }
+  if(t1 < t0 + 5){
+    printf(1, "timer interrupts not counted: %d %d\n", t0, t1);
+    exit();
+  }
 
-    // try to crash the kernel by passing in a bad string pointer
-    if(link("nosuchfile", (char*)p) != -1){
-      printf(stdout, "link should not succeed\n");
+  // Another CPU's counts are taken there, by a cross-CPU call.
+  if(n > 1){
+    intrinfo(1, c0);
+    intrinfo(1, c1);
+    if(c1[T_CALL] <= c0[T_CALL]){
+      printf(1, "intrinfo made no cross-cpu call\n");
       exit();
     }
   }
 
-  printf(stdout, "validate ok\n");
+  printf(1, "intrinfo test OK\n");
 }
 
-// does unintialized data start out zero?
-char uninit[10000];
+// a CPU taken offline takes no interrupts until it is back,
+// and CPU 0 can't go.
 void
-bsstest(void)
+hotplugtest(void)
 {
-  int i;
+  static uint c[256];
+  uint n0;
+  int n, up;
 
-  printf(stdout, "bss test\n");
-  for(i = 0; i < sizeof(uninit); i++){
-    if(uninit[i] != '\0'){
-      printf(stdout, "bss test failed\n");
-      exit();
-    }
+  printf(1, "hotplug test\n");
+
+  for(n = 0; intrinfo(n, c) == 0; n++)
+    ;
+  if(cpuonline(0, 0) != -1 || cpuonline(n, 0) != -1 ||
+     cpuonline(1, 1) != -1){
+    printf(1, "cpuonline accepted a bad cpu\n");
+    exit();
   }
-  printf(stdout, "bss test ok\n");
+  if(n < 2){
+    printf(1, "hotplug test OK (one cpu)\n");
+    return;
+  }
+  if(cpuonline(1, 0) != 0 || cpuonline(1, 0) != -1){
+    printf(1, "cpuoffline failed\n");
+    exit();
+  }
+  intrinfo(1, c);
+  n0 = c[T_IRQ0 + IRQ_TIMER];
+  up = uptime();
+  while(uptime() < up + 5)
+    ;
+  intrinfo(1, c);
+  if(c[T_IRQ0 + IRQ_TIMER] != n0){
+    printf(1, "offline cpu took timer interrupts\n");
+    exit();
+  }
+  if(cpuonline(1, 1) != 0){
+    printf(1, "cpuonline failed\n");
+    exit();
+  }
+  // It answers cross-CPU calls again.
+  intrinfo(1, c);
+  n0 = c[T_CALL];
+  intrinfo(1, c);
+  if(c[T_CALL] == n0){
+    printf(1, "cpu did not come back\n");
+    exit();
+  }
+  printf(1, "hotplug test OK\n");
+}
+
+// every CPU reports a vendor and 4MB pages, and
+// cpuinfo refuses a CPU that isn't there.
+void
+cpuinfotest(void)
+{
+  static uint c[256];
+  struct cpuinfo ci;
+  int n, i;
+
+  printf(1, "cpuinfo test\n");
+
+  for(n = 0; intrinfo(n, c) == 0; n++)
+    ;
+  for(i = 0; i < n; i++){
+    if(cpuinfo(i, &ci) != 0 || ci.vendor[0] == 0 ||
+       !(ci.features & CPUF_PSE)){
+      printf(1, "cpuinfo %d failed\n", i);
+      exit();
+    }
+  }
+  if(cpuinfo(-1, &ci) != -1 || cpuinfo(n, &ci) != -1 ||
+     cpuinfo(0, (struct cpuinfo*)0xffffffff) != -1){
+    printf(1, "cpuinfo accepted bad arguments\n");
+    exit();
+  }
+  printf(1, "cpuinfo test OK\n");
+}
+
+// dmesg returns whole lines of the kernel's messages, each
+// with a level and a time, up to as many bytes as asked for.
+void
+dmesgtest(void)
+{
+  static char buf[512];
+  int n;
+
+  printf(1, "dmesg test\n");
+
+  n = dmesg(buf, sizeof(buf));
+  if(n <= 0 || n > sizeof(buf) || buf[0] != '<' || buf[2] != '>' ||
+     buf[3] != '[' || buf[n-1] != '\n'){
+    printf(1, "dmesg returned %d bad bytes\n", n);
+    exit();
+  }
+  if(dmesg(buf, 0) != 0 || dmesg(buf, -1) != -1 ||
+     dmesg((char*)0xffffffff, sizeof(buf)) != -1){
+    printf(1, "dmesg accepted bad arguments\n");
+    exit();
+  }
+  printf(1, "dmesg test OK\n");
+}
+
+// Run echo with its output in a pipe, moved to stdout by dup3
+// with flags.  Returns the number of bytes echo wrote.
+int
+echoto(int flags)
+{
+  static char *args[] = { "echo", "hi", 0 };
+  char buf[8];
+  int fds[2], pid, n, m;
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  pid = fork();
+  if(pid == 0){
+    close(fds[0]);
+    if(dup3(fds[1], 1, flags) != 1)
+      exit();
+    close(fds[1]);
+    exec("echo", args);
+    exit();
+  }
+  close(fds[1]);
+  for(n = 0; (m = read(fds[0], buf, sizeof(buf))) > 0; n += m)
+    ;
+  close(fds[0]);
+  wait();
+  return n;
+}
+
+// Run path with argv and its output in buf, which has room
+// for n bytes and a nul.  Returns the length of the output.
+int
+runout(char *path, char **argv, char *buf, int n)
+{
+  int fds[2], pid, i, m;
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  pid = fork();
+  if(pid == 0){
+    close(fds[0]);
+    dup2(fds[1], 1);
+    close(fds[1]);
+    exec(path, argv);
+    exit();
+  }
+  close(fds[1]);
+  for(i = 0; i < n && (m = read(fds[0], buf+i, n-i)) > 0; i += m)
+    ;
+  buf[i] = 0;
+  close(fds[0]);
+  wait();
+  return i;
+}
+
+// Make file path hold s.
+void
+mkscript(char *path, char *s)
+{
+  int fd;
+
+  fd = open(path, O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, s, strlen(s)) != strlen(s)){
+    printf(1, "create %s failed\n", path);
+    exit();
+  }
+  close(fd);
+}
+
+// exec runs a file that starts #! with the interpreter named,
+// passing it the rest of the line and the file's path, which
+// makes sh scripts work; but not with a script as interpreter.
+void
+shebangtest(void)
+{
+  static char buf[64];
+  char *args[] = { "script1", "world", 0 };
+
+  printf(1, "shebang test\n");
+
+  mkscript("script1", "#! echo  hello there  \nignored\n");
+  mkscript("script2", "#!/sh\n# comment\necho sh ran\n");
+  mkscript("script3", "#!script2\n");
+  runout("script1", args, buf, sizeof(buf)-1);
+  if(strcmp(buf, "hello there script1 world\n") != 0){
+    printf(1, "#!echo printed %s\n", buf);
+    exit();
+  }
+  args[0] = "script2";
+  args[1] = 0;
+  runout("script2", args, buf, sizeof(buf)-1);
+  if(strcmp(buf, "sh ran\n") != 0){
+    printf(1, "#!/sh printed %s\n", buf);
+    exit();
+  }
+  args[0] = "script3";
+  if(runout("script3", args, buf, sizeof(buf)-1) != 0){
+    printf(1, "script ran as an interpreter\n");
+    exit();
+  }
+  unlink("script1");
+  unlink("script2");
+  unlink("script3");
+  printf(1, "shebang test OK\n");
+}
+
+// exec passes the environment to main, where getenv finds it,
+// and setenv and unsetenv change it.
+void
+envtest(void)
+{
+  static char buf[128];
+  char *args[] = { "env", 0 }, *env1[] = { "A=1", "B=two", 0 };
+  char **oldenv;
+
+  printf(1, "env test\n");
+
+  oldenv = environ;
+  environ = env1;
+  if(strcmp(getenv("B"), "two") != 0 || getenv("C") != 0 ||
+     runout("env", args, buf, sizeof(buf)-1) <= 0 ||
+     strcmp(buf, "A=1\nB=two\n") != 0){
+    printf(1, "env printed %s\n", buf);
+    exit();
+  }
+  if(setenv("C", "3", 0) != 0 || setenv("A", "x", 0) != 0 ||
+     setenv("B", "2", 1) != 0 || unsetenv("A") != 0 ||
+     setenv("D=", "4", 1) != -1){
+    printf(1, "setenv failed\n");
+    exit();
+  }
+  runout("env", args, buf, sizeof(buf)-1);
+  if(strcmp(buf, "B=2\nC=3\n") != 0){
+    printf(1, "env printed %s after setenv\n", buf);
+    exit();
+  }
+  environ = oldenv;
+  printf(1, "env test OK\n");
+}
+
+// exec loads a position-independent program at a base of its
+// choosing and relocates the pointers in its data, or has the
+// interpreter the program names, ldso, relocate them.
+void
+pietest(void)
+{
+  char buf[32], *args[] = { "pie", "a", "bc", 0 };
+
+  printf(1, "pie test\n");
+
+  runout("pie", args, buf, sizeof(buf)-1);
+  if(strcmp(buf, "a bc\n") != 0){
+    printf(1, "pie printed %s\n", buf);
+    exit();
+  }
+  args[0] = "dynpie";
+  runout("dynpie", args, buf, sizeof(buf)-1);
+  if(strcmp(buf, "a bc\n") != 0){
+    printf(1, "dynpie printed %s\n", buf);
+    exit();
+  }
+  printf(1, "pie test OK\n");
+}
+
+// Failing system calls say why in errno.
+void
+errnotest(void)
+{
+  int fd, fds[2];
+  char c;
+
+  printf(1, "errno test\n");
+
+  if(open("errno.none", 0) >= 0 || errno != ENOENT){
+    printf(1, "open missing file: errno %d\n", errno);
+    exit();
+  }
+  if(close(-1) >= 0 || errno != EBADF){
+    printf(1, "close(-1): errno %d\n", errno);
+    exit();
+  }
+  if(mkdir("errnodir") < 0 || (fd = open("errnodir/f", O_CREATE|O_RDWR)) < 0){
+    printf(1, "errnodir create failed\n");
+    exit();
+  }
+  close(fd);
+  if(mkdir("errnodir") >= 0 || errno != EEXIST){
+    printf(1, "mkdir existing: errno %d\n", errno);
+    exit();
+  }
+  if(unlink("errnodir") >= 0 || errno != ENOTEMPTY){
+    printf(1, "unlink full dir: errno %d\n", errno);
+    exit();
+  }
+  if(unlink("errnodir/f") < 0 || unlink("errnodir") < 0){
+    printf(1, "errnodir unlink failed\n");
+    exit();
+  }
+  if(pipe(fds) < 0 || fcntl(fds[0], F_SETFL, O_NONBLOCK) < 0){
+    printf(1, "errno pipe failed\n");
+    exit();
+  }
+  if(read(fds[0], &c, 1) >= 0 || errno != EAGAIN){
+    printf(1, "empty nonblocking read: errno %d\n", errno);
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  if(kill(1000000, SIGTERM) >= 0 || errno != ESRCH){
+    printf(1, "kill missing pid: errno %d\n", errno);
+    exit();
+  }
+  if(strcmp(strerror(ENOENT), "no such file or directory") != 0){
+    printf(1, "strerror(ENOENT) is %s\n", strerror(ENOENT));
+    exit();
+  }
+  printf(1, "errno test OK\n");
+}
+
+// exec maps the text read-only and the data and bss writable,
+// with the bss zeroed.
+char bssbytes[2*1024];
+
+void
+wxtest(void)
+{
+  int i, pid, ppid;
+
+  printf(1, "w^x test\n");
+
+  for(i = 0; i < sizeof(bssbytes)/sizeof(bssbytes[0]); i++){
+    if(bssbytes[i] != 0){
+      printf(1, "bss not zero at %d\n", i);
+      exit();
+    }
+  }
+  bssbytes[0] = 1;
+
+  ppid = getpid();
+  pid = fork();
+  if(pid < 0){
+    printf(1, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    *(volatile char*)wxtest = 0;
+    printf(1, "oops could write text at %x\n", wxtest);
+    kill(ppid, SIGKILL);
+    exit();
+  }
+  wait();
+  printf(1, "w^x test OK\n");
+}
+
+// dup2 and dup3 copy a descriptor to a given one, closing
+// what it had open, and dup3 can mark it close-on-exec.
+void
+dup2test(void)
+{
+  int fd, fds[2];
+  char c;
+
+  printf(1, "dup2 test\n");
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  fd = open("dup2file", O_CREATE|O_RDWR);
+  if(fd < 0 || dup2(fds[0], fd) != fd || dup2(fd, fd) != fd){
+    printf(1, "dup2 failed\n");
+    exit();
+  }
+  // fd is now the pipe, not the file.
+  if(write(fds[1], "x", 1) != 1 || read(fd, &c, 1) != 1 || c != 'x'){
+    printf(1, "dup2 didn't replace the descriptor\n");
+    exit();
+  }
+  if(dup2(fd, -1) != -1 || dup2(fd, NOFILE) != -1 || dup2(NOFILE, fd) != -1 ||
+     dup3(fd, fd, 0) != -1 || dup3(fd, fds[0], 1) != -1){
+    printf(1, "dup2 accepted bad arguments\n");
+    exit();
+  }
+  close(fd);
+  close(fds[0]);
+  close(fds[1]);
+  unlink("dup2file");
+  if(echoto(0) != 3 || echoto(O_CLOEXEC) != 0){
+    printf(1, "dup3 O_CLOEXEC wasn't honored by exec\n");
+    exit();
+  }
+  printf(1, "dup2 test OK\n");
+}
+
+// pread and pwrite go to the offset they're given, and leave
+// the file's own offset where it was.
+void
+preadtest(void)
+{
+  char buf[8];
+  int fd, fds[2];
+
+  printf(1, "pread test\n");
+
+  unlink("preadfile");
+  fd = open("preadfile", O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, "abcdef", 6) != 6){
+    printf(1, "create preadfile failed\n");
+    exit();
+  }
+  memset(buf, 0, sizeof(buf));
+  if(pwrite(fd, "XY", 2, 1) != 2 || pread(fd, buf, 3, 0) != 3 ||
+     strcmp(buf, "aXY") != 0){
+    printf(1, "pread or pwrite at an offset failed\n");
+    exit();
+  }
+  // The offset is still at the end.
+  if(write(fd, "g", 1) != 1 || pread(fd, buf, sizeof(buf)-1, 0) != 7 ||
+     strcmp(buf, "aXYdefg") != 0 || pread(fd, buf, 1, 7) != 0){
+    printf(1, "pread or pwrite moved the offset\n");
+    exit();
+  }
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  if(pread(fds[0], buf, 1, 0) != -1 || pwrite(fds[1], "x", 1, 0) != -1 ||
+     pread(fd, buf, 1, -1) != -1){
+    printf(1, "pread or pwrite accepted a pipe or bad offset\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  close(fd);
+  unlink("preadfile");
+  printf(1, "pread test OK\n");
+}
+
+// sendfile copies from a file to a pipe or another file,
+// from the file's offset or from one it's given.
+void
+sendfiletest(void)
+{
+  static char buf[6000];
+  int fd, fd2, fds[2], i, n, off;
+
+  printf(1, "sendfile test\n");
+
+  unlink("sendfile1");
+  unlink("sendfile2");
+  for(i = 0; i < sizeof(buf); i++)
+    buf[i] = 'a' + i % 26;
+  fd = open("sendfile1", O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, buf, sizeof(buf)) != sizeof(buf)){
+    printf(1, "create sendfile1 failed\n");
+    exit();
+  }
+  fd2 = open("sendfile2", O_CREATE|O_RDWR);
+  off = 10;
+  if(sendfile(fd2, fd, &off, sizeof(buf)) != sizeof(buf)-10 ||
+     off != sizeof(buf)){
+    printf(1, "sendfile to a file failed\n");
+    exit();
+  }
+  memset(buf, 0, sizeof(buf));
+  if(pread(fd2, buf, sizeof(buf), 0) != sizeof(buf)-10){
+    printf(1, "sendfile2 is the wrong size\n");
+    exit();
+  }
+  for(i = 0; i < sizeof(buf)-10; i++)
+    if(buf[i] != 'a' + (i+10) % 26){
+      printf(1, "sendfile2 is wrong at %d\n", i);
+      exit();
+    }
+  // From fd's own offset, which is at the end, nothing.
+  if(sendfile(fd2, fd, 0, 100) != 0){
+    printf(1, "sendfile from the end sent something\n");
+    exit();
+  }
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  close(fd);
+  fd = open("sendfile1", O_RDONLY);
+  if(sendfile(fds[1], fd, 0, 26) != 26 || (n = read(fds[0], buf, 100)) != 26 ||
+     buf[0] != 'a' || buf[25] != 'z'){
+    printf(1, "sendfile to a pipe failed\n");
+    exit();
+  }
+  if(sendfile(fd, fds[0], 0, 1) != -1 || sendfile(fds[1], fds[0], 0, 1) != -1){
+    printf(1, "sendfile accepted a bad descriptor\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  close(fd);
+  close(fd2);
+  unlink("sendfile1");
+  unlink("sendfile2");
+  printf(1, "sendfile test OK\n");
+}
+
+// fcntl duplicates descriptors, and gets and sets their
+// flags and those of their files.
+void
+fcntltest(void)
+{
+  struct stat st;
+  int fd, fd2, fds[2];
+  char c;
+
+  printf(1, "fcntl test\n");
+
+  unlink("fcntlfile");
+  fd = open("fcntlfile", O_CREATE|O_RDWR);
+  if(fd < 0 || fcntl(fd, F_GETFL, 0) != O_RDWR || fcntl(fd, F_GETFD, 0) != 0){
+    printf(1, "fcntl F_GETFL or F_GETFD failed\n");
+    exit();
+  }
+  fd2 = fcntl(fd, F_DUPFD_CLOEXEC, 10);
+  if(fd2 < 10 || fcntl(fd2, F_GETFD, 0) != FD_CLOEXEC ||
+     fcntl(fd2, F_SETFD, 0) != 0 || fcntl(fd2, F_GETFD, 0) != 0){
+    printf(1, "fcntl F_DUPFD_CLOEXEC or F_SETFD failed\n");
+    exit();
+  }
+  // Appends go at the end, even after a write through the other.
+  if(write(fd, "ab", 2) != 2 || fcntl(fd2, F_SETFL, O_APPEND) != 0 ||
+     fcntl(fd, F_GETFL, 0) != (O_RDWR|O_APPEND)){
+    printf(1, "fcntl F_SETFL failed\n");
+    exit();
+  }
+  close(fd);
+  fd = open("fcntlfile", O_RDWR);
+  if(write(fd, "x", 1) != 1 || write(fd2, "c", 1) != 1 ||
+     fstat(fd, &st) != 0 || st.size != 3){
+    printf(1, "O_APPEND write didn't go at the end\n");
+    exit();
+  }
+  close(fd);
+  close(fd2);
+  // O_CLOEXEC marks the descriptor, but not dups of it.
+  fd = open("fcntlfile", O_RDONLY|O_CLOEXEC);
+  fd2 = dup(fd);
+  if(fcntl(fd, F_GETFD, 0) != FD_CLOEXEC || fcntl(fd2, F_GETFD, 0) != 0 ||
+     fcntl(fd, F_GETFL, 0) != O_RDONLY){
+    printf(1, "open O_CLOEXEC failed\n");
+    exit();
+  }
+  close(fd);
+  close(fd2);
+  unlink("fcntlfile");
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  if(fcntl(fds[0], F_SETFL, O_NONBLOCK) != 0 || read(fds[0], &c, 1) != -1){
+    printf(1, "non-blocking pipe read didn't fail\n");
+    exit();
+  }
+  if(fcntl(fds[0], F_DUPFD, NOFILE) != -1 || fcntl(fds[0], 99, 0) != -1 ||
+     fcntl(NOFILE, F_GETFL, 0) != -1){
+    printf(1, "fcntl accepted bad arguments\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  printf(1, "fcntl test OK\n");
+}
+
+// A pipe holds a page until F_SETPIPE_SZ grows it, keeping
+// what's in it, and can't shrink below what it holds.
+void
+pipesizetest(void)
+{
+  static char buf[4*4096];
+  int fds[2], i, n;
+
+  printf(1, "pipe size test\n");
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  if(fcntl(fds[0], F_GETPIPE_SZ, 0) != 4096){
+    printf(1, "new pipe isn't a page\n");
+    exit();
+  }
+  for(i = 0; i < sizeof(buf); i++)
+    buf[i] = i % 251;
+  if(write(fds[1], buf, 100) != 100 ||
+     fcntl(fds[1], F_SETPIPE_SZ, 3*4096) != 4*4096 ||
+     fcntl(fds[0], F_GETPIPE_SZ, 0) != 4*4096 ||
+     write(fds[1], buf+100, sizeof(buf)-100) != sizeof(buf)-100){
+    printf(1, "growing a pipe failed\n");
+    exit();
+  }
+  if(fcntl(fds[1], F_SETPIPE_SZ, 4096) != -1 ||
+     fcntl(fds[1], F_SETPIPE_SZ, 1024*1024) != -1 ||
+     fcntl(0, F_GETPIPE_SZ, 0) != -1){
+    printf(1, "F_SETPIPE_SZ accepted bad sizes\n");
+    exit();
+  }
+  memset(buf, 0, sizeof(buf));
+  for(n = 0; n < sizeof(buf); n += i)
+    if((i = read(fds[0], buf+n, sizeof(buf)-n)) <= 0)
+      break;
+  for(i = 0; i < sizeof(buf); i++)
+    if(buf[i] != (char)(i % 251)){
+      printf(1, "pipe lost data at %d\n", i);
+      exit();
+    }
+  if(fcntl(fds[0], F_SETPIPE_SZ, 1) != 4096){
+    printf(1, "shrinking an empty pipe failed\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  printf(1, "pipe size test OK\n");
+}
+
+// poll reports what's ready on pipes, waits for a writer in
+// another process, and times out.
+void
+polltest(void)
+{
+  struct pollfd pf[3];
+  int fds[2], pid, t;
+  char c;
+
+  printf(1, "poll test\n");
+
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  pf[0].fd = fds[0];
+  pf[0].events = POLLIN;
+  pf[1].fd = fds[1];
+  pf[1].events = POLLIN | POLLOUT;
+  pf[2].fd = 99;
+  pf[2].events = POLLIN;
+  if(poll(pf, 3, 0) != 2 || pf[0].revents != 0 ||
+     pf[1].revents != POLLOUT || pf[2].revents != POLLNVAL){
+    printf(1, "poll of an empty pipe failed\n");
+    exit();
+  }
+  t = uptime();
+  if(poll(pf, 1, 50) != 0 || uptime() - t < 3){
+    printf(1, "poll didn't time out\n");
+    exit();
+  }
+  pid = fork();
+  if(pid == 0){
+    sleep(5);
+    write(fds[1], "x", 1);
+    exit();
+  }
+  if(poll(pf, 1, -1) != 1 || pf[0].revents != POLLIN ||
+     read(fds[0], &c, 1) != 1 || c != 'x'){
+    printf(1, "poll didn't see the write\n");
+    exit();
+  }
+  wait();
+  close(fds[1]);
+  if(poll(pf, 1, -1) != 1 || pf[0].revents != POLLHUP){
+    printf(1, "poll didn't see the writer close\n");
+    exit();
+  }
+  close(fds[0]);
+  if(poll(pf, 1, 0) != 1 || pf[0].revents != POLLNVAL ||
+     poll((struct pollfd*)0xffffffff, 1, 0) != -1 || poll(pf, -1, 0) != -1){
+    printf(1, "poll accepted bad arguments\n");
+    exit();
+  }
+  printf(1, "poll test OK\n");
+}
+
+// init makes /dev/mouse, which reads only whole events and
+// can't be written.  With O_NONBLOCK, a read fails rather
+// than wait for the mouse to move.
+void
+mousetest(void)
+{
+  struct mouseevent e;
+  int fd;
+
+  printf(1, "mouse test\n");
+
+  fd = open("/dev/mouse", O_RDWR);
+  if(fd < 0){
+    printf(1, "open /dev/mouse failed\n");
+    exit();
+  }
+  if(read(fd, (char*)&e, sizeof(e)-1) != -1 || write(fd, "x", 1) != -1){
+    printf(1, "mouse accepted a short read or a write\n");
+    exit();
+  }
+  close(fd);
+  fd = open("/dev/mouse", O_RDONLY|O_NONBLOCK);
+  if(fd < 0 || read(fd, (char*)&e, sizeof(e)) != -1){
+    printf(1, "non-blocking mouse read didn't fail\n");
+    exit();
+  }
+  close(fd);
+  printf(1, "mouse test OK\n");
+}
+
+// ioctl gets and sets the console's settings, and only the
+// console's.
+void
+ioctltest(void)
+{
+  struct termios t, old;
+  int fds[2], fd;
+
+  printf(1, "ioctl test\n");
+
+  if(ioctl(1, TCGETS, &old) != 0 || !(old.lflag & ICANON)){
+    printf(1, "ioctl TCGETS failed\n");
+    exit();
+  }
+  t = old;
+  t.lflag &= ~(ICANON | ECHO);
+  if(ioctl(1, TCSETS, &t) != 0 || ioctl(1, TCGETS, &t) != 0 ||
+     (t.lflag & (ICANON | ECHO)) != 0 || !(t.lflag & ISIG)){
+    ioctl(1, TCSETS, &old);
+    printf(1, "ioctl TCSETS failed\n");
+    exit();
+  }
+  ioctl(1, TCSETS, &old);
+  if(ioctl(1, 99, &t) != -1 || ioctl(1, TCGETS, (void*)0xffffffff) != -1){
+    printf(1, "ioctl accepted bad arguments\n");
+    exit();
+  }
+  if(pipe(fds) != 0){
+    printf(1, "pipe failed\n");
+    exit();
+  }
+  fd = open("ioctlfile", O_CREATE|O_RDWR);
+  if(ioctl(fds[0], TCGETS, &t) != -1 || ioctl(fd, TCGETS, &t) != -1){
+    printf(1, "ioctl on a pipe or file succeeded\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  close(fd);
+  unlink("ioctlfile");
+  printf(1, "ioctl test OK\n");
+}
+
+// irqaffinity moves the disk interrupt to CPU 0, where the
+// next disk writes are then counted.
+void
+irqaffinitytest(void)
+{
+  static uint c[256];
+  uint n0;
+  int fd;
+
+  printf(1, "irqaffinity test\n");
+
+  if(irqaffinity(-1, 1) != -1 || irqaffinity(IRQ_IDE, 0) != -1 ||
+     irqaffinity(3, 1) != -1){
+    printf(1, "irqaffinity accepted bad arguments\n");
+    exit();
+  }
+  if(irqaffinity(IRQ_IDE, 1) != 0){
+    printf(1, "irqaffinity failed\n");
+    exit();
+  }
+  intrinfo(0, c);
+  n0 = c[T_IRQ0 + IRQ_IDE];
+  fd = open("irqaff", O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, c, sizeof(c)) != sizeof(c)){
+    printf(1, "write irqaff failed\n");
+    exit();
+  }
+  close(fd);
+  unlink("irqaff");
+  intrinfo(0, c);
+  if(c[T_IRQ0 + IRQ_IDE] == n0){
+    printf(1, "disk interrupt not moved to cpu 0\n");
+    exit();
+  }
+  irqaffinity(IRQ_IDE, ~0);
+
+  printf(1, "irqaffinity test OK\n");
+}
+
+// the monotonic clock advances across a sleep, and the
+// real-time clock is past 2020.
+void
+clocktest(void)
+{
+  struct timespec t0, t1;
+  struct timeval tv;
+  struct rtcdate r;
+  struct stat st;
+  int up, fd;
+
+  printf(1, "clock test\n");
+
+  if(clock_gettime(CLOCK_MONOTONIC, &t0) < 0){
+    printf(1, "clock_gettime failed\n");
+    exit();
+  }
+  up = uptime();
+  sleep(10);
+  clock_gettime(CLOCK_MONOTONIC, &t1);
+  if(uptime() - up < 10){
+    printf(1, "sleep woke early\n");
+    exit();
+  }
+  if(t1.tv_nsec >= 1000000000 ||
+     t1.tv_sec < t0.tv_sec ||
+     (t1.tv_sec == t0.tv_sec && t1.tv_nsec <= t0.tv_nsec)){
+    printf(1, "monotonic clock did not advance\n");
+    exit();
+  }
+  if(gettimeofday(&tv) < 0 || tv.tv_sec < 1577836800 ||
+     tv.tv_usec >= 1000000){
+    printf(1, "bad time of day %d\n", tv.tv_sec);
+    exit();
+  }
+  if(clock_gettime(99, &t0) != -1){
+    printf(1, "clock_gettime accepted a bad clock\n");
+    exit();
+  }
+
+  // a sub-tick sleep lasts at least as long as asked
+  clock_gettime(CLOCK_MONOTONIC, &t0);
+  if(usleep(2000) != 0){
+    printf(1, "usleep failed\n");
+    exit();
+  }
+  clock_gettime(CLOCK_MONOTONIC, &t1);
+  if((t1.tv_sec - t0.tv_sec)*1000000000 + t1.tv_nsec - t0.tv_nsec < 2000000){
+    printf(1, "usleep woke early\n");
+    exit();
+  }
+
+  // the RTC reads sensibly, and a file written now carries the
+  // time of day as its modification time
+  if(date(&r) < 0 || r.year < 2020 || r.month < 1 || r.month > 12 ||
+     r.day < 1 || r.day > 31 || r.hour > 23 || r.minute > 59 ||
+     r.second > 59){
+    printf(1, "bad rtc date\n");
+    exit();
+  }
+  fd = open("mtime", O_CREATE|O_RDWR);
+  if(fd < 0 || write(fd, "x", 1) != 1 || fstat(fd, &st) < 0){
+    printf(1, "mtime file failed\n");
+    exit();
+  }
+  close(fd);
+  unlink("mtime");
+  gettimeofday(&tv);
+  if(st.mtime > tv.tv_sec || st.mtime + 2 < tv.tv_sec){
+    printf(1, "bad mtime %d at %d\n", st.mtime, tv.tv_sec);
+    exit();
+  }
+
+  printf(1, "clock test OK\n");
+}
+
+// the RTC's alarm interrupt wakes a process sleeping on it.
+void
+rtcalarmtest(void)
+{
+  struct timeval t0, t1;
+
+  printf(1, "rtc alarm test\n");
+  if(rtcalarm(0) != -1){
+    printf(1, "rtcalarm accepted 0 seconds\n");
+    exit();
+  }
+  gettimeofday(&t0);
+  if(rtcalarm(1) != 0){
+    printf(1, "rtcalarm failed\n");
+    exit();
+  }
+  gettimeofday(&t1);
+  if(t1.tv_sec - t0.tv_sec > 3){
+    printf(1, "rtc alarm late\n");
+    exit();
+  }
+  printf(1, "rtc alarm test OK\n");
+}
+
+void
+sbrktest(void)
+{
+  int fds[2], pid, pids[10], ppid;
+  char *a, *b, *c, *lastaddr, *oldbrk, *p, scratch;
+  uint amt;
+
+  printf(stdout, "sbrk test\n");
+  oldbrk = sbrk(0);
+
+  // can one sbrk() less than a page?
+  a = sbrk(0);
+  int i;
+  for(i = 0; i < 5000; i++){
+    b = sbrk(1);
+    if(b != a){
+      printf(stdout, "sbrk test failed %d %x %x\n", i, a, b);
+      exit();
+    }
+    *b = 1;
+    a = b + 1;
+  }
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "sbrk test fork failed\n");
+    exit();
+  }
+  c = sbrk(1);
+  c = sbrk(1);
+  if(c != a + 1){
+    printf(stdout, "sbrk test failed post-fork\n");
+    exit();
+  }
+  if(pid == 0)
+    exit();
+  wait();
+
+  // can one grow address space to something big?
+#define BIG (100*1024*1024)
+  a = sbrk(0);
+  amt = (BIG) - (uint)a;
+  p = sbrk(amt);
+  if (p != a) {
+    printf(stdout, "sbrk test failed to grow big address space; enough phys mem?\n");
+    exit();
+  }
+  lastaddr = (char*) (BIG-1);
+  *lastaddr = 99;
+
+  // can one de-allocate?
+  a = sbrk(0);
+  c = sbrk(-4096);
+  if(c == (char*)0xffffffff){
+    printf(stdout, "sbrk could not deallocate\n");
+    exit();
+  }
+  c = sbrk(0);
+  if(c != a - 4096){
+    printf(stdout, "sbrk deallocation produced wrong address, a %x c %x\n", a, c);
+    exit();
+  }
+
+  // can one re-allocate that page?
+  a = sbrk(0);
+  c = sbrk(4096);
+  if(c != a || sbrk(0) != a + 4096){
+    printf(stdout, "sbrk re-allocation failed, a %x c %x\n", a, c);
+    exit();
+  }
+  if(*lastaddr == 99){
+    // should be zero
+    printf(stdout, "sbrk de-allocation didn't really deallocate\n");
+    exit();
+  }
+
+  a = sbrk(0);
+  c = sbrk(-(sbrk(0) - oldbrk));
+  if(c != a){
+    printf(stdout, "sbrk downsize failed, a %x c %x\n", a, c);
+    exit();
+  }
+
+  // can we read the kernel's memory?
+  for(a = (char*)(KERNBASE); a < (char*) (KERNBASE+2000000); a += 50000){
+    ppid = getpid();
+    pid = fork();
+    if(pid < 0){
+      printf(stdout, "fork failed\n");
+      exit();
+    }
+    if(pid == 0){
+      printf(stdout, "oops could read %x = %x\n", a, *a);
+      kill(ppid, SIGKILL);
+      exit();
+    }
+    wait();
+  }
+
+  // if we run the system out of memory, does it clean up the last
+  // failed allocation?
+  if(pipe(fds) != 0){
+    printf(1, "pipe() failed\n");
+    exit();
+  }
+  for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
+    if((pids[i] = fork()) == 0){
+      // allocate a lot of memory
+      sbrk(BIG - (uint)sbrk(0));
+      write(fds[1], "x", 1);
+      // sit around until killed
+      for(;;) sleep(1000);
+    }
+    if(pids[i] != -1)
+      read(fds[0], &scratch, 1);
+  }
+  // if those failed allocations freed up the pages they did allocate,
+  // we'll be able to allocate here
+  c = sbrk(4096);
+  for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
+    if(pids[i] == -1)
+      continue;
+    kill(pids[i], SIGKILL);
+    wait();
+  }
+  if(c == (char*)0xffffffff){
+    printf(stdout, "failed sbrk leaked memory\n");
+    exit();
+  }
+
+  if(sbrk(0) > oldbrk)
+    sbrk(-(sbrk(0) - oldbrk));
+
+  printf(stdout, "sbrk test OK\n");
+}
+
+// sbrk can grow the heap past the size of physical memory,
+// since pages are only allocated when touched, by the process
+// or by the kernel on its behalf.
+void
+lazytest(void)
+{
+  char *a, *p;
+  int fds[2], pid;
+  uint amt;
+
+  printf(stdout, "lazy sbrk test\n");
+
+  amt = 300*1024*1024;
+  a = sbrk(amt);
+  if(a == (char*)-1){
+    printf(stdout, "lazy sbrk failed\n");
+    exit();
+  }
+  p = a + amt - 4096;
+  *p = 'x';
+  if(a[amt/2] != 0){
+    printf(stdout, "untouched page not zero\n");
+    exit();
+  }
+
+  // the kernel writes to an untouched page
+  if(pipe(fds) != 0){
+    printf(stdout, "pipe failed\n");
+    exit();
+  }
+  write(fds[1], "y", 1);
+  if(read(fds[0], a + amt/4, 1) != 1 || a[amt/4] != 'y'){
+    printf(stdout, "read into untouched page failed\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+
+  // fork copies only the touched pages
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    if(*p != 'x' || a[amt/4] != 'y' || a[amt/8] != 0)
+      printf(stdout, "child sees wrong heap\n");
+    exit();
+  }
+  wait();
+
+  if(sbrk(-amt) == (char*)-1){
+    printf(stdout, "lazy sbrk shrink failed\n");
+    exit();
+  }
+
+  printf(stdout, "lazy sbrk test OK\n");
+}
+
+// Initialized data spanning several pages of the executable,
+// which exec leaves on disk until the program touches it.
+int demandwords[3*1024] = { [0] = 1, [1024] = 2, [2048] = 3, [3*1024-1] = 4 };
+
+// exec reads program pages in on demand.  Check that data
+// pages come in right, whether the program, a forked child,
+// or the kernel touches them first.
+void
+demandtest(void)
+{
+  int fds[2], pid, w;
+
+  printf(stdout, "demand paging test\n");
+
+  if(pipe(fds) != 0){
+    printf(stdout, "pipe failed\n");
+    exit();
+  }
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    // the kernel reads an untouched page
+    write(fds[1], &demandwords[2048], sizeof(int));
+    if(demandwords[1024] != 2 || demandwords[3*1024-1] != 4)
+      printf(stdout, "child sees wrong data\n");
+    exit();
+  }
+  wait();
+  if(read(fds[0], &w, sizeof(w)) != sizeof(w) || w != 3){
+    printf(stdout, "kernel read wrong data\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+  if(demandwords[0] != 1 || demandwords[1] != 0 || demandwords[2048] != 3){
+    printf(stdout, "wrong data\n");
+    exit();
+  }
+
+  printf(stdout, "demand paging test OK\n");
+}
+
+// mmap: anonymous and file-backed mappings, private mappings
+// copied on write across fork, shared ones shared, and munmap.
+void
+mmaptest(void)
+{
+  char *p, *q, *f, buf[100];
+  int fd, fds[2], i, pid;
+
+  printf(stdout, "mmap test\n");
+
+  p = mmap(0, 3*4096, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0);
+  q = mmap(0, 4096, PROT_READ|PROT_WRITE, MAP_SHARED|MAP_ANONYMOUS, -1, 0);
+  if(p == MAP_FAILED || q == MAP_FAILED){
+    printf(stdout, "mmap failed\n");
+    exit();
+  }
+  if(p[0] != 0 || p[2*4096+10] != 0){
+    printf(stdout, "anonymous mapping not zero\n");
+    exit();
+  }
+  p[0] = 'a';
+  q[0] = 'a';
+
+  // the kernel reads and writes mapped memory
+  if(pipe(fds) != 0){
+    printf(stdout, "pipe failed\n");
+    exit();
+  }
+  if(write(fds[1], p, 1) != 1 || read(fds[0], p + 4096, 1) != 1 || p[4096] != 'a'){
+    printf(stdout, "pipe through mapping failed\n");
+    exit();
+  }
+  close(fds[0]);
+  close(fds[1]);
+
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    if(p[0] != 'a' || p[4096] != 'a')
+      printf(stdout, "child sees wrong private mapping\n");
+    p[0] = 'c';
+    q[0] = 'c';
+    exit();
+  }
+  wait();
+  if(p[0] != 'a'){
+    printf(stdout, "child's write to private mapping seen\n");
+    exit();
+  }
+  if(q[0] != 'c'){
+    printf(stdout, "child's write to shared mapping not seen\n");
+    exit();
+  }
+
+  // file-backed, with the mapping running past the end of the file
+  fd = open("mmapfile", O_CREATE|O_RDWR);
+  for(i = 0; i < sizeof(buf); i++)
+    buf[i] = i;
+  for(i = 0; i < 50; i++)
+    write(fd, buf, sizeof(buf));
+  f = mmap(0, 2*4096, PROT_READ|PROT_WRITE, MAP_PRIVATE, fd, 0);
+  close(fd);
+  if(f == MAP_FAILED){
+    printf(stdout, "file mmap failed\n");
+    exit();
+  }
+  for(i = 0; i < 2*4096; i++){
+    if(f[i] != (i < 5000 ? i % 100 : 0)){
+      printf(stdout, "file mapping wrong at %d\n", i);
+      exit();
+    }
+  }
+  f[0] = 'x';
+  fd = open("mmapfile", O_RDONLY);
+  if(read(fd, buf, 1) != 1 || buf[0] != 0){
+    printf(stdout, "private mapping changed file\n");
+    exit();
+  }
+  close(fd);
+  unlink("mmapfile");
+
+  if(munmap(p, 3*4096) < 0 || munmap(q, 4096) < 0 || munmap(f, 2*4096) < 0){
+    printf(stdout, "munmap failed\n");
+    exit();
+  }
+  pid = fork();
+  if(pid == 0){
+    printf(stdout, "unmapped page readable: %x\n", p[0]);
+    exit();
+  }
+  wait();
+
+  printf(stdout, "mmap test OK\n");
+}
+
+// Shared file mappings: stores reach the file at msync or
+// munmap, separate mappings of a file share pages, and write
+// system calls show up in mappings.
+void
+mmapsharedtest(void)
+{
+  char *f, *g, buf[10];
+  int fd, i, pid;
+  struct stat st;
+
+  printf(stdout, "mmap shared file test\n");
+
+  fd = open("mmapshared", O_CREATE|O_RDWR);
+  for(i = 0; i < 3000; i++)
+    write(fd, "a", 1);
+  f = mmap(0, 4096, PROT_READ|PROT_WRITE, MAP_SHARED, fd, 0);
+  if(f == MAP_FAILED || f[0] != 'a' || f[2999] != 'a' || f[3000] != 0){
+    printf(stdout, "shared file mmap failed\n");
+    exit();
+  }
+  f[0] = 'b';
+  if(msync(f, 4096, MS_SYNC) < 0){
+    printf(stdout, "msync failed\n");
+    exit();
+  }
+  close(fd);
+  fd = open("mmapshared", O_RDWR);
+  if(read(fd, buf, 2) != 2 || buf[0] != 'b' || buf[1] != 'a'){
+    printf(stdout, "msync didn't write the file\n");
+    exit();
+  }
+
+  // a write system call shows up in the mapping
+  write(fd, "w", 1);
+  if(f[2] != 'w'){
+    printf(stdout, "mapping doesn't see write\n");
+    exit();
+  }
+
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    g = mmap(0, 4096, PROT_READ|PROT_WRITE, MAP_SHARED, fd, 0);
+    if(g == MAP_FAILED || g == f || g[0] != 'b')
+      printf(stdout, "second mapping wrong\n");
+    else
+      g[1] = 'c';
+    exit();
+  }
+  wait();
+  if(f[1] != 'c'){
+    printf(stdout, "mappings don't share pages\n");
+    exit();
+  }
+  f[3500] = 'z';
+  if(munmap(f, 4096) < 0){
+    printf(stdout, "munmap failed\n");
+    exit();
+  }
+  close(fd);
+
+  // stores past the end of the file don't extend it
+  fd = open("mmapshared", O_RDONLY);
+  if(read(fd, buf, 3) != 3 || buf[1] != 'c' || fstat(fd, &st) < 0 ||
+     st.size != 3000){
+    printf(stdout, "file wrong after munmap\n");
+    exit();
+  }
+  close(fd);
+  unlink("mmapshared");
+
+  printf(stdout, "mmap shared file test OK\n");
+}
+
+// System V shared memory: a segment attached before fork is
+// shared with the child, one found by key is shared with an
+// unrelated attach, and the last detach removes the segment.
+void
+shmtest(void)
+{
+  char *p, *q;
+  int id, pid;
+
+  printf(stdout, "shm test\n");
+
+  id = shmget(1234, 2*4096, IPC_CREAT|IPC_EXCL);
+  if(id < 0){
+    printf(stdout, "shmget failed\n");
+    exit();
+  }
+  if(shmget(1234, 4096, IPC_CREAT|IPC_EXCL) >= 0 || shmget(1234, 0, 0) != id){
+    printf(stdout, "shmget by key wrong\n");
+    exit();
+  }
+  p = shmat(id, 0, 0);
+  if(p == (char*)-1 || p[0] != 0){
+    printf(stdout, "shmat failed\n");
+    exit();
+  }
+  p[0] = 'p';
+
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    p[1] = 'c';
+    q = shmat(shmget(1234, 0, 0), 0, 0);
+    if(q == (char*)-1 || q == p || q[0] != 'p'){
+      printf(stdout, "second attach wrong\n");
+      exit();
+    }
+    q[4096] = 'q';
+    shmdt(q);
+    exit();
+  }
+  wait();
+  if(p[1] != 'c' || p[4096] != 'q'){
+    printf(stdout, "child's writes not shared\n");
+    exit();
+  }
+
+  if(shmdt(p) < 0 || shmdt(p) >= 0){
+    printf(stdout, "shmdt wrong\n");
+    exit();
+  }
+  if(shmget(1234, 0, 0) >= 0){
+    printf(stdout, "segment outlived its last detach\n");
+    exit();
+  }
+
+  // removing an unattached segment
+  id = shmget(IPC_PRIVATE, 4096, 0);
+  if(id < 0 || shmctl(id, IPC_RMID, 0) < 0 || shmat(id, 0, 0) != (char*)-1){
+    printf(stdout, "IPC_RMID failed\n");
+    exit();
+  }
+
+  printf(stdout, "shm test OK\n");
+}
+
+void
+validateint(int *p)
+{
+  int res;
+  asm("mov %%esp, %%ebx\n\t"
+      "mov %3, %%esp\n\t"
+      "int %2\n\t"
+      "mov %%ebx, %%esp" :
+      "=a" (res) :
+      "a" (SYS_sleep), "n" (T_SYSCALL), "c" (p) :
+      "ebx");
+}
+
+// exec may place the heap, mmap areas, and stack at random,
+// but keeps them in that order.
+void
+layouttest(void)
+{
+  char *brk, *p, c;
+
+  printf(stdout, "layout test\n");
+  brk = sbrk(0);
+  p = mmap(0, 4096, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0);
+  if(p == MAP_FAILED){
+    printf(stdout, "mmap failed\n");
+    exit();
+  }
+  if(!(brk <= p && p < &c && (uint)&c < KERNBASE)){
+    printf(stdout, "bad layout: heap %x mmap %x stack %x\n", brk, p, &c);
+    exit();
+  }
+  munmap(p, 4096);
+  printf(stdout, "layout test ok\n");
+}
+
+// the kernel copies system call arguments through the page
+// table, including from memory it has not touched yet.
+void
+copytest(void)
+{
+  char *p, *q, name[MAXPATH+10];
+  int fd, i;
+
+  printf(stdout, "copy test\n");
+
+  memset(name, 'a', sizeof(name));
+  name[sizeof(name)-1] = 0;
+  if(open(name, O_CREATE|O_RDWR) >= 0){
+    printf(stdout, "open of over-long path succeeded\n");
+    exit();
+  }
+
+  p = mmap(0, 3*4096, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0);
+  q = mmap(0, 4096, PROT_READ, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0);
+  if(p == MAP_FAILED || q == MAP_FAILED){
+    printf(stdout, "mmap failed\n");
+    exit();
+  }
+  strcpy(p + 4096 - 4, "copyf");  // straddles a page boundary
+  fd = open(p + 4096 - 4, O_CREATE|O_RDWR);
+  if(fd < 0){
+    printf(stdout, "open of path in mapping failed\n");
+    exit();
+  }
+  for(i = 0; i < 4096; i++)
+    p[i] = i;
+  if(write(fd, p, 4096) != 4096 || write(fd, p + 2*4096, 100) != 100){
+    printf(stdout, "write from mapping failed\n");
+    exit();
+  }
+  close(fd);
+  fd = open("copyf", O_RDONLY);
+  if(read(fd, q, 10) != -1){
+    printf(stdout, "read into read-only mapping succeeded\n");
+    exit();
+  }
+  close(fd);
+  fd = open("copyf", O_RDONLY);
+  if(read(fd, p + 4096 + 100, 4096 + 100) != 4096 + 100){
+    printf(stdout, "read into mapping failed\n");
+    exit();
+  }
+  for(i = 0; i < 4096; i++){
+    if(p[4096 + 100 + i] != (char)i){
+      printf(stdout, "read wrong data into mapping\n");
+      exit();
+    }
+  }
+  close(fd);
+  unlink("copyf");
+
+  // Structures the kernel fills in go through the same checks.
+  if(clock_gettime(CLOCK_MONOTONIC, (struct timespec*)q) != -1 ||
+     clock_gettime(CLOCK_MONOTONIC, (struct timespec*)KERNBASE) != -1){
+    printf(stdout, "clock_gettime into bad memory succeeded\n");
+    exit();
+  }
+  if(clock_gettime(CLOCK_MONOTONIC, (struct timespec*)(p + 4096 - 4)) != 0){
+    printf(stdout, "clock_gettime into mapping failed\n");
+    exit();
+  }
+  munmap(p, 3*4096);
+  munmap(q, 4096);
+
+  printf(stdout, "copy test ok\n");
+}
+
+void
+validatetest(void)
+{
+  int hi, pid;
+  uint p;
+
+  printf(stdout, "validate test\n");
+  hi = 1100*1024;
+
+  for(p = 0; p <= (uint)hi; p += 4096){
+    if((pid = fork()) == 0){
+      // try to crash the kernel by passing in a badly placed integer
+      validateint((int*)p);
+      exit();
+    }
+    sleep(0);
+    sleep(0);
+    kill(pid, SIGKILL);
+    wait();
+
+    // try to crash the kernel by passing in a bad string pointer
+    if(link("nosuchfile", (char*)p) != -1){
+      printf(stdout, "link should not succeed\n");
+      exit();
+    }
+  }
+
+  printf(stdout, "validate ok\n");
+}
+
+// does unintialized data start out zero?
+char uninit[10000];
+void
+bsstest(void)
+{
+  int i;
+
+  printf(stdout, "bss test\n");
+  for(i = 0; i < sizeof(uninit); i++){
+    if(uninit[i] != '\0'){
+      printf(stdout, "bss test failed\n");
+      exit();
+    }
+  }
+  printf(stdout, "bss test ok\n");
+}
+
+// Recurse n levels deep, using about 1KB of stack per level.
+int
+recurse(int n)
+{
+  volatile char buf[1000];
+
+  buf[0] = n;
+  if(n == 0)
+    return 0;
+  return recurse(n - 1) + (buf[0] != (char)n);
+}
+
+// The stack grows on demand up to its limit, and running off
+// the end kills the process rather than writing over memory.
+void
+stacktest(void)
+{
+  int pid;
+
+  printf(stdout, "stack test\n");
+
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    if(recurse(500) != 0)
+      printf(stdout, "deep stack wrong\n");
+    exit();
+  }
+  wait();
+
+  pid = fork();
+  if(pid < 0){
+    printf(stdout, "fork failed\n");
+    exit();
+  }
+  if(pid == 0){
+    recurse(100000);
+    printf(stdout, "stack overflow not caught\n");
+    exit();
+  }
+  wait();
+
+  printf(stdout, "stack test OK\n");
 }
 
 // does exec return an error if the arguments
@@ -1764,10 +3501,18 @@ main(int argc, char *argv[])
   sharedfd();
 
   bigargtest();
+  stacktest();
   bigwrite();
   bigargtest();
   bsstest();
   sbrktest();
+  lazytest();
+  demandtest();
+  mmaptest();
+  mmapsharedtest();
+  shmtest();
+  copytest();
+  layouttest();
   validatetest();
 
   opentest();
@@ -1790,9 +3535,36 @@ main(int argc, char *argv[])
   subdir();
   linktest();
   unlinkread();
+  dcachetest();
   dirfile();
   iref();
   forktest();
+  clonetest();
+  signaltest();
+  pgrptest();
+  getppidtest();
+  pinfotest();
+  sysinfotest();
+  intrinfotest();
+  irqaffinitytest();
+  hotplugtest();
+  cpuinfotest();
+  ioctltest();
+  dmesgtest();
+  mousetest();
+  polltest();
+  pipesizetest();
+  dup2test();
+  fcntltest();
+  preadtest();
+  sendfiletest();
+  shebangtest();
+  envtest();
+  wxtest();
+  pietest();
+  errnotest();
+  clocktest();
+  rtcalarmtest();
   bigdir(); // slow
 
   uio();
@@ -0,0 +1,14 @@
+// The vDSO page, which the kernel maps read-only at VDSO (see
+// memlayout.h) in every user address space, so that programs
+// can read the time without a system call.  The clock tick
+// updates it; ulib's gettimeofday reads it.
+
+struct vdso {
+  volatile uint seq;   // Odd while the kernel is changing the page
+  uint boottime;       // Unix time when the clock started
+  uint64 ns;           // ns since boot at the last update
+  uint64 tsc;          // TSC at the last update, or 0 if the TSC isn't the clock
+  uint mult;           // ns = (TSC ticks * mult) >> VDSOSHIFT
+};
+
+#define VDSOSHIFT 24
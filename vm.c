@@ -6,10 +6,20 @@
 #include "mmu.h"
 #include "proc.h"
 #include "elf.h"
+#include "spinlock.h"
+#include "mman.h"
+#include "traps.h"
+#include "cpuinfo.h"
+#include "percpu.h"
+#include "ansi.h"
 
 extern char data[];  // defined by kernel.ld
 pde_t *kpgdir;  // for use in scheduler()
 
+// Serializes lazy allocation, so that threads faulting on
+// the same page don't both map it.
+static struct spinlock lazylock;
+
 // Set up CPU's kernel segment descriptors.
 // Run once on entry on each CPU.
 void
@@ -26,12 +36,34 @@ seginit(void)
   c->gdt[SEG_KDATA] = SEG(STA_W, 0, 0xffffffff, 0);
   c->gdt[SEG_UCODE] = SEG(STA_X|STA_R, 0, 0xffffffff, DPL_USER);
   c->gdt[SEG_UDATA] = SEG(STA_W, 0, 0xffffffff, DPL_USER);
+  c->gdt[SEG_UTLS] = SEG(STA_W, 0, 0xffffffff, DPL_USER);
+  c->gdt[SEG_KCPU] = SEG(STA_W, percpuoff[c - cpus], 0xffffffff, 0);
   lgdt(c->gdt, sizeof(c->gdt));
+  loadgs(SEG_KCPU << 3);
+}
+
+// Turn on SMEP and SMAP if the CPU has them, so that the kernel
+// faults if it executes or touches a user page.  The kernel only
+// reaches user memory through its own mapping of the page (see
+// copyin and copyout), so it never needs to lift SMAP with stac.
+// Run once on entry on each CPU.
+void
+smapinit(void)
+{
+  uint cr4;
+
+  cr4 = rcr4();
+  if(cpuhas(CPUF_SMEP))
+    cr4 |= CR4_SMEP;
+  if(cpuhas(CPUF_SMAP))
+    cr4 |= CR4_SMAP;
+  lcr4(cr4);
 }
 
 // Return the address of the PTE in page table pgdir
 // that corresponds to virtual address va.  If alloc!=0,
-// create any required page table pages.
+// create any required page table pages.  Returns 0 for
+// addresses in a 4MB kernel page, which have no PTE.
 static pte_t *
 walkpgdir(pde_t *pgdir, const void *va, int alloc)
 {
@@ -39,6 +71,8 @@ walkpgdir(pde_t *pgdir, const void *va, int alloc)
   pte_t *pgtab;
 
   pde = &pgdir[PDX(va)];
+  if(*pde & PTE_PS)
+    return 0;
   if(*pde & PTE_P){
     pgtab = (pte_t*)P2V(PTE_ADDR(*pde));
   } else {
@@ -87,18 +121,21 @@ mappages(pde_t *pgdir, void *va, uint size, uint pa, int perm)
 //
 // setupkvm() and exec() set up every page table like this:
 //
-//   0..KERNBASE: user memory (text+data+stack+heap), mapped to
-//                phys memory allocated by the kernel
+//   0..KERNBASE: user memory (text+data+heap, mmap'd areas, and
+//                the stack), mapped to phys memory allocated by
+//                the kernel
 //   KERNBASE..KERNBASE+EXTMEM: mapped to 0..EXTMEM (for I/O space)
 //   KERNBASE+EXTMEM..data: mapped to EXTMEM..V2P(data)
 //                for the kernel's instructions and r/o data
 //   data..KERNBASE+PHYSTOP: mapped to V2P(data)..PHYSTOP,
 //                                  rw data + free physical memory
+//   FBBASE..DEVSPACE: mapped to the framebuffer, if any (see fbmap)
 //   0xfe000000..0: mapped direct (devices such as ioapic)
 //
 // The kernel allocates physical memory for its heap and for user memory
 // between V2P(end) and the end of physical memory (PHYSTOP)
-// (directly addressable from end..P2V(PHYSTOP)).
+// (directly addressable from end..P2V(PHYSTOP)), using what the
+// firmware's memory map says is RAM (see kinit2).
 
 // This table defines the kernel's mappings, which are present in
 // every process's page table.
@@ -112,8 +149,36 @@ static struct kmap {
  { (void*)KERNLINK, V2P(KERNLINK), V2P(data), 0},     // kern text+rodata
  { (void*)data,     V2P(data),     PHYSTOP,   PTE_W}, // kern data+memory
  { (void*)DEVSPACE, DEVSPACE,      0,         PTE_W}, // more devices
+ { (void*)FBBASE,   0,             0,         PTE_W}, // framebuffer (fbmap)
 };
 
+// Like mappages, but use 4MB pages (entry.S turned on CR4_PSE)
+// for the parts of the range that are aligned for them, so
+// that the kernel's mappings take no page table pages and
+// few TLB entries.
+static int
+kmappages(pde_t *pgdir, void *va, uint size, uint pa, int perm)
+{
+  uint a, n;
+
+  a = (uint)va;
+  while(size > 0){
+    if(a % PDSIZE == 0 && pa % PDSIZE == 0 && size >= PDSIZE &&
+       (pgdir[PDX(a)] & PTE_P) == 0){
+      pgdir[PDX(a)] = pa | perm | PTE_P | PTE_PS;
+      n = PDSIZE;
+    } else {
+      if(mappages(pgdir, (void*)a, PGSIZE, pa, perm) < 0)
+        return -1;
+      n = PGSIZE;
+    }
+    a += n;
+    pa += n;
+    size -= n;
+  }
+  return 0;
+}
+
 // Set up kernel part of a page table.
 pde_t*
 setupkvm(void)
@@ -127,19 +192,39 @@ setupkvm(void)
   if (P2V(PHYSTOP) > (void*)DEVSPACE)
     panic("PHYSTOP too high");
   for(k = kmap; k < &kmap[NELEM(kmap)]; k++)
-    if(mappages(pgdir, k->virt, k->phys_end - k->phys_start,
-                (uint)k->phys_start, k->perm) < 0) {
+    if(kmappages(pgdir, k->virt, k->phys_end - k->phys_start,
+                 (uint)k->phys_start, k->perm) < 0) {
       freevm(pgdir);
       return 0;
     }
   return pgdir;
 }
 
+// Map the framebuffer, size bytes at physical address pa, at
+// FBBASE in the kernel's page table and in those setupkvm makes
+// from now on.  Call before there are other page tables.
+void*
+fbmap(uint pa, uint size)
+{
+  struct kmap *k;
+
+  size = PGROUNDUP(size);
+  if(size > FBMAX)
+    return 0;
+  k = &kmap[NELEM(kmap)-1];
+  if(kmappages(kpgdir, k->virt, size, pa, k->perm) < 0)
+    return 0;
+  k->phys_start = pa;
+  k->phys_end = pa + size;
+  return k->virt;
+}
+
 // Allocate one page table for the machine for the kernel address
 // space for scheduler processes.
 void
 kvmalloc(void)
 {
+  initlock(&lazylock, "lazy");
   kpgdir = setupkvm();
   switchkvm();
 }
@@ -173,10 +258,39 @@ switchuvm(struct proc *p)
   // forbids I/O instructions (e.g., inb and outb) from user space
   mycpu()->ts.iomb = (ushort) 0xFFFF;
   ltr(SEG_TSS << 3);
+  // trapret reloads %fs, and with it this descriptor.
+  mycpu()->gdt[SEG_UTLS] = SEG(STA_W, p->tlsbase, 0xffffffff, DPL_USER);
   lcr3(V2P(p->pgdir));  // switch to process's address space
   popcli();
 }
 
+static void
+reloadcr3(void *unused)
+{
+  lcr3(rcr3());
+}
+
+// Flush stale translations for pgdir from the TLB of every
+// CPU that may be using it, after its PTEs have changed, by
+// reloading %cr3 there (see callmany), waiting until they
+// have.  Pages a PTE used to point to must not be reused until
+// this returns.
+void
+tlbflush(pde_t *pgdir)
+{
+  struct cpu *c;
+  struct proc *p;
+  uint mask;
+
+  __sync_synchronize();  // publish the PTEs before looking
+  mask = 0;
+  for(c = cpus; c < cpus+ncpu; c++)
+    if((p = c->proc) != 0 && p->pgdir == pgdir)
+      mask |= 1 << (c - cpus);
+  if(mask)
+    callmany(mask, reloadcr3, 0);
+}
+
 // Load the initcode into address 0 of pgdir.
 // sz must be less than a page.
 void
@@ -233,13 +347,13 @@ allocuvm(pde_t *pgdir, uint oldsz, uint newsz)
   for(; a < newsz; a += PGSIZE){
     mem = kalloc();
     if(mem == 0){
-      cprintf("allocuvm out of memory\n");
+      cprintf(KWARN "allocuvm:" KEND " out of memory\n");
       deallocuvm(pgdir, newsz, oldsz);
       return 0;
     }
     memset(mem, 0, PGSIZE);
     if(mappages(pgdir, (char*)a, PGSIZE, V2P(mem), PTE_W|PTE_U) < 0){
-      cprintf("allocuvm out of memory (2)\n");
+      cprintf(KWARN "allocuvm:" KEND " out of memory (2)\n");
       deallocuvm(pgdir, newsz, oldsz);
       kfree(mem);
       return 0;
@@ -257,23 +371,39 @@ deallocuvm(pde_t *pgdir, uint oldsz, uint newsz)
 {
   pte_t *pte;
   uint a, pa;
+  char *pages[64];
+  int i, n;
 
   if(newsz >= oldsz)
     return oldsz;
 
+  // Unmap a batch of pages, flush the TLBs that may still
+  // map them, and only then free them.
   a = PGROUNDUP(newsz);
-  for(; a  < oldsz; a += PGSIZE){
-    pte = walkpgdir(pgdir, (char*)a, 0);
-    if(!pte)
-      a = PGADDR(PDX(a) + 1, 0, 0) - PGSIZE;
-    else if((*pte & PTE_P) != 0){
-      pa = PTE_ADDR(*pte);
-      if(pa == 0)
-        panic("kfree");
-      char *v = P2V(pa);
-      kfree(v);
-      *pte = 0;
+  while(a < oldsz){
+    n = 0;
+    acquire(&lazylock);
+    for(; a < oldsz && n < NELEM(pages); a += PGSIZE){
+      pte = walkpgdir(pgdir, (char*)a, 0);
+      if(!pte)
+        a = PGADDR(PDX(a) + 1, 0, 0) - PGSIZE;
+      else if((*pte & PTE_P) != 0){
+        pa = PTE_ADDR(*pte);
+        if(pa == 0)
+          panic("kfree");
+        pages[n++] = P2V(pa);
+        *pte = 0;
+      } else if(*pte & PTE_SWAP){
+        swapfree(SWAPSLOT(*pte));
+        *pte = 0;
+      }
     }
+    release(&lazylock);
+    if(n == 0)
+      break;
+    tlbflush(pgdir);
+    for(i = 0; i < n; i++)
+      kfree(pages[i]);
   }
   return newsz;
 }
@@ -289,7 +419,7 @@ freevm(pde_t *pgdir)
     panic("freevm: no pgdir");
   deallocuvm(pgdir, KERNBASE, 0);
   for(i = 0; i < NPDENTRIES; i++){
-    if(pgdir[i] & PTE_P){
+    if((pgdir[i] & (PTE_P|PTE_PS)) == PTE_P){
       char * v = P2V(PTE_ADDR(pgdir[i]));
       kfree(v);
     }
@@ -297,17 +427,13 @@ freevm(pde_t *pgdir)
   kfree((char*)pgdir);
 }
 
-// Clear PTE_U on a page. Used to create an inaccessible
-// page beneath the user stack.
+// Free a user address space: its page table, memory,
+// and areas.
 void
-clearpteu(pde_t *pgdir, char *uva)
+freeuvm(pde_t *pgdir)
 {
-  pte_t *pte;
-
-  pte = walkpgdir(pgdir, uva, 0);
-  if(pte == 0)
-    panic("clearpteu");
-  *pte &= ~PTE_U;
+  vmafree(pgdir);
+  freevm(pgdir);
 }
 
 // Given a parent process's page table, create a copy
@@ -316,23 +442,48 @@ pde_t*
 copyuvm(pde_t *pgdir, uint sz)
 {
   pde_t *d;
-  pte_t *pte;
-  uint pa, i, flags;
-  char *mem;
+  pte_t *pte, *cpte;
+  uint i, e;
+  char *mem, *old;
 
   if((d = setupkvm()) == 0)
     return 0;
   for(i = 0; i < sz; i += PGSIZE){
-    if((pte = walkpgdir(pgdir, (void *) i, 0)) == 0)
-      panic("copyuvm: pte should exist");
-    if(!(*pte & PTE_P))
-      panic("copyuvm: page not present");
-    pa = PTE_ADDR(*pte);
-    flags = PTE_FLAGS(*pte);
-    if((mem = kalloc()) == 0)
+    // Skip heap pages that haven't been touched yet;
+    // the child will allocate its own.  Hold a reference
+    // to the page while copying it, so reclaim leaves it be.
+    acquire(&lazylock);
+    if((pte = walkpgdir(pgdir, (void *) i, 0)) == 0){
+      release(&lazylock);
+      i = PGADDR(PDX(i) + 1, 0, 0) - PGSIZE;
+      continue;
+    }
+    e = *pte;
+    old = 0;
+    if(e & PTE_P){
+      old = P2V(PTE_ADDR(e));
+      kref(old);
+    } else if(e & PTE_SWAP)
+      swapdup(SWAPSLOT(e));
+    release(&lazylock);
+    if(e & PTE_SWAP){
+      // Out on swap: share the slot.
+      if((cpte = walkpgdir(d, (void*)i, 1)) == 0){
+        swapfree(SWAPSLOT(e));
+        goto bad;
+      }
+      *cpte = e;
+      continue;
+    }
+    if(old == 0)
+      continue;
+    if((mem = kalloc()) == 0){
+      kfree(old);
       goto bad;
-    memmove(mem, (char*)P2V(pa), PGSIZE);
-    if(mappages(d, (void*)i, PGSIZE, V2P(mem), flags) < 0) {
+    }
+    memmove(mem, old, PGSIZE);
+    kfree(old);
+    if(mappages(d, (void*)i, PGSIZE, V2P(mem), PTE_FLAGS(e)) < 0) {
       kfree(mem);
       goto bad;
     }
@@ -344,6 +495,385 @@ bad:
   return 0;
 }
 
+// Heap pages are allocated lazily: sbrk only raises p->sz, and
+// each page gets memory when it is first touched.  Pages of
+// program segments and mmap'd areas are filled in from their
+// files or with zeros the same way (see vma.c).  The page fault
+// handler calls lazyalloc for faults in user space, as do kernel
+// paths that reach user memory other than by loads and stores,
+// such as futexes and ptrace.
+
+// Return the PTE of pgdir for user address va, or 0 if there is
+// none.  If the page is out on swap, take a reference to its
+// slot for lazypage, so that the slot stays put while the page
+// is read back in; lazymap or lazyfree drops it.
+uint
+uvmpte(pde_t *pgdir, uint va)
+{
+  pte_t *pte;
+  uint e;
+
+  acquire(&lazylock);
+  pte = walkpgdir(pgdir, (char*)va, 0);
+  e = pte ? *pte : 0;
+  if((e & (PTE_P|PTE_SWAP)) == PTE_SWAP)
+    swapdup(SWAPSLOT(e));
+  release(&lazylock);
+  return e;
+}
+
+// Return a new page holding the contents of the page of pgdir,
+// whose process has size sz, at user address va, whose PTE,
+// from uvmpte, is pte, and set *perm to the PTE bits it should
+// be mapped with.  Returns 0 if va is in neither the heap nor
+// an accessible area, memory ran out, or the file can't be
+// read.  Sleeps, so the caller must hold no spinlocks; pgdir is
+// only used to find the area.
+char*
+lazypage(pde_t *pgdir, uint sz, uint va, uint pte, int *perm)
+{
+  char *mem;
+  int prot;
+
+  if(pte & PTE_SWAP){
+    // Back in from swap.  *perm is the swap PTE, so that
+    // lazymap can tell whether the page is still out.
+    if((mem = kalloc()) == 0){
+      swapfree(SWAPSLOT(pte));
+      return 0;
+    }
+    swapread(SWAPSLOT(pte), mem);
+    *perm = pte;
+    return mem;
+  }
+  if(va >= KERNBASE || (mem = kalloc()) == 0)
+    return 0;
+  memset(mem, 0, PGSIZE);
+  switch(vmafill(pgdir, va, &mem, &prot)){
+  case 0:
+    // The heap.
+    if(va >= sz)
+      break;
+    *perm = PTE_W|PTE_U;
+    return mem;
+  case 1:
+    if(prot == PROT_NONE)
+      break;
+    *perm = PTE_U;
+    if(prot & PROT_WRITE)
+      *perm |= PTE_W;
+    return mem;
+  }
+  kfree(mem);
+  return 0;
+}
+
+// Map mem, from lazypage, at user address va in pgdir, unless
+// the page was mapped or swapped out meanwhile, perhaps by
+// another thread, in which case free mem.  Returns 0 if the
+// page is mapped for user access, or may be touched again to
+// fault it in, -1 if it is a kernel-only page or memory ran
+// out.
+int
+lazymap(pde_t *pgdir, uint va, char *mem, int perm)
+{
+  pte_t *pte;
+  int r;
+
+  va = PGROUNDDOWN(va);
+  r = 0;
+  acquire(&lazylock);
+  pte = walkpgdir(pgdir, (char*)va, 0);
+  if(perm & PTE_SWAP){
+    if(pte && *pte == perm){
+      *pte = V2P(mem) | (PTE_FLAGS(perm) & ~PTE_SWAP) | PTE_P | PTE_A;
+      swapfree(SWAPSLOT(perm));
+    } else
+      kfree(mem);
+    swapfree(SWAPSLOT(perm));  // uvmpte's reference
+  } else if(pte && (*pte & (PTE_P|PTE_SWAP))){
+    kfree(mem);
+    if((*pte & PTE_U) == 0)
+      r = -1;
+  } else if(mappages(pgdir, (char*)va, PGSIZE, V2P(mem), perm|PTE_A) < 0){
+    kfree(mem);
+    r = -1;
+  }
+  release(&lazylock);
+  return r;
+}
+
+// Free mem, from lazypage, instead of mapping it.
+void
+lazyfree(char *mem, int perm)
+{
+  kfree(mem);
+  if(perm & PTE_SWAP)
+    swapfree(SWAPSLOT(perm));
+}
+
+// Back user address va in pgdir, whose process has size sz,
+// with a page if it has none, reading it in from swap if it was
+// evicted.  Returns 0 if the page is mapped for user access,
+// -1 if va is out of range, is a kernel-only page, or can't be
+// backed.
+int
+lazyalloc(pde_t *pgdir, uint sz, uint va)
+{
+  char *mem;
+  uint pte;
+  int perm;
+
+  if(va >= KERNBASE)
+    return -1;
+  pte = uvmpte(pgdir, va);
+  if(pte & PTE_P)
+    return (pte & PTE_U) ? 0 : -1;
+  if((mem = lazypage(pgdir, sz, va, pte, &perm)) == 0)
+    return -1;
+  return lazymap(pgdir, va, mem, perm);
+}
+
+// Map the pages of from between start and end into to as well,
+// for fork.  With cow set, writable pages become read-only
+// copy-on-write pages in both, so that the first write to one
+// gets a private copy (see cowfault); otherwise the two share
+// them.  The caller must tlbflush from afterward.
+int
+uvmshare(pde_t *from, pde_t *to, uint start, uint end, int cow)
+{
+  pte_t *pte, *cpte;
+  uint a, pa;
+  int r;
+
+  r = 0;
+  acquire(&lazylock);
+  for(a = start; a < end; a += PGSIZE){
+    if((pte = walkpgdir(from, (char*)a, 0)) == 0){
+      a = PGADDR(PDX(a) + 1, 0, 0) - PGSIZE;
+      continue;
+    }
+    if(!(*pte & (PTE_P|PTE_SWAP)))
+      continue;
+    if(cow && (*pte & PTE_W))
+      *pte = (*pte & ~PTE_W) | PTE_COW;
+    if(*pte & PTE_SWAP){
+      // Out on swap: share the slot.
+      if((cpte = walkpgdir(to, (char*)a, 1)) == 0){
+        r = -1;
+        break;
+      }
+      *cpte = *pte;
+      swapdup(SWAPSLOT(*pte));
+      continue;
+    }
+    pa = PTE_ADDR(*pte);
+    if(mappages(to, (char*)a, PGSIZE, pa, PTE_FLAGS(*pte)) < 0){
+      r = -1;
+      break;
+    }
+    kref(P2V(pa));
+  }
+  release(&lazylock);
+  return r;
+}
+
+// Make the page of pgdir at user address va writable, as on a
+// write fault: if it is copy-on-write, copy it unless no one
+// else still uses it.  Returns -1 if the page is missing or
+// read-only, 0 if it is writable or has just been evicted.
+int
+cowfault(pde_t *pgdir, uint va)
+{
+  pte_t *pte;
+  char *mem, *old;
+  int r, changed;
+
+  if(va >= KERNBASE)
+    return -1;
+  r = changed = 0;
+  acquire(&lazylock);
+  pte = walkpgdir(pgdir, (char*)va, 0);
+  if(pte && (*pte & PTE_SWAP))
+    ;  // evicted meanwhile; touching it again reads it back in
+  else if(pte == 0 || (*pte & (PTE_P|PTE_U)) != (PTE_P|PTE_U))
+    r = -1;
+  else if(*pte & PTE_W)
+    ;  // another thread got here first
+  else if((*pte & PTE_COW) == 0)
+    r = -1;
+  else {
+    old = P2V(PTE_ADDR(*pte));
+    if(krefcount(old) == 1){
+      *pte = (*pte & ~PTE_COW) | PTE_W;
+      changed = 1;
+    } else if((mem = kalloc()) == 0)
+      r = -1;
+    else {
+      memmove(mem, old, PGSIZE);
+      *pte = V2P(mem) | (PTE_FLAGS(*pte) & ~PTE_COW) | PTE_W;
+      kfree(old);
+      changed = 1;
+    }
+  }
+  release(&lazylock);
+  if(changed)
+    tlbflush(pgdir);
+  return r;
+}
+
+// Give pgdir a page of its own at the read-only user address
+// va, for a tracer to store to: copy the page if anyone else
+// still uses it.  The page stays read-only, and is marked dirty
+// so that reclaim keeps the store rather than reading the file
+// in again.  Returns -1 if the page is missing or memory ran
+// out.
+int
+uvmprivate(pde_t *pgdir, uint va)
+{
+  pte_t *pte;
+  char *mem, *old;
+  int r, changed;
+
+  if(va >= KERNBASE)
+    return -1;
+  r = changed = 0;
+  acquire(&lazylock);
+  pte = walkpgdir(pgdir, (char*)va, 0);
+  if(pte == 0 || (*pte & (PTE_P|PTE_U)) != (PTE_P|PTE_U))
+    r = -1;
+  else {
+    old = P2V(PTE_ADDR(*pte));
+    if(krefcount(old) == 1)
+      *pte |= PTE_D;
+    else if((mem = kalloc()) == 0)
+      r = -1;
+    else {
+      memmove(mem, old, PGSIZE);
+      *pte = V2P(mem) | PTE_FLAGS(*pte) | PTE_D;
+      kfree(old);
+      changed = 1;
+    }
+  }
+  release(&lazylock);
+  if(changed)
+    tlbflush(pgdir);
+  return r;
+}
+
+// If the page of pgdir at user address va has been written
+// since the last call, mark it clean and return it with a
+// reference for the caller, else return 0.
+char*
+uvmdirty(pde_t *pgdir, uint va)
+{
+  pte_t *pte;
+  char *page;
+
+  page = 0;
+  acquire(&lazylock);
+  pte = walkpgdir(pgdir, (char*)va, 0);
+  if(pte && (*pte & (PTE_P|PTE_D)) == (PTE_P|PTE_D)){
+    *pte &= ~PTE_D;
+    page = P2V(PTE_ADDR(*pte));
+    kref(page);
+  }
+  release(&lazylock);
+  if(page)
+    tlbflush(pgdir);  // so the next store sets PTE_D again
+  return page;
+}
+
+// Look for a page of pgdir to evict, for reclaim, from user
+// address *va up, clock fashion: clear the accessed bit of each
+// page that has it, and take the first that doesn't.  Pages
+// that are shared, or in shared areas, stay.  A clean page of a
+// private file area is unmapped, to be read in again when next
+// touched; any other page gets a swap slot and a swap PTE.  Sets
+// *page to the page and *slot to the slot, or -1, advances *va
+// past the page, and returns 1; the caller flushes TLBs, writes
+// the page to the slot, and frees it.  Returns 0 if it found
+// nothing before KERNBASE.  The caller must keep pgdir from
+// being freed.
+int
+uvmevict(pde_t *pgdir, uint *va, char **page, int *slot)
+{
+  pte_t *pte;
+  uint a, e;
+  int kind, s;
+
+  for(a = PGROUNDDOWN(*va); a < KERNBASE; a += PGSIZE){
+    acquire(&lazylock);
+    if((pte = walkpgdir(pgdir, (char*)a, 0)) == 0){
+      release(&lazylock);
+      a = PGADDR(PDX(a) + 1, 0, 0) - PGSIZE;
+      continue;
+    }
+    e = *pte;
+    if((e & (PTE_P|PTE_U)) != (PTE_P|PTE_U) ||
+       krefcount(P2V(PTE_ADDR(e))) != 1){
+      release(&lazylock);
+      continue;
+    }
+    if(e & PTE_A){
+      *pte &= ~PTE_A;
+      release(&lazylock);
+      continue;
+    }
+    release(&lazylock);
+
+    // vmatable.lock comes before lazylock.
+    if((kind = vmaevict(pgdir, a)) < 0)
+      continue;
+    acquire(&lazylock);
+    if(*pte != e){
+      release(&lazylock);
+      continue;
+    }
+    s = -1;
+    if(kind == 1 && (e & PTE_D) == 0)
+      *pte = 0;
+    else if((s = swapalloc()) >= 0)
+      *pte = (s << PTXSHIFT) | PTE_SWAP |
+             (PTE_FLAGS(e) & (PTE_W|PTE_U|PTE_D|PTE_COW));
+    else {
+      release(&lazylock);
+      continue;
+    }
+    release(&lazylock);
+    *page = P2V(PTE_ADDR(e));
+    *slot = s;
+    *va = a + PGSIZE;
+    return 1;
+  }
+  return 0;
+}
+
+// Make sure that the current process's memory from va to
+// va+len is backed, and writable if write is set.  Returns -1
+// if it cannot be.
+int
+uvmfaultin(uint va, uint len, int write)
+{
+  uint a, last;
+  struct proc *curproc = myproc();
+
+  if(len == 0)
+    return 0;
+  if(va + len < va)
+    return -1;
+  last = PGROUNDDOWN(va + len - 1);
+  for(a = PGROUNDDOWN(va); ; a += PGSIZE){
+    if(lazyalloc(curproc->pgdir, curproc->sz, a) < 0)
+      return -1;
+    if(write && cowfault(curproc->pgdir, a) < 0)
+      return -1;
+    if(a == last)
+      break;
+  }
+  return 0;
+}
+
 //PAGEBREAK!
 // Map user virtual address to kernel address.
 char*
@@ -352,18 +882,56 @@ uva2ka(pde_t *pgdir, char *uva)
   pte_t *pte;
 
   pte = walkpgdir(pgdir, uva, 0);
-  if((*pte & PTE_P) == 0)
+  if(pte == 0 || (*pte & PTE_P) == 0)
     return 0;
   if((*pte & PTE_U) == 0)
     return 0;
   return (char*)P2V(PTE_ADDR(*pte));
 }
 
-// Copy len bytes from p to user address va in page table pgdir.
-// Most useful when pgdir is not the current page table.
-// uva2ka ensures this only works for PTE_U pages.
+// Return the page of pgdir, whose process has size sz, at user
+// address va, backing it first if need be, with a reference for
+// the caller to kfree.  With write set, make the page writable
+// and mark it dirty, as a store through the user mapping would.
+// Returns 0 if the page is not accessible.  The kernel reaches
+// the page through its own mapping, so pgdir need not be the
+// current page table.
+char*
+uvmpage(pde_t *pgdir, uint sz, uint va, int write)
+{
+  pte_t *pte;
+  char *page;
+  int need, evicted;
+
+  need = PTE_P|PTE_U;
+  if(write)
+    need |= PTE_W;
+  do {
+    if(lazyalloc(pgdir, sz, va) < 0)
+      return 0;
+    if(write && cowfault(pgdir, va) < 0)
+      return 0;
+    page = 0;
+    acquire(&lazylock);
+    pte = walkpgdir(pgdir, (char*)va, 0);
+    if(pte && (*pte & need) == need){
+      if(write)
+        *pte |= PTE_D;
+      page = P2V(PTE_ADDR(*pte));
+      kref(page);
+    }
+    // reclaim may have taken it again before the kref.
+    evicted = pte && (*pte & PTE_SWAP);
+    release(&lazylock);
+  } while(page == 0 && evicted);
+  return page;
+}
+
+// Copy len bytes from p to user address va in page table pgdir,
+// whose process has size sz, a page at a time.
+// Returns -1 if some page cannot be written.
 int
-copyout(pde_t *pgdir, uint va, void *p, uint len)
+copyout(pde_t *pgdir, uint sz, uint va, void *p, uint len)
 {
   char *buf, *pa0;
   uint n, va0;
@@ -371,13 +939,39 @@ copyout(pde_t *pgdir, uint va, void *p, uint len)
   buf = (char*)p;
   while(len > 0){
     va0 = (uint)PGROUNDDOWN(va);
-    pa0 = uva2ka(pgdir, (char*)va0);
-    if(pa0 == 0)
+    if((pa0 = uvmpage(pgdir, sz, va0, 1)) == 0)
       return -1;
     n = PGSIZE - (va - va0);
     if(n > len)
       n = len;
     memmove(pa0 + (va - va0), buf, n);
+    kfree(pa0);
+    len -= n;
+    buf += n;
+    va = va0 + PGSIZE;
+  }
+  return 0;
+}
+
+// Copy len bytes to p from user address va in page table pgdir,
+// whose process has size sz.  Returns -1 if some page cannot
+// be read.
+int
+copyin(pde_t *pgdir, uint sz, void *p, uint va, uint len)
+{
+  char *buf, *pa0;
+  uint n, va0;
+
+  buf = (char*)p;
+  while(len > 0){
+    va0 = (uint)PGROUNDDOWN(va);
+    if((pa0 = uvmpage(pgdir, sz, va0, 0)) == 0)
+      return -1;
+    n = PGSIZE - (va - va0);
+    if(n > len)
+      n = len;
+    memmove(buf, pa0 + (va - va0), n);
+    kfree(pa0);
     len -= n;
     buf += n;
     va = va0 + PGSIZE;
@@ -385,6 +979,35 @@ copyout(pde_t *pgdir, uint va, void *p, uint len)
   return 0;
 }
 
+// Copy the nul-terminated string at user address va in page
+// table pgdir, whose process has size sz, to p, which has room
+// for max bytes.  Returns the length of the string, not
+// including nul, or -1 if it cannot be read or is too long.
+int
+copyinstr(pde_t *pgdir, uint sz, char *p, uint va, uint max)
+{
+  char *pa0;
+  uint n, va0, i;
+
+  i = 0;
+  while(i < max){
+    va0 = (uint)PGROUNDDOWN(va);
+    if((pa0 = uvmpage(pgdir, sz, va0, 0)) == 0)
+      return -1;
+    n = PGSIZE - (va - va0);
+    if(n > max - i)
+      n = max - i;
+    for(; n > 0; n--, i++, va++){
+      if((p[i] = pa0[va - va0]) == 0){
+        kfree(pa0);
+        return i;
+      }
+    }
+    kfree(pa0);
+  }
+  return -1;
+}
+
 //PAGEBREAK!
 // Blank page.
 //PAGEBREAK!
@@ -0,0 +1,499 @@
+// Virtual memory areas.
+//
+// exec doesn't read a program into memory.  It records where
+// each segment lives in the executable, and the page fault
+// handler reads pages in as the program touches them (see
+// lazyalloc in vm.c).  mmap records areas the same way, backed
+// by a file or by zeros, and shmat for shared memory segments
+// (see shm.c).  Areas are keyed by page directory, so
+// threads sharing an address space share its areas too.
+//
+// Program segments lie below p->sz, with the heap above them.
+// mmap places areas top-down from p->mmapbase, which exec picks
+// at random below MMAPTOP, and the heap may not grow into them.
+
+#include "types.h"
+#include "defs.h"
+#include "param.h"
+#include "memlayout.h"
+#include "mmu.h"
+#include "proc.h"
+#include "spinlock.h"
+#include "sleeplock.h"
+#include "fs.h"
+#include "file.h"
+#include "mman.h"
+#include "vma.h"
+#include "errno.h"
+
+struct {
+  struct spinlock lock;
+  struct vma vma[NVMA];
+} vmatable;
+
+void
+vmainit(void)
+{
+  initlock(&vmatable.lock, "vma");
+}
+
+// Return a free slot in the table, or 0 if there is none.
+// Caller must hold vmatable.lock.
+static struct vma*
+vmaslot(void)
+{
+  struct vma *v;
+
+  for(v = vmatable.vma; v < &vmatable.vma[NVMA]; v++)
+    if(v->pgdir == 0)
+      return v;
+  return 0;
+}
+
+// Return an area of pgdir overlapping start to end, or 0.
+// Caller must hold vmatable.lock.
+static struct vma*
+vmaoverlap1(pde_t *pgdir, uint start, uint end)
+{
+  struct vma *v;
+
+  for(v = vmatable.vma; v < &vmatable.vma[NVMA]; v++)
+    if(v->pgdir == pgdir && start < v->end && end > v->start)
+      return v;
+  return 0;
+}
+
+// Does any area of pgdir overlap addresses start to end?
+int
+vmaoverlap(pde_t *pgdir, uint start, uint end)
+{
+  int r;
+
+  acquire(&vmatable.lock);
+  r = vmaoverlap1(pgdir, start, end) != 0;
+  release(&vmatable.lock);
+  return r;
+}
+
+// Record that addresses start to end of pgdir, with protection
+// prot and MAP_ flags, come from ip, starting at offset off, of
+// which the first filesz bytes are file data.  ip may be 0 for
+// zeroed memory.  If shm is set, the area is an attachment of
+// that shared memory segment.  Returns -1 if the table is full
+// or the range is taken.  Caller must hold vmatable.lock.
+static int
+vmaadd1(pde_t *pgdir, uint start, uint end, int prot, int flags,
+        struct inode *ip, uint off, uint filesz, struct shmseg *shm)
+{
+  struct vma *v;
+
+  if(vmaoverlap1(pgdir, start, end) || (v = vmaslot()) == 0)
+    return -1;
+  v->pgdir = pgdir;
+  v->start = start;
+  v->end = end;
+  v->prot = prot;
+  v->flags = flags;
+  v->ip = ip ? idup(ip) : 0;
+  v->off = off;
+  v->filesz = filesz;
+  v->shm = shm;
+  if(shm)
+    shmdup(shm);
+  return 0;
+}
+
+// As vmaadd1, for callers not holding vmatable.lock.
+int
+vmaadd(pde_t *pgdir, uint start, uint end, int prot, int flags,
+       struct inode *ip, uint off, uint filesz)
+{
+  int r;
+
+  acquire(&vmatable.lock);
+  r = vmaadd1(pgdir, start, end, prot, flags, ip, off, filesz, 0);
+  release(&vmatable.lock);
+  return r;
+}
+
+// Give the address space to, a copy of from made by fork, the
+// same areas.  copyuvm has copied the pages below sz; pages of
+// areas above it are shared with to, or copy-on-write if the
+// area is private.  Returns -1 if the table fills up or memory
+// runs out; the caller frees to with freeuvm, which drops any
+// areas added.
+int
+vmacopy(pde_t *from, pde_t *to, uint sz)
+{
+  struct vma *v, *nv;
+
+  acquire(&vmatable.lock);
+  for(v = vmatable.vma; v < &vmatable.vma[NVMA]; v++){
+    if(v->pgdir != from)
+      continue;
+    if((nv = vmaslot()) == 0)
+      goto bad;
+    *nv = *v;
+    nv->pgdir = to;
+    if(nv->ip)
+      idup(nv->ip);
+    if(nv->shm)
+      shmdup(nv->shm);
+    if(v->start >= sz &&
+       uvmshare(from, to, v->start, v->end, v->flags & MAP_PRIVATE) < 0)
+      goto bad;
+  }
+  release(&vmatable.lock);
+  tlbflush(from);  // for pages uvmshare made read-only
+  return 0;
+
+bad:
+  release(&vmatable.lock);
+  tlbflush(from);
+  return -1;
+}
+
+// Fill the zeroed page *mem from whatever area of pgdir holds
+// user address va, set *prot to the area's protection, and
+// return 1.  For a shared file area, replace *mem with the page
+// cache's page instead.  Return 0 if va is in no area, -1 if
+// the file can't be read.  Sleeps, and doesn't look at pgdir
+// itself, only at the areas recorded for it.
+int
+vmafill(pde_t *pgdir, uint va, char **mem, int *prot)
+{
+  struct vma *v, a;
+  uint lo, hi;
+  char *page;
+  int r;
+
+  va = PGROUNDDOWN(va);
+  acquire(&vmatable.lock);
+  if((v = vmaoverlap1(pgdir, va, va + PGSIZE)) == 0){
+    release(&vmatable.lock);
+    return 0;
+  }
+  a = *v;
+  if(a.ip)
+    idup(a.ip);
+  release(&vmatable.lock);
+  *prot = a.prot;
+  if(a.ip == 0)
+    return 1;
+
+  r = 1;
+  if(a.flags & MAP_SHARED){
+    if((page = pcget(a.ip, a.off + (va - a.start))) == 0)
+      r = -1;
+    else {
+      kfree(*mem);
+      *mem = page;
+    }
+  } else {
+    // The part of the page holding file data.  Past the end
+    // of the file, readi reads less, leaving zeros.
+    lo = va < a.start ? a.start : va;
+    hi = va + PGSIZE;
+    if(hi > a.start + a.filesz)
+      hi = a.start + a.filesz;
+    if(lo < hi){
+      ilock(a.ip);
+      if(readi(a.ip, *mem + (lo - va), a.off + (lo - a.start), hi - lo) < 0)
+        r = -1;
+      iunlock(a.ip);
+    }
+  }
+  begin_op();
+  iput(a.ip);
+  end_op();
+  return r;
+}
+
+// Write page, the page at offset off of ip, back to the file,
+// without extending the file.
+static void
+writeback(struct inode *ip, uint off, char *page)
+{
+  int max, n, i;
+
+  // A few blocks at a time, as filewrite does, to fit in
+  // a log transaction.
+  max = ((MAXOPBLOCKS-1-1-2-2) / 2) * BSIZE;
+  for(i = 0; i < PGSIZE; i += n){
+    n = PGSIZE - i < max ? PGSIZE - i : max;
+    begin_op();
+    ilock(ip);
+    if(off + i < ip->size){
+      if(off + i + n > ip->size)
+        n = ip->size - (off + i);
+      writei(ip, page + i, off + i, n);
+    } else
+      n = PGSIZE - i;
+    iunlock(ip);
+    end_op();
+  }
+}
+
+// How reclaim may evict the page of pgdir at user address va
+// (see uvmevict): returns -1 if it is in a shared area and must
+// stay, 1 if it is in a private file area, so that a clean copy
+// can be read in again, and 0 if it is anonymous memory.
+int
+vmaevict(pde_t *pgdir, uint va)
+{
+  struct vma *v;
+  int r;
+
+  r = 0;
+  acquire(&vmatable.lock);
+  if((v = vmaoverlap1(pgdir, va, va + PGSIZE)) != 0){
+    if(v->flags & MAP_SHARED)
+      r = -1;
+    else if(v->ip)
+      r = 1;
+  }
+  release(&vmatable.lock);
+  return r;
+}
+
+// Write the pages of pgdir's shared file areas between start
+// and end that have been stored to since the last time back to
+// their files.
+void
+vmasync(pde_t *pgdir, uint start, uint end)
+{
+  struct vma *v, a;
+  uint va;
+  char *page;
+
+  for(;;){
+    // The lowest such area at or above start.
+    a.pgdir = 0;
+    acquire(&vmatable.lock);
+    for(v = vmatable.vma; v < &vmatable.vma[NVMA]; v++){
+      if(v->pgdir == pgdir && v->ip && (v->flags & MAP_SHARED) &&
+         (v->prot & PROT_WRITE) && start < v->end && end > v->start &&
+         (a.pgdir == 0 || v->start < a.start))
+        a = *v;
+    }
+    if(a.pgdir)
+      idup(a.ip);
+    release(&vmatable.lock);
+    if(a.pgdir == 0)
+      return;
+
+    va = start > a.start ? start : a.start;
+    for(; va < end && va < a.end; va += PGSIZE)
+      if((page = uvmdirty(pgdir, va)) != 0){
+        writeback(a.ip, a.off + (va - a.start), page);
+        kfree(page);
+      }
+    start = a.end;
+    begin_op();
+    iput(a.ip);
+    end_op();
+  }
+}
+
+// Remove the parts of pgdir's areas between start and end,
+// splitting any area that straddles the range.  Returns -1
+// if the table is full.
+static int
+vmaremove(pde_t *pgdir, uint start, uint end)
+{
+  struct vma *v, *nv;
+  struct inode *ip;
+  struct shmseg *shm;
+  uint n;
+
+  for(;;){
+    ip = 0;
+    shm = 0;
+    acquire(&vmatable.lock);
+    if((v = vmaoverlap1(pgdir, start, end)) == 0){
+      release(&vmatable.lock);
+      return 0;
+    }
+    if(v->start < start && v->end > end){
+      // Split it in two; the copy keeps the part above
+      // the range on the next pass.
+      if((nv = vmaslot()) == 0){
+        release(&vmatable.lock);
+        return -1;
+      }
+      *nv = *v;
+      if(nv->ip)
+        idup(nv->ip);
+      if(nv->shm)
+        shmdup(nv->shm);
+      v->end = end;
+    }
+    if(v->start < start){
+      // Keep the part below the range.
+      v->end = start;
+      if(v->filesz > start - v->start)
+        v->filesz = start - v->start;
+    } else if(v->end > end){
+      // Keep the part above the range.
+      n = end - v->start;
+      v->filesz = v->filesz > n ? v->filesz - n : 0;
+      v->off += n;
+      v->start = end;
+    } else {
+      ip = v->ip;
+      shm = v->shm;
+      v->pgdir = 0;
+      v->ip = 0;
+      v->shm = 0;
+    }
+    release(&vmatable.lock);
+    if(ip){
+      begin_op();
+      iput(ip);
+      end_op();
+    }
+    if(shm)
+      shmput(shm);
+  }
+}
+
+// Forget every area of pgdir, which is being freed,
+// writing shared file pages back first.
+void
+vmafree(pde_t *pgdir)
+{
+  vmasync(pgdir, 0, KERNBASE);
+  vmaremove(pgdir, 0, KERNBASE);
+}
+
+// Find room for len bytes of mappings in pgdir, whose process
+// has size sz, as high as possible below base.  Returns the
+// start, or 0 if there is no room.  Caller must hold
+// vmatable.lock.
+static uint
+vmaspace(pde_t *pgdir, uint sz, uint base, uint len)
+{
+  struct vma *v;
+  uint end;
+
+  end = base;
+  while(end >= len && end - len >= PGROUNDUP(sz)){
+    if((v = vmaoverlap1(pgdir, end - len, end)) == 0)
+      return end - len;
+    end = v->start;
+  }
+  return 0;
+}
+
+// Add an area of len bytes to the current process, from offset
+// off of ip, or zeroed if ip is 0, with protection prot and MAP_
+// flags, attaching shared memory segment shm if that is set.
+// Use addr if that range is free, else pick a place.  Returns
+// the start, or 0.
+uint
+vmamap(uint addr, uint len, int prot, int flags, struct inode *ip,
+       uint off, struct shmseg *shm)
+{
+  uint start;
+  struct proc *curproc = myproc();
+
+  if(len == 0 || off % PGSIZE != 0)
+    return 0;
+  len = PGROUNDUP(len);
+  if(len == 0 || len >= MMAPTOP)
+    return 0;
+
+  acquire(&vmatable.lock);
+  if(addr % PGSIZE == 0 && addr >= PGROUNDUP(curproc->sz) &&
+     addr + len > addr && addr + len <= MMAPTOP &&
+     vmaoverlap1(curproc->pgdir, addr, addr + len) == 0)
+    start = addr;
+  else if((start = vmaspace(curproc->pgdir, curproc->sz,
+                                 curproc->mmapbase, len)) == 0){
+    release(&vmatable.lock);
+    return 0;
+  }
+  if(vmaadd1(curproc->pgdir, start, start + len, prot, flags,
+             ip, off, ip ? len : 0, shm) < 0){
+    release(&vmatable.lock);
+    return 0;
+  }
+  release(&vmatable.lock);
+  return start;
+}
+
+// Map len bytes into the current process, from offset off of
+// ip, or zeroed if ip is 0, with protection prot and MAP_
+// flags; see vmamap.  Shared anonymous mappings get their pages
+// now, so that processes forked later share them; shared file
+// mappings share the page cache's pages.  Returns the address,
+// or a negated error number.
+int
+mmap(uint addr, uint len, int prot, int flags, struct inode *ip, uint off)
+{
+  uint start;
+
+  if((flags & (MAP_SHARED|MAP_PRIVATE)) == 0 ||
+     (flags & (MAP_SHARED|MAP_PRIVATE)) == (MAP_SHARED|MAP_PRIVATE))
+    return -EINVAL;
+  flags &= MAP_SHARED|MAP_PRIVATE;
+  if((start = vmamap(addr, len, prot, flags, ip, off, 0)) == 0)
+    return -ENOMEM;
+  if((flags & MAP_SHARED) && ip == 0 && prot != PROT_NONE &&
+     uvmfaultin(start, len, 0) < 0){
+    munmap(start, len);
+    return -ENOMEM;
+  }
+  return start;
+}
+
+// If a shared memory segment is attached at addr in pgdir,
+// return the size of the attachment, else 0.
+uint
+vmashm(pde_t *pgdir, uint addr)
+{
+  struct vma *v;
+  uint len;
+
+  len = 0;
+  acquire(&vmatable.lock);
+  for(v = vmatable.vma; v < &vmatable.vma[NVMA]; v++)
+    if(v->pgdir == pgdir && v->start == addr && v->shm)
+      len = v->end - v->start;
+  release(&vmatable.lock);
+  return len;
+}
+
+// Remove the current process's mappings from addr to addr+len.
+int
+munmap(uint addr, uint len)
+{
+  uint end;
+  struct proc *curproc = myproc();
+
+  end = PGROUNDUP(addr + len);
+  if(addr % PGSIZE != 0 || len == 0 || end <= addr || end > MMAPTOP)
+    return -EINVAL;
+  // Only mmap's areas; program segments lie below sz.
+  if(addr < curproc->sz)
+    return -EINVAL;
+  vmasync(curproc->pgdir, addr, end);
+  if(vmaremove(curproc->pgdir, addr, end) < 0)
+    return -ENOMEM;
+  deallocuvm(curproc->pgdir, end, addr);
+  return 0;
+}
+
+// Write the current process's shared file mappings from addr
+// to addr+len back to their files.
+int
+msync(uint addr, uint len)
+{
+  uint end;
+
+  end = PGROUNDUP(addr + len);
+  if(addr % PGSIZE != 0 || end < addr || end > MMAPTOP)
+    return -EINVAL;
+  vmasync(myproc()->pgdir, addr, end);
+  return 0;
+}
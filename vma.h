@@ -0,0 +1,15 @@
+// A virtual memory area: a range of a user address space whose
+// pages are filled in when first touched, from a file or with
+// zeros.  exec makes one for each program segment, mmap one
+// for each mapping, and shmat one for each attachment.
+struct vma {
+  pde_t *pgdir;        // address space the area belongs to; 0 if free
+  uint start;          // first address, page-aligned
+  uint end;            // one past the last address
+  int prot;            // PROT_ bits (see mman.h)
+  int flags;           // MAP_SHARED or MAP_PRIVATE
+  struct inode *ip;    // backing file, or 0 for zeros
+  uint off;            // file offset of start
+  uint filesz;         // bytes taken from the file; the rest are zero
+  struct shmseg *shm;  // shared memory segment attached here, or 0
+};
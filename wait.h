@@ -0,0 +1,13 @@
+// Options and status for waitpid.
+
+#define WNOHANG    1  // Return 0 instead of waiting
+#define WUNTRACED  2  // Also report children stopped by a signal
+
+// A status is 0 for a normal exit, the signal number for a
+// process killed by a signal, or 0x7F | sig<<8 for a traced
+// process, or with WUNTRACED a child, stopped by signal sig.
+#define WIFEXITED(s)    (((s) & 0x7F) == 0)
+#define WIFSIGNALED(s)  (((s) & 0x7F) != 0 && ((s) & 0x7F) != 0x7F)
+#define WTERMSIG(s)     ((s) & 0x7F)
+#define WIFSTOPPED(s)   (((s) & 0xFF) == 0x7F)
+#define WSTOPSIG(s)     (((s) >> 8) & 0xFF)
@@ -9,6 +9,24 @@ inb(ushort port)
   return data;
 }
 
+static inline ushort
+inw(ushort port)
+{
+  ushort data;
+
+  asm volatile("in %1,%0" : "=a" (data) : "d" (port));
+  return data;
+}
+
+static inline uint
+inl(ushort port)
+{
+  uint data;
+
+  asm volatile("in %1,%0" : "=a" (data) : "d" (port));
+  return data;
+}
+
 static inline void
 insl(int port, void *addr, int cnt)
 {
@@ -30,6 +48,12 @@ outw(ushort port, ushort data)
   asm volatile("out %0,%1" : : "a" (data), "d" (port));
 }
 
+static inline void
+outl(ushort port, uint data)
+{
+  asm volatile("out %0,%1" : : "a" (data), "d" (port));
+}
+
 static inline void
 outsl(int port, const void *addr, int cnt)
 {
@@ -117,6 +141,21 @@ sti(void)
   asm volatile("sti");
 }
 
+static inline void
+hlt(void)
+{
+  asm volatile("hlt");
+}
+
+// Enable interrupts and halt until one arrives.  sti takes
+// effect only after the next instruction, so none can be taken
+// between the two and leave the hlt waiting for another.
+static inline void
+stihlt(void)
+{
+  asm volatile("sti; hlt");
+}
+
 static inline uint
 xchg(volatile uint *addr, uint newval)
 {
@@ -130,6 +169,58 @@ xchg(volatile uint *addr, uint newval)
   return result;
 }
 
+// Tell the processor it is in a spin loop.
+static inline void
+pause(void)
+{
+  asm volatile("pause");
+}
+
+// If *addr is old, make it new.  Returns what *addr was.
+static inline uint
+cmpxchg(volatile uint *addr, uint old, uint new)
+{
+  uint result;
+
+  asm volatile("lock; cmpxchgl %2, %0" :
+               "+m" (*addr), "=a" (result) :
+               "r" (new), "1" (old) :
+               "cc");
+  return result;
+}
+
+// Add n to *addr.  Returns what *addr was.
+static inline uint
+xadd(volatile uint *addr, uint n)
+{
+  asm volatile("lock; xaddl %0, %1" :
+               "+r" (n), "+m" (*addr) : :
+               "cc");
+  return n;
+}
+
+static inline uint64
+rdtsc(void)
+{
+  uint64 tsc;
+  asm volatile("rdtsc" : "=A" (tsc));
+  return tsc;
+}
+
+static inline uint64
+rdmsr(uint msr)
+{
+  uint64 v;
+  asm volatile("rdmsr" : "=A" (v) : "c" (msr));
+  return v;
+}
+
+static inline void
+wrmsr(uint msr, uint64 v)
+{
+  asm volatile("wrmsr" : : "c" (msr), "A" (v));
+}
+
 static inline uint
 rcr2(void)
 {
@@ -144,6 +235,109 @@ lcr3(uint val)
   asm volatile("movl %0,%%cr3" : : "r" (val));
 }
 
+static inline uint
+rcr3(void)
+{
+  uint val;
+  asm volatile("movl %%cr3,%0" : "=r" (val));
+  return val;
+}
+
+static inline uint
+rcr0(void)
+{
+  uint val;
+  asm volatile("movl %%cr0,%0" : "=r" (val));
+  return val;
+}
+
+static inline void
+lcr0(uint val)
+{
+  asm volatile("movl %0,%%cr0" : : "r" (val));
+}
+
+static inline void
+lcr4(uint val)
+{
+  asm volatile("movl %0,%%cr4" : : "r" (val));
+}
+
+static inline uint
+rcr4(void)
+{
+  uint val;
+  asm volatile("movl %%cr4,%0" : "=r" (val));
+  return val;
+}
+
+// Execute CPUID for leaf and subleaf.
+static inline void
+cpuidsub(uint leaf, uint sub, uint *eax, uint *ebx, uint *ecx, uint *edx)
+{
+  asm volatile("cpuid" :
+               "=a" (*eax), "=b" (*ebx), "=c" (*ecx), "=d" (*edx) :
+               "a" (leaf), "c" (sub));
+}
+
+// Execute CPUID for leaf (subleaf 0).
+static inline void
+cpuidregs(uint leaf, uint *eax, uint *ebx, uint *ecx, uint *edx)
+{
+  cpuidsub(leaf, 0, eax, ebx, ecx, edx);
+}
+
+// Set extended control register 0, which says what xsave saves.
+static inline void
+lxcr0(uint64 val)
+{
+  asm volatile("xsetbv" : : "c" (0), "a" ((uint)val), "d" ((uint)(val >> 32)));
+}
+
+// Floating point state save and restore.  xsave and xrstor
+// handle every component XCR0 enables.
+static inline void
+fnsave(void *p)
+{
+  asm volatile("fnsave (%0)" : : "r" (p) : "memory");
+}
+
+static inline void
+frstor(void *p)
+{
+  asm volatile("frstor (%0)" : : "r" (p) : "memory");
+}
+
+static inline void
+fxsave(void *p)
+{
+  asm volatile("fxsave (%0)" : : "r" (p) : "memory");
+}
+
+static inline void
+fxrstor(void *p)
+{
+  asm volatile("fxrstor (%0)" : : "r" (p) : "memory");
+}
+
+static inline void
+xsave(void *p)
+{
+  asm volatile("xsave (%0)" : : "r" (p), "a" (~0), "d" (~0) : "memory");
+}
+
+static inline void
+xrstor(void *p)
+{
+  asm volatile("xrstor (%0)" : : "r" (p), "a" (~0), "d" (~0) : "memory");
+}
+
+static inline void
+fninit(void)
+{
+  asm volatile("fninit");
+}
+
 //PAGEBREAK: 36
 // Layout of the trap frame built on the stack by the
 // hardware and by trapasm.S, and passed to trap().